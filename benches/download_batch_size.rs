@@ -0,0 +1,78 @@
+//! Times the download of a large file with several `cursor_batch_size`, against a server at
+//! `MONGO_URI`: `cargo bench --bench download_batch_size`.
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::{Client, Database};
+use mongodb_gridfs::{
+    options::{GridFSBucketOptions, GridFSDownloadOptions},
+    GridFSBucket, GridFSError,
+};
+use std::time::{Duration, Instant};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+use uuid::Uuid;
+
+/// The length of the downloaded file.
+const FILE_LENGTH: usize = 64 * 1024 * 1024;
+/// The number of downloads timed for each batch size.
+const ROUNDS: u32 = 5;
+
+fn db_name_new() -> String {
+    "test_".to_owned()
+        + Uuid::new_v4()
+            .hyphenated()
+            .encode_lower(&mut Uuid::encode_buffer())
+}
+
+async fn time_download(
+    bucket: &GridFSBucket,
+    id: bson::oid::ObjectId,
+    cursor_batch_size: Option<u32>,
+) -> Result<Duration, GridFSError> {
+    let options = GridFSDownloadOptions::builder()
+        .cursor_batch_size(cursor_batch_size)
+        .build();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut cursor = bucket
+            .open_download_stream_with_options(id, options.clone())
+            .await?;
+        let mut length = 0;
+        while let Some(data) = cursor.next().await {
+            length += data?.len();
+        }
+        assert_eq!(length, FILE_LENGTH);
+    }
+    Ok(start.elapsed() / ROUNDS)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), GridFSError> {
+    let client = Client::with_uri_str(
+        &std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017/".to_string()),
+    )
+    .await?;
+    let dbname = db_name_new();
+    let db: Database = client.database(&dbname);
+    let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+    let content = vec![42u8; FILE_LENGTH];
+    let id = bucket
+        .upload_from_stream("bench.bin", content.as_slice(), None)
+        .await?;
+
+    // One chunk per round trip, the default 8MiB batches, and batches at the 16MiB limit
+    // of a reply.
+    for cursor_batch_size in [Some(1), Some(4), None, Some(64)] {
+        let elapsed = time_download(&bucket, id, cursor_batch_size).await?;
+        let label = cursor_batch_size.map_or("default".to_string(), |size| size.to_string());
+        println!(
+            "cursor_batch_size {:>7}: {:>8.1} ms, {:>7.1} MiB/s",
+            label,
+            elapsed.as_secs_f64() * 1000.0,
+            FILE_LENGTH as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64()
+        );
+    }
+
+    db.drop(None).await?;
+    Ok(())
+}
@@ -0,0 +1,47 @@
+fn main() {
+    // The gRPC service of the `examples-extra` feature, generated without a .proto file.
+    #[cfg(feature = "examples-extra")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+
+        let method = |name: &str, route_name: &str, input_type: &str, output_type: &str| {
+            Method::builder()
+                .name(name)
+                .route_name(route_name)
+                .input_type(format!("crate::grpc::{}", input_type))
+                .output_type(format!("crate::grpc::{}", output_type))
+                .codec_path("tonic_prost::ProstCodec")
+        };
+        let service = Service::builder()
+            .name("GridFs")
+            .package("gridfs")
+            .comment("Uploads, downloads and lists the files of a GridFS bucket.")
+            .method(
+                method("upload", "Upload", "UploadRequest", "UploadResponse")
+                    .comment(
+                        "Uploads a file from a stream of frames. The first frame names the file.",
+                    )
+                    .client_streaming()
+                    .build(),
+            )
+            .method(
+                method(
+                    "download",
+                    "Download",
+                    "DownloadRequest",
+                    "DownloadResponse",
+                )
+                .comment("Downloads a file as a stream of chunks.")
+                .server_streaming()
+                .build(),
+            )
+            .method(
+                method("list", "List", "ListRequest", "FileEntry")
+                    .comment("Lists the files, optionally with a filename.")
+                    .server_streaming()
+                    .build(),
+            )
+            .build();
+        Builder::new().build_transport(false).compile(&[service]);
+    }
+}
@@ -29,7 +29,7 @@ async fn main() -> Result<(), GridFSError> {
     println!("{}", id);
 
     let mut cursor = bucket.open_download_stream(id).await?;
-    let buffer = cursor.next().await.unwrap();
+    let buffer = cursor.next().await.unwrap()?;
     println!("{:?}", buffer);
 
     db.drop(None).await?;
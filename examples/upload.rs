@@ -1,5 +1,5 @@
-use mongodb::{error::Error, Client, Database};
-use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket};
+use mongodb::{Client, Database};
+use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket, GridFSError};
 use uuid::Uuid;
 
 fn db_name_new() -> String {
@@ -10,7 +10,7 @@ fn db_name_new() -> String {
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Error> {
+async fn main() -> Result<(), GridFSError> {
     let client = Client::with_uri_str(
         &std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017/".to_string()),
     )
@@ -23,5 +23,6 @@ async fn main() -> Result<(), Error> {
         .await?;
     println!("{}", id);
 
-    db.drop(None).await
+    db.drop(None).await?;
+    Ok(())
 }
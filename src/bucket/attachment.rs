@@ -0,0 +1,350 @@
+use crate::{
+    bucket::{
+        routing::{chunk_collection_of, chunk_filter, chunk_routing_projection},
+        upload::ReadSource,
+        GridFSBucket,
+    },
+    file_info::get_number,
+    options::GridFSUploadOptions,
+    GridFSError,
+};
+use bson::{doc, oid::ObjectId, Bson, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::io::AsyncRead;
+use mongodb::{
+    options::{FindOneAndDeleteOptions, TransactionOptions},
+    ClientSession, Collection,
+};
+use std::convert::TryFrom;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::io::AsyncRead;
+
+/// A stored file attached to a parent document, pushed on an array of the parent by
+/// [`GridFSBucket::attach`] as `{id, filename, length, contentType}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Attachment {
+    /// The id of the stored file.
+    pub id: ObjectId,
+    pub filename: String,
+    /// The length of the file, in bytes.
+    pub length: u64,
+    /// The MIME type of the file, from the `contentType` of the metadata of its upload.
+    pub content_type: Option<String>,
+}
+
+impl Attachment {
+    /// The attachment of the subdocument @document of a parent, None when it's malformed.
+    pub fn from_document(document: &Document) -> Option<Attachment> {
+        Some(Attachment {
+            id: document.get_object_id("id").ok()?,
+            filename: document.get_str("filename").ok()?.to_string(),
+            length: u64::try_from(get_number(document, "length")?).ok()?,
+            content_type: document.get_str("contentType").ok().map(str::to_string),
+        })
+    }
+}
+
+impl From<&Attachment> for Document {
+    fn from(attachment: &Attachment) -> Document {
+        let mut document = doc! {
+            "id":attachment.id,
+            "filename":&attachment.filename,
+            "length":attachment.length as i64,
+        };
+        if let Some(content_type) = &attachment.content_type {
+            document.insert("contentType", content_type);
+        }
+        document
+    }
+}
+
+impl GridFSBucket {
+    /**
+    Uploads a user file like [`GridFSBucket::upload_from_stream`] and attaches it to the
+    document @parent_id of the collection @parent: the [`Attachment`] of the file is pushed
+    on the array @field of the parent. Returns the attachment.
+
+    The content type of the attachment is the `contentType` of the metadata of @options.
+    The file is removed when it can't be attached.
+
+    # Errors
+
+    Raise [`GridFSError::ParentNotFound`] when @parent has no document @parent_id.
+    */
+    pub async fn attach(
+        &mut self,
+        parent: &Collection<Document>,
+        parent_id: impl Into<Bson>,
+        field: &str,
+        filename: &str,
+        source: impl AsyncRead + Unpin,
+        options: Option<GridFSUploadOptions>,
+    ) -> Result<Attachment, GridFSError> {
+        let parent_id = parent_id.into();
+        let parent_not_found = || GridFSError::ParentNotFound {
+            collection: parent.name().to_string(),
+        };
+        if parent
+            .count_documents(doc! {"_id":parent_id.clone()}, None)
+            .await?
+            == 0
+        {
+            return Err(parent_not_found());
+        }
+
+        let filename = self.checked_filename(filename)?;
+        let content_type = options
+            .as_ref()
+            .and_then(|options| options.metadata.as_ref())
+            .and_then(|metadata| metadata.get_str("contentType").ok())
+            .map(str::to_string);
+        let (id, length, _) = self
+            .upload_chunks_with_diagnostics(None, &filename, ReadSource(source), options)
+            .await?;
+        let attachment = Attachment {
+            id,
+            filename,
+            length,
+            content_type,
+        };
+
+        let error = match parent
+            .update_one(
+                doc! {"_id":parent_id},
+                doc! {"$push":{field:Document::from(&attachment)}},
+                None,
+            )
+            .await
+        {
+            Ok(result) if result.matched_count > 0 => return Ok(attachment),
+            // Removed meanwhile.
+            Ok(_) => parent_not_found(),
+            Err(error) => error.into(),
+        };
+        self.delete(id).await?;
+        Err(error)
+    }
+
+    /**
+    Detaches the stored file @id from the document @parent_id of the collection @parent: its
+    attachment is pulled from the array @field of the parent, then the file is deleted.
+
+    With @transaction, both happen in a transaction, so the parent never references a
+    deleted file nor a file stays unreferenced. The transactions require a replica set or a
+    sharded cluster.
+
+    # Errors
+
+    Raise [`GridFSError::ParentNotFound`] when @parent has no document @parent_id.
+    Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+    */
+    pub async fn detach(
+        &self,
+        parent: &Collection<Document>,
+        parent_id: impl Into<Bson>,
+        field: &str,
+        id: ObjectId,
+        transaction: bool,
+    ) -> Result<(), GridFSError> {
+        let parent_id = parent_id.into();
+        if !transaction {
+            let pulled = parent
+                .update_one(
+                    doc! {"_id":parent_id},
+                    doc! {"$pull":{field:{"id":id}}},
+                    None,
+                )
+                .await?;
+            if pulled.matched_count == 0 {
+                return Err(GridFSError::ParentNotFound {
+                    collection: parent.name().to_string(),
+                });
+            }
+            return self.delete(id).await;
+        }
+
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let mut session = self.files_collection().client().start_session(None).await?;
+        let transaction_options = TransactionOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        session.start_transaction(transaction_options).await?;
+        if let Err(error) = self
+            .detach_in(&mut session, parent, parent_id, field, id)
+            .await
+        {
+            session.abort_transaction().await.ok();
+            return Err(error);
+        }
+        session.commit_transaction().await?;
+        Ok(())
+    }
+
+    /// Pulls the attachment of the file @id from the array @field of the document
+    /// @parent_id of @parent, and deletes the file, in the transaction of @session.
+    async fn detach_in(
+        &self,
+        session: &mut ClientSession,
+        parent: &Collection<Document>,
+        parent_id: Bson,
+        field: &str,
+        id: ObjectId,
+    ) -> Result<(), GridFSError> {
+        let pulled = parent
+            .update_one_with_session(
+                doc! {"_id":parent_id},
+                doc! {"$pull":{field:{"id":id}}},
+                None,
+                session,
+            )
+            .await?;
+        if pulled.matched_count == 0 {
+            return Err(GridFSError::ParentNotFound {
+                collection: parent.name().to_string(),
+            });
+        }
+
+        // The write concern is the one of the transaction.
+        let bucket_name = self.options.clone().unwrap_or_default().bucket_name;
+        let files = self
+            .db
+            .collection::<Document>(&(bucket_name.clone() + ".files"));
+        let find_one_and_delete_options = FindOneAndDeleteOptions::builder()
+            .projection(chunk_routing_projection(self.chunk_shard_key()))
+            .build();
+        let file = files
+            .find_one_and_delete_with_session(doc! {"_id":id}, find_one_and_delete_options, session)
+            .await?
+            .ok_or(GridFSError::FileNotFound())?;
+        self.db
+            .collection::<Document>(&chunk_collection_of(&file, &bucket_name))
+            .delete_many_with_session(
+                chunk_filter(&file, id, self.chunk_shard_key()),
+                None,
+                session,
+            )
+            .await?;
+        #[cfg(feature = "content-search")]
+        self.db
+            .collection::<Document>(&(bucket_name + ".content"))
+            .delete_one_with_session(doc! {"_id":id}, None, session)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Attachment, GridFSBucket};
+    use crate::{
+        options::{GridFSBucketOptions, GridFSUploadOptions},
+        GridFSError,
+    };
+    use bson::{doc, oid::ObjectId, Document};
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn attachment_document() {
+        let attachment = Attachment {
+            id: ObjectId::parse_str("5f8f8c44b54764421b7156c9").unwrap(),
+            filename: "test.txt".into(),
+            length: 9,
+            content_type: Some("text/plain".into()),
+        };
+        let document = Document::from(&attachment);
+        assert_eq!(
+            document,
+            doc! {
+                "id":attachment.id,
+                "filename":"test.txt",
+                "length":9_i64,
+                "contentType":"text/plain",
+            }
+        );
+        assert_eq!(Attachment::from_document(&document), Some(attachment));
+        assert_eq!(
+            Attachment::from_document(&doc! {"filename":"test.txt"}),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn attach_and_detach() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let posts = db.collection::<Document>("posts");
+        posts.insert_one(doc! {"_id":1}, None).await?;
+
+        let options = GridFSUploadOptions::builder()
+            .metadata(Some(doc! {"contentType":"text/plain"}))
+            .build();
+        let attachment = bucket
+            .attach(
+                &posts,
+                1,
+                "attachments",
+                "test.txt",
+                "test data".as_bytes(),
+                Some(options),
+            )
+            .await?;
+        assert_eq!(attachment.length, 9);
+        assert_eq!(attachment.content_type.as_deref(), Some("text/plain"));
+        let post = posts.find_one(doc! {"_id":1}, None).await?.unwrap();
+        let attachments = post.get_array("attachments").unwrap();
+        assert_eq!(
+            Attachment::from_document(attachments[0].as_document().unwrap()),
+            Some(attachment.clone())
+        );
+
+        assert!(matches!(
+            bucket
+                .attach(
+                    &posts,
+                    2,
+                    "attachments",
+                    "test.txt",
+                    "data".as_bytes(),
+                    None
+                )
+                .await,
+            Err(GridFSError::ParentNotFound { .. })
+        ));
+        let files = db.collection::<Document>("fs.files");
+        assert_eq!(files.count_documents(doc! {}, None).await?, 1);
+
+        bucket
+            .detach(&posts, 1, "attachments", attachment.id, false)
+            .await?;
+        let post = posts.find_one(doc! {"_id":1}, None).await?.unwrap();
+        assert!(post.get_array("attachments").unwrap().is_empty());
+        assert_eq!(files.count_documents(doc! {}, None).await?, 0);
+        assert!(matches!(
+            bucket
+                .detach(&posts, 1, "attachments", attachment.id, false)
+                .await,
+            Err(GridFSError::FileNotFound())
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
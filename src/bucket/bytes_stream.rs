@@ -0,0 +1,129 @@
+use crate::{
+    bucket::{chunks::GridFSChunkStream, GridFSBucket},
+    options::GridFSDownloadOptions,
+    GridFSError,
+};
+use bson::oid::ObjectId;
+use bytes::Bytes;
+#[cfg(feature = "async-std-runtime")]
+use futures::Stream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::Stream;
+
+/// Stream of the chunks of a stored file as [`Bytes`], returned by
+/// [`GridFSBucket::open_download_stream_bytes`].
+///
+/// The length of the file is known from its files collection document: [`Stream::size_hint`]
+/// gives the number of chunks left, [`GridFSBytesStream::len`] the length of the file, e.g.
+/// to preallocate a buffer or to send a `Content-Length` header. The chunks are checked
+/// like those of a [`GridFSChunkStream`].
+pub struct GridFSBytesStream {
+    chunks: GridFSChunkStream,
+}
+
+impl GridFSBytesStream {
+    /// The length of the file, in bytes.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.chunks.len()
+    }
+
+    /// The number of bytes not yielded yet.
+    pub fn remaining(&self) -> u64 {
+        self.chunks.remaining()
+    }
+}
+
+impl Stream for GridFSBytesStream {
+    type Item = Result<Bytes, GridFSError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.chunks)
+            .poll_next(cx)
+            .map(|item| item.map(|chunk| chunk.map(|(_, data)| data)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl GridFSBucket {
+    /**
+     Opens a [`GridFSBytesStream`] from which the application can read the contents of the
+     stored file specified by @id as [`Bytes`], knowing its length.
+
+     Behaves like [`GridFSBucket::open_download_chunk_stream`] with the download @options:
+     with a `range`, the stream yields the whole chunks covering the range.
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::FileExpired`] when the requested file has expired.
+     Raise [`GridFSError::InvalidFile`] when the files collection document is malformed.
+     The stream ends with [`GridFSError::InvalidChunk`] when a chunk is missing or has an
+     unexpected length.
+    */
+    pub async fn open_download_stream_bytes(
+        &self,
+        id: ObjectId,
+        options: Option<GridFSDownloadOptions>,
+    ) -> Result<GridFSBytesStream, GridFSError> {
+        let chunks = self.open_download_chunk_stream(id, options).await?;
+        Ok(GridFSBytesStream { chunks })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{options::GridFSBucketOptions, GridFSError};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::{Stream, StreamExt};
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::{Stream, StreamExt};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_bytes() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let mut stream = bucket.open_download_stream_bytes(id, None).await?;
+        assert_eq!(stream.len(), 9);
+        assert_eq!(stream.size_hint(), (3, Some(3)));
+        assert_eq!(stream.next().await.unwrap()?, "test");
+        assert_eq!(stream.remaining(), 5);
+        assert_eq!(stream.size_hint(), (2, Some(2)));
+        assert_eq!(stream.next().await.unwrap()?, " dat");
+        assert_eq!(stream.next().await.unwrap()?, "a");
+        assert_eq!(stream.size_hint(), (0, Some(0)));
+        assert!(stream.next().await.is_none());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
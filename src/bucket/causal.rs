@@ -0,0 +1,191 @@
+use crate::{
+    bucket::{GridFSBucket, GridFSDownloadStream},
+    options::GridFSDownloadOptions,
+    GridFSError,
+};
+use bson::{doc, oid::ObjectId, Document, Timestamp};
+use mongodb::{
+    options::{FindOneOptions, ReadPreference, SelectionCriteria},
+    Client, ClientSession, ClusterTime,
+};
+
+/// A point in the history of the cluster, to read the writes of another client.
+///
+/// A service gets a token after its uploads with [`GridFSBucket::causal_token`] and hands it
+/// to another service, with its own client, which passes it to
+/// [`GridFSBucket::open_download_stream_after`] or to [`CausalToken::apply`] on a session:
+/// the reads then observe the uploaded files, even on lagging secondaries.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CausalToken {
+    operation_time: Timestamp,
+    cluster_time: Option<ClusterTime>,
+}
+
+impl CausalToken {
+    /// The operation time of the token.
+    pub fn operation_time(&self) -> Timestamp {
+        self.operation_time
+    }
+
+    /// The cluster time of the token.
+    pub fn cluster_time(&self) -> Option<&ClusterTime> {
+        self.cluster_time.as_ref()
+    }
+
+    /// Advances @session to the token, making its reads causally consistent with it.
+    pub fn apply(&self, session: &mut ClientSession) {
+        session.advance_operation_time(self.operation_time);
+        if let Some(cluster_time) = &self.cluster_time {
+            session.advance_cluster_time(cluster_time);
+        }
+    }
+
+    /// The token as a document, to transmit it to another service.
+    pub fn to_document(&self) -> Document {
+        let mut document = doc! {"operationTime": self.operation_time};
+        if let Some(cluster_time) = &self.cluster_time {
+            if let Ok(cluster_time) = bson::to_bson(cluster_time) {
+                document.insert("clusterTime", cluster_time);
+            }
+        }
+        document
+    }
+
+    /// Reads a token written by [`CausalToken::to_document`]. Returns `None` when
+    /// @document isn't a token.
+    pub fn from_document(document: &Document) -> Option<CausalToken> {
+        let operation_time = document.get_timestamp("operationTime").ok()?;
+        let cluster_time = match document.get("clusterTime") {
+            Some(cluster_time) => Some(bson::from_bson(cluster_time.clone()).ok()?),
+            None => None,
+        };
+        Some(CausalToken {
+            operation_time,
+            cluster_time,
+        })
+    }
+
+    /// Starts a causally consistent session advanced to the token.
+    pub(crate) async fn start_session(
+        &self,
+        client: &Client,
+    ) -> mongodb::error::Result<ClientSession> {
+        let mut session = client.start_session(None).await?;
+        self.apply(&mut session);
+        Ok(session)
+    }
+}
+
+impl GridFSBucket {
+    /**
+    Returns a [`CausalToken`] covering every write acknowledged so far by the client of the
+    bucket, like the uploads which have returned.
+
+    Returns `None` when the deployment doesn't track operation times, e.g. a standalone
+    server, where every read already observes the acknowledged writes.
+    */
+    pub async fn causal_token(&self) -> Result<Option<CausalToken>, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let files = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name + ".files"));
+        let mut session = files.client().start_session(None).await?;
+        // The operation time of a read on the primary follows the writes it acknowledged.
+        let find_one_options = FindOneOptions::builder()
+            .projection(doc! {"_id":1})
+            .selection_criteria(SelectionCriteria::ReadPreference(ReadPreference::Primary))
+            .build();
+        files
+            .find_one_with_session(doc! {}, find_one_options, &mut session)
+            .await?;
+        Ok(session.operation_time().map(|operation_time| CausalToken {
+            operation_time,
+            cluster_time: session.cluster_time().cloned(),
+        }))
+    }
+
+    /**
+     Opens a Stream from which the application can read the contents of the stored file
+     specified by @id, observing every write covered by @token.
+
+     Behaves like [`GridFSBucket::open_download_stream`], but the files collection document
+     and the chunks are read in causally consistent sessions advanced to @token.
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::FileExpired`] when the requested file has expired.
+    */
+    pub async fn open_download_stream_after(
+        &self,
+        id: ObjectId,
+        token: &CausalToken,
+    ) -> Result<GridFSDownloadStream, GridFSError> {
+        let options = GridFSDownloadOptions::builder()
+            .causal_token(Some(token.clone()))
+            .build();
+        self.open_download_stream_with_options(id, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CausalToken;
+    use crate::{options::GridFSBucketOptions, GridFSBucket, GridFSError};
+    use bson::{doc, Timestamp};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn causal_token_document() {
+        let token = CausalToken {
+            operation_time: Timestamp {
+                time: 1_700_000_000,
+                increment: 3,
+            },
+            cluster_time: None,
+        };
+        let document = token.to_document();
+        assert_eq!(CausalToken::from_document(&document), Some(token));
+        assert_eq!(CausalToken::from_document(&doc! {"operationTime": 1}), None);
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_after() -> Result<(), GridFSError> {
+        let uri = std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string());
+        let client = Client::with_uri_str(&uri).await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        if let Some(token) = bucket.causal_token().await? {
+            let token = CausalToken::from_document(&token.to_document()).unwrap();
+            // Another service, with its own client.
+            let other_client = Client::with_uri_str(&uri).await?;
+            let other = GridFSBucket::new(
+                other_client.database(&dbname),
+                Some(GridFSBucketOptions::default()),
+            );
+            let mut cursor = other.open_download_stream_after(id, &token).await?;
+            assert_eq!(cursor.next().await.unwrap()?, b"test data");
+            assert!(cursor.next().await.is_none());
+        }
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,503 @@
+use crate::{
+    bucket::{status::visible, tier::TIER_FIELD, GridFSBucket},
+    options::{ChecksumAlgorithm, GridFSBucketOptions, GridFSDownloadOptions},
+    FileInfo, GridFSError,
+};
+use bson::{doc, oid::ObjectId, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+#[cfg(feature = "md5")]
+use md5::{Digest, Md5};
+use mongodb::options::{FindOptions, UpdateOptions};
+#[cfg(all(feature = "sha256", not(feature = "md5")))]
+use sha2::Digest;
+#[cfg(feature = "sha256")]
+use sha2::Sha256;
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// The checksums built, in the order they're looked for in a file.
+const ALGORITHMS: &[ChecksumAlgorithm] = &[
+    #[cfg(feature = "md5")]
+    ChecksumAlgorithm::Md5,
+    #[cfg(feature = "sha256")]
+    ChecksumAlgorithm::Sha256,
+];
+
+/// The checksum of a content being read or written.
+pub(crate) enum Checksum {
+    #[cfg(feature = "md5")]
+    Md5(Md5),
+    #[cfg(feature = "sha256")]
+    Sha256(Sha256),
+}
+
+impl Checksum {
+    /// The checksum of @algorithm, None when its feature isn't built.
+    pub(crate) fn new(algorithm: ChecksumAlgorithm) -> Option<Checksum> {
+        match algorithm {
+            #[cfg(feature = "md5")]
+            ChecksumAlgorithm::Md5 => Some(Checksum::Md5(Md5::new())),
+            #[cfg(feature = "sha256")]
+            ChecksumAlgorithm::Sha256 => Some(Checksum::Sha256(Sha256::new())),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// The checksum of @algorithm.
+    ///
+    /// # Errors
+    ///
+    /// Raise [`GridFSError::InvalidConfiguration`] when the feature of @algorithm isn't built.
+    pub(crate) fn built(algorithm: ChecksumAlgorithm) -> Result<Checksum, GridFSError> {
+        Checksum::new(algorithm).ok_or_else(|| GridFSError::InvalidConfiguration {
+            reason: format!("the {} feature isn't built", algorithm.field()),
+        })
+    }
+
+    pub(crate) fn algorithm(&self) -> ChecksumAlgorithm {
+        match self {
+            #[cfg(feature = "md5")]
+            Checksum::Md5(_) => ChecksumAlgorithm::Md5,
+            #[cfg(feature = "sha256")]
+            Checksum::Sha256(_) => ChecksumAlgorithm::Sha256,
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            #[cfg(feature = "md5")]
+            Checksum::Md5(md5) => md5.update(data),
+            #[cfg(feature = "sha256")]
+            Checksum::Sha256(sha256) => sha256.update(data),
+        }
+    }
+
+    /// The checksum in lowercase hexadecimal, as stored in the files collection documents.
+    pub(crate) fn finalize(self) -> String {
+        match self {
+            #[cfg(feature = "md5")]
+            Checksum::Md5(md5) => format!("{:02x}", md5.finalize()),
+            #[cfg(feature = "sha256")]
+            Checksum::Sha256(sha256) => format!("{:02x}", sha256.finalize()),
+        }
+    }
+}
+
+/// The checksums stored by the uploads of a bucket with @options.
+pub(crate) fn upload_algorithms(options: &GridFSBucketOptions) -> Vec<ChecksumAlgorithm> {
+    ALGORITHMS
+        .iter()
+        .copied()
+        .filter(|algorithm| match algorithm {
+            ChecksumAlgorithm::Md5 => !options.disable_md5,
+            #[cfg(feature = "sha256")]
+            ChecksumAlgorithm::Sha256 => options.checksum_algorithm == ChecksumAlgorithm::Sha256,
+        })
+        .collect()
+}
+
+/// The checksum of @file a download verifies: the @preferred one when the file has it, or
+/// else the first one it has. None when the file has no checksum built.
+pub(crate) fn expected_checksum(
+    file: &Document,
+    preferred: ChecksumAlgorithm,
+) -> Option<(ChecksumAlgorithm, String)> {
+    std::iter::once(preferred)
+        .chain(ALGORITHMS.iter().copied())
+        .filter(|algorithm| ALGORITHMS.contains(algorithm))
+        .find_map(|algorithm| {
+            file.get_str(algorithm.field())
+                .ok()
+                .map(|checksum| (algorithm, checksum.to_lowercase()))
+        })
+}
+
+/// The outcome of [`GridFSBucket::backfill_checksums`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BackfillReport {
+    /// The number of files given the preferred checksum.
+    pub files_backfilled: u64,
+    /// The number of files not backfilled: deleted or backfilled by another job meanwhile.
+    pub files_skipped: u64,
+    /// The files whose content doesn't match the checksum they have, or with a malformed
+    /// chunk. They aren't backfilled.
+    pub corrupted: Vec<ObjectId>,
+}
+
+/// `n files backfilled, n skipped, n corrupted`.
+impl Display for BackfillReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files backfilled, {} skipped, {} corrupted",
+            self.files_backfilled,
+            self.files_skipped,
+            self.corrupted.len()
+        )
+    }
+}
+
+impl GridFSBucket {
+    /**
+    Adds the checksum preferred by the bucket,
+    [`GridFSBucketOptions::checksum_algorithm`], to the stored files without it, e.g. written
+    by another driver or before the bucket preferred it, in the order of their ids. Returns
+    the [`BackfillReport`] of the job.
+
+    The content of each file is read and verified against the checksum it already has, if
+    any: the files not matching it are reported as corrupted and aren't backfilled. The
+    tiered files are skipped.
+
+    # Errors
+
+    Raise [`GridFSError::MongoError`] when the files can't be read or updated.
+    Raise [`GridFSError::InvalidFile`] when a files collection document is malformed.
+    Raise [`GridFSError::InvalidConfiguration`] when the feature of the preferred checksum
+    isn't built.
+    */
+    pub async fn backfill_checksums(&self) -> Result<BackfillReport, GridFSError> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let preferred = dboptions.checksum_algorithm;
+        Checksum::built(preferred)?;
+        let field = preferred.field();
+        let find_options = FindOptions::builder()
+            .sort(doc! {"_id":1})
+            .no_cursor_timeout(true)
+            .build();
+        let mut cursor = self
+            .files_collection()
+            .find(
+                visible(doc! {
+                    "length":{"$exists":true},
+                    field:{"$exists":false},
+                    TIER_FIELD:{"$exists":false},
+                }),
+                find_options,
+            )
+            .await?;
+        let update_options = UpdateOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+
+        let mut report = BackfillReport::default();
+        while let Some(document) = cursor.next().await {
+            let document = document?;
+            let id = FileInfo::try_from(document.clone())?.id;
+            let expected = expected_checksum(&document, preferred);
+            match self.checksum_file(id, preferred, expected).await {
+                Ok(checksum) => {
+                    let updated = self
+                        .files_collection()
+                        .update_one(
+                            doc! {"_id":id, field:{"$exists":false}},
+                            doc! {"$set":{field:checksum}},
+                            update_options.clone(),
+                        )
+                        .await?;
+                    match updated.matched_count {
+                        0 => report.files_skipped += 1,
+                        _ => report.files_backfilled += 1,
+                    }
+                }
+                Err(GridFSError::FileNotFound()) => report.files_skipped += 1,
+                Err(GridFSError::InvalidChunk(_, _) | GridFSError::ChecksumMismatch { .. }) => {
+                    report.corrupted.push(id);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(report)
+    }
+
+    /// The @algorithm checksum of the content of the file @id, verified against its
+    /// @expected checksum.
+    async fn checksum_file(
+        &self,
+        id: ObjectId,
+        algorithm: ChecksumAlgorithm,
+        expected: Option<(ChecksumAlgorithm, String)>,
+    ) -> Result<String, GridFSError> {
+        let (mut chunks, _) = self
+            .open_chunk_stream(id, &GridFSDownloadOptions::default())
+            .await?;
+        let mut checksum = Checksum::built(algorithm)?;
+        let mut verified =
+            expected.and_then(|(algorithm, expected)| Some((Checksum::new(algorithm)?, expected)));
+        while let Some(data) = chunks.next().await {
+            let data = data?;
+            checksum.update(&data);
+            if let Some((verified, _)) = verified.as_mut() {
+                verified.update(&data);
+            }
+        }
+        if let Some((verified, expected)) = verified {
+            let actual = verified.finalize();
+            if actual != expected {
+                let error = GridFSError::ChecksumMismatch { expected, actual };
+                chunks.report(&error);
+                return Err(error);
+            }
+        }
+        Ok(checksum.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expected_checksum, upload_algorithms, Checksum};
+    #[cfg(feature = "md5")]
+    use crate::bucket::GridFSBucket;
+    use crate::{
+        options::{ChecksumAlgorithm, GridFSBucketOptions},
+        GridFSError,
+    };
+    use bson::doc;
+    #[cfg(feature = "md5")]
+    use bson::Document;
+    #[cfg(feature = "md5")]
+    use mongodb::{Client, Database};
+    #[cfg(feature = "md5")]
+    use uuid::Uuid;
+
+    #[cfg(feature = "md5")]
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[cfg(feature = "md5")]
+    #[test]
+    fn checksum() {
+        let mut md5 = Checksum::new(ChecksumAlgorithm::Md5).unwrap();
+        md5.update(b"test ");
+        md5.update(b"data");
+        assert_eq!(md5.algorithm(), ChecksumAlgorithm::Md5);
+        assert_eq!(md5.finalize(), "eb733a00c0c9d336e65691a37ab54293");
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn sha256_checksum() {
+        let mut sha256 = Checksum::new(ChecksumAlgorithm::Sha256).unwrap();
+        sha256.update(b"test data");
+        assert_eq!(sha256.algorithm(), ChecksumAlgorithm::Sha256);
+        assert_eq!(
+            sha256.finalize(),
+            "916f0027a575074ce72a331777c3478d6513f786a591bd892da1a577bf2335f9"
+        );
+    }
+
+    #[cfg(all(feature = "sha256", not(feature = "md5")))]
+    #[test]
+    fn negotiation_without_md5() {
+        let (md5, sha256) = (ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha256);
+        assert!(Checksum::new(md5).is_none());
+        assert!(matches!(
+            Checksum::built(md5),
+            Err(GridFSError::InvalidConfiguration { .. })
+        ));
+        let file = doc! {"md5":"eb733a", "sha256":"916f00"};
+        assert_eq!(
+            expected_checksum(&file, md5),
+            Some((sha256, "916f00".to_string()))
+        );
+        assert_eq!(expected_checksum(&doc! {"md5":"eb733a"}, md5), None);
+        assert!(upload_algorithms(&GridFSBucketOptions::default()).is_empty());
+        let options = GridFSBucketOptions::builder()
+            .checksum_algorithm(sha256)
+            .build();
+        assert_eq!(upload_algorithms(&options), [sha256]);
+    }
+
+    #[cfg(feature = "md5")]
+    #[test]
+    fn negotiation() {
+        let md5 = ChecksumAlgorithm::Md5;
+        assert_eq!(
+            expected_checksum(&doc! {"md5":"EB733A"}, md5),
+            Some((md5, "eb733a".to_string()))
+        );
+        assert_eq!(expected_checksum(&doc! {"length":9_i64}, md5), None);
+        assert_eq!(
+            upload_algorithms(&GridFSBucketOptions::default()),
+            [ChecksumAlgorithm::Md5]
+        );
+        let options = GridFSBucketOptions::builder().disable_md5(true).build();
+        assert!(upload_algorithms(&options).is_empty());
+
+        #[cfg(feature = "sha256")]
+        {
+            let sha256 = ChecksumAlgorithm::Sha256;
+            let file = doc! {"md5":"eb733a", "sha256":"916f00"};
+            assert_eq!(
+                expected_checksum(&file, sha256),
+                Some((sha256, "916f00".to_string()))
+            );
+            assert_eq!(
+                expected_checksum(&file, md5),
+                Some((md5, "eb733a".to_string()))
+            );
+            // Written by another driver: the md5 is verified.
+            assert_eq!(
+                expected_checksum(&doc! {"md5":"eb733a"}, sha256),
+                Some((md5, "eb733a".to_string()))
+            );
+            let options = GridFSBucketOptions::builder()
+                .checksum_algorithm(sha256)
+                .build();
+            assert_eq!(upload_algorithms(&options), [md5, sha256]);
+            let options = GridFSBucketOptions::builder()
+                .checksum_algorithm(sha256)
+                .disable_md5(true)
+                .build();
+            assert_eq!(upload_algorithms(&options), [sha256]);
+        }
+    }
+
+    #[cfg(feature = "md5")]
+    #[tokio::test]
+    async fn backfill_checksums() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().disable_md5(true).build()),
+        );
+        let without = bucket
+            .upload_from_stream("without.txt", "test data".as_bytes(), None)
+            .await?;
+        let files = db.collection::<Document>("fs.files");
+        assert!(files
+            .find_one(doc! {"_id":without}, None)
+            .await?
+            .unwrap()
+            .get("md5")
+            .is_none());
+
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let with = bucket
+            .upload_from_stream("with.txt", "test data".as_bytes(), None)
+            .await?;
+        let report = bucket.backfill_checksums().await?;
+        assert_eq!(report.files_backfilled, 1);
+        assert_eq!(
+            report.to_string(),
+            "1 files backfilled, 0 skipped, 0 corrupted"
+        );
+        for id in [without, with] {
+            let file = files.find_one(doc! {"_id":id}, None).await?.unwrap();
+            assert_eq!(file.get_str("md5"), Ok("eb733a00c0c9d336e65691a37ab54293"));
+        }
+        assert_eq!(bucket.backfill_checksums().await?.files_backfilled, 0);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[cfg(all(feature = "md5", feature = "sha256"))]
+    #[tokio::test]
+    async fn backfill_sha256() -> Result<(), GridFSError> {
+        use crate::options::GridFSDownloadOptions;
+        #[cfg(feature = "async-std-runtime")]
+        use futures::stream::StreamExt;
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        use tokio_stream::StreamExt;
+
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        // Written by another driver, with an md5 only.
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let legacy = bucket
+            .upload_from_stream("legacy.txt", "test data".as_bytes(), None)
+            .await?;
+        let corrupted = bucket
+            .upload_from_stream("corrupted.txt", "test data".as_bytes(), None)
+            .await?;
+        let files = db.collection::<Document>("fs.files");
+        files
+            .update_one(
+                doc! {"_id":corrupted},
+                doc! {"$set":{"md5":"00000000000000000000000000000000"}},
+                None,
+            )
+            .await?;
+
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .checksum_algorithm(ChecksumAlgorithm::Sha256)
+                    .build(),
+            ),
+        );
+        let verify = GridFSDownloadOptions::builder()
+            .verify_checksum(true)
+            .build();
+        let mut cursor = bucket
+            .open_download_stream_with_options(legacy, verify.clone())
+            .await?;
+        while let Some(chunk) = cursor.next().await {
+            chunk?;
+        }
+        let mut cursor = bucket
+            .open_download_stream_with_options(corrupted, verify.clone())
+            .await?;
+        let mut result = Ok(vec![]);
+        while let Some(chunk) = cursor.next().await {
+            result = chunk;
+        }
+        assert!(matches!(result, Err(GridFSError::ChecksumMismatch { .. })));
+
+        let id = bucket
+            .upload_from_stream("new.txt", "test data".as_bytes(), None)
+            .await?;
+        let file = files.find_one(doc! {"_id":id}, None).await?.unwrap();
+        assert_eq!(file.get_str("md5"), Ok("eb733a00c0c9d336e65691a37ab54293"));
+        let sha256 = "916f0027a575074ce72a331777c3478d6513f786a591bd892da1a577bf2335f9";
+        assert_eq!(file.get_str("sha256"), Ok(sha256));
+
+        let report = bucket.backfill_checksums().await?;
+        assert_eq!(report.files_backfilled, 1);
+        assert_eq!(report.corrupted, [corrupted]);
+        let file = files.find_one(doc! {"_id":legacy}, None).await?.unwrap();
+        assert_eq!(file.get_str("sha256"), Ok(sha256));
+        let file = files.find_one(doc! {"_id":corrupted}, None).await?.unwrap();
+        assert!(file.get("sha256").is_none());
+
+        // The sha256 is verified first.
+        files
+            .update_one(
+                doc! {"_id":legacy},
+                doc! {"$set":{"md5":"00000000000000000000000000000000"}},
+                None,
+            )
+            .await?;
+        let mut cursor = bucket
+            .open_download_stream_with_options(legacy, verify)
+            .await?;
+        while let Some(chunk) = cursor.next().await {
+            chunk?;
+        }
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,475 @@
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use crate::bucket::qos::Slot;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use crate::bucket::GridFSBucket;
+use crate::bucket::{causal::CausalToken, op_stats::Operation};
+#[cfg(feature = "test-util")]
+use crate::options::ChaosOptions;
+use crate::{file_info::get_number, options::GridFSDownloadOptions, GridFSError};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use bson::oid::ObjectId;
+use bson::{doc, spec::BinarySubtype, Binary, Bson, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::Stream;
+use futures_util::{lock::Mutex, stream::unfold};
+use mongodb::{
+    options::{FindOneOptions, FindOptions},
+    ClientSession, Collection,
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::Stream;
+
+/// Extracts the bytes of the chunk @n.
+///
+/// Chunks written with the old binary subtype (0x02) by legacy drivers are accepted: bson
+/// already strips their inner length prefix.
+pub(crate) fn chunk_data(mut chunk: Document, n: i64) -> Result<Vec<u8>, GridFSError> {
+    match chunk.remove("data") {
+        Some(Bson::Binary(Binary {
+            subtype: BinarySubtype::Generic | BinarySubtype::BinaryOld,
+            bytes,
+        })) => Ok(bytes),
+        Some(Bson::Binary(Binary {
+            subtype: BinarySubtype::Encrypted,
+            ..
+        })) => Err(GridFSError::InvalidChunk(
+            n,
+            "data is encrypted: the client has no auto-encryption to decrypt it".into(),
+        )),
+        Some(Bson::Binary(Binary { subtype, .. })) => Err(GridFSError::InvalidChunk(
+            n,
+            format!("unsupported binary subtype {:?}", subtype),
+        )),
+        Some(data) => Err(GridFSError::InvalidChunk(
+            n,
+            format!("data is a {:?}, not binary data", data.element_type()),
+        )),
+        None => Err(GridFSError::InvalidChunk(n, "data is missing".into())),
+    }
+}
+
+pub(crate) type DocumentStream = Pin<Box<dyn Stream<Item = mongodb::error::Result<Document>> + Send>>;
+
+type CursorFuture = Pin<Box<dyn Future<Output = mongodb::error::Result<DocumentStream>> + Send>>;
+
+/// The session the queries of a download run in.
+#[derive(Clone)]
+pub(crate) enum ReadSession {
+    /// A causally consistent session advanced to the token, started by each query.
+    Causal(CausalToken),
+    /// The session of the caller, locked by each query and each batch.
+    Shared(Arc<Mutex<ClientSession>>),
+}
+
+impl ReadSession {
+    /// The session of a download with @options: the session of the caller, or else a session
+    /// advanced to the causal token.
+    pub(crate) fn of(options: &GridFSDownloadOptions) -> Option<ReadSession> {
+        match (&options.session, &options.causal_token) {
+            (Some(session), _) => Some(ReadSession::Shared(session.clone())),
+            (None, Some(token)) => Some(ReadSession::Causal(token.clone())),
+            (None, None) => None,
+        }
+    }
+
+    /// Finds the first document of @collection matching @filter in the session.
+    pub(crate) async fn find_one(
+        &self,
+        collection: &Collection<Document>,
+        filter: Document,
+        options: FindOneOptions,
+    ) -> mongodb::error::Result<Option<Document>> {
+        match self {
+            ReadSession::Causal(token) => {
+                let mut session = token.start_session(collection.client()).await?;
+                collection
+                    .find_one_with_session(filter, options, &mut session)
+                    .await
+            }
+            ReadSession::Shared(session) => {
+                collection
+                    .find_one_with_session(filter, options, &mut *session.lock().await)
+                    .await
+            }
+        }
+    }
+}
+
+/// Opens a cursor on the chunks matching @filter. With a @session, the cursor belongs to the
+/// session.
+pub(crate) async fn open_chunks(
+    chunks: Collection<Document>,
+    filter: Document,
+    find_options: FindOptions,
+    session: Option<ReadSession>,
+) -> mongodb::error::Result<DocumentStream> {
+    match session {
+        None => Ok(Box::pin(chunks.find(filter, find_options).await?)),
+        Some(ReadSession::Causal(token)) => {
+            let mut session = token.start_session(chunks.client()).await?;
+            let cursor = chunks
+                .find_with_session(filter, find_options, &mut session)
+                .await?;
+            // The cursor needs its session for each batch: both move along the stream.
+            Ok(Box::pin(unfold(
+                (cursor, session),
+                |(mut cursor, mut session)| async move {
+                    let item = cursor.next(&mut session).await?;
+                    Some((item, (cursor, session)))
+                },
+            )))
+        }
+        Some(ReadSession::Shared(session)) => {
+            let cursor = chunks
+                .find_with_session(filter, find_options, &mut *session.lock().await)
+                .await?;
+            Ok(Box::pin(unfold(
+                (cursor, session),
+                |(mut cursor, session)| async move {
+                    let item = cursor.next(&mut *session.lock().await).await?;
+                    Some((item, (cursor, session)))
+                },
+            )))
+        }
+    }
+}
+
+/// Stream of the chunks of a file, in order.
+///
+/// When the chunks cursor fails, the chunks query is re-issued from the next expected chunk,
+/// up to `retries` times, so each byte of the file is yielded exactly once.
+pub(crate) struct ChunkStream {
+    chunks: Collection<Document>,
+    // The filter of the chunks of the file.
+    filter: Document,
+    find_options: FindOptions,
+    session: Option<ReadSession>,
+    cursor: Option<DocumentStream>,
+    reopening: Option<CursorFuture>,
+    // The n of the next chunk to yield.
+    next_n: i64,
+    retries: u32,
+    // The age after which the cursor is reopened, and when it was opened.
+    cursor_refresh: Option<Duration>,
+    opened_at: Instant,
+    // Whether the n of each chunk is checked against the expected one.
+    check_n: bool,
+    done: bool,
+    #[cfg(feature = "test-util")]
+    chaos: Option<(Arc<ChaosOptions>, usize)>,
+    // The priority class slot, held until the stream is dropped.
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    slot: Option<Slot>,
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    throttling: Option<Pin<Box<tokio::time::Sleep>>>,
+    // The bucket and the id of the file, to quarantine it when it's found corrupted.
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    file: Option<(GridFSBucket, ObjectId)>,
+    // The download span, ended when the stream is dropped.
+    #[cfg(feature = "otel")]
+    span: Option<opentelemetry::global::BoxedSpan>,
+    // The download counted by the bucket, ended when the stream is dropped.
+    operation: Option<Operation>,
+}
+
+impl ChunkStream {
+    pub(crate) fn new(
+        chunks: Collection<Document>,
+        filter: Document,
+        find_options: FindOptions,
+        cursor: DocumentStream,
+        session: Option<ReadSession>,
+        retries: u32,
+    ) -> ChunkStream {
+        ChunkStream {
+            chunks,
+            filter,
+            find_options,
+            session,
+            cursor: Some(cursor),
+            reopening: None,
+            next_n: 0,
+            retries,
+            cursor_refresh: None,
+            opened_at: Instant::now(),
+            check_n: false,
+            done: false,
+            #[cfg(feature = "test-util")]
+            chaos: None,
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            slot: None,
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            throttling: None,
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            file: None,
+            #[cfg(feature = "otel")]
+            span: None,
+            operation: None,
+        }
+    }
+
+    /// Starts the stream at the chunk @n, the first one matched by the cursor.
+    pub(crate) fn starting_at(mut self, n: i64) -> ChunkStream {
+        self.next_n = n;
+        self
+    }
+
+    /// Reopens the cursor from the next expected chunk once it's open for @cursor_refresh.
+    pub(crate) fn with_cursor_refresh(mut self, cursor_refresh: Option<Duration>) -> ChunkStream {
+        self.cursor_refresh = cursor_refresh;
+        self
+    }
+
+    /// Checks the n of each chunk, so a missing chunk fails the stream instead of shifting
+    /// the chunks after it.
+    pub(crate) fn checking_n(mut self) -> ChunkStream {
+        self.check_n = true;
+        self
+    }
+
+    #[cfg(feature = "otel")]
+    pub(crate) fn with_span(mut self, span: opentelemetry::global::BoxedSpan) -> ChunkStream {
+        self.span = Some(span);
+        self
+    }
+
+    pub(crate) fn with_operation(mut self, operation: Operation) -> ChunkStream {
+        self.operation = Some(operation);
+        self
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub(crate) fn with_slot(mut self, slot: Slot) -> ChunkStream {
+        self.slot = Some(slot);
+        self
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub(crate) fn with_file(mut self, bucket: GridFSBucket, id: ObjectId) -> ChunkStream {
+        self.file = Some((bucket, id));
+        self
+    }
+
+    /// Reports the @error found in the file, quarantined when it's a corruption and the
+    /// bucket quarantines the corrupted files. The download is counted as failed.
+    pub(crate) fn report(&mut self, _error: &GridFSError) {
+        if let Some(operation) = self.operation.as_mut() {
+            operation.fail();
+        }
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        if let Some((bucket, id)) = &self.file {
+            bucket.quarantine_on(*id, _error);
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    pub(crate) fn with_chaos(mut self, chaos: Option<Arc<ChaosOptions>>) -> ChunkStream {
+        self.chaos = chaos.map(|chaos| (chaos, 0));
+        self
+    }
+
+    /// Reopens the cursor from the next expected chunk if a retry is left.
+    /// Otherwise the stream ends with @error.
+    fn retry_or_fail(&mut self, error: GridFSError) -> Option<GridFSError> {
+        self.cursor = None;
+        if self.retries == 0 {
+            self.done = true;
+            return Some(error);
+        }
+        self.retries -= 1;
+        self.reopen();
+        None
+    }
+
+    /// Drops the cursor and reopens it from the next expected chunk.
+    fn reopen(&mut self) {
+        self.cursor = None;
+        let chunks = self.chunks.clone();
+        let mut filter = self.filter.clone();
+        filter.insert("n", doc! {"$gte":self.next_n});
+        let find_options = self.find_options.clone();
+        let session = self.session.clone();
+        self.reopening = Some(Box::pin(open_chunks(chunks, filter, find_options, session)));
+    }
+}
+
+impl Stream for ChunkStream {
+    type Item = Result<Vec<u8>, GridFSError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let polled = self.as_mut().poll_chunk(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(data))) => {
+                if let Some(operation) = &self.operation {
+                    operation.bytes_out(data.len() as u64);
+                }
+            }
+            Poll::Ready(Some(Err(_))) => {
+                if let Some(operation) = self.operation.as_mut() {
+                    operation.fail();
+                }
+            }
+            _ => {}
+        }
+        polled
+    }
+}
+
+impl ChunkStream {
+    fn poll_chunk(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Vec<u8>, GridFSError>>> {
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        if let Some(throttling) = self.throttling.as_mut() {
+            if throttling.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.throttling = None;
+        }
+        loop {
+            if self.done {
+                return Poll::Ready(None);
+            }
+            // Aborted by the shutdown of the bucket: the slot is released at once.
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            if self.slot.as_ref().is_some_and(Slot::is_aborted) {
+                self.slot = None;
+                self.done = true;
+                return Poll::Ready(Some(Err(GridFSError::ShuttingDown())));
+            }
+            if let Some(reopening) = self.reopening.as_mut() {
+                match reopening.as_mut().poll(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Ok(cursor)) => {
+                        self.reopening = None;
+                        self.cursor = Some(cursor);
+                        self.opened_at = Instant::now();
+                    }
+                    Poll::Ready(Err(error)) => {
+                        self.reopening = None;
+                        match self.retry_or_fail(error.into()) {
+                            Some(error) => return Poll::Ready(Some(Err(error))),
+                            None => continue,
+                        }
+                    }
+                }
+            }
+            let cursor = match self.cursor.as_mut() {
+                Some(cursor) => cursor,
+                None => return Poll::Ready(None),
+            };
+            let item = match cursor.as_mut().poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => {
+                    self.done = true;
+                    return Poll::Ready(None);
+                }
+                Poll::Ready(Some(item)) => item.map_err(GridFSError::from),
+            };
+            #[cfg(feature = "test-util")]
+            let item = match self.chaos.as_mut() {
+                Some((chaos, polled)) => {
+                    let n = *polled;
+                    *polled += 1;
+                    chaos.inject_download_failure(n).and(item)
+                }
+                None => item,
+            };
+            match item {
+                Ok(chunk) => {
+                    let n = self.next_n;
+                    self.next_n += 1;
+                    let data = match get_number(&chunk, "n") {
+                        Some(found) if self.check_n && found != n => Err(GridFSError::InvalidChunk(
+                            n,
+                            format!("missing, found chunk {}", found),
+                        )),
+                        None if self.check_n => {
+                            Err(GridFSError::InvalidChunk(n, "n is missing".into()))
+                        }
+                        _ => chunk_data(chunk, n),
+                    };
+                    if let Err(error) = &data {
+                        self.report(error);
+                        self.done = true;
+                    } else if self
+                        .cursor_refresh
+                        .is_some_and(|cursor_refresh| self.opened_at.elapsed() >= cursor_refresh)
+                    {
+                        self.reopen();
+                    }
+                    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+                    if let (Ok(data), Some(slot)) = (&data, self.slot.as_mut()) {
+                        if let Some(delay) = slot.delay(data.len()) {
+                            self.throttling = Some(Box::pin(tokio::time::sleep(delay)));
+                        }
+                    }
+                    return Poll::Ready(Some(data));
+                }
+                Err(error) => {
+                    if let Some(error) = self.retry_or_fail(error) {
+                        return Poll::Ready(Some(Err(error)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::chunk_data;
+    use crate::GridFSError;
+    use bson::{doc, spec::BinarySubtype, Binary, Document};
+
+    #[test]
+    fn chunk_data_generic() {
+        let chunk =
+            doc! {"n": 0, "data": Binary{subtype: BinarySubtype::Generic, bytes: vec![1, 2, 3]}};
+        assert_eq!(chunk_data(chunk, 0).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn chunk_data_binary_old() {
+        // Round trip through bson bytes, as read from the server.
+        let chunk =
+            doc! {"n": 0, "data": Binary{subtype: BinarySubtype::BinaryOld, bytes: vec![1, 2, 3]}};
+        let mut bytes = vec![];
+        chunk.to_writer(&mut bytes).unwrap();
+        let chunk = Document::from_reader(&mut bytes.as_slice()).unwrap();
+        assert_eq!(chunk_data(chunk, 0).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn chunk_data_invalid() {
+        let chunk = doc! {"n": 1, "data": Binary{subtype: BinarySubtype::Uuid, bytes: vec![0; 16]}};
+        assert!(matches!(
+            chunk_data(chunk, 1),
+            Err(GridFSError::InvalidChunk(1, _))
+        ));
+        let chunk = doc! {"n": 2, "data": "not binary"};
+        assert!(matches!(
+            chunk_data(chunk, 2),
+            Err(GridFSError::InvalidChunk(2, _))
+        ));
+        let chunk = doc! {"n": 3};
+        assert!(matches!(
+            chunk_data(chunk, 3),
+            Err(GridFSError::InvalidChunk(3, _))
+        ));
+        let chunk =
+            doc! {"n": 4, "data": Binary{subtype: BinarySubtype::Encrypted, bytes: vec![1, 2, 3]}};
+        assert!(matches!(
+            chunk_data(chunk, 4),
+            Err(GridFSError::InvalidChunk(4, reason)) if reason.contains("encrypted")
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{
+    bucket::{chunk_stream::ChunkStream, GridFSBucket},
+    options::GridFSDownloadOptions,
+    FileInfo, GridFSError,
+};
+use bson::oid::ObjectId;
+use bytes::Bytes;
+#[cfg(feature = "async-std-runtime")]
+use futures::Stream;
+use std::{
+    convert::TryFrom,
+    ops::Range,
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::Stream;
+
+/// The first n and the number of bytes of the chunks of @chunk_size covering the @range of
+/// a file of @length bytes: the whole file without range.
+fn chunk_span(length: u64, chunk_size: u32, range: Option<&Range<u64>>) -> (u32, u64) {
+    let chunk_size = chunk_size.max(1) as u64;
+    match range {
+        Some(range) => {
+            let first_n = range.start / chunk_size;
+            let last_n = (range.end.max(range.start + 1) - 1) / chunk_size;
+            let end = ((last_n + 1) * chunk_size).min(length);
+            (first_n as u32, end.saturating_sub(first_n * chunk_size))
+        }
+        None => (0, length),
+    }
+}
+
+/// Stream of the chunks of a stored file as `(n, data)` pairs, in order, returned by
+/// [`GridFSBucket::open_download_chunk_stream`].
+///
+/// The low-level access to the chunks, e.g. to serve HTTP ranges aligned on the chunks or to
+/// process the chunks in parallel: the stream checks each chunk against the files
+/// collection document, so a chunk yielded is the chunk `n` of the file with the length
+/// the spec gives it. [`GridFSBytesStream`](crate::bucket::GridFSBytesStream) is built on it.
+pub struct GridFSChunkStream {
+    chunks: ChunkStream,
+    length: u64,
+    chunk_size: u32,
+    // The n of the next chunk, and the bytes left to yield.
+    next_n: u32,
+    remaining: u64,
+    done: bool,
+}
+
+impl GridFSChunkStream {
+    /// The stream of the @chunks of a file of @length bytes in chunks of @chunk_size,
+    /// starting at the chunk @first_n and yielding @remaining bytes.
+    pub(crate) fn new(
+        chunks: ChunkStream,
+        length: u64,
+        chunk_size: u32,
+        first_n: u32,
+        remaining: u64,
+    ) -> GridFSChunkStream {
+        GridFSChunkStream {
+            chunks: chunks.checking_n(),
+            length,
+            chunk_size,
+            next_n: first_n,
+            remaining,
+            done: false,
+        }
+    }
+
+    /// The length of the file, in bytes.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// The size of the chunks of the file, in bytes. Only the last chunk is smaller.
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    /// The n of the next chunk.
+    pub fn next_n(&self) -> u32 {
+        self.next_n
+    }
+
+    /// The number of bytes not yielded yet.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Ends the stream with @error, reported like the other corruptions of the file.
+    fn fail(&mut self, error: GridFSError) -> Poll<Option<Result<(u32, Bytes), GridFSError>>> {
+        self.chunks.report(&error);
+        self.done = true;
+        Poll::Ready(Some(Err(error)))
+    }
+}
+
+impl Stream for GridFSChunkStream {
+    type Item = Result<(u32, Bytes), GridFSError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        let n = self.next_n;
+        match Pin::new(&mut self.chunks).poll_next(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Some(Ok(data))) => {
+                let expected = self.remaining.min(self.chunk_size as u64);
+                if expected == 0 {
+                    return self.fail(GridFSError::InvalidChunk(
+                        n as i64,
+                        "extra chunk past the end of the file".into(),
+                    ));
+                }
+                if data.len() as u64 != expected {
+                    return self.fail(GridFSError::InvalidChunk(
+                        n as i64,
+                        format!("{} bytes, expected {}", data.len(), expected),
+                    ));
+                }
+                self.next_n += 1;
+                self.remaining -= expected;
+                Poll::Ready(Some(Ok((n, Bytes::from(data)))))
+            }
+            Poll::Ready(Some(Err(error))) => {
+                self.done = true;
+                Poll::Ready(Some(Err(error)))
+            }
+            Poll::Ready(None) if self.remaining > 0 => {
+                self.fail(GridFSError::InvalidChunk(n as i64, "missing".into()))
+            }
+            Poll::Ready(None) => {
+                self.done = true;
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let chunks = self.remaining.div_ceil(self.chunk_size.max(1) as u64) as usize;
+        (chunks, Some(chunks))
+    }
+}
+
+impl GridFSBucket {
+    /**
+     Opens a [`GridFSChunkStream`] from which the application can read the chunks of the
+     stored file specified by @id as `(n, data)` pairs, in order.
+
+     Behaves like [`GridFSBucket::open_download_stream_with_options`], @options selecting
+     the server and the read concern. With a `range`, the stream yields the whole chunks
+     covering the range. `verify_checksum` is ignored: the checksum covers the whole file.
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::FileExpired`] when the requested file has expired.
+     Raise [`GridFSError::InvalidFile`] when the files collection document is malformed.
+     The stream ends with [`GridFSError::InvalidChunk`] when a chunk is missing, has an
+     unexpected length or is past the end of the file.
+    */
+    pub async fn open_download_chunk_stream(
+        &self,
+        id: ObjectId,
+        options: Option<GridFSDownloadOptions>,
+    ) -> Result<GridFSChunkStream, GridFSError> {
+        let options = options.unwrap_or_default();
+        let (chunks, file) = self
+            .counted_download(self.open_chunk_stream(id, &options))
+            .await?;
+        let file = FileInfo::try_from(file)?;
+        let (first_n, remaining) = chunk_span(file.length, file.chunk_size, options.range.as_ref());
+        Ok(GridFSChunkStream::new(
+            chunks,
+            file.length,
+            file.chunk_size,
+            first_n,
+            remaining,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_span, GridFSBucket};
+    use crate::{
+        options::{GridFSBucketOptions, GridFSDownloadOptions},
+        GridFSError,
+    };
+    use bson::{doc, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::{Stream, StreamExt};
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::{Stream, StreamExt};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn chunk_span_of_range() {
+        assert_eq!(chunk_span(9, 4, None), (0, 9));
+        assert_eq!(chunk_span(9, 4, Some(&(5..6))), (1, 4));
+        assert_eq!(chunk_span(9, 4, Some(&(3..20))), (0, 9));
+        assert_eq!(chunk_span(9, 4, Some(&(8..8))), (2, 1));
+        assert_eq!(chunk_span(9, 4, Some(&(12..16))), (3, 0));
+    }
+
+    #[tokio::test]
+    async fn open_download_chunk_stream() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let mut stream = bucket.open_download_chunk_stream(id, None).await?;
+        assert_eq!(stream.len(), 9);
+        assert_eq!(stream.size_hint(), (3, Some(3)));
+        assert_eq!(stream.next().await.unwrap()?, (0, "test".into()));
+        assert_eq!(stream.next().await.unwrap()?, (1, " dat".into()));
+        assert_eq!(stream.next().await.unwrap()?, (2, "a".into()));
+        assert!(stream.next().await.is_none());
+
+        let options = GridFSDownloadOptions::builder().range(Some(5..9)).build();
+        let mut stream = bucket.open_download_chunk_stream(id, Some(options)).await?;
+        assert_eq!(stream.next_n(), 1);
+        assert_eq!(stream.next().await.unwrap()?, (1, " dat".into()));
+        assert_eq!(stream.next().await.unwrap()?, (2, "a".into()));
+        assert!(stream.next().await.is_none());
+
+        // A missing chunk fails the stream instead of shifting the chunks after it.
+        db.collection::<Document>("fs.chunks")
+            .delete_one(doc! {"files_id":id, "n":1}, None)
+            .await?;
+        let mut stream = bucket.open_download_chunk_stream(id, None).await?;
+        assert_eq!(stream.next().await.unwrap()?, (0, "test".into()));
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(GridFSError::InvalidChunk(1, _)))
+        ));
+        assert!(stream.next().await.is_none());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
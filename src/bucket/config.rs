@@ -0,0 +1,230 @@
+use crate::{bucket::GridFSBucket, options::GridFSBucketOptions, GridFSError};
+use mongodb::{options::ClientOptions, Client};
+use std::{str::FromStr, time::Duration};
+
+/// The prefix of the bucket options in a connection string.
+const URI_OPTION_PREFIX: &str = "gridfs.";
+
+/// The environment variable holding the connection string of [`GridFSBucket::from_env`].
+const URI_ENV_VAR: &str = "MONGO_URI";
+
+/// Parses the @value of the connection string option @key.
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, GridFSError> {
+    value
+        .parse()
+        .map_err(|_| GridFSError::InvalidConfiguration {
+            reason: format!("invalid value {:?} for {}{}", value, URI_OPTION_PREFIX, key),
+        })
+}
+
+/// Splits the `gridfs.` options out of the connection string @uri. Returns the connection
+/// string left for the driver, and the bucket options.
+fn split_uri(uri: &str) -> Result<(String, GridFSBucketOptions), GridFSError> {
+    let mut options = GridFSBucketOptions::default();
+    let (base, query) = match uri.split_once('?') {
+        Some((base, query)) => (base, query),
+        None => (uri, ""),
+    };
+    let mut driver_options = vec![];
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let key = match pair.strip_prefix(URI_OPTION_PREFIX) {
+            Some(key) => key,
+            None => {
+                driver_options.push(pair);
+                continue;
+            }
+        };
+        let (key, value) = key.split_once('=').unwrap_or((key, ""));
+        match key {
+            "bucket" => options.bucket_name = value.to_string(),
+            "chunkSize" => options.chunk_size_bytes = parse_value(key, value)?,
+            "disableMd5" => options.disable_md5 = parse_value(key, value)?,
+            "downloadRetries" => options.download_retries = parse_value(key, value)?,
+            "inlineThreshold" => options.inline_threshold = Some(parse_value(key, value)?),
+            "maxInFlightChunks" => options.max_in_flight_chunks = parse_value(key, value)?,
+            "slowOpThresholdMS" => {
+                options.slow_op_threshold = Some(Duration::from_millis(parse_value(key, value)?))
+            }
+            "uniqueFilenames" => options.unique_filenames = parse_value(key, value)?,
+            _ => {
+                return Err(GridFSError::InvalidConfiguration {
+                    reason: format!("unknown option {}{}", URI_OPTION_PREFIX, key),
+                })
+            }
+        }
+    }
+    if options.bucket_name.is_empty() {
+        return Err(GridFSError::InvalidConfiguration {
+            reason: "the bucket name is empty".into(),
+        });
+    }
+    options.validate()?;
+    let uri = match driver_options.is_empty() {
+        true => base.to_string(),
+        false => format!("{}?{}", base, driver_options.join("&")),
+    };
+    Ok((uri, options))
+}
+
+impl GridFSBucket {
+    /**
+    Connects to the connection string @uri and opens the bucket it configures, for the
+    deployments configured by their environment. The bucket is in the default database of
+    the connection string, and its options are read from the `gridfs.` options of the
+    query, which aren't passed to the driver:
+    - `gridfs.bucket`: [`GridFSBucketOptions::bucket_name`];
+    - `gridfs.chunkSize`: [`GridFSBucketOptions::chunk_size_bytes`];
+    - `gridfs.disableMd5`: [`GridFSBucketOptions::disable_md5`];
+    - `gridfs.downloadRetries`: [`GridFSBucketOptions::download_retries`];
+    - `gridfs.inlineThreshold`: [`GridFSBucketOptions::inline_threshold`];
+    - `gridfs.maxInFlightChunks`: [`GridFSBucketOptions::max_in_flight_chunks`];
+    - `gridfs.slowOpThresholdMS`: [`GridFSBucketOptions::slow_op_threshold`], in
+      milliseconds;
+    - `gridfs.uniqueFilenames`: [`GridFSBucketOptions::unique_filenames`].
+
+    ```rust,no_run
+    # use mongodb_gridfs::{GridFSBucket, GridFSError};
+    # async fn open() -> Result<GridFSBucket, GridFSError> {
+    let bucket = GridFSBucket::from_uri_with_bucket(
+        "mongodb://localhost:27017/media?retryWrites=true&gridfs.bucket=images&gridfs.chunkSize=1048576",
+    )
+    .await?;
+    # Ok(bucket)
+    # }
+    ```
+
+    # Errors
+
+    Raise [`GridFSError::InvalidConfiguration`] when a `gridfs.` option is unknown or
+    malformed, or when the connection string has no default database.
+    Raise [`GridFSError::InvalidChunkSize`] when the chunk size is out of bounds.
+    Raise [`GridFSError::InvalidBucketName`] when the bucket name isn't a valid collection name
+    prefix in the database.
+    Raise [`GridFSError::MongoError`] when the driver rejects the connection string.
+    */
+    pub async fn from_uri_with_bucket(uri: &str) -> Result<GridFSBucket, GridFSError> {
+        let (uri, options) = split_uri(uri)?;
+        let client_options = ClientOptions::parse(&uri).await?;
+        let database = client_options.default_database.clone().ok_or_else(|| {
+            GridFSError::InvalidConfiguration {
+                reason: "the connection string has no default database".into(),
+            }
+        })?;
+        let client = Client::with_options(client_options)?;
+        GridFSBucket::try_new(client.database(&database), Some(options))
+    }
+
+    /**
+    Opens the bucket configured by the connection string of the `MONGO_URI` environment
+    variable, see [`GridFSBucket::from_uri_with_bucket`].
+
+    # Errors
+
+    Raise [`GridFSError::InvalidConfiguration`] when `MONGO_URI` isn't set, and the errors of
+    [`GridFSBucket::from_uri_with_bucket`].
+    */
+    pub async fn from_env() -> Result<GridFSBucket, GridFSError> {
+        let uri = std::env::var(URI_ENV_VAR).map_err(|_| GridFSError::InvalidConfiguration {
+            reason: format!("{} isn't set", URI_ENV_VAR),
+        })?;
+        GridFSBucket::from_uri_with_bucket(&uri).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_uri;
+    use crate::{bucket::GridFSBucket, GridFSError, GridFSErrorCode};
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn split_uri_options() -> Result<(), GridFSError> {
+        let (uri, options) = split_uri(
+            "mongodb://localhost:27017/media?retryWrites=true&gridfs.bucket=images&gridfs.chunkSize=1048576&w=majority&gridfs.uniqueFilenames=true",
+        )?;
+        assert_eq!(
+            uri,
+            "mongodb://localhost:27017/media?retryWrites=true&w=majority"
+        );
+        assert_eq!(options.bucket_name, "images");
+        assert_eq!(options.chunk_size_bytes, 1048576);
+        assert!(options.unique_filenames);
+
+        let (uri, options) = split_uri(
+            "mongodb://localhost:27017/media?gridfs.disableMd5=true&gridfs.inlineThreshold=16384&gridfs.slowOpThresholdMS=2000",
+        )?;
+        assert_eq!(uri, "mongodb://localhost:27017/media");
+        assert_eq!(options.bucket_name, "fs");
+        assert!(options.disable_md5);
+        assert_eq!(options.inline_threshold, Some(16384));
+        assert_eq!(options.slow_op_threshold, Some(Duration::from_secs(2)));
+        assert_eq!(
+            split_uri("mongodb://localhost:27017/media")?.0,
+            "mongodb://localhost:27017/media"
+        );
+
+        for uri in [
+            "mongodb://localhost/media?gridfs.chunkSize=big",
+            "mongodb://localhost/media?gridfs.colour=blue",
+            "mongodb://localhost/media?gridfs.bucket=",
+        ] {
+            assert_eq!(
+                split_uri(uri).unwrap_err().code(),
+                GridFSErrorCode::InvalidConfiguration
+            );
+        }
+        assert!(matches!(
+            split_uri("mongodb://localhost/media?gridfs.chunkSize=0"),
+            Err(GridFSError::InvalidChunkSize(0))
+        ));
+        assert_eq!(
+            split_uri("mongodb://localhost/media?gridfs.bucket=system.fs")
+                .unwrap_err()
+                .code(),
+            GridFSErrorCode::InvalidBucketName
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn from_uri_with_bucket() -> Result<(), GridFSError> {
+        let dbname = db_name_new();
+        let uri = std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string());
+        let bucket = GridFSBucket::from_uri_with_bucket(&format!(
+            "{}{}?gridfs.bucket=images&gridfs.chunkSize=4",
+            uri, dbname
+        ))
+        .await?;
+        let id = bucket
+            .clone()
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        assert_eq!(bucket.files_collection().name(), "images.files");
+        assert_eq!(
+            bucket
+                .chunks_collection()
+                .count_documents(bson::doc! {"files_id":id}, None)
+                .await?,
+            3
+        );
+
+        assert_eq!(
+            GridFSBucket::from_uri_with_bucket("mongodb://localhost:27017/")
+                .await
+                .unwrap_err()
+                .code(),
+            GridFSErrorCode::InvalidConfiguration
+        );
+
+        bucket.db.drop(None).await?;
+        Ok(())
+    }
+}
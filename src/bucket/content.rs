@@ -0,0 +1,192 @@
+use crate::{bucket::GridFSBucket, content::ContentMatch, FileInfo, GridFSError};
+use bson::{doc, oid::ObjectId, Document};
+use futures_util::stream::StreamExt;
+use mongodb::{
+    error::Result,
+    options::{AggregateOptions, UpdateOptions, WriteConcern},
+};
+use std::convert::TryFrom;
+
+impl GridFSBucket {
+    pub(crate) async fn create_content_index(&self, collection_name: &str) -> Result<Document> {
+        self.db
+            .run_command(
+                doc! {
+                "createIndexes": collection_name,
+                "indexes": [
+                    {
+                        "key": {
+                            "text":"text"
+                        },
+                        "name": collection_name.to_owned()+"_text_index",
+                }]},
+                None,
+            )
+            .await
+    }
+
+    /// Stores the extracted @text of the file @id.
+    pub(crate) async fn store_content(
+        &self,
+        id: ObjectId,
+        text: String,
+        write_concern: Option<WriteConcern>,
+    ) -> Result<()> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let content = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name + ".content"));
+        let update_options = UpdateOptions::builder()
+            .upsert(true)
+            .write_concern(write_concern)
+            .build();
+        content
+            .update_one(doc! {"_id":id}, doc! {"$set":{"text":text}}, update_options)
+            .await?;
+        Ok(())
+    }
+
+    /**
+    Finds the stored files whose extracted text matches @query, most relevant first.
+    @query follows the syntax of the `$text` operator of MongoDB.
+
+    Only the files uploaded with a
+    [`TextExtractor`](crate::content::TextExtractor) have a text. Requires the
+    `content-search` feature.
+
+    # Errors
+
+    Raise [`GridFSError::InvalidFile`] when a files collection document is malformed.
+    */
+    pub async fn search_content(
+        &self,
+        query: &str,
+    ) -> std::result::Result<Vec<ContentMatch>, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let selection_criteria = dboptions.read_selection_criteria();
+        let bucket_name = dboptions.bucket_name;
+        let content = self
+            .db
+            .collection::<Document>(&(bucket_name.clone() + ".content"));
+
+        let aggregate_options = AggregateOptions::builder()
+            .selection_criteria(selection_criteria)
+            .read_concern(dboptions.read_concern)
+            .build();
+        let mut cursor = content
+            .aggregate(
+                vec![
+                    doc! {"$match":{"$text":{"$search":query}}},
+                    doc! {"$project":{"score":{"$meta":"textScore"}}},
+                    doc! {"$sort":{"score":-1}},
+                    doc! {"$lookup":{
+                        "from":bucket_name + ".files",
+                        "localField":"_id",
+                        "foreignField":"_id",
+                        "as":"file",
+                    }},
+                    doc! {"$unwind":"$file"},
+                ],
+                aggregate_options,
+            )
+            .await?;
+
+        let mut matches = vec![];
+        while let Some(found) = cursor.next().await {
+            let found = found?;
+            let file = found
+                .get_document("file")
+                .map_err(|_| GridFSError::InvalidFile("file isn't a document".into()))?;
+            matches.push(ContentMatch {
+                file: FileInfo::try_from(file.clone())?,
+                score: found.get_f64("score").unwrap_or_default(),
+            });
+        }
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{
+        content::{TextExtraction, TextExtractor},
+        options::GridFSBucketOptions,
+        GridFSError,
+    };
+    use mongodb::{Client, Database};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    struct PlainTextExtractor;
+
+    struct PlainTextExtraction {
+        bytes: Vec<u8>,
+    }
+
+    impl TextExtractor for PlainTextExtractor {
+        fn start(&self, filename: &str) -> Option<Box<dyn TextExtraction>> {
+            if filename.ends_with(".txt") {
+                Some(Box::new(PlainTextExtraction { bytes: vec![] }))
+            } else {
+                None
+            }
+        }
+    }
+
+    impl TextExtraction for PlainTextExtraction {
+        fn update(&mut self, chunk: &[u8]) {
+            self.bytes.extend_from_slice(chunk);
+        }
+
+        fn finish(self: Box<Self>) -> Option<String> {
+            String::from_utf8(self.bytes).ok()
+        }
+    }
+
+    #[tokio::test]
+    async fn search_content() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(4)
+                    .text_extractor(Some(Arc::new(PlainTextExtractor)))
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream("report.txt", "quarterly revenue report".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("notes.txt", "meeting notes".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("report.bin", "revenue".as_bytes(), None)
+            .await?;
+
+        let matches = bucket.search_content("revenue").await?;
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].file.id, id);
+        assert!(matches[0].score > 0.0);
+
+        bucket.delete(id).await?;
+        assert!(bucket.search_content("revenue").await?.is_empty());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
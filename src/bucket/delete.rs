@@ -1,11 +1,79 @@
-use crate::{bucket::GridFSBucket, GridFSError};
-use bson::{doc, oid::ObjectId, Document};
-use mongodb::options::DeleteOptions;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use crate::options::Priority;
+use crate::{
+    bucket::{
+        routing::{chunk_collection_of, chunk_filter, chunk_routing_projection},
+        status::STATUS_FIELD,
+        GridFSBucket,
+    },
+    FileStatus, GridFSError,
+};
+use bson::{doc, oid::ObjectId, Bson, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use mongodb::options::FindOneAndUpdateOptions;
+use mongodb::options::{DeleteOptions, FindOneAndDeleteOptions, FindOptions};
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use std::{panic::resume_unwind, sync::Arc};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::task::JoinHandle;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// The number of chunks removed at once by the deletions in the background.
+const DELETE_BATCH_CHUNKS: i64 = 1000;
+
+/// Handle on a deletion running in the background, started by [`GridFSBucket::delete_async`].
+///
+/// The deletion goes on when the handle is dropped.
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+pub struct DeletionHandle {
+    chunks_deleted: Arc<AtomicU64>,
+    task: JoinHandle<Result<u64, GridFSError>>,
+}
+
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+impl DeletionHandle {
+    /// The number of chunks of the file removed so far.
+    pub fn chunks_deleted(&self) -> u64 {
+        self.chunks_deleted.load(Ordering::Relaxed)
+    }
+
+    /// Whether the deletion is over.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /**
+    Waits for the end of the deletion. Returns the number of chunks removed.
+
+    # Errors
+
+    Raise [`GridFSError::MongoError`] when the deletion fails or is cancelled by the shutdown
+    of the runtime. The file stays hidden, and its deletion is resumed by
+    [`GridFSBucket::purge_deleted`].
+    */
+    pub async fn wait(self) -> Result<u64, GridFSError> {
+        match self.task.await {
+            Ok(result) => result,
+            Err(error) if error.is_panic() => resume_unwind(error.into_panic()),
+            Err(_) => Err(mongodb::error::Error::from(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "deletion cancelled",
+            ))
+            .into()),
+        }
+    }
+}
 
 impl GridFSBucket {
     /**
     Given a @id, delete this stored file’s files collection document and
-    associated chunks from a GridFS bucket.
+    associated chunks from a GridFS bucket. The files derived from it, see
+    [`GridFSBucket::attach_derived`], are left behind: they are deleted with it by
+    [`GridFSBucket::delete_with_derived`].
      [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#file-deletion)
 
 
@@ -47,30 +115,187 @@ impl GridFSBucket {
      Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
     */
     pub async fn delete(&self, id: ObjectId) -> Result<(), GridFSError> {
+        let _writer = self.write_guard().await;
         let dboptions = self.options.clone().unwrap_or_default();
-        let bucket_name = dboptions.bucket_name;
+        let bucket_name = dboptions.bucket_name.clone();
         let file_collection = bucket_name.clone() + ".files";
         let files = self.db.collection::<Document>(&file_collection);
-        let chunk_collection = bucket_name + ".chunks";
-        let chunks = self.db.collection::<Document>(&chunk_collection);
+        #[cfg(feature = "content-search")]
+        let content_collection = bucket_name.clone() + ".content";
 
         let mut delete_option = DeleteOptions::default();
         if let Some(write_concern) = dboptions.write_concern.clone() {
             delete_option.write_concern = Some(write_concern);
         }
-
-        let delete_result = files
-            .delete_one(doc! {"_id":id}, delete_option.clone())
-            .await?;
+        let chunks_delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        let find_one_and_delete_options = FindOneAndDeleteOptions::builder()
+            .projection(chunk_routing_projection(self.chunk_shard_key()))
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
 
         // If there is no such file listed in the files collection,
         // drivers MUST raise an error.
-        if delete_result.deleted_count == 0 {
-            return Err(GridFSError::FileNotFound());
+        let file = files
+            .find_one_and_delete(doc! {"_id":id}, find_one_and_delete_options)
+            .await?
+            .ok_or(GridFSError::FileNotFound())?;
+
+        self.db
+            .collection::<Document>(&chunk_collection_of(&file, &bucket_name))
+            .delete_many(
+                chunk_filter(&file, id, self.chunk_shard_key()),
+                chunks_delete_options,
+            )
+            .await?;
+
+        #[cfg(feature = "content-search")]
+        self.db
+            .collection::<Document>(&content_collection)
+            .delete_one(doc! {"_id":id}, delete_option)
+            .await?;
+        Ok(())
+    }
+
+    /**
+    Deletes the stored file @id in the background: the file is hidden from the readers at
+    once, then its chunks are removed by batches, as a [`Priority::Batch`] operation, and
+    its files collection document last. The returned handle follows the progress of the
+    deletion. Requires a tokio runtime.
+
+    Prefer it to [`GridFSBucket::delete`] for the files with many chunks. An interrupted
+    deletion is resumed by [`GridFSBucket::purge_deleted`].
+
+    # Errors
+
+    Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+    */
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub async fn delete_async(&self, id: ObjectId) -> Result<DeletionHandle, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let files = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name.clone() + ".files"));
+        let find_one_and_update_options = FindOneAndUpdateOptions::builder()
+            .projection(chunk_routing_projection(self.chunk_shard_key()))
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        let file = files
+            .find_one_and_update(
+                doc! {"_id":id},
+                doc! {"$set":{STATUS_FIELD:FileStatus::Deleting.as_str()}},
+                find_one_and_update_options,
+            )
+            .await?
+            .ok_or(GridFSError::FileNotFound())?;
+
+        let chunks_deleted = Arc::new(AtomicU64::new(0));
+        let progress = chunks_deleted.clone();
+        let bucket = self.clone();
+        let task = tokio::spawn(async move {
+            let _writer = bucket.write_guard().await;
+            let _slot = bucket.qos.acquire(Priority::Batch).await?;
+            bucket.purge_file(&file, id, &progress).await?;
+            Ok(progress.load(Ordering::Relaxed))
+        });
+        Ok(DeletionHandle {
+            chunks_deleted,
+            task,
+        })
+    }
+
+    /**
+    Completes the deletions of the files hidden by [`GridFSBucket::delete_async`], e.g.
+    after a restart, from a maintenance job. Returns the number of files deleted.
+
+    # Errors
+
+    Raise [`GridFSError::InvalidFile`] when the `_id` of a hidden file isn't an ObjectId.
+    */
+    pub async fn purge_deleted(&self) -> Result<u64, GridFSError> {
+        let _writer = self.write_guard().await;
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        let _slot = self.qos.acquire(Priority::Batch).await?;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let files = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name + ".files"));
+        let find_options = FindOptions::builder()
+            .projection(chunk_routing_projection(self.chunk_shard_key()))
+            .build();
+        let mut cursor = files
+            .find(
+                doc! {STATUS_FIELD:FileStatus::Deleting.as_str()},
+                find_options,
+            )
+            .await?;
+        let chunks_deleted = AtomicU64::new(0);
+        let mut purged = 0;
+        while let Some(file) = cursor.next().await {
+            let file = file?;
+            let id = file
+                .get_object_id("_id")
+                .map_err(|_| GridFSError::InvalidFile("_id isn't an ObjectId".into()))?;
+            self.purge_file(&file, id, &chunks_deleted).await?;
+            purged += 1;
         }
+        Ok(purged)
+    }
 
-        chunks
-            .delete_many(doc! {"files_id":id}, delete_option)
+    /// Removes the chunks of the hidden @file @id by batches, counting them in
+    /// @chunks_deleted, then its files collection document.
+    async fn purge_file(
+        &self,
+        file: &Document,
+        id: ObjectId,
+        chunks_deleted: &AtomicU64,
+    ) -> Result<(), GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let bucket_name = dboptions.bucket_name.clone();
+        let chunks = self
+            .db
+            .collection::<Document>(&chunk_collection_of(file, &bucket_name));
+        let filter = chunk_filter(file, id, self.chunk_shard_key());
+        let delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        let find_options = FindOptions::builder()
+            .projection(doc! {"_id":1})
+            .limit(DELETE_BATCH_CHUNKS)
+            .build();
+        loop {
+            // The file stays hidden, for the purge after the restart.
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            if self.qos.is_aborted() {
+                return Err(GridFSError::ShuttingDown());
+            }
+            let mut cursor = chunks.find(filter.clone(), find_options.clone()).await?;
+            let mut batch = vec![];
+            while let Some(chunk) = cursor.next().await {
+                batch.push(chunk?.get("_id").cloned().unwrap_or(Bson::Null));
+            }
+            if batch.is_empty() {
+                break;
+            }
+            let deleted = chunks
+                .delete_many(doc! {"_id":{"$in":batch}}, delete_options.clone())
+                .await?
+                .deleted_count;
+            chunks_deleted.fetch_add(deleted, Ordering::Relaxed);
+        }
+
+        self.db
+            .collection::<Document>(&(bucket_name.clone() + ".files"))
+            .delete_one(
+                doc! {"_id":id, STATUS_FIELD:FileStatus::Deleting.as_str()},
+                delete_options.clone(),
+            )
+            .await?;
+        #[cfg(feature = "content-search")]
+        self.db
+            .collection::<Document>(&(bucket_name + ".content"))
+            .delete_one(doc! {"_id":id}, delete_options)
             .await?;
         Ok(())
     }
@@ -156,4 +381,88 @@ mod tests {
         db.drop(None).await?;
         Ok(())
     }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[tokio::test]
+    async fn delete_async() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data, a bit longer".as_bytes(), None)
+            .await?;
+
+        let deletion = bucket.delete_async(id).await?;
+        assert!(matches!(
+            bucket.open_download_stream(id).await,
+            Err(GridFSError::FileNotFound())
+        ));
+        assert_eq!(deletion.wait().await?, 6);
+        assert_eq!(
+            db.collection::<Document>("fs.files")
+                .count_documents(doc! {}, None)
+                .await?,
+            0
+        );
+        assert_eq!(
+            db.collection::<Document>("fs.chunks")
+                .count_documents(doc! {}, None)
+                .await?,
+            0
+        );
+        assert!(matches!(
+            bucket.delete_async(id).await,
+            Err(GridFSError::FileNotFound())
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn purge_deleted() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        let kept = bucket
+            .upload_from_stream("kept.txt", "kept data".as_bytes(), None)
+            .await?;
+        // A deletion interrupted after a part of the chunks.
+        let files = db.collection::<Document>("fs.files");
+        files
+            .update_one(doc! {"_id":id}, doc! {"$set":{"status":"deleting"}}, None)
+            .await?;
+        let chunks = db.collection::<Document>("fs.chunks");
+        chunks.delete_one(doc! {"files_id":id, "n":0}, None).await?;
+        assert!(bucket.publish(id).await.is_err());
+
+        assert_eq!(bucket.purge_deleted().await?, 1);
+        assert_eq!(files.count_documents(doc! {}, None).await?, 1);
+        assert_eq!(chunks.count_documents(doc! {"files_id":id}, None).await?, 0);
+        assert_eq!(
+            chunks.count_documents(doc! {"files_id":kept}, None).await?,
+            3
+        );
+        assert_eq!(bucket.purge_deleted().await?, 0);
+
+        db.drop(None).await?;
+        Ok(())
+    }
 }
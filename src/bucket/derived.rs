@@ -0,0 +1,249 @@
+use crate::{bucket::GridFSBucket, GridFSError};
+use bson::{doc, oid::ObjectId, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::{
+    error::Result,
+    options::{FindOptions, UpdateOptions},
+    Cursor,
+};
+use std::sync::atomic::Ordering;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// Field of the files collection document holding the id of the parent file.
+pub(crate) const DERIVED_FROM_FIELD: &str = "metadata.derivedFrom";
+/// Field of the files collection document holding the kind of derivation (thumbnail, preview...).
+pub(crate) const DERIVED_KIND_FIELD: &str = "metadata.derivedKind";
+
+impl GridFSBucket {
+    async fn create_derived_index(&self, collection_name: &str) -> Result<Document> {
+        self.db
+            .run_command(
+                doc! {
+                "createIndexes": collection_name,
+                "indexes": [
+                    {
+                        "key": {
+                            DERIVED_FROM_FIELD:1,
+                            DERIVED_KIND_FIELD:1
+                        },
+                        "name": collection_name.to_owned()+"_derived_index",
+                }]},
+                None,
+            )
+            .await
+    }
+
+    /**
+    Records that the stored file @child_id is derived from the stored file @parent_id
+    (a thumbnail of an image, a preview of a document...). @kind qualifies the relationship.
+
+    The relationship is stored in the `metadata` of the child files collection document and
+    an index is maintained on it, created by the first attachment of the bucket.
+
+    # Errors
+
+    Raise [`GridFSError::FileNotFound`] when the parent or the child doesn't exists.
+     */
+    pub async fn attach_derived(
+        &self,
+        parent_id: ObjectId,
+        child_id: ObjectId,
+        kind: &str,
+    ) -> std::result::Result<(), GridFSError> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let file_collection = dboptions.bucket_name.clone() + ".files";
+        let files = self.db.collection::<Document>(&file_collection);
+
+        if files.count_documents(doc! {"_id":parent_id}, None).await? == 0 {
+            return Err(GridFSError::FileNotFound());
+        }
+
+        // Created once for the clones of the bucket, like the indexes of the uploads.
+        if !self.derived_indexed.load(Ordering::Acquire) {
+            self.create_derived_index(&file_collection).await?;
+            self.derived_indexed.store(true, Ordering::Release);
+        }
+
+        let update_options = UpdateOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        let update_result = files
+            .update_one(
+                doc! {"_id":child_id},
+                doc! {"$set":{DERIVED_FROM_FIELD:parent_id, DERIVED_KIND_FIELD:kind}},
+                update_options,
+            )
+            .await?;
+        if update_result.matched_count == 0 {
+            return Err(GridFSError::FileNotFound());
+        }
+        Ok(())
+    }
+
+    /**
+    Find the files collection documents derived from @parent_id with the given @kind.
+    When @kind is `None`, every derived file is returned.
+     */
+    pub async fn get_derived(
+        &self,
+        parent_id: ObjectId,
+        kind: Option<&str>,
+    ) -> Result<Cursor<Document>> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let selection_criteria = dboptions.read_selection_criteria();
+        let file_collection = dboptions.bucket_name + ".files";
+        let files = self.db.collection::<Document>(&file_collection);
+
+        let mut filter = doc! {DERIVED_FROM_FIELD:parent_id};
+        if let Some(kind) = kind {
+            filter.insert(DERIVED_KIND_FIELD, kind);
+        }
+        let find_options = FindOptions::builder()
+            .read_concern(dboptions.read_concern)
+            .selection_criteria(selection_criteria)
+            .build();
+        files.find(filter, find_options).await
+    }
+
+    /**
+    Deletes the stored file @id like [`GridFSBucket::delete`] and, recursively,
+    every file derived from it.
+
+    # Errors
+
+    Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     */
+    pub async fn delete_with_derived(&self, id: ObjectId) -> std::result::Result<(), GridFSError> {
+        let mut derived_ids = vec![];
+        let mut to_visit = vec![id];
+        while let Some(parent_id) = to_visit.pop() {
+            let mut cursor = self.get_derived(parent_id, None).await?;
+            while let Some(child) = cursor.next().await {
+                if let Ok(child_id) = child?.get_object_id("_id") {
+                    if child_id != id && !derived_ids.contains(&child_id) {
+                        derived_ids.push(child_id);
+                        to_visit.push(child_id);
+                    }
+                }
+            }
+        }
+
+        self.delete(id).await?;
+        for derived_id in derived_ids {
+            match self.delete(derived_id).await {
+                // Already removed by a concurrent delete.
+                Ok(()) | Err(GridFSError::FileNotFound()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{options::GridFSBucketOptions, GridFSError};
+    use bson::{doc, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    use std::sync::atomic::Ordering;
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn attach_and_get_derived() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = &GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let parent = bucket
+            .clone()
+            .upload_from_stream("image.png", "image data".as_bytes(), None)
+            .await?;
+        let thumbnail = bucket
+            .clone()
+            .upload_from_stream("image_thumb.png", "thumb".as_bytes(), None)
+            .await?;
+        let preview = bucket
+            .clone()
+            .upload_from_stream("image_preview.png", "preview".as_bytes(), None)
+            .await?;
+
+        bucket
+            .attach_derived(parent, thumbnail, "thumbnail")
+            .await?;
+        bucket.attach_derived(parent, preview, "preview").await?;
+        assert!(bucket.derived_indexed.load(Ordering::Acquire));
+        let indexes = bucket.files_collection().list_index_names().await?;
+        assert!(indexes.contains(&"fs.files_derived_index".to_string()));
+
+        let thumbnails: Vec<Document> = bucket
+            .get_derived(parent, Some("thumbnail"))
+            .await?
+            .map(|doc| doc.unwrap())
+            .collect()
+            .await;
+        assert_eq!(thumbnails.len(), 1);
+        assert_eq!(thumbnails[0].get_object_id("_id").unwrap(), thumbnail);
+
+        let all: Vec<Document> = bucket
+            .get_derived(parent, None)
+            .await?
+            .map(|doc| doc.unwrap())
+            .collect()
+            .await;
+        assert_eq!(all.len(), 2);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delete_with_derived() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = &GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let parent = bucket
+            .clone()
+            .upload_from_stream("image.png", "image data".as_bytes(), None)
+            .await?;
+        let thumbnail = bucket
+            .clone()
+            .upload_from_stream("image_thumb.png", "thumb".as_bytes(), None)
+            .await?;
+        bucket
+            .attach_derived(parent, thumbnail, "thumbnail")
+            .await?;
+
+        bucket.delete_with_derived(parent).await?;
+
+        let count = db
+            .collection::<Document>("fs.files")
+            .count_documents(doc! {}, None)
+            .await?;
+        assert_eq!(count, 0, "Parent and derived files should be deleted");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -1,17 +1,153 @@
-use crate::{bucket::GridFSBucket, GridFSError};
-use bson::{doc, oid::ObjectId, Document};
+#[cfg(any(feature = "md5", feature = "sha256"))]
+use crate::bucket::checksum::{expected_checksum, Checksum};
+use crate::{
+    bucket::{
+        chunk_stream::{open_chunks, ChunkStream, DocumentStream, ReadSession},
+        inline::inline_chunks,
+        routing::{chunk_collection_of, chunk_filter},
+        status::visible,
+        tier::TIER_FIELD,
+        GridFSBucket,
+    },
+    file_info::{get_number, is_expired},
+    options::{ChecksumAlgorithm, GridFSDownloadByNameOptions, GridFSDownloadOptions},
+    GridFSError,
+};
+use bson::{doc, oid::ObjectId, DateTime, Document};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use bytes::Bytes;
 #[cfg(feature = "async-std-runtime")]
-use futures::{Stream, StreamExt};
-use mongodb::options::{FindOneOptions, FindOptions, SelectionCriteria};
+use futures::Stream;
+use mongodb::options::{FindOneOptions, FindOptions, ReadPreference, SelectionCriteria};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::sync::mpsc;
 #[cfg(any(feature = "default", feature = "tokio-runtime"))]
 use tokio_stream::{Stream, StreamExt};
 
+/// The bytes of chunks fetched by each round trip to the server by default.
+const DEFAULT_BATCH_BYTES: u32 = 8 * 1024 * 1024;
+
+/// The number of chunks of @chunk_size bytes fetched by each round trip of a download with
+/// @options.
+fn cursor_batch_size(options: &GridFSDownloadOptions, chunk_size: u32) -> u32 {
+    options
+        .cursor_batch_size
+        .or(options.prefetch)
+        .unwrap_or(DEFAULT_BATCH_BYTES / chunk_size.max(1))
+        .max(1)
+}
+
+/// Whether the reads with @selection_criteria may be served by a secondary.
+fn reads_secondaries(selection_criteria: &Option<SelectionCriteria>) -> bool {
+    !matches!(
+        selection_criteria,
+        None | Some(SelectionCriteria::ReadPreference(ReadPreference::Primary))
+    )
+}
+
+/// Stream of the chunks of a stored file, returned by [`GridFSBucket::open_download_stream`]
+/// and the other downloads. Named, so it can be stored in a struct field.
+pub struct GridFSDownloadStream {
+    chunks: ChunkStream,
+    // Bytes to drop at the beginning of the first chunk, and bytes left to yield, of a range.
+    skip: usize,
+    remaining: Option<u64>,
+    // The checksum of the yielded chunks, and the expected one.
+    #[cfg(any(feature = "md5", feature = "sha256"))]
+    digest: Option<(Checksum, String)>,
+}
+
+impl GridFSDownloadStream {
+    pub(crate) fn new(chunks: ChunkStream) -> GridFSDownloadStream {
+        GridFSDownloadStream {
+            chunks,
+            skip: 0,
+            remaining: None,
+            #[cfg(any(feature = "md5", feature = "sha256"))]
+            digest: None,
+        }
+    }
+
+    /// The stream of the @chunks of @file opened with @options, verifying the @preferred
+    /// checksum when the file has it.
+    #[cfg_attr(not(any(feature = "md5", feature = "sha256")), allow(unused_variables))]
+    fn with_options(
+        chunks: ChunkStream,
+        file: &Document,
+        options: &GridFSDownloadOptions,
+        preferred: ChecksumAlgorithm,
+    ) -> GridFSDownloadStream {
+        let mut stream = GridFSDownloadStream::new(chunks);
+        match &options.range {
+            Some(range) => {
+                let chunk_size = get_number(file, "chunkSize").unwrap_or(0).max(1) as u64;
+                stream.skip = (range.start % chunk_size) as usize;
+                stream.remaining = Some(range.end.saturating_sub(range.start));
+            }
+            #[cfg(any(feature = "md5", feature = "sha256"))]
+            None if options.verify_checksum => {
+                stream.digest = expected_checksum(file, preferred)
+                    .and_then(|(algorithm, expected)| Some((Checksum::new(algorithm)?, expected)));
+            }
+            None => {}
+        }
+        stream
+    }
+}
+
+impl Stream for GridFSDownloadStream {
+    type Item = Result<Vec<u8>, GridFSError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.remaining == Some(0) {
+            return Poll::Ready(None);
+        }
+        match Pin::new(&mut self.chunks).poll_next(cx) {
+            Poll::Ready(Some(Ok(mut data))) => {
+                if self.skip > 0 {
+                    data.drain(..self.skip.min(data.len()));
+                    self.skip = 0;
+                }
+                if let Some(remaining) = self.remaining {
+                    data.truncate(remaining.min(data.len() as u64) as usize);
+                    self.remaining = Some(remaining - data.len() as u64);
+                }
+                #[cfg(any(feature = "md5", feature = "sha256"))]
+                if let Some((checksum, _)) = self.digest.as_mut() {
+                    checksum.update(&data);
+                }
+                Poll::Ready(Some(Ok(data)))
+            }
+            #[cfg(any(feature = "md5", feature = "sha256"))]
+            Poll::Ready(None) => match self.digest.take() {
+                Some((checksum, expected)) => {
+                    let actual = checksum.finalize();
+                    if actual == expected {
+                        Poll::Ready(None)
+                    } else {
+                        let error = GridFSError::ChecksumMismatch { expected, actual };
+                        self.chunks.report(&error);
+                        Poll::Ready(Some(Err(error)))
+                    }
+                }
+                None => Poll::Ready(None),
+            },
+            poll => poll,
+        }
+    }
+}
+
 impl GridFSBucket {
     /// Opens a Stream from which the application can read the contents of the stored file
     /// specified by @id.
     /// [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#file-download)
     ///
-    /// Returns a [`Stream`].
+    /// Returns a [`Stream`] and the filename of the file. The filename is optional in the
+    /// spec: it's `None` when the files collection document doesn't have a string filename.
     ///
     /// # Examples
     ///
@@ -47,8 +183,8 @@ impl GridFSBucket {
     ///  #     println!("{}", id);
     ///  #
     ///  let (mut cursor, filename) = bucket.open_download_stream_with_filename(id).await?;
-    ///  assert_eq!(filename, "test.txt");
-    ///  let buffer = cursor.next().await.unwrap();
+    ///  assert_eq!(filename.as_deref(), Some("test.txt"));
+    ///  let buffer = cursor.next().await.unwrap()?;
     ///  #     println!("{:?}", buffer);
     ///  #
     ///  #     db.drop(None).await?;
@@ -59,31 +195,81 @@ impl GridFSBucket {
     ///  # Errors
     ///
     ///  Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+    ///  Raise [`GridFSError::FileExpired`] when the requested file has expired.
     ///
     pub async fn open_download_stream_with_filename(
         &self,
         id: ObjectId,
-    ) -> Result<(impl Stream<Item = Vec<u8>>, String), GridFSError> {
+    ) -> Result<(GridFSDownloadStream, Option<String>), GridFSError> {
+        let (chunks, file) = self
+            .counted_download(self.open_chunk_stream(id, &GridFSDownloadOptions::default()))
+            .await?;
+        let filename = file.get_str("filename").ok().map(str::to_string);
+        Ok((GridFSDownloadStream::new(chunks), filename))
+    }
+
+    /// Opens the chunks of the file @id with the download @options. Returns them with the
+    /// files collection document of the file.
+    pub(crate) async fn open_chunk_stream(
+        &self,
+        id: ObjectId,
+        options: &GridFSDownloadOptions,
+    ) -> Result<(ChunkStream, Document), GridFSError> {
+        self.open_chunk_stream_by_filter(doc! {"_id":id}, None, None, options)
+            .await
+    }
+
+    /// Opens the chunks of the first file matching @filter in the order @sort, after @skip
+    /// files, with the download @options.
+    async fn open_chunk_stream_by_filter(
+        &self,
+        filter: Document,
+        sort: Option<Document>,
+        skip: Option<u64>,
+        options: &GridFSDownloadOptions,
+    ) -> Result<(ChunkStream, Document), GridFSError> {
         let dboptions = self.options.clone().unwrap_or_default();
+        let (selection_criteria, chunks_selection_criteria) = match &options.selection_criteria {
+            Some(selection_criteria) => (
+                Some(selection_criteria.clone()),
+                Some(selection_criteria.clone()),
+            ),
+            None => (
+                dboptions.read_selection_criteria(),
+                dboptions.chunks_selection_criteria(),
+            ),
+        };
+        let read_concern = options
+            .read_concern
+            .clone()
+            .or(dboptions.read_concern.clone());
+        let session = ReadSession::of(options);
+        // The query is repeated on the legacy bucket when the file isn't found.
+        let legacy = self
+            .legacy_bucket()
+            .map(|legacy| (legacy, filter.clone(), sort.clone()));
         let bucket_name = dboptions.bucket_name;
         let file_collection = bucket_name.clone() + ".files";
         let files = self.db.collection::<Document>(&file_collection);
-        let chunk_collection = bucket_name + ".chunks";
-        let chunks = self.db.collection::<Document>(&chunk_collection);
 
-        let mut find_one_options = FindOneOptions::default();
-        let mut find_options = FindOptions::builder().sort(doc! {"n":1}).build();
+        let mut find_one_options = FindOneOptions::builder()
+            .sort(sort)
+            .skip(skip)
+            .max_time(options.timeout)
+            .build();
+        let mut find_options = FindOptions::builder()
+            .sort(doc! {"n":1})
+            .max_time(options.timeout)
+            .no_cursor_timeout(options.no_cursor_timeout)
+            .build();
 
-        if let Some(read_concern) = dboptions.read_concern {
+        if let Some(read_concern) = read_concern {
             find_one_options.read_concern = Some(read_concern.clone());
             find_options.read_concern = Some(read_concern);
         }
-        if let Some(read_preference) = dboptions.read_preference {
-            find_one_options.selection_criteria =
-                Some(SelectionCriteria::ReadPreference(read_preference.clone()));
-            find_options.selection_criteria =
-                Some(SelectionCriteria::ReadPreference(read_preference));
-        }
+        let fallback = dboptions.primary_fallback && reads_secondaries(&selection_criteria);
+        find_one_options.selection_criteria = selection_criteria;
+        find_options.selection_criteria = chunks_selection_criteria;
 
         /*
         Drivers must first retrieve the files collection document for this
@@ -91,19 +277,107 @@ impl GridFSBucket {
         existed, is in the process of being deleted, or has been corrupted,
         and the driver MUST raise an error.
         */
-        let file = files.find_one(doc! {"_id":id}, find_one_options).await?;
+        let file = match &session {
+            Some(session) => {
+                session
+                    .find_one(&files, visible(filter), find_one_options)
+                    .await?
+            }
+            None => {
+                let file = files
+                    .find_one(visible(filter.clone()), find_one_options.clone())
+                    .await?;
+                if file.is_none() && fallback {
+                    // The file may not be replicated yet: the file and its chunks are read
+                    // from the primary.
+                    find_one_options.selection_criteria =
+                        Some(SelectionCriteria::ReadPreference(ReadPreference::Primary));
+                    find_options.selection_criteria = find_one_options.selection_criteria.clone();
+                    files.find_one(visible(filter), find_one_options).await?
+                } else {
+                    file
+                }
+            }
+        };
 
         if let Some(file) = file {
-            let filename = file.get_str("filename").unwrap().to_string();
-            let stream = chunks
-                .find(doc! {"files_id":id}, find_options.clone())
-                .await
-                .unwrap()
-                .map(|item| {
-                    let i = item.unwrap();
-                    i.get_binary_generic("data").unwrap().clone()
-                });
-            Ok((stream, filename))
+            if is_expired(&file, self.now()) {
+                return Err(GridFSError::FileExpired());
+            }
+            let id = file
+                .get_object_id("_id")
+                .map_err(|_| GridFSError::InvalidFile("_id isn't an ObjectId".into()))?;
+            let chunks = self
+                .db
+                .collection::<Document>(&chunk_collection_of(&file, &bucket_name));
+            #[cfg(feature = "test-util")]
+            if let Some(chaos) = &self.chaos {
+                chaos.delay().await;
+            }
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            let slot = self.qos.acquire(self.priority).await?;
+            let filter = chunk_filter(&file, id, self.chunk_shard_key());
+            let chunk_size = get_number(&file, "chunkSize")
+                .unwrap_or(0)
+                .clamp(1, u32::MAX as i64) as u32;
+            find_options.batch_size = Some(cursor_batch_size(options, chunk_size));
+            // Only the chunks covering the range are read.
+            let mut first_n = 0;
+            let mut range_filter = filter.clone();
+            if let Some(range) = &options.range {
+                let chunk_size = chunk_size as u64;
+                first_n = (range.start / chunk_size) as i64;
+                let last_n = (range.end.max(range.start + 1) - 1) / chunk_size;
+                range_filter.insert("n", doc! {"$gte":first_n, "$lte":last_n as i64});
+            }
+            // The chunks of a tiered file are fetched from the tier backend, without retry.
+            let (cursor, retries, cursor_refresh) =
+                match (inline_chunks(&file), file.get_str(TIER_FIELD)) {
+                    // The content of an inlined file is in its files collection document.
+                    (Some(cursor), _) => {
+                        let cursor: DocumentStream =
+                            Box::pin(futures_util::StreamExt::skip(cursor, first_n as usize));
+                        (cursor, 0, None)
+                    }
+                    (None, Ok(key)) => {
+                        let cursor = self.fetch_tiered(&file, id, key).await?;
+                        let cursor: DocumentStream =
+                            Box::pin(futures_util::StreamExt::skip(cursor, first_n as usize));
+                        (cursor, 0, None)
+                    }
+                    (None, Err(_)) => (
+                        open_chunks(
+                            chunks.clone(),
+                            range_filter,
+                            find_options.clone(),
+                            session.clone(),
+                        )
+                        .await?,
+                        dboptions.download_retries,
+                        options.cursor_refresh,
+                    ),
+                };
+            let stream = ChunkStream::new(chunks, filter, find_options, cursor, session, retries)
+                .starting_at(first_n)
+                .with_cursor_refresh(cursor_refresh);
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            let stream = stream.with_slot(slot).with_file(self.clone(), id);
+            #[cfg(feature = "test-util")]
+            let stream = stream.with_chaos(self.chaos.clone());
+            #[cfg(feature = "otel")]
+            let stream = stream.with_span(crate::otel::download_span(&file));
+            Ok((stream, file))
+        } else if let Some((legacy, filter, sort)) = legacy {
+            let (stream, file) =
+                Box::pin(legacy.open_chunk_stream_by_filter(filter, sort, skip, options)).await?;
+            #[cfg(all(
+                any(feature = "md5", feature = "sha256"),
+                any(feature = "default", feature = "tokio-runtime")
+            ))]
+            if let Ok(id) = file.get_object_id("_id") {
+                self.copy_through(id);
+            }
+            Ok((stream, file))
         } else {
             Err(GridFSError::FileNotFound())
         }
@@ -114,7 +388,9 @@ impl GridFSBucket {
      specified by @id.
      [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#file-download)
 
-     Returns a [`Stream`].
+     Returns a [`Stream`] of the chunks of the file. An error is yielded if the chunks
+     can't be read. When the chunks cursor fails midway, the download resumes after the
+     last yielded chunk up to [`GridFSBucketOptions::download_retries`](crate::options::GridFSBucketOptions::download_retries) times.
 
      # Examples
 
@@ -150,7 +426,7 @@ impl GridFSBucket {
      #     println!("{}", id);
      #
      let mut cursor = bucket.open_download_stream(id).await?;
-     let buffer = cursor.next().await.unwrap();
+     let buffer = cursor.next().await.unwrap()?;
      #     println!("{:?}", buffer);
      #
      #     db.drop(None).await?;
@@ -161,24 +437,270 @@ impl GridFSBucket {
      # Errors
 
      Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::FileExpired`] when the requested file has expired.
     */
     pub async fn open_download_stream(
         &self,
         id: ObjectId,
-    ) -> Result<impl Stream<Item = Vec<u8>>, GridFSError> {
+    ) -> Result<GridFSDownloadStream, GridFSError> {
         let (stream, _) = self.open_download_stream_with_filename(id).await?;
         Ok(stream)
     }
+
+    /// The download stream of the @chunks of @file, opened with @options.
+    fn download_stream(
+        &self,
+        chunks: ChunkStream,
+        file: &Document,
+        options: &GridFSDownloadOptions,
+    ) -> GridFSDownloadStream {
+        let preferred = self.options.clone().unwrap_or_default().checksum_algorithm;
+        GridFSDownloadStream::with_options(chunks, file, options, preferred)
+    }
+
+    /**
+     Opens a Stream from which the application can read the contents of the first stored
+     file matching @filter, e.g. `{"metadata.sha256": ...}`, in the order @sort, with the
+     download @options.
+
+     Resolves the file and opens its chunks in one call, like
+     [`GridFSBucket::open_download_stream_with_options`] does from an id.
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when no file matches @filter.
+     Raise [`GridFSError::FileExpired`] when the matching file has expired.
+    */
+    pub async fn open_download_stream_by_filter(
+        &self,
+        filter: Document,
+        sort: Option<Document>,
+        options: Option<GridFSDownloadOptions>,
+    ) -> Result<GridFSDownloadStream, GridFSError> {
+        let options = options.unwrap_or_default();
+        let (chunks, file) = self
+            .counted_download(self.open_chunk_stream_by_filter(filter, sort, None, &options))
+            .await?;
+        Ok(self.download_stream(chunks, &file, &options))
+    }
+
+    /**
+     Opens a Stream from which the application can read the contents of the stored file
+     specified by @id, with the download @options.
+
+     Behaves like [`GridFSBucket::open_download_stream`]. With a `range`, the stream yields
+     only the bytes of the range.
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::FileExpired`] when the requested file has expired.
+     The stream ends with [`GridFSError::ChecksumMismatch`] when `verify_checksum` is set
+     and the content doesn't match the checksum of the file.
+    */
+    pub async fn open_download_stream_with_options(
+        &self,
+        id: ObjectId,
+        options: GridFSDownloadOptions,
+    ) -> Result<GridFSDownloadStream, GridFSError> {
+        let (chunks, file) = self
+            .counted_download(self.open_chunk_stream(id, &options))
+            .await?;
+        Ok(self.download_stream(chunks, &file, &options))
+    }
+
+    /**
+     Opens a Stream from which the application can read the contents of the revision
+     `options.revision` of the stored file @filename: by default the most recent one. The
+     revision is downloaded with `options.download`.
+     [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#file-download-by-filename)
+
+     # Errors
+
+     Raise [`GridFSError::FilenameNotFound`] when no file has the name @filename.
+     Raise [`GridFSError::RevisionNotFound`] when @filename has fewer revisions than
+     requested.
+     Raise [`GridFSError::FileExpired`] when the requested revision has expired.
+    */
+    pub async fn open_download_stream_by_name(
+        &self,
+        filename: &str,
+        options: Option<GridFSDownloadByNameOptions>,
+    ) -> Result<GridFSDownloadStream, GridFSError> {
+        let options = options.unwrap_or_default();
+        let revision = options.revision;
+        let (sort, skip) = if revision >= 0 {
+            (doc! {"uploadDate":1}, revision as u64)
+        } else {
+            (doc! {"uploadDate":-1}, (-(revision as i64) - 1) as u64)
+        };
+        match self
+            .counted_download(self.open_chunk_stream_by_filter(
+                doc! {"filename":filename},
+                Some(sort),
+                Some(skip),
+                &options.download,
+            ))
+            .await
+        {
+            Ok((chunks, file)) => Ok(self.download_stream(chunks, &file, &options.download)),
+            Err(GridFSError::FileNotFound()) => Err(match self.is_stored(filename).await? {
+                true => GridFSError::RevisionNotFound {
+                    filename: filename.to_owned(),
+                    revision,
+                },
+                false => GridFSError::FilenameNotFound {
+                    filename: filename.to_owned(),
+                },
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    /**
+     Opens a Stream from which the application can read the contents of the revision of the
+     stored file @filename which was current at @date: the last one uploaded at or before
+     @date, e.g. to reproduce an output computed from the file at that date. The revision is
+     downloaded with the download @options.
+
+     # Errors
+
+     Raise [`GridFSError::FilenameNotFound`] when no file has the name @filename.
+     Raise [`GridFSError::RevisionNotFoundAt`] when every revision of @filename was uploaded
+     after @date.
+     Raise [`GridFSError::FileExpired`] when the revision has expired.
+    */
+    pub async fn open_download_stream_as_of(
+        &self,
+        filename: &str,
+        date: DateTime,
+        options: Option<GridFSDownloadOptions>,
+    ) -> Result<GridFSDownloadStream, GridFSError> {
+        let options = options.unwrap_or_default();
+        match self
+            .counted_download(self.open_chunk_stream_by_filter(
+                doc! {"filename":filename, "uploadDate":{"$lte":date}},
+                Some(doc! {"uploadDate":-1, "_id":-1}),
+                None,
+                &options,
+            ))
+            .await
+        {
+            Ok((chunks, file)) => Ok(self.download_stream(chunks, &file, &options)),
+            Err(GridFSError::FileNotFound()) => Err(match self.is_stored(filename).await? {
+                true => GridFSError::RevisionNotFoundAt {
+                    filename: filename.to_owned(),
+                    date,
+                },
+                false => GridFSError::FilenameNotFound {
+                    filename: filename.to_owned(),
+                },
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Whether a revision of @filename is stored.
+    async fn is_stored(&self, filename: &str) -> Result<bool, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let find_one_options = FindOneOptions::builder()
+            .projection(doc! {"_id":1})
+            .selection_criteria(dboptions.read_selection_criteria())
+            .read_concern(dboptions.read_concern)
+            .build();
+        Ok(self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name + ".files"))
+            .find_one(visible(doc! {"filename":filename}), find_one_options)
+            .await?
+            .is_some())
+    }
+
+    /**
+     Downloads the file @id into the channel @sender with the download @options, one frame
+     per chunk, and returns the number of bytes sent. Requires a tokio runtime.
+
+     The download waits while the channel is full. It stops when the receiver is dropped.
+     The receiver only sees the channel closed: the returned result tells whether the file
+     was sent whole.
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::FileExpired`] when the requested file has expired.
+    */
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub async fn download_to_channel(
+        &self,
+        id: ObjectId,
+        sender: mpsc::Sender<Bytes>,
+        options: Option<GridFSDownloadOptions>,
+    ) -> Result<u64, GridFSError> {
+        self.download_to_channels(id, vec![sender], options).await
+    }
+
+    /**
+     Downloads the file @id into each channel of @senders with the download @options, e.g. to
+     hash the file while it's sent in a response, and returns the number of bytes sent. The
+     chunks are read once: every channel receives the same frames. Requires a tokio runtime.
+
+     The download goes at the pace of the slowest receiver. A dropped receiver stops receiving
+     the frames, and the download stops once every receiver is dropped.
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::FileExpired`] when the requested file has expired.
+    */
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub async fn download_to_channels(
+        &self,
+        id: ObjectId,
+        mut senders: Vec<mpsc::Sender<Bytes>>,
+        options: Option<GridFSDownloadOptions>,
+    ) -> Result<u64, GridFSError> {
+        let mut stream = self
+            .open_download_stream_with_options(id, options.unwrap_or_default())
+            .await?;
+        let mut length = 0;
+        while !senders.is_empty() {
+            let data = match stream.next().await {
+                Some(data) => Bytes::from(data?),
+                None => break,
+            };
+            let mut open = Vec::with_capacity(senders.len());
+            for sender in senders {
+                if sender.send(data.clone()).await.is_ok() {
+                    open.push(sender);
+                }
+            }
+            if !open.is_empty() {
+                length += data.len() as u64;
+            }
+            senders = open;
+        }
+        Ok(length)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::GridFSBucket;
-    use crate::{options::GridFSBucketOptions, GridFSError};
-    use bson::oid::ObjectId;
+    use super::{cursor_batch_size, GridFSBucket};
+    use crate::{
+        options::{
+            ChunkBinarySubtype, GridFSBucketOptions, GridFSDownloadByNameOptions,
+            GridFSDownloadOptions, GridFSUploadOptions,
+        },
+        GridFSError,
+    };
+    use bson::{doc, oid::ObjectId, spec::BinarySubtype, DateTime, Document};
     #[cfg(feature = "async-std-runtime")]
     use futures::stream::StreamExt;
+    use futures_util::lock::Mutex;
     use mongodb::{Client, Database};
+    use std::{sync::Arc, time::Duration};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio::sync::mpsc;
     #[cfg(any(feature = "default", feature = "tokio-runtime"))]
     use tokio_stream::StreamExt;
     use uuid::Uuid;
@@ -190,6 +712,267 @@ mod tests {
                 .encode_lower(&mut Uuid::encode_buffer())
     }
 
+    #[test]
+    fn cursor_batch_size_default() {
+        let options = GridFSDownloadOptions::default();
+        assert_eq!(cursor_batch_size(&options, 255 * 1024), 32);
+        assert_eq!(cursor_batch_size(&options, 15 * 1024 * 1024), 1);
+        assert_eq!(cursor_batch_size(&options, 0), 8 * 1024 * 1024);
+        let options = GridFSDownloadOptions::builder().prefetch(Some(4)).build();
+        assert_eq!(cursor_batch_size(&options, 255 * 1024), 4);
+        let options = GridFSDownloadOptions::builder()
+            .cursor_batch_size(Some(2))
+            .prefetch(Some(4))
+            .build();
+        assert_eq!(cursor_batch_size(&options, 255 * 1024), 2);
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_with_cursor_refresh() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data 1234567890".as_bytes(), None)
+            .await?;
+
+        // The cursor is reopened after each chunk.
+        let options = GridFSDownloadOptions::builder()
+            .cursor_batch_size(Some(2))
+            .no_cursor_timeout(Some(true))
+            .cursor_refresh(Some(Duration::ZERO))
+            .build();
+        let mut cursor = bucket
+            .open_download_stream_with_options(id, options)
+            .await?;
+        let mut content = vec![];
+        while let Some(data) = cursor.next().await {
+            content.extend(data?);
+        }
+        assert_eq!(content, b"test data 1234567890");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_with_options() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let options = GridFSDownloadOptions::builder()
+            .range(Some(3..7))
+            .prefetch(Some(1))
+            .build();
+        let mut cursor = bucket
+            .open_download_stream_with_options(id, options)
+            .await?;
+        let mut content = vec![];
+        while let Some(data) = cursor.next().await {
+            content.extend(data?);
+        }
+        assert_eq!(content, b"t da");
+
+        #[cfg(any(feature = "md5", feature = "sha256"))]
+        {
+            let options = GridFSDownloadOptions::builder()
+                .verify_checksum(true)
+                .build();
+            let mut cursor = bucket
+                .open_download_stream_with_options(id, options.clone())
+                .await?;
+            while let Some(data) = cursor.next().await {
+                data?;
+            }
+
+            db.collection::<Document>("fs.files")
+                .update_one(doc! {"_id":id}, doc! {"$set":{"md5":"0"}}, None)
+                .await?;
+            let mut cursor = bucket
+                .open_download_stream_with_options(id, options)
+                .await?;
+            let mut result = Ok(());
+            while let Some(data) = cursor.next().await {
+                if let Err(error) = data {
+                    result = Err(error);
+                }
+            }
+            assert!(matches!(
+                result,
+                Err(GridFSError::ChecksumMismatch { expected, .. }) if expected == "0"
+            ));
+        }
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[tokio::test]
+    async fn download_to_channels() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let (sender, mut receiver) = mpsc::channel(1);
+        let download = tokio::spawn({
+            let bucket = bucket.clone();
+            async move { bucket.download_to_channel(id, sender, None).await }
+        });
+        let mut content = vec![];
+        while let Some(frame) = receiver.recv().await {
+            content.extend(frame);
+        }
+        assert_eq!(content, b"test data");
+        assert_eq!(download.await.unwrap()?, 9);
+
+        // A dropped receiver doesn't stop the others.
+        let (sender, mut receiver) = mpsc::channel(4);
+        let (dropped, _) = mpsc::channel(4);
+        assert_eq!(
+            bucket
+                .download_to_channels(id, vec![sender, dropped], None)
+                .await?,
+            9
+        );
+        assert_eq!(receiver.recv().await.unwrap(), "test");
+        assert_eq!(receiver.recv().await.unwrap(), " dat");
+        assert_eq!(receiver.recv().await.unwrap(), "a");
+        assert!(receiver.recv().await.is_none());
+
+        assert!(matches!(
+            bucket
+                .download_to_channel(ObjectId::new(), mpsc::channel(1).0, None)
+                .await,
+            Err(GridFSError::FileNotFound())
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_by_name() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        for content in ["first", "second", "third"] {
+            bucket
+                .upload_from_stream("test.txt", content.as_bytes(), None)
+                .await?;
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let mut cursor = bucket
+            .open_download_stream_by_name("test.txt", None)
+            .await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"third");
+        for (revision, content) in [(0, "first"), (1, "second"), (-2, "second"), (-3, "first")] {
+            let mut cursor = bucket
+                .open_download_stream_by_name(
+                    "test.txt",
+                    Some(
+                        GridFSDownloadByNameOptions::builder()
+                            .revision(revision)
+                            .build(),
+                    ),
+                )
+                .await?;
+            assert_eq!(cursor.next().await.unwrap()?, content.as_bytes());
+        }
+
+        assert!(matches!(
+            bucket
+                .open_download_stream_by_name(
+                    "test.txt",
+                    Some(GridFSDownloadByNameOptions::builder().revision(3).build()),
+                )
+                .await,
+            Err(GridFSError::RevisionNotFound { revision: 3, .. })
+        ));
+        assert!(matches!(
+            bucket.open_download_stream_by_name("missing.txt", None).await,
+            Err(GridFSError::FilenameNotFound { filename }) if filename == "missing.txt"
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_as_of() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        for (content, date) in [("first", 1000), ("second", 2000), ("third", 3000)] {
+            let options = GridFSUploadOptions::builder()
+                .upload_date(Some(DateTime::from_millis(date)))
+                .build();
+            bucket
+                .upload_from_stream("test.txt", content.as_bytes(), Some(options))
+                .await?;
+        }
+
+        for (date, content) in [(1000, "first"), (2999, "second"), (5000, "third")] {
+            let mut cursor = bucket
+                .open_download_stream_as_of("test.txt", DateTime::from_millis(date), None)
+                .await?;
+            assert_eq!(cursor.next().await.unwrap()?, content.as_bytes());
+        }
+
+        assert!(matches!(
+            bucket
+                .open_download_stream_as_of("test.txt", DateTime::from_millis(999), None)
+                .await,
+            Err(GridFSError::RevisionNotFoundAt { .. })
+        ));
+        assert!(matches!(
+            bucket
+                .open_download_stream_as_of("missing.txt", DateTime::from_millis(5000), None)
+                .await,
+            Err(GridFSError::FilenameNotFound { .. })
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn open_download_stream() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
@@ -207,11 +990,99 @@ mod tests {
         assert_eq!(id.to_hex(), id.to_hex());
 
         let mut cursor = bucket.open_download_stream(id).await?;
-        let buffer = cursor.next().await.unwrap();
+        let buffer = cursor.next().await.unwrap()?;
         assert_eq!(buffer, [116, 101, 115, 116, 32, 100, 97, 116, 97]);
         db.drop(None).await?;
         Ok(())
     }
+    #[tokio::test]
+    async fn open_download_stream_by_filter() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = &GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        for (data, sha) in [
+            ("old data", "abc"),
+            ("new data", "abc"),
+            ("other data", "def"),
+        ] {
+            bucket
+                .clone()
+                .upload_from_stream(
+                    "test.txt",
+                    data.as_bytes(),
+                    Some(
+                        GridFSUploadOptions::builder()
+                            .metadata(Some(doc! {"sha256":sha}))
+                            .build(),
+                    ),
+                )
+                .await?;
+        }
+
+        let mut cursor = bucket
+            .open_download_stream_by_filter(
+                doc! {"metadata.sha256":"abc"},
+                Some(doc! {"uploadDate":-1, "_id":-1}),
+                None,
+            )
+            .await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"new data");
+        assert!(matches!(
+            bucket
+                .open_download_stream_by_filter(doc! {"metadata.sha256":"none"}, None, None)
+                .await,
+            Err(GridFSError::FileNotFound())
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_in_session() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = &GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        bucket
+            .clone()
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let session = Arc::new(Mutex::new(client.start_session(None).await?));
+        let options = GridFSDownloadByNameOptions::builder()
+            .download(
+                GridFSDownloadOptions::builder()
+                    .session(Some(session.clone()))
+                    .range(Some(2..7))
+                    .build(),
+            )
+            .build();
+        let mut cursor = bucket
+            .open_download_stream_by_name("test.txt", Some(options))
+            .await?;
+        let mut content = vec![];
+        while let Some(data) = cursor.next().await {
+            content.extend(data?);
+        }
+        assert_eq!(content, b"st da");
+        // The session is only locked by the queries of the download.
+        assert!(session.try_lock().is_some());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn open_download_stream_chunk_size() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
@@ -232,17 +1103,178 @@ mod tests {
         assert_eq!(id.to_hex(), id.to_hex());
 
         let mut cursor = bucket.open_download_stream(id).await?;
-        let buffer = cursor.next().await.unwrap();
+        let buffer = cursor.next().await.unwrap()?;
         assert_eq!(buffer, [116, 101, 115, 116]);
 
-        let buffer = cursor.next().await.unwrap();
+        let buffer = cursor.next().await.unwrap()?;
         assert_eq!(buffer, [32, 100, 97, 116]);
 
-        let buffer = cursor.next().await.unwrap();
+        let buffer = cursor.next().await.unwrap()?;
         assert_eq!(buffer, [97]);
 
-        let buffer = cursor.next().await;
-        assert_eq!(buffer, None);
+        assert!(cursor.next().await.is_none());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_binary_old() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(4)
+                    .chunk_binary_subtype(ChunkBinarySubtype::BinaryOld)
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let chunk = db
+            .collection::<Document>("fs.chunks")
+            .find_one(doc! { "files_id": id, "n": 0 }, None)
+            .await?
+            .unwrap();
+        assert!(matches!(
+            chunk.get("data"),
+            Some(bson::Bson::Binary(bson::Binary {
+                subtype: BinarySubtype::BinaryOld,
+                ..
+            }))
+        ));
+
+        let mut content = vec![];
+        let mut cursor = bucket.open_download_stream(id).await?;
+        while let Some(chunk) = cursor.next().await {
+            content.extend(chunk?);
+        }
+        assert_eq!(content, b"test data");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_invalid_chunk() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        db.collection::<Document>("fs.chunks")
+            .update_one(
+                doc! { "files_id": id, "n": 0 },
+                doc! { "$set": { "data": "not binary" } },
+                None,
+            )
+            .await?;
+
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert!(matches!(
+            cursor.next().await,
+            Some(Err(GridFSError::InvalidChunk(0, _)))
+        ));
+        assert!(cursor.next().await.is_none());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_without_filename() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        db.collection::<Document>("fs.files")
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$unset": { "filename": "" } },
+                None,
+            )
+            .await?;
+
+        let (mut cursor, filename) = bucket.open_download_stream_with_filename(id).await?;
+        assert_eq!(filename, None);
+        assert_eq!(cursor.next().await.unwrap()?, b"test data");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_download_stream_expired() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .expire_after(Some(Duration::from_secs(24 * 3600)))
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let chunk = db
+            .collection::<Document>("fs.chunks")
+            .find_one(doc! { "files_id": id }, None)
+            .await?
+            .unwrap();
+        assert!(chunk.get_datetime("expireAt").is_ok());
+        let indexes = db
+            .run_command(doc! {"listIndexes":"fs.chunks"}, None)
+            .await?;
+        let ttl_index = indexes
+            .get_document("cursor")
+            .unwrap()
+            .get_array("firstBatch")
+            .unwrap()
+            .iter()
+            .any(|index| {
+                index.as_document().unwrap().get_str("name") == Ok("fs.chunks_expire_index")
+            });
+        assert!(ttl_index);
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test data");
+
+        db.collection::<Document>("fs.files")
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$set": { "expireAt": bson::DateTime::from_millis(0) } },
+                None,
+            )
+            .await?;
+        assert!(matches!(
+            bucket.open_download_stream(id).await,
+            Err(GridFSError::FileExpired())
+        ));
 
         db.drop(None).await?;
         Ok(())
@@ -1,11 +1,43 @@
 use crate::bucket::GridFSBucket;
-use bson::Document;
-use mongodb::error::Result;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use crate::GridFSError;
+use bson::{doc, Document};
+use mongodb::{error::Result, options::DeleteOptions};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use std::time::Duration;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::sync::OwnedRwLockReadGuard;
+
+/// Held by a write of the bucket until it ends: [`GridFSBucket::drop_guarded`] waits for it.
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+pub(crate) type WriteGuard = OwnedRwLockReadGuard<()>;
+/// Without the tokio runtime, there is no [`GridFSBucket::drop_guarded`] to wait for the writes.
+#[cfg(not(any(feature = "default", feature = "tokio-runtime")))]
+pub(crate) struct WriteGuard;
 
 impl GridFSBucket {
+    /// Waits for a pending [`GridFSBucket::drop_guarded`], then holds off the next ones
+    /// until the returned guard is dropped. Taken once by each write operation, and not by
+    /// the operations it calls: a nested guard would wait forever behind a pending drop.
+    pub(crate) async fn write_guard(&self) -> WriteGuard {
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        return self.writers.clone().read_owned().await;
+        #[cfg(not(any(feature = "default", feature = "tokio-runtime")))]
+        WriteGuard
+    }
+
+    /// Like [`GridFSBucket::write_guard`], but `None` instead of waiting when a drop is
+    /// pending, for the best effort writes.
+    pub(crate) fn try_write_guard(&self) -> Option<WriteGuard> {
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        return self.writers.clone().try_read_owned().ok();
+        #[cfg(not(any(feature = "default", feature = "tokio-runtime")))]
+        Some(WriteGuard)
+    }
+
     /**
     Drops the files and chunks collections associated with this
-    bucket.
+    bucket, and its content collection with the `content-search` feature.
     [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#dropping-an-entire-gridfs-bucket)
      */
     pub async fn drop(&self) -> Result<()> {
@@ -21,6 +53,12 @@ impl GridFSBucket {
         // FIXME: MongoError(Error { kind: CommandError(CommandError { code: 14, code_name: "TypeMismatch", message: "\"writeConcern\" had the wrong type. Expected object, found null", labels: [] }), labels: [] })
         files.drop(None).await?;
 
+        #[cfg(feature = "content-search")]
+        self.db
+            .collection::<Document>(&(bucket_name.clone() + ".content"))
+            .drop(None)
+            .await?;
+
         let chunk_collection = bucket_name + ".chunks";
         let chunks = self.db.collection::<Document>(&chunk_collection);
 
@@ -32,6 +70,55 @@ impl GridFSBucket {
 
         Ok(())
     }
+
+    /**
+    Deletes every file of this bucket, but keeps its collections and their indexes: faster
+    than a [`GridFSBucket::drop`], and doesn't need the privilege to drop or create
+    collections.
+     */
+    pub async fn clear(&self) -> Result<()> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let bucket_name = dboptions.bucket_name;
+        let delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern)
+            .build();
+
+        // The files first, so the readers don't find files whose chunks are gone.
+        self.db
+            .collection::<Document>(&(bucket_name.clone() + ".files"))
+            .delete_many(doc! {}, delete_options.clone())
+            .await?;
+        #[cfg(feature = "content-search")]
+        self.db
+            .collection::<Document>(&(bucket_name.clone() + ".content"))
+            .delete_many(doc! {}, delete_options.clone())
+            .await?;
+        self.db
+            .collection::<Document>(&(bucket_name + ".chunks"))
+            .delete_many(doc! {}, delete_options)
+            .await?;
+
+        Ok(())
+    }
+
+    /**
+    Drops the bucket like [`GridFSBucket::drop`], once the writes in flight on this bucket
+    and its clones have ended: the uploads, the deletes, the renames, the migrations and the
+    maintenance jobs. New writes wait until the drop is done.
+
+    # Errors
+
+    Raise [`GridFSError::BucketBusy`] when writes are still in flight after @timeout.
+    */
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub async fn drop_guarded(&self, timeout: Duration) -> std::result::Result<(), GridFSError> {
+        let _writers = tokio::time::timeout(timeout, self.writers.write())
+            .await
+            .map_err(|_| GridFSError::BucketBusy())?;
+        self.drop().await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -39,8 +126,11 @@ mod tests {
     use super::GridFSBucket;
     use crate::{options::GridFSBucketOptions, GridFSError};
 
+    use bson::Document;
     use mongodb::Client;
     use mongodb::Database;
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use std::time::Duration;
     use uuid::Uuid;
     fn db_name_new() -> String {
         "test_".to_owned()
@@ -75,4 +165,85 @@ mod tests {
         db.drop(None).await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn clear_bucket() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = &GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        bucket
+            .clone()
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        bucket.clear().await?;
+
+        let files = db.collection::<Document>("fs.files");
+        assert_eq!(files.count_documents(None, None).await?, 0);
+        assert_eq!(
+            db.collection::<Document>("fs.chunks")
+                .count_documents(None, None)
+                .await?,
+            0
+        );
+        let index_names = files.list_index_names().await?;
+        assert!(index_names.contains(&"fs.files_index".to_string()));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[tokio::test]
+    async fn drop_guarded() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = &GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        bucket
+            .clone()
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        // A write in flight on a clone of the bucket.
+        let writer = bucket.clone().write_guard().await;
+        assert!(matches!(
+            bucket.drop_guarded(Duration::from_millis(50)).await,
+            Err(GridFSError::BucketBusy())
+        ));
+        assert!(db
+            .list_collection_names(None)
+            .await?
+            .contains(&"fs.files".to_string()));
+
+        drop(writer);
+        bucket.drop_guarded(Duration::from_millis(50)).await?;
+        assert!(db.list_collection_names(None).await?.is_empty());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[tokio::test]
+    async fn try_write_guard() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str("mongodb://localhost:27017/").await?;
+        let bucket = GridFSBucket::new(client.database(&db_name_new()), None);
+
+        // A drop pending on a clone of the bucket.
+        let dropping = bucket.clone().writers.clone().write_owned().await;
+        assert!(bucket.try_write_guard().is_none());
+        drop(dropping);
+        let writer = bucket.try_write_guard();
+        assert!(writer.is_some());
+        assert!(bucket.clone().try_write_guard().is_some());
+        Ok(())
+    }
 }
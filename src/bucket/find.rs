@@ -1,12 +1,16 @@
-use crate::{bucket::GridFSBucket, options::GridFSFindOptions};
-use bson::Document;
+use crate::{
+    bucket::{status::with_status, GridFSBucket},
+    options::GridFSFindOptions,
+};
+use bson::{doc, Document};
 use mongodb::error::Result;
 use mongodb::options::FindOptions;
 use mongodb::Cursor;
 
 impl GridFSBucket {
     /**
-    Find and return the files collection documents that match @filter.
+    Find and return the files collection documents that match @filter. Only the available
+    files are found, unless [`GridFSFindOptions::status`] requests another state.
     [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#generic-find-on-files-collection)
 
     # Examples
@@ -48,21 +52,51 @@ impl GridFSBucket {
         options: GridFSFindOptions,
     ) -> Result<Cursor<Document>> {
         let dboptions = self.options.clone().unwrap_or_default();
+        let selection_criteria = dboptions.read_selection_criteria();
         let bucket_name = dboptions.bucket_name;
         let file_collection = bucket_name + ".files";
         let files = self.db.collection::<Document>(&file_collection);
 
         let find_options = FindOptions::builder()
             .allow_disk_use(options.allow_disk_use)
+            .batch_size(options.batch_size)
             .limit(options.limit)
             .max_time(options.max_time)
             .no_cursor_timeout(options.no_cursor_timeout)
             .skip(options.skip)
             .sort(options.sort)
+            .projection(options.projection)
+            .collation(options.collation)
+            .comment_bson(options.comment)
             .read_concern(dboptions.read_concern)
+            .selection_criteria(selection_criteria)
             .build();
 
-        files.find(filter, find_options).await
+        files
+            .find(
+                with_status(filter, options.status.unwrap_or_default()),
+                find_options,
+            )
+            .await
+    }
+
+    /**
+    Returns the @n most recent files matching @filter, the latest uploaded first.
+
+    With an equality on `filename` in @filter, the files are read from the
+    `{filename, uploadDate}` index of the bucket, in order. A filter without an equality on
+    `filename` doesn't use the index: the server sorts the matching files in memory,
+    keeping only @n of them.
+    */
+    pub async fn recent(&self, n: u32, filter: Document) -> Result<Cursor<Document>> {
+        self.find(
+            filter,
+            GridFSFindOptions::builder()
+                .sort(Some(doc! {"uploadDate":-1}))
+                .limit(Some(n as i64))
+                .build(),
+        )
+        .await
     }
 }
 
@@ -76,7 +110,10 @@ mod tests {
     use bson::doc;
     #[cfg(feature = "async-std-runtime")]
     use futures::stream::StreamExt;
-    use mongodb::{Client, Database};
+    use mongodb::{
+        options::{Collation, CollationStrength},
+        Client, Database,
+    };
     #[cfg(any(feature = "default", feature = "tokio-runtime"))]
     use tokio_stream::StreamExt;
     use uuid::Uuid;
@@ -88,6 +125,44 @@ mod tests {
                 .encode_lower(&mut Uuid::encode_buffer())
     }
 
+    #[tokio::test]
+    async fn recent() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let mut ids = vec![];
+        for filename in ["a.txt", "b.txt", "a.txt", "a.txt"] {
+            ids.push(
+                bucket
+                    .upload_from_stream(filename, "test data".as_bytes(), None)
+                    .await?,
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+
+        let mut cursor = bucket.recent(2, doc! {"filename":"a.txt"}).await?;
+        let mut found = vec![];
+        while let Some(file) = cursor.next().await {
+            found.push(file?.get_object_id("_id").unwrap());
+        }
+        assert_eq!(found, vec![ids[3], ids[2]]);
+
+        let mut cursor = bucket.recent(10, doc! {}).await?;
+        let mut found = vec![];
+        while let Some(file) = cursor.next().await {
+            found.push(file?.get_object_id("_id").unwrap());
+        }
+        ids.reverse();
+        assert_eq!(found, ids);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn find_a_file() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
@@ -142,10 +217,48 @@ mod tests {
             .find(doc! {"filename":"null.txt"}, GridFSFindOptions::default())
             .await?;
 
-        match cursor.next().await {
-            None => assert!(true),
-            Some(_) => assert!(false),
+        assert!(cursor.next().await.is_none());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn find_with_options() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        for filename in ["a.txt", "B.txt", "c.txt"] {
+            bucket
+                .upload_from_stream(filename, "test data".as_bytes(), None)
+                .await?;
+        }
+
+        let options = GridFSFindOptions::builder()
+            .skip(Some(1))
+            .batch_size(Some(1))
+            .sort(Some(doc! {"filename":1}))
+            .projection(Some(doc! {"filename":1, "_id":0}))
+            .collation(Some(
+                Collation::builder()
+                    .locale("en")
+                    .strength(CollationStrength::Secondary)
+                    .build(),
+            ))
+            .comment(Some("find_with_options".into()))
+            .build();
+        let mut cursor = bucket.find(doc! {}, options).await?;
+        let mut filenames = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            assert_eq!(doc.len(), 1);
+            filenames.push(doc.get_str("filename").unwrap().to_string());
         }
+        assert_eq!(filenames, vec!["B.txt", "c.txt"]);
 
         db.drop(None).await?;
         Ok(())
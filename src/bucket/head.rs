@@ -0,0 +1,183 @@
+use crate::{bucket::GridFSBucket, options::GridFSDownloadOptions, GridFSError};
+use bson::oid::ObjectId;
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+impl GridFSBucket {
+    /**
+     Reads at most @max_bytes from the beginning of the stored file specified by @id, with the
+     download @options.
+
+     Only the chunks needed to cover @max_bytes are fetched from the chunks collection,
+     which makes it suitable for previews or snippets of large text files.
+
+     # Examples
+
+     ```rust
+     # use mongodb::Client;
+     # use mongodb::Database;
+     use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket, GridFSError};
+     # use uuid::Uuid;
+     # fn db_name_new() -> String {
+     #     "test_".to_owned()
+     #         + Uuid::new_v4()
+     #             .hyphenated()
+     #             .encode_lower(&mut Uuid::encode_buffer())
+     # }
+     #
+     # #[tokio::main]
+     # async fn main() -> Result<(), GridFSError> {
+     #     let client = Client::with_uri_str(
+     #         &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+     #     )
+     #     .await?;
+     #     let dbname = db_name_new();
+     #     let db: Database = client.database(&dbname);
+     let bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+     #     let id = bucket
+     #         .clone()
+     #         .upload_from_stream("README.md", "# Title\n\nSome long text".as_bytes(), None)
+     #         .await?;
+     let preview = bucket.read_head(id, 7, None).await?;
+     assert_eq!(preview, b"# Title");
+     #
+     #     db.drop(None).await?;
+     #     Ok(())
+     # }
+     ```
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::FileExpired`] when the requested file has expired.
+    */
+    pub async fn read_head(
+        &self,
+        id: ObjectId,
+        max_bytes: usize,
+        options: Option<GridFSDownloadOptions>,
+    ) -> Result<Vec<u8>, GridFSError> {
+        self.read_range(id, 0, max_bytes, options).await
+    }
+
+    /**
+     Reads at most @length bytes of the stored file specified by @id, starting at @offset,
+     with the download @options: their `range` is replaced by the requested one.
+
+     Only the chunks covering the requested range are fetched from the chunks collection,
+     which gives random access to the content of a file without downloading it.
+     The returned buffer is shorter than @length when the range goes past the end of the file.
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::FileExpired`] when the requested file has expired.
+    */
+    pub async fn read_range(
+        &self,
+        id: ObjectId,
+        offset: u64,
+        length: usize,
+        options: Option<GridFSDownloadOptions>,
+    ) -> Result<Vec<u8>, GridFSError> {
+        let mut options = options.unwrap_or_default();
+        options.range = Some(offset..offset.saturating_add(length as u64));
+        let mut stream = self.open_download_stream_with_options(id, options).await?;
+        let mut range = Vec::new();
+        while let Some(data) = stream.next().await {
+            range.extend(data?);
+        }
+        Ok(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{options::GridFSBucketOptions, GridFSError};
+    use bson::oid::ObjectId;
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn read_head() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = &GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(8).build()),
+        );
+        let id = bucket
+            .clone()
+            .upload_from_stream("test.txt", "test data 1234567890".as_bytes(), None)
+            .await?;
+
+        assert_eq!(bucket.read_head(id, 0, None).await?, b"");
+        assert_eq!(bucket.read_head(id, 4, None).await?, b"test");
+        assert_eq!(bucket.read_head(id, 10, None).await?, b"test data ");
+        assert_eq!(
+            bucket.read_head(id, 100, None).await?,
+            b"test data 1234567890"
+        );
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_range() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = &GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(8).build()),
+        );
+        let id = bucket
+            .clone()
+            .upload_from_stream("test.txt", "test data 1234567890".as_bytes(), None)
+            .await?;
+
+        assert_eq!(bucket.read_range(id, 5, 4, None).await?, b"data");
+        assert_eq!(bucket.read_range(id, 8, 8, None).await?, b"a 123456");
+        assert_eq!(bucket.read_range(id, 6, 10, None).await?, b"ata 123456");
+        assert_eq!(bucket.read_range(id, 16, 10, None).await?, b"7890");
+        assert_eq!(bucket.read_range(id, 30, 10, None).await?, b"");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_head_not_existing_file() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = &GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+
+        let head = bucket.read_head(ObjectId::new(), 10, None).await;
+        assert!(head.is_err());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{bucket::GridFSBucket, is_unauthorized, GridFSError};
+use bson::{doc, Document};
+use mongodb::options::FindOneOptions;
+use std::time::{Duration, Instant};
+
+/// The actions the uploads and the deletes run on the collections of the bucket.
+const WRITE_ACTIONS: [&str; 3] = ["insert", "update", "remove"];
+
+/// What a bucket can do on its deployment, returned by [`GridFSBucket::healthcheck`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BucketHealth {
+    /// The round trip time of a `ping` of the server.
+    pub latency: Duration,
+    /// Whether the user can read the files and the chunks collections.
+    pub can_read: bool,
+    /// Whether the user can insert, update and remove the documents of the files and the
+    /// chunks collections.
+    pub can_write: bool,
+    /// Whether the user can create the indexes of the files and the chunks collections.
+    pub can_create_indexes: bool,
+    /// Whether the files collection has its `filename`, `uploadDate` index.
+    pub files_index: bool,
+    /// Whether the chunks collection has its `files_id`, `n` index.
+    pub chunks_index: bool,
+}
+
+impl BucketHealth {
+    /// Whether the bucket can serve the downloads and the uploads: the indexes are created by
+    /// the first upload when they are missing, which the user must be allowed to.
+    pub fn is_ready(&self) -> bool {
+        self.can_read
+            && self.can_write
+            && ((self.files_index && self.chunks_index) || self.can_create_indexes)
+    }
+}
+
+/// Whether one of the @privileges of the user, as listed by `connectionStatus`, allows the
+/// @action on the collection @collection of the database @db.
+fn is_granted(privileges: &[Document], db: &str, collection: &str, action: &str) -> bool {
+    privileges.iter().any(|privilege| {
+        let applies = privilege.get_document("resource").is_ok_and(|resource| {
+            resource.get_bool("anyResource").unwrap_or(false)
+                || (resource
+                    .get_str("db")
+                    .is_ok_and(|name| name.is_empty() || name == db)
+                    && resource
+                        .get_str("collection")
+                        .is_ok_and(|name| name.is_empty() || name == collection))
+        });
+        applies
+            && privilege
+                .get_array("actions")
+                .is_ok_and(|actions| actions.iter().any(|a| a.as_str() == Some(action)))
+    })
+}
+
+impl GridFSBucket {
+    /**
+    Pings the server of the bucket. Returns the round trip time.
+
+    # Errors
+
+    Raise [`GridFSError::MongoError`] when the server can't be reached.
+    */
+    pub async fn ping(&self) -> Result<Duration, GridFSError> {
+        let started = Instant::now();
+        self.db.run_command(doc! {"ping":1}, None).await?;
+        Ok(started.elapsed())
+    }
+
+    /**
+    Probes the bucket without writing: the server is pinged, the collections of the bucket
+    are read, and the privileges of the user and the indexes of the collections are listed.
+    Returns the [`BucketHealth`] of the bucket, e.g. for a readiness probe checking
+    [`BucketHealth::is_ready`] before serving traffic.
+
+    The bucket is initialized lazily: its collections and indexes are only created by the
+    first upload. Without access control, the user can do anything.
+
+    # Errors
+
+    Raise [`GridFSError::MongoError`] when the server can't be reached.
+    */
+    pub async fn healthcheck(&self) -> Result<BucketHealth, GridFSError> {
+        let latency = self.ping().await?;
+        let bucket_name = self.options.clone().unwrap_or_default().bucket_name;
+        let file_collection = bucket_name.clone() + ".files";
+        let chunk_collection = bucket_name + ".chunks";
+        let collections = [file_collection.as_str(), chunk_collection.as_str()];
+
+        let mut can_read = true;
+        for collection in collections {
+            let find_options = FindOneOptions::builder().projection(doc! {"_id":1}).build();
+            match self
+                .db
+                .collection::<Document>(collection)
+                .find_one(doc! {}, find_options)
+                .await
+            {
+                Ok(_) => {}
+                Err(error) if is_unauthorized(&error) => can_read = false,
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        let status = self
+            .db
+            .run_command(doc! {"connectionStatus":1, "showPrivileges":true}, None)
+            .await?;
+        let auth_info = status.get_document("authInfo").cloned().unwrap_or_default();
+        // Unauthenticated reads are only allowed without access control.
+        let unrestricted = can_read
+            && auth_info
+                .get_array("authenticatedUsers")
+                .map_or(true, |users| users.is_empty());
+        let privileges: Vec<Document> = auth_info
+            .get_array("authenticatedUserPrivileges")
+            .map(|privileges| {
+                privileges
+                    .iter()
+                    .filter_map(|privilege| privilege.as_document().cloned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let granted = |action: &str| {
+            unrestricted
+                || collections
+                    .iter()
+                    .all(|collection| is_granted(&privileges, self.db.name(), collection, action))
+        };
+        let can_write = WRITE_ACTIONS.iter().all(|action| granted(action));
+        let can_create_indexes = granted("createIndex");
+
+        let files_index = can_read
+            && self
+                .has_index(&file_collection, &["filename", "uploadDate"])
+                .await?;
+        let chunks_index = can_read
+            && self
+                .has_index(&chunk_collection, &self.chunks_index_fields())
+                .await?;
+        Ok(BucketHealth {
+            latency,
+            can_read,
+            can_write,
+            can_create_indexes,
+            files_index,
+            chunks_index,
+        })
+    }
+
+    /// Whether the collection @collection_name exists with an ascending index starting with
+    /// @fields. False when the user isn't allowed to list them.
+    async fn has_index(&self, collection_name: &str, fields: &[&str]) -> Result<bool, GridFSError> {
+        match self
+            .db
+            .list_collection_names(doc! {"name":collection_name})
+            .await
+        {
+            Ok(names) if names.is_empty() => return Ok(false),
+            Ok(_) => {}
+            Err(error) if is_unauthorized(&error) => return Ok(false),
+            Err(error) => return Err(error.into()),
+        }
+        match self.has_ascending_index(collection_name, fields).await {
+            Err(GridFSError::MongoError(error)) if is_unauthorized(&error) => Ok(false),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_granted, BucketHealth, GridFSBucket};
+    use crate::{options::GridFSBucketOptions, GridFSError};
+    use bson::{doc, Document};
+    use mongodb::{Client, Database};
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn granted_privileges() {
+        let privileges: Vec<Document> = vec![
+            doc! {"resource":{"db":"media", "collection":"fs.files"}, "actions":["find", "insert"]},
+            doc! {"resource":{"db":"media", "collection":""}, "actions":["createIndex"]},
+            doc! {"resource":{"cluster":true}, "actions":["remove"]},
+        ];
+        assert!(is_granted(&privileges, "media", "fs.files", "insert"));
+        assert!(!is_granted(&privileges, "media", "fs.chunks", "insert"));
+        assert!(!is_granted(&privileges, "other", "fs.files", "insert"));
+        assert!(is_granted(&privileges, "media", "fs.chunks", "createIndex"));
+        assert!(!is_granted(&privileges, "media", "fs.files", "remove"));
+
+        let privileges = vec![doc! {"resource":{"anyResource":true}, "actions":["remove"]}];
+        assert!(is_granted(&privileges, "media", "fs.files", "remove"));
+        assert!(!is_granted(&[], "media", "fs.files", "find"));
+    }
+
+    #[test]
+    fn health_is_ready() {
+        let mut health = BucketHealth {
+            latency: Duration::from_millis(1),
+            can_read: true,
+            can_write: true,
+            can_create_indexes: false,
+            files_index: true,
+            chunks_index: true,
+        };
+        assert!(health.is_ready());
+        health.chunks_index = false;
+        assert!(!health.is_ready());
+        health.can_create_indexes = true;
+        assert!(health.is_ready());
+        health.can_write = false;
+        assert!(!health.is_ready());
+    }
+
+    #[tokio::test]
+    async fn healthcheck() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+
+        // Nothing is written by the probe.
+        let health = bucket.healthcheck().await?;
+        assert!(health.can_read && health.can_write && health.can_create_indexes);
+        assert!(!health.files_index && !health.chunks_index);
+        assert!(health.is_ready());
+        assert!(db.list_collection_names(None).await?.is_empty());
+
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        let health = bucket.healthcheck().await?;
+        assert!(health.files_index && health.chunks_index);
+        assert!(bucket.ping().await? < Duration::from_secs(60));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
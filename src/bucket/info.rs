@@ -0,0 +1,221 @@
+use crate::{
+    bucket::{status::visible, GridFSBucket},
+    FileInfo, GridFSError,
+};
+use bson::{doc, oid::ObjectId, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::options::{FindOneOptions, FindOptions};
+use std::{collections::HashMap, convert::TryFrom};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+impl GridFSBucket {
+    /**
+     Returns the [`FileInfo`] of the stored file specified by @id.
+
+     # Examples
+
+     ```rust
+     # use mongodb::Client;
+     # use mongodb::Database;
+     use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket, GridFSError};
+     # use uuid::Uuid;
+     # fn db_name_new() -> String {
+     #     "test_".to_owned()
+     #         + Uuid::new_v4()
+     #             .hyphenated()
+     #             .encode_lower(&mut Uuid::encode_buffer())
+     # }
+     #
+     # #[tokio::main]
+     # async fn main() -> Result<(), GridFSError> {
+     #     let client = Client::with_uri_str(
+     #         &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+     #     )
+     #     .await?;
+     #     let dbname = db_name_new();
+     #     let db: Database = client.database(&dbname);
+     let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+     let id = bucket
+         .upload_from_stream("test.txt", "test data".as_bytes(), None)
+         .await?;
+     let info = bucket.file_info(id).await?;
+     assert_eq!(info.length, 9);
+     #
+     #     db.drop(None).await?;
+     #     Ok(())
+     # }
+     ```
+
+     # Errors
+
+     Raise [`GridFSError::FileNotFound`] when the requested id doesn't exists.
+     Raise [`GridFSError::InvalidFile`] when the files collection document is malformed.
+    */
+    pub async fn file_info(&self, id: ObjectId) -> Result<FileInfo, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let selection_criteria = dboptions.read_selection_criteria();
+        let bucket_name = dboptions.bucket_name;
+        let file_collection = bucket_name + ".files";
+        let files = self.db.collection::<Document>(&file_collection);
+
+        let mut find_one_options = FindOneOptions::default();
+        if let Some(read_concern) = dboptions.read_concern {
+            find_one_options.read_concern = Some(read_concern);
+        }
+        find_one_options.selection_criteria = selection_criteria;
+
+        let file = files
+            .find_one(visible(doc! {"_id":id}), find_one_options)
+            .await?
+            .ok_or(GridFSError::FileNotFound())?;
+        FileInfo::try_from(file)
+    }
+
+    /**
+     Returns the [`FileInfo`] of the stored files specified by @ids, in the order of @ids,
+     with a single query: None for the ids not found, e.g. when rendering a listing
+     referencing many files.
+
+     # Errors
+
+     Raise [`GridFSError::InvalidFile`] when a files collection document is malformed.
+    */
+    pub async fn get_file_infos(
+        &self,
+        ids: &[ObjectId],
+    ) -> Result<Vec<Option<FileInfo>>, GridFSError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let dboptions = self.options.clone().unwrap_or_default();
+        let find_options = FindOptions::builder()
+            .selection_criteria(dboptions.read_selection_criteria())
+            .read_concern(dboptions.read_concern)
+            .build();
+        let mut cursor = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name + ".files"))
+            .find(visible(doc! {"_id":{"$in":ids.to_vec()}}), find_options)
+            .await?;
+        let mut found = HashMap::with_capacity(ids.len());
+        while let Some(file) = cursor.next().await {
+            let file = FileInfo::try_from(file?)?;
+            found.insert(file.id, file);
+        }
+        Ok(ids.iter().map(|id| found.get(id).cloned()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{options::GridFSBucketOptions, GridFSError};
+    use bson::{doc, oid::ObjectId, Document};
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn file_info() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let info = bucket.file_info(id).await?;
+        assert_eq!(info.id, id);
+        assert_eq!(info.length, 9);
+        assert_eq!(info.chunk_size, 4);
+        assert_eq!(info.filename.as_deref(), Some("test.txt"));
+        assert!(info.upload_date.is_some());
+
+        // As written by a driver storing numbers as doubles.
+        db.collection::<Document>("fs.files")
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$set": { "length": 9.0, "chunkSize": 4.0 } },
+                None,
+            )
+            .await?;
+        let info = bucket.file_info(id).await?;
+        assert_eq!(info.length, 9);
+        assert_eq!(info.chunk_size, 4);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn file_info_not_existing_file() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+
+        let info = bucket.file_info(ObjectId::new()).await;
+        assert!(matches!(info, Err(GridFSError::FileNotFound())));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_file_infos() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let first = bucket
+            .upload_from_stream("first.txt", "test".as_bytes(), None)
+            .await?;
+        let second = bucket
+            .upload_from_stream("second.txt", "test data".as_bytes(), None)
+            .await?;
+        let missing = ObjectId::new();
+
+        let infos = bucket
+            .get_file_infos(&[second, missing, first, second])
+            .await?;
+        let filenames: Vec<_> = infos
+            .iter()
+            .map(|info| info.as_ref().and_then(|info| info.filename.as_deref()))
+            .collect();
+        assert_eq!(
+            filenames,
+            vec![
+                Some("second.txt"),
+                None,
+                Some("first.txt"),
+                Some("second.txt")
+            ]
+        );
+        assert_eq!(infos[0].as_ref().unwrap().length, 9);
+        assert!(bucket.get_file_infos(&[]).await?.is_empty());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
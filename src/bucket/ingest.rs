@@ -0,0 +1,288 @@
+use crate::{bucket::GridFSBucket, options::IngestOptions, GridFSError};
+use bson::oid::ObjectId;
+use glob::Pattern;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::{JoinHandle, JoinSet},
+};
+
+/// Handle on a running directory ingestion started by [`GridFSBucket::ingest_directory`].
+///
+/// The ingestion stops when [`IngestHandle::shutdown`] is called or when the handle is dropped.
+pub struct IngestHandle {
+    results: mpsc::UnboundedReceiver<(PathBuf, Result<ObjectId, GridFSError>)>,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl IngestHandle {
+    /// Waits for the next uploaded file. Returns the path of the file and the result of its upload.
+    ///
+    /// Returns `None` once the ingestion is stopped.
+    pub async fn next(&mut self) -> Option<(PathBuf, Result<ObjectId, GridFSError>)> {
+        self.results.recv().await
+    }
+
+    /// Stops watching the directory and waits for the uploads in progress, if any.
+    pub async fn shutdown(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for IngestHandle {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+fn is_included(include: &[Pattern], relative_path: &Path) -> bool {
+    include.is_empty()
+        || include
+            .iter()
+            .any(|pattern| pattern.matches_path(relative_path))
+}
+
+impl GridFSBucket {
+    /**
+    Watches the directory @path and uploads the files created or modified in it.
+
+    A file is uploaded once it has not been modified for [`IngestOptions::debounce`]. The
+    filename of the uploaded file is its path relative to the watched directory.
+    Requires the `watch-fs` feature and a tokio runtime.
+
+    # Examples
+
+    ```rust,no_run
+    # use mongodb::Client;
+    use mongodb_gridfs::{options::{GridFSBucketOptions, IngestOptions}, GridFSBucket, GridFSError};
+    #
+    # #[tokio::main]
+    # async fn main() -> Result<(), GridFSError> {
+    #     let client = Client::with_uri_str("mongodb://localhost:27017/").await?;
+    #     let db = client.database("test");
+    let bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+    let mut ingestion = bucket.ingest_directory(
+        "/var/spool/drop",
+        IngestOptions::builder().include(vec!["*.pdf".into()]).build(),
+    )?;
+    while let Some((path, result)) = ingestion.next().await {
+        println!("{:?}: {:?}", path, result.map(|id| id.to_hex()));
+    }
+    #     Ok(())
+    # }
+    ```
+
+    # Errors
+
+    Raise [`GridFSError::WatchError`] when the directory can't be watched.
+    Raise [`GridFSError::InvalidConfiguration`] when an include pattern is invalid.
+    */
+    pub fn ingest_directory(
+        &self,
+        path: impl AsRef<Path>,
+        options: IngestOptions,
+    ) -> Result<IngestHandle, GridFSError> {
+        let root = path.as_ref().canonicalize().map_err(notify::Error::io)?;
+        let include = options
+            .include
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern).map_err(|e| GridFSError::InvalidConfiguration {
+                    reason: format!("invalid include pattern {:?}: {}", pattern, e),
+                })
+            })
+            .collect::<Result<Vec<Pattern>, _>>()?;
+
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = events_tx.send(event);
+        })?;
+        let mode = if options.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(&root, mode)?;
+
+        let (results_tx, results) = mpsc::unbounded_channel();
+        let (shutdown, mut shutdown_rx) = oneshot::channel();
+        let bucket = self.clone();
+        let debounce = options.debounce;
+        let upload_options = options.upload_options;
+
+        let task = tokio::spawn(async move {
+            // The watcher stops when dropped: keep it alive with the task.
+            let _watcher = watcher;
+            let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+            // The uploads run beside the loop, so the events and the shutdown aren't delayed
+            // by the files being uploaded.
+            let mut uploads = JoinSet::new();
+            let mut ticker =
+                tokio::time::interval(debounce.max(std::time::Duration::from_millis(20)) / 2);
+            loop {
+                tokio::select! {
+                    _ = &mut shutdown_rx => break,
+                    event = events_rx.recv() => match event {
+                        Some(Ok(event)) => {
+                            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                                for path in event.paths {
+                                    pending.insert(path, Instant::now());
+                                }
+                            }
+                        }
+                        Some(Err(_)) => {}
+                        None => break,
+                    },
+                    Some(_) = uploads.join_next(), if !uploads.is_empty() => {}
+                    _ = ticker.tick() => {
+                        let ready: Vec<PathBuf> = pending
+                            .iter()
+                            .filter(|(_, last_event)| last_event.elapsed() >= debounce)
+                            .map(|(path, _)| path.clone())
+                            .collect();
+                        for path in ready {
+                            pending.remove(&path);
+                            let relative_path = match path.strip_prefix(&root) {
+                                Ok(relative_path) => relative_path.to_path_buf(),
+                                Err(_) => continue,
+                            };
+                            if !path.is_file() || !is_included(&include, &relative_path) {
+                                continue;
+                            }
+                            let filename = relative_path
+                                .components()
+                                .map(|component| component.as_os_str().to_string_lossy())
+                                .collect::<Vec<_>>()
+                                .join("/");
+                            let mut bucket = bucket.clone();
+                            let upload_options = upload_options.clone();
+                            let results_tx = results_tx.clone();
+                            uploads.spawn(async move {
+                                let result = match tokio::fs::File::open(&path).await {
+                                    Ok(file) => bucket
+                                        .upload_from_stream(&filename, file, upload_options)
+                                        .await,
+                                    Err(e) => Err(notify::Error::io(e).into()),
+                                };
+                                // The handle may be dropped meanwhile.
+                                let _ = results_tx.send((path, result));
+                            });
+                        }
+                    }
+                }
+            }
+            while uploads.join_next().await.is_some() {}
+        });
+
+        Ok(IngestHandle {
+            results,
+            shutdown: Some(shutdown),
+            task: Some(task),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_included, GridFSBucket};
+    use crate::{
+        options::{GridFSBucketOptions, IngestOptions},
+        GridFSError,
+    };
+    use bson::{doc, Document};
+    use glob::Pattern;
+    use mongodb::{Client, Database};
+    use std::{path::Path, time::Duration};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn invalid_include_pattern() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let bucket = GridFSBucket::new(client.database(&db_name_new()), None);
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            bucket.ingest_directory(
+                dir.path(),
+                IngestOptions::builder().include(vec!["[".into()]).build(),
+            ),
+            Err(GridFSError::InvalidConfiguration { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn include_patterns() {
+        let include = vec![Pattern::new("*.txt").unwrap()];
+        assert!(is_included(&include, Path::new("a.txt")));
+        assert!(!is_included(&include, Path::new("a.pdf")));
+        assert!(is_included(&[], Path::new("a.pdf")));
+    }
+
+    #[tokio::test]
+    async fn ingest_directory() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut ingestion = bucket.ingest_directory(
+            dir.path(),
+            IngestOptions::builder()
+                .include(vec!["*.txt".into()])
+                .debounce(Duration::from_millis(100))
+                .build(),
+        )?;
+        std::fs::write(dir.path().join("ignored.bin"), "ignored").unwrap();
+        std::fs::write(dir.path().join("test.txt"), "test data").unwrap();
+
+        let (path, result) = tokio::time::timeout(Duration::from_secs(10), ingestion.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(path.file_name().unwrap(), "test.txt");
+        let id = result?;
+        ingestion.shutdown().await;
+
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_str("filename").unwrap(), "test.txt");
+        let count = db
+            .collection::<Document>("fs.files")
+            .count_documents(doc! {}, None)
+            .await?;
+        assert_eq!(count, 1);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
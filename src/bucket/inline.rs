@@ -0,0 +1,125 @@
+use crate::bucket::chunk_stream::DocumentStream;
+use bson::{doc, Document};
+use futures_util::stream::iter;
+
+/// Field of the files collection document of an inlined file, holding its content: the file
+/// has no chunks.
+pub(crate) const INLINE_FIELD: &str = "inlineData";
+
+/// The content of the inlined @file as a stream of its only chunk document. None when the
+/// file is chunked.
+pub(crate) fn inline_chunks(file: &Document) -> Option<DocumentStream> {
+    let data = file.get(INLINE_FIELD)?.clone();
+    Some(Box::pin(iter(vec![Ok(doc! {"n":0, "data":data})])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inline_chunks, INLINE_FIELD};
+    use crate::{
+        bucket::GridFSBucket,
+        options::{GridFSBucketOptions, GridFSDownloadOptions},
+        GridFSError,
+    };
+    use bson::{doc, spec::BinarySubtype, Binary, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn inline_chunks_of_file() -> Result<(), GridFSError> {
+        assert!(inline_chunks(&doc! {"length":9_i64}).is_none());
+        let data = Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: b"test data".to_vec(),
+        };
+        let mut chunks = inline_chunks(&doc! {INLINE_FIELD:data.clone()}).unwrap();
+        assert_eq!(
+            chunks.next().await.transpose()?,
+            Some(doc! {"n":0, "data":data})
+        );
+        assert!(chunks.next().await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn inline_small_files() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(8)
+                    .inline_threshold(Some(16))
+                    .build(),
+            ),
+        );
+        let small = bucket
+            .upload_from_stream("small.txt", "test".as_bytes(), None)
+            .await?;
+        let large = bucket
+            .upload_from_stream("large.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let files = db.collection::<Document>("fs.files");
+        let chunks = db.collection::<Document>("fs.chunks");
+        let file = files.find_one(doc! {"_id":small}, None).await?.unwrap();
+        assert_eq!(file.get_i64("length").unwrap(), 4);
+        assert_eq!(file.get_binary_generic(INLINE_FIELD).unwrap(), b"test");
+        assert_eq!(
+            chunks
+                .count_documents(doc! {"files_id":small}, None)
+                .await?,
+            0
+        );
+        // Larger than a chunk: chunked.
+        let file = files.find_one(doc! {"_id":large}, None).await?.unwrap();
+        assert!(file.get(INLINE_FIELD).is_none());
+        assert_eq!(
+            chunks
+                .count_documents(doc! {"files_id":large}, None)
+                .await?,
+            2
+        );
+
+        let mut cursor = bucket.open_download_stream(small).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test");
+        assert!(cursor.next().await.is_none());
+        let mut cursor = bucket
+            .open_download_stream_with_options(
+                small,
+                GridFSDownloadOptions::builder().range(Some(1..3)).build(),
+            )
+            .await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"es");
+
+        // Downloads read both layouts, whatever the threshold.
+        let bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let mut cursor = bucket.open_download_stream(small).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test");
+        let mut content = vec![];
+        let mut cursor = bucket.open_download_stream(large).await?;
+        while let Some(chunk) = cursor.next().await {
+            content.extend(chunk?);
+        }
+        assert_eq!(content, b"test data");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+use crate::bucket::GridFSBucket;
+#[cfg(any(feature = "md5", feature = "sha256"))]
+use crate::{
+    bucket::{
+        checksum::{expected_checksum, Checksum},
+        chunk_stream::chunk_data,
+        inline::INLINE_FIELD,
+        routing::{chunk_filter, CHUNKS_COLLECTION_FIELD},
+        status::STATUS_FIELD,
+        tier::TIER_FIELD,
+    },
+    encryption::{chunk_data_field, ENCRYPTION_KEY_FIELD},
+    is_duplicate_key,
+    options::GridFSDownloadOptions,
+    FileInfo, FileStatus, GridFSError,
+};
+#[cfg(any(feature = "md5", feature = "sha256"))]
+use bson::{doc, oid::ObjectId, Document};
+#[cfg(all(
+    any(feature = "md5", feature = "sha256"),
+    feature = "async-std-runtime"
+))]
+use futures::StreamExt;
+#[cfg(any(feature = "md5", feature = "sha256"))]
+use mongodb::options::{DeleteOptions, FindOptions, InsertOneOptions, UpdateOptions};
+#[cfg(any(feature = "md5", feature = "sha256"))]
+use std::convert::TryFrom;
+use std::sync::Arc;
+#[cfg(all(
+    any(feature = "md5", feature = "sha256"),
+    any(feature = "default", feature = "tokio-runtime")
+))]
+use tokio_stream::StreamExt;
+
+impl GridFSBucket {
+    /// The legacy bucket the downloads fall back to, see
+    /// [`GridFSBucketOptions::legacy_bucket`](crate::options::GridFSBucketOptions::legacy_bucket).
+    pub(crate) fn legacy_bucket(&self) -> Option<Arc<GridFSBucket>> {
+        self.options
+            .as_ref()
+            .and_then(|options| options.legacy_bucket.clone())
+    }
+
+    /// Copies the file @id, found in the legacy bucket by a download, in the background when
+    /// the bucket copies through. A failed copy is emitted as a `tracing` warning with the
+    /// `tracing` feature.
+    #[cfg(all(
+        any(feature = "md5", feature = "sha256"),
+        any(feature = "default", feature = "tokio-runtime")
+    ))]
+    pub(crate) fn copy_through(&self, id: ObjectId) {
+        if self
+            .options
+            .as_ref()
+            .is_some_and(|options| options.legacy_copy_through)
+        {
+            let mut bucket = self.clone();
+            tokio::spawn(async move {
+                match bucket.copy_from_legacy(id).await {
+                    // Copied by a concurrent download.
+                    Ok(()) | Err(GridFSError::AlreadyExists { .. }) => {}
+                    #[cfg(feature = "tracing")]
+                    Err(error) => {
+                        tracing::warn!(id = %id, error = %error, "GridFS copy through failed")
+                    }
+                    #[cfg(not(feature = "tracing"))]
+                    Err(_) => {}
+                }
+            });
+        }
+    }
+
+    /**
+    Copies the stored file @id from the
+    [`legacy_bucket`](crate::options::GridFSBucketOptions::legacy_bucket) of the bucket to
+    the bucket, with the same id and files collection document.
+
+    The content read from the legacy bucket is checked against the checksum of the file
+    when it has one, and the chunks written are read back and checked against the content
+    read. The copy stays pending, hidden from the readers, until it's verified: a copy which
+    doesn't match is removed. The copy records the checksum of the file.
+
+    A copy which fails once the id is reserved is removed. A copy interrupted midway, e.g. by
+    a crash, leaves the file pending in the bucket: it's removed with [`GridFSBucket::delete`]
+    before copying the file again.
+
+    # Errors
+
+    Raise [`GridFSError::InvalidConfiguration`] when the bucket has no legacy bucket, or when
+    the feature of the checksum algorithm of the bucket isn't built.
+    Raise [`GridFSError::FileNotFound`] when the legacy bucket doesn't have the file @id.
+    Raise [`GridFSError::AlreadyExists`] when the bucket already has the file @id.
+    Raise [`GridFSError::ChecksumMismatch`] when the content read doesn't match the checksum
+    of the file, or the copy doesn't match the content read.
+    */
+    #[cfg(any(feature = "md5", feature = "sha256"))]
+    pub async fn copy_from_legacy(&mut self, id: ObjectId) -> Result<(), GridFSError> {
+        let _writer = self.write_guard().await;
+        let legacy = self
+            .legacy_bucket()
+            .ok_or_else(|| GridFSError::InvalidConfiguration {
+                reason: "no legacy bucket".into(),
+            })?;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let bucket_name = dboptions.bucket_name.clone();
+        let file_collection = bucket_name.clone() + ".files";
+        let chunk_collection = bucket_name + ".chunks";
+        let files = self.db.collection::<Document>(&file_collection);
+        let chunks = self.db.collection::<Document>(&chunk_collection);
+
+        let (mut source, legacy_file) = legacy
+            .open_chunk_stream(id, &GridFSDownloadOptions::default())
+            .await?;
+        let legacy_info = FileInfo::try_from(legacy_file.clone())?;
+        let expected = expected_checksum(&legacy_file, dboptions.checksum_algorithm);
+        let algorithm = expected
+            .as_ref()
+            .map_or(dboptions.checksum_algorithm, |(algorithm, _)| *algorithm);
+        let mut read = Checksum::built(algorithm)?;
+
+        self.ensure_file_index(&files, &file_collection, &chunk_collection)
+            .await?;
+        // The chunks of the copy are in the chunks collection of the bucket, in the clear or
+        // encrypted with the key of the bucket.
+        let mut file = legacy_file.clone();
+        for field in [
+            CHUNKS_COLLECTION_FIELD,
+            INLINE_FIELD,
+            TIER_FIELD,
+            ENCRYPTION_KEY_FIELD,
+        ] {
+            file.remove(field);
+        }
+        if let Some(encryption) = &dboptions.chunk_encryption {
+            file.insert(ENCRYPTION_KEY_FIELD, encryption.key_id.clone());
+        }
+        // Reserves the id: a concurrent copy of the file stops there.
+        let status = file.insert(STATUS_FIELD, FileStatus::Pending.as_str());
+        let insert_options = InsertOneOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        files
+            .insert_one(file.clone(), insert_options)
+            .await
+            .map_err(|error| match is_duplicate_key(&error) {
+                true => GridFSError::AlreadyExists {
+                    id: Some(id),
+                    filename: None,
+                },
+                false => error.into(),
+            })?;
+
+        // From there on, a failed copy is removed: the reservation included.
+        let chunk_base = chunk_filter(&file, id, self.chunk_shard_key());
+        let copied: Result<(), GridFSError> = async {
+            let insert_options = InsertOneOptions::builder()
+                .write_concern(dboptions.write_concern_of_chunks())
+                .build();
+            let subtype = dboptions.chunk_binary_subtype.into();
+            let mut n = 0;
+            while let Some(data) = source.next().await {
+                let data = data?;
+                read.update(&data);
+                let mut chunk = chunk_base.clone();
+                chunk.insert("n", n);
+                chunk.insert(
+                    "data",
+                    chunk_data_field(dboptions.chunk_encryption.as_ref(), subtype, data).await?,
+                );
+                if let Some(expire_at) = legacy_info.expire_at {
+                    chunk.insert("expireAt", expire_at);
+                }
+                chunks.insert_one(chunk, insert_options.clone()).await?;
+                n += 1;
+            }
+            let read = read.finalize();
+
+            if let Some((_, expected)) = expected.filter(|(_, expected)| *expected != read) {
+                return Err(GridFSError::ChecksumMismatch {
+                    expected,
+                    actual: read,
+                });
+            }
+            let find_options = FindOptions::builder().sort(doc! {"n":1}).build();
+            let mut cursor = chunks.find(chunk_base.clone(), find_options).await?;
+            let mut written = Checksum::built(algorithm)?;
+            let mut n = 0;
+            while let Some(chunk) = cursor.next().await {
+                written.update(&chunk_data(chunk?, n)?);
+                n += 1;
+            }
+            let written = written.finalize();
+            if written != read {
+                return Err(GridFSError::ChecksumMismatch {
+                    expected: read,
+                    actual: written,
+                });
+            }
+
+            // Published with the state of the legacy file.
+            let mut set = doc! {algorithm.field():read};
+            let mut update = Document::new();
+            match status {
+                Some(status) => {
+                    set.insert(STATUS_FIELD, status);
+                }
+                None => {
+                    update.insert("$unset", doc! {STATUS_FIELD:""});
+                }
+            }
+            update.insert("$set", set);
+            let update_options = UpdateOptions::builder()
+                .write_concern(dboptions.write_concern_of_files())
+                .build();
+            files
+                .update_one(doc! {"_id":id}, update, update_options)
+                .await?;
+            Ok(())
+        }
+        .await;
+        if let Err(error) = copied {
+            let delete_options = DeleteOptions::builder()
+                .write_concern(dboptions.write_concern_of_chunks())
+                .build();
+            chunks.delete_many(chunk_base, delete_options).await?;
+            let delete_options = DeleteOptions::builder()
+                .write_concern(dboptions.write_concern_of_files())
+                .build();
+            files.delete_one(doc! {"_id":id}, delete_options).await?;
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{options::GridFSBucketOptions, GridFSError};
+    use bson::{doc, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    use std::sync::Arc;
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn download_from_legacy_bucket() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut legacy = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .bucket_name("legacy".into())
+                    .chunk_size_bytes(4)
+                    .build(),
+            ),
+        );
+        let id = legacy
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        #[cfg_attr(not(any(feature = "md5", feature = "sha256")), allow(unused_mut))]
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .legacy_bucket(Some(Arc::new(legacy)))
+                    .build(),
+            ),
+        );
+
+        let mut stream = bucket.open_download_stream(id).await?;
+        let mut content = vec![];
+        while let Some(data) = stream.next().await {
+            content.extend(data?);
+        }
+        assert_eq!(content, b"test data");
+        let files = db.collection::<Document>("fs.files");
+        assert_eq!(files.count_documents(doc! {}, None).await?, 0);
+
+        #[cfg(any(feature = "md5", feature = "sha256"))]
+        {
+            bucket.copy_from_legacy(id).await?;
+            let file = files.find_one(doc! {"_id":id}, None).await?.unwrap();
+            assert_eq!(file.get_str("filename"), Ok("test.txt"));
+            assert!(file.get("status").is_none());
+            assert!(file.get_str("md5").is_ok());
+            let chunks = db.collection::<Document>("fs.chunks");
+            assert_eq!(chunks.count_documents(doc! {"files_id":id}, None).await?, 3);
+            assert!(matches!(
+                bucket.copy_from_legacy(id).await,
+                Err(GridFSError::AlreadyExists { .. })
+            ));
+
+            // A corrupted legacy file isn't copied.
+            let other = bucket
+                .legacy_bucket()
+                .unwrap()
+                .as_ref()
+                .clone()
+                .upload_from_stream("other.txt", "test data".as_bytes(), None)
+                .await?;
+            db.collection::<Document>("legacy.files")
+                .update_one(doc! {"_id":other}, doc! {"$set":{"md5":"0"}}, None)
+                .await?;
+            assert!(matches!(
+                bucket.copy_from_legacy(other).await,
+                Err(GridFSError::ChecksumMismatch { .. })
+            ));
+            assert_eq!(files.count_documents(doc! {"_id":other}, None).await?, 0);
+            assert_eq!(
+                chunks
+                    .count_documents(doc! {"files_id":other}, None)
+                    .await?,
+                0
+            );
+
+            // Nor one missing a chunk: the reserved copy is removed.
+            db.collection::<Document>("legacy.chunks")
+                .delete_one(doc! {"files_id":other, "n":1}, None)
+                .await?;
+            assert!(bucket.copy_from_legacy(other).await.is_err());
+            assert_eq!(files.count_documents(doc! {"_id":other}, None).await?, 0);
+            assert_eq!(
+                chunks
+                    .count_documents(doc! {"files_id":other}, None)
+                    .await?,
+                0
+            );
+        }
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
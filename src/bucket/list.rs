@@ -0,0 +1,230 @@
+use crate::{
+    bucket::{status::visible, GridFSBucket},
+    FileInfo, GridFSError,
+};
+use bson::{doc, oid::ObjectId, DateTime, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::options::FindOptions;
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// The position of a page of [`GridFSBucket::list_files`]: the page starts after the file
+/// with this upload date and id.
+///
+/// The token is opaque: it's exchanged with the clients as a string, with [`Display`] and
+/// [`FromStr`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageToken {
+    upload_date: DateTime,
+    id: ObjectId,
+}
+
+impl PageToken {
+    /// The token of the page after @file.
+    fn after(file: &FileInfo) -> PageToken {
+        PageToken {
+            upload_date: file.upload_date.unwrap_or(DateTime::MIN),
+            id: file.id,
+        }
+    }
+
+    /// The files listed after the token, in the order of [`GridFSBucket::list_files`].
+    fn filter(&self) -> Document {
+        doc! {"$or":[
+            {"uploadDate":{"$lt":self.upload_date}},
+            {"uploadDate":self.upload_date, "_id":{"$lt":self.id}},
+        ]}
+    }
+}
+
+/// 16 hexadecimal digits of the upload date, followed by the 24 of the id.
+impl Display for PageToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:016x}{}",
+            self.upload_date.timestamp_millis() as u64,
+            self.id.to_hex()
+        )
+    }
+}
+
+impl FromStr for PageToken {
+    type Err = GridFSError;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        let invalid = || GridFSError::InvalidPageToken(token.to_string());
+        if token.len() != 40 || !token.is_ascii() {
+            return Err(invalid());
+        }
+        let (upload_date, id) = token.split_at(16);
+        let upload_date = u64::from_str_radix(upload_date, 16).map_err(|_| invalid())?;
+        Ok(PageToken {
+            upload_date: DateTime::from_millis(upload_date as i64),
+            id: ObjectId::parse_str(id).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A page of [`GridFSBucket::list_files`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilePage {
+    pub files: Vec<FileInfo>,
+    /// The token of the next page. None on the last page.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_token"))]
+    pub next: Option<PageToken>,
+}
+
+#[cfg(feature = "serde")]
+fn serialize_token<S: serde::Serializer>(
+    token: &Option<PageToken>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match token {
+        Some(token) => serializer.collect_str(token),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl GridFSBucket {
+    /**
+    Lists a page of at most @page_size files matching @filter, the latest uploaded first,
+    starting after the token @after of the previous page, or from the first file.
+
+    The pages are positioned on the last file of the previous page instead of skipping
+    files: the files uploaded or deleted between two pages don't shift the listing, so no
+    file is listed twice or skipped.
+
+    # Errors
+
+    Raise [`GridFSError::InvalidFile`] when a files collection document is malformed.
+    */
+    pub async fn list_files(
+        &self,
+        filter: Document,
+        page_size: u32,
+        after: Option<&PageToken>,
+    ) -> Result<FilePage, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let page_size = page_size.max(1) as usize;
+        let mut filters = vec![filter, doc! {"length":{"$exists":true}}];
+        if let Some(after) = after {
+            filters.push(after.filter());
+        }
+        // One more file tells whether there is a next page.
+        let find_options = FindOptions::builder()
+            .sort(doc! {"uploadDate":-1, "_id":-1})
+            .limit(page_size as i64 + 1)
+            .selection_criteria(dboptions.read_selection_criteria())
+            .read_concern(dboptions.read_concern)
+            .build();
+        let mut cursor = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name + ".files"))
+            .find(visible(doc! {"$and":filters}), find_options)
+            .await?;
+        let mut files = Vec::with_capacity(page_size + 1);
+        while let Some(file) = cursor.next().await {
+            files.push(FileInfo::try_from(file?)?);
+        }
+        let next = match files.len() > page_size {
+            true => {
+                files.truncate(page_size);
+                files.last().map(PageToken::after)
+            }
+            false => None,
+        };
+        Ok(FilePage { files, next })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PageToken;
+    use crate::{bucket::GridFSBucket, options::GridFSBucketOptions, GridFSError, GridFSErrorCode};
+    use bson::{doc, oid::ObjectId, DateTime};
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn page_token() -> Result<(), GridFSError> {
+        let token = PageToken {
+            upload_date: DateTime::from_millis(1_700_000_000_000),
+            id: ObjectId::parse_str("65a1b2c3d4e5f60718293a4b").unwrap(),
+        };
+        assert_eq!(
+            token.to_string(),
+            "0000018bcfe5680065a1b2c3d4e5f60718293a4b"
+        );
+        assert_eq!(token.to_string().parse::<PageToken>()?, token);
+        for token in [
+            "",
+            "0000018bcfe56800",
+            "0000018bcfe5680065a1b2c3d4e5f60718293a4z",
+        ] {
+            assert_eq!(
+                token.parse::<PageToken>().unwrap_err().code(),
+                GridFSErrorCode::InvalidPageToken
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_files() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let mut ids = vec![];
+        for i in 0..5 {
+            ids.push(
+                bucket
+                    .upload_from_stream(&format!("file-{}", i), "test data".as_bytes(), None)
+                    .await?,
+            );
+        }
+        ids.reverse();
+
+        let page = bucket.list_files(doc! {}, 2, None).await?;
+        let mut listed: Vec<ObjectId> = page.files.iter().map(|file| file.id).collect();
+        // Added between two pages: the listing doesn't shift.
+        bucket
+            .upload_from_stream("file-5", "test data".as_bytes(), None)
+            .await?;
+        let mut next = page.next;
+        while let Some(token) = next {
+            let token = token.to_string().parse::<PageToken>()?;
+            let page = bucket.list_files(doc! {}, 2, Some(&token)).await?;
+            listed.extend(page.files.iter().map(|file| file.id));
+            next = page.next;
+        }
+        assert_eq!(listed, ids);
+
+        let page = bucket
+            .list_files(doc! {"filename":"file-1"}, 2, None)
+            .await?;
+        assert_eq!(page.files.len(), 1);
+        assert!(page.next.is_none());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
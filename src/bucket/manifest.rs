@@ -0,0 +1,322 @@
+use crate::{bucket::GridFSBucket, FileInfo, GridFSError};
+use bson::{doc, Bson, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::io::{AsyncWrite, AsyncWriteExt};
+use futures_util::stream::{Stream, StreamExt};
+use mongodb::options::FindOptions;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// A file listed in an external manifest, compared to the bucket by [`GridFSBucket::reconcile`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ManifestEntry {
+    /// The name of the file.
+    pub filename: String,
+    /// The MD5 checksum of the file, in hexadecimal.
+    pub digest: String,
+    /// The length of the file in bytes.
+    pub length: u64,
+}
+
+impl ManifestEntry {
+    /// Whether the stored file @info has the length and the digest of the entry.
+    fn matches(&self, info: &FileInfo) -> bool {
+        info.length == self.length
+            && info
+                .md5
+                .as_ref()
+                .is_some_and(|md5| md5.eq_ignore_ascii_case(&self.digest))
+    }
+}
+
+/// The differences between an external manifest and a bucket, returned by
+/// [`GridFSBucket::reconcile`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ReconcileReport {
+    /// The entries of the manifest without a stored file.
+    pub missing: Vec<ManifestEntry>,
+    /// The stored files absent from the manifest.
+    pub extra: Vec<FileInfo>,
+    /// The entries of the manifest whose stored file has another length or digest.
+    pub mismatched: Vec<(ManifestEntry, FileInfo)>,
+}
+
+impl ReconcileReport {
+    /// Whether the bucket matches the manifest.
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// `consistent`, or the number of missing, extra and mismatched files.
+impl Display for ReconcileReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_consistent() {
+            return write!(f, "consistent");
+        }
+        write!(
+            f,
+            "{} missing, {} extra, {} mismatched",
+            self.missing.len(),
+            self.extra.len(),
+            self.mismatched.len()
+        )
+    }
+}
+
+/// The manifest entry of @info, as a line of JSON.
+pub(crate) fn manifest_line(info: &FileInfo) -> String {
+    let entry = doc! {
+        "id": info.id.to_hex(),
+        "filename": info.filename.clone().map_or(Bson::Null, Bson::String),
+        "length": info.length as i64,
+        "md5": info.md5.clone().map_or(Bson::Null, Bson::String),
+        "uploadDate": info
+            .upload_date
+            .and_then(|upload_date| upload_date.try_to_rfc3339_string().ok())
+            .map_or(Bson::Null, Bson::String),
+        "metadata": info.metadata.clone().map_or(Bson::Null, Bson::Document),
+    };
+    Bson::Document(entry).into_relaxed_extjson().to_string() + "\n"
+}
+
+impl GridFSBucket {
+    /**
+    Writes to @writer the manifest of the stored files matching @filter, as newline-delimited
+    JSON: one object per file with its `id`, `filename`, `length`, `md5`, `uploadDate` and
+    `metadata`. Returns the number of files written.
+
+    The files are streamed from the cursor, so the listing is never held in memory. The
+    files whose upload is in progress are left out.
+
+    # Errors
+
+    Raise [`GridFSError::InvalidFile`] when a files collection document is malformed.
+    Raise [`GridFSError::MongoError`] when @writer fails.
+    */
+    pub async fn export_manifest<W>(
+        &self,
+        mut writer: W,
+        filter: Document,
+    ) -> Result<u64, GridFSError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let selection_criteria = dboptions.read_selection_criteria();
+        let file_collection = dboptions.bucket_name + ".files";
+        let files = self.db.collection::<Document>(&file_collection);
+
+        let find_options = FindOptions::builder()
+            .read_concern(dboptions.read_concern)
+            .selection_criteria(selection_criteria)
+            .build();
+        let mut cursor = files
+            .find(
+                doc! {"$and":[filter, {"uploadDate":{"$exists":true}}]},
+                find_options,
+            )
+            .await?;
+
+        let mut count = 0;
+        while let Some(file) = cursor.next().await {
+            let info = FileInfo::try_from(file?)?;
+            writer
+                .write_all(manifest_line(&info).as_bytes())
+                .await
+                .map_err(mongodb::error::Error::from)?;
+            count += 1;
+        }
+        writer.flush().await.map_err(mongodb::error::Error::from)?;
+        Ok(count)
+    }
+
+    /**
+    Compares the external @manifest to the stored files and reports the entries without a
+    stored file, the stored files absent from the manifest and the files whose length or
+    digest differ.
+
+    Files are matched by filename. Only the latest revision of a filename is compared: the
+    previous revisions are not reported. A stored file without an MD5 checksum is reported as
+    mismatched. The files whose upload is in progress are left out.
+
+    # Errors
+
+    Raise [`GridFSError::InvalidFile`] when a files collection document is malformed.
+    */
+    pub async fn reconcile<S>(&self, manifest: S) -> Result<ReconcileReport, GridFSError>
+    where
+        S: Stream<Item = ManifestEntry>,
+    {
+        let mut entries: HashMap<String, ManifestEntry> = manifest
+            .map(|entry| (entry.filename.clone(), entry))
+            .collect()
+            .await;
+
+        let dboptions = self.options.clone().unwrap_or_default();
+        let selection_criteria = dboptions.read_selection_criteria();
+        let file_collection = dboptions.bucket_name + ".files";
+        let files = self.db.collection::<Document>(&file_collection);
+
+        let find_options = FindOptions::builder()
+            .sort(doc! {"filename":1, "uploadDate":-1})
+            .read_concern(dboptions.read_concern)
+            .selection_criteria(selection_criteria)
+            .build();
+        let mut cursor = files
+            .find(doc! {"uploadDate":{"$exists":true}}, find_options)
+            .await?;
+
+        let mut report = ReconcileReport::default();
+        let mut previous_filename: Option<String> = None;
+        while let Some(file) = cursor.next().await {
+            let info = FileInfo::try_from(file?)?;
+            if info.filename.is_some() && info.filename == previous_filename {
+                continue;
+            }
+            previous_filename = info.filename.clone();
+            match info
+                .filename
+                .as_ref()
+                .and_then(|filename| entries.remove(filename))
+            {
+                Some(entry) if entry.matches(&info) => {}
+                Some(entry) => report.mismatched.push((entry, info)),
+                None => report.extra.push(info),
+            }
+        }
+        report.missing = entries.into_values().collect();
+        report.missing.sort_by(|a, b| a.filename.cmp(&b.filename));
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{manifest_line, GridFSBucket, ManifestEntry, ReconcileReport};
+    use crate::{options::GridFSBucketOptions, FileInfo, FileStatus, GridFSError};
+    use bson::{doc, oid::ObjectId, DateTime};
+    use futures_util::stream;
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn manifest_line_format() {
+        let id = ObjectId::parse_str("5f5a6f3a1c9d440000a1b2c3").unwrap();
+        let info = FileInfo {
+            id,
+            length: 9,
+            chunk_size: 4,
+            upload_date: Some(DateTime::from_millis(0)),
+            filename: Some("test.txt".into()),
+            md5: None,
+            metadata: Some(doc! {"owner":"test"}),
+            expire_at: None,
+            status: FileStatus::Available,
+        };
+        assert_eq!(
+            manifest_line(&info),
+            "{\"id\":\"5f5a6f3a1c9d440000a1b2c3\",\"filename\":\"test.txt\",\"length\":9,\"md5\":null,\"uploadDate\":\"1970-01-01T00:00:00Z\",\"metadata\":{\"owner\":\"test\"}}\n"
+        );
+    }
+
+    #[test]
+    fn reconcile_report_display() {
+        let mut report = ReconcileReport::default();
+        assert_eq!(report.to_string(), "consistent");
+        report.missing.push(ManifestEntry {
+            filename: "test.txt".into(),
+            digest: "eb733a00c0c9d336e65691a37ab54293".into(),
+            length: 9,
+        });
+        assert_eq!(report.to_string(), "1 missing, 0 extra, 0 mismatched");
+    }
+
+    #[tokio::test]
+    async fn export_manifest() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("other.txt", "other data".as_bytes(), None)
+            .await?;
+
+        let mut manifest = vec![];
+        let count = bucket
+            .export_manifest(&mut manifest, doc! {"filename":"test.txt"})
+            .await?;
+        assert_eq!(count, 1);
+        let manifest = String::from_utf8(manifest).unwrap();
+        assert_eq!(manifest.lines().count(), 1);
+        assert!(manifest.starts_with(&format!("{{\"id\":\"{}\",\"filename\":\"test.txt\"", id)));
+        assert!(manifest.contains("\"md5\":\"eb733a00c0c9d336e65691a37ab54293\""));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reconcile() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        bucket
+            .upload_from_stream("old.txt", "old".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("test.txt", "old".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("changed.txt", "changed".as_bytes(), None)
+            .await?;
+
+        let entry = |filename: &str, digest: &str, length| ManifestEntry {
+            filename: filename.into(),
+            digest: digest.into(),
+            length,
+        };
+        let manifest = vec![
+            entry("test.txt", "EB733A00C0C9D336E65691A37AB54293", 9),
+            entry("changed.txt", "eb733a00c0c9d336e65691a37ab54293", 7),
+            entry("missing.txt", "eb733a00c0c9d336e65691a37ab54293", 9),
+        ];
+        let report = bucket.reconcile(stream::iter(manifest.clone())).await?;
+        assert!(!report.is_consistent());
+        assert_eq!(report.missing, vec![manifest[2].clone()]);
+        assert_eq!(report.extra.len(), 1);
+        assert_eq!(report.extra[0].filename.as_deref(), Some("old.txt"));
+        assert_eq!(report.mismatched.len(), 1);
+        assert_eq!(report.mismatched[0].0, manifest[1]);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    bucket::{
+        inline::INLINE_FIELD,
+        routing::{chunk_collection_of, chunk_filter, CHUNKS_COLLECTION_FIELD},
+        status::visible,
+        tier::TIER_FIELD,
+        upload::check_chunk_size,
+        GridFSBucket,
+    },
+    encryption::{chunk_data_field, ENCRYPTION_KEY_FIELD},
+    options::{GridFSDownloadOptions, ProgressUpdate},
+    FileInfo, GridFSError,
+};
+use bson::{doc, Bson, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::{
+    options::{DeleteOptions, FindOptions, InsertOneOptions, UpdateOptions},
+    Collection,
+};
+use std::{convert::TryFrom, sync::Arc};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+impl GridFSBucket {
+    /**
+    Rewrites the stored files matching @filter with chunks of @new_size bytes. Returns the
+    number of files rewritten. @progress is updated with this number after each file.
+
+    The chunks of a file are copied to another chunks collection: the chunks collection of
+    the bucket, or `<bucket_name>.chunks.<new_size>` for the files already there. The files
+    collection document is then switched to the new chunks and chunk size in a single update,
+    so the readers see either the former or the new chunks, and the former chunks are removed.
+
+    The files already stored with chunks of @new_size are left out: an interrupted migration
+    is resumed by running it again. The files whose upload is in progress and the tiered
+    files are left out too, as is a file changed during its copy.
+
+    # Errors
+
+    Raise [`GridFSError::InvalidChunkSize`] when @new_size is 0 or over 15MiB.
+    */
+    pub async fn migrate_chunk_size(
+        &self,
+        new_size: u32,
+        filter: Document,
+        progress: Option<Arc<dyn ProgressUpdate + Send + Sync>>,
+    ) -> Result<u64, GridFSError> {
+        check_chunk_size(new_size)?;
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let bucket_name = dboptions.bucket_name.clone();
+        let files = self
+            .db
+            .collection::<Document>(&(bucket_name.clone() + ".files"));
+
+        let find_options = FindOptions::builder().sort(doc! {"_id":1}).build();
+        let mut cursor = files
+            .find(
+                visible(doc! {"$and":[
+                    filter,
+                    {
+                        "length":{"$exists":true},
+                        "chunkSize":{"$ne":new_size as i32},
+                        TIER_FIELD:{"$exists":false},
+                        INLINE_FIELD:{"$exists":false},
+                    },
+                ]}),
+                find_options,
+            )
+            .await?;
+
+        let insert_options = InsertOneOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        let update_options = UpdateOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        let delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        let subtype = dboptions.chunk_binary_subtype.into();
+
+        let mut migrated = 0;
+        while let Some(document) = cursor.next().await {
+            let document = document?;
+            let file = FileInfo::try_from(document.clone())?;
+            let former_collection = chunk_collection_of(&document, &bucket_name);
+            let bucket_chunks = bucket_name.clone() + ".chunks";
+            let target_collection = if former_collection == bucket_chunks {
+                format!("{}.chunks.{}", bucket_name, new_size)
+            } else {
+                bucket_chunks.clone()
+            };
+            self.ensure_chunks_index(&target_collection).await?;
+            let target: Collection<Document> = self.db.collection(&target_collection);
+            let chunk_base = chunk_filter(&document, file.id, self.chunk_shard_key());
+            // The chunks left by an interrupted copy of the file.
+            target
+                .delete_many(chunk_base.clone(), delete_options.clone())
+                .await?;
+
+            let (mut chunks, _) = self
+                .open_chunk_stream(file.id, &GridFSDownloadOptions::default())
+                .await?;
+            let mut buffer = Vec::with_capacity(new_size as usize);
+            let mut n = 0;
+            loop {
+                let data = chunks.next().await.transpose()?;
+                if let Some(data) = &data {
+                    buffer.extend_from_slice(data);
+                }
+                while buffer.len() >= new_size as usize || (data.is_none() && !buffer.is_empty()) {
+                    let rest = buffer.split_off(buffer.len().min(new_size as usize));
+                    let mut chunk = chunk_base.clone();
+                    chunk.insert("n", n);
+                    chunk.insert(
+                        "data",
+                        chunk_data_field(
+                            dboptions.chunk_encryption.as_ref(),
+                            subtype,
+                            std::mem::replace(&mut buffer, rest),
+                        )
+                        .await?,
+                    );
+                    target.insert_one(chunk, insert_options.clone()).await?;
+                    n += 1;
+                }
+                if data.is_none() {
+                    break;
+                }
+            }
+
+            // The swap only applies to the file as it was copied.
+            let swap = doc! {
+                "_id":file.id,
+                "chunkSize":document.get("chunkSize").cloned().unwrap_or(Bson::Null),
+                "length":document.get("length").cloned().unwrap_or(Bson::Null),
+                CHUNKS_COLLECTION_FIELD:document
+                    .get(CHUNKS_COLLECTION_FIELD)
+                    .cloned()
+                    .unwrap_or_else(|| Bson::Document(doc! {"$exists":false})),
+            };
+            let mut set = doc! {"chunkSize":new_size as i32};
+            let mut unset = Document::new();
+            if target_collection == bucket_chunks {
+                unset.insert(CHUNKS_COLLECTION_FIELD, "");
+            } else {
+                set.insert(CHUNKS_COLLECTION_FIELD, &target_collection);
+            }
+            // The chunks are written with the data key of the bucket.
+            match &dboptions.chunk_encryption {
+                Some(encryption) => set.insert(ENCRYPTION_KEY_FIELD, encryption.key_id.clone()),
+                None => unset.insert(ENCRYPTION_KEY_FIELD, ""),
+            };
+            let mut update = doc! {"$set":set};
+            if !unset.is_empty() {
+                update.insert("$unset", unset);
+            }
+            let swapped = files
+                .update_one(swap, update, update_options.clone())
+                .await?
+                .matched_count
+                == 1;
+            let stale_collection = if swapped {
+                former_collection
+            } else {
+                target_collection
+            };
+            self.db
+                .collection::<Document>(&stale_collection)
+                .delete_many(chunk_base, delete_options.clone())
+                .await?;
+            if swapped {
+                migrated += 1;
+                if let Some(progress) = &progress {
+                    progress.update(migrated as usize);
+                }
+            }
+        }
+        Ok(migrated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bucket::GridFSBucket,
+        options::{GridFSBucketOptions, ProgressUpdate},
+        GridFSError,
+    };
+    use bson::{doc, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[derive(Default)]
+    struct FilesRecorder(AtomicUsize);
+
+    impl ProgressUpdate for FilesRecorder {
+        fn update(&self, position: usize) {
+            self.0.store(position, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_chunk_size() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("other.txt", "other data".as_bytes(), None)
+            .await?;
+
+        assert!(matches!(
+            bucket.migrate_chunk_size(0, doc! {}, None).await,
+            Err(GridFSError::InvalidChunkSize(0))
+        ));
+
+        let progress = Arc::new(FilesRecorder::default());
+        assert_eq!(
+            bucket
+                .migrate_chunk_size(8, doc! {"filename":"test.txt"}, Some(progress.clone()))
+                .await?,
+            1
+        );
+        assert_eq!(progress.0.load(Ordering::SeqCst), 1);
+        let files = db.collection::<Document>("fs.files");
+        let file = files.find_one(doc! {"_id":id}, None).await?.unwrap();
+        assert_eq!(file.get_i32("chunkSize").unwrap(), 8);
+        assert_eq!(file.get_str("chunksCollection").unwrap(), "fs.chunks.8");
+        assert_eq!(
+            db.collection::<Document>("fs.chunks.8")
+                .count_documents(doc! {"files_id":id}, None)
+                .await?,
+            2
+        );
+        assert_eq!(
+            db.collection::<Document>("fs.chunks")
+                .count_documents(doc! {"files_id":id}, None)
+                .await?,
+            0
+        );
+        let mut cursor = bucket.open_download_stream(id).await?;
+        let mut content = vec![];
+        while let Some(data) = cursor.next().await {
+            content.extend(data?);
+        }
+        assert_eq!(content, b"test data");
+
+        // Resumed: the migrated files are left out.
+        assert_eq!(bucket.migrate_chunk_size(8, doc! {}, None).await?, 1);
+        assert_eq!(bucket.migrate_chunk_size(8, doc! {}, None).await?, 0);
+
+        // Back to the chunks collection of the bucket.
+        assert_eq!(bucket.migrate_chunk_size(3, doc! {}, None).await?, 2);
+        let file = files.find_one(doc! {"_id":id}, None).await?.unwrap();
+        assert_eq!(file.get_i32("chunkSize").unwrap(), 3);
+        assert!(file.get("chunksCollection").is_none());
+        assert_eq!(
+            db.collection::<Document>("fs.chunks")
+                .count_documents(doc! {"files_id":id}, None)
+                .await?,
+            3
+        );
+        let mut cursor = bucket.open_download_stream(id).await?;
+        let mut content = vec![];
+        while let Some(data) = cursor.next().await {
+            content.extend(data?);
+        }
+        assert_eq!(content, b"test data");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -1,11 +1,100 @@
+mod attachment;
+mod bytes_stream;
+mod causal;
+#[cfg(any(feature = "md5", feature = "sha256"))]
+mod checksum;
+mod chunk_stream;
+mod chunks;
+mod config;
+#[cfg(feature = "content-search")]
+mod content;
 mod delete;
+mod derived;
 mod download;
 mod drop;
 mod find;
+mod head;
+mod health;
+mod info;
+#[cfg(feature = "watch-fs")]
+mod ingest;
+mod inline;
+mod legacy;
+mod list;
+mod manifest;
+mod migrate;
+mod normalize;
+mod op_stats;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+pub(crate) mod qos;
+mod quarantine;
 mod rename;
+mod report;
+mod reserve;
+mod resume;
+mod rotate;
+mod routing;
+#[cfg(feature = "prometheus")]
+mod sampler;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+mod scope;
+mod stats;
+pub(crate) mod status;
+mod tier;
 mod upload;
-use crate::options::GridFSBucketOptions;
-use mongodb::Database;
+pub(crate) use upload::check_chunk_size;
+mod upload_many;
+mod upload_stream;
+#[cfg(feature = "md5")]
+mod verify;
+mod warm;
+#[cfg(feature = "test-util")]
+use crate::options::ChaosOptions;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use crate::options::Priority;
+use crate::{
+    bucket_name,
+    clock::{Clock, SystemClock},
+    options::GridFSBucketOptions,
+    GridFSError,
+};
+pub use attachment::Attachment;
+use bson::{DateTime, Document};
+pub use bytes_stream::GridFSBytesStream;
+pub use causal::CausalToken;
+#[cfg(any(feature = "md5", feature = "sha256"))]
+pub use checksum::BackfillReport;
+pub use chunks::GridFSChunkStream;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+pub use delete::DeletionHandle;
+pub use download::GridFSDownloadStream;
+pub use health::BucketHealth;
+#[cfg(feature = "watch-fs")]
+pub use ingest::IngestHandle;
+pub use list::{FilePage, PageToken};
+pub use manifest::{ManifestEntry, ReconcileReport};
+use mongodb::{Collection, Database};
+pub use normalize::{LegacyChange, NormalizeReport, NormalizedFile};
+use op_stats::OpCounters;
+pub use op_stats::OpStats;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use qos::Qos;
+pub use quarantine::QuarantinedFile;
+pub use report::{LatencyHistogram, UploadDiagnostics, UploadReport};
+pub use resume::PartialUpload;
+#[cfg(feature = "prometheus")]
+pub use sampler::StatsSampler;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+pub use scope::{BucketScope, ScopedTransfer};
+pub use stats::BucketStats;
+use std::sync::{atomic::AtomicBool, Arc};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::sync::RwLock;
+pub use upload_stream::GridFSUploadStream;
+#[cfg(all(feature = "md5", any(feature = "default", feature = "tokio-runtime")))]
+pub use verify::VerifyReport;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+pub use warm::Warmer;
 
 /// GridFS bucket. A prefix under which a GridFS system’s collections are stored.
 /// [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#configurable-gridfsbucket-class)
@@ -15,6 +104,20 @@ pub struct GridFSBucket {
     pub(crate) options: Option<GridFSBucketOptions>,
     // internal: when true should check the indexes
     pub(crate) never_write: bool,
+    #[cfg(feature = "test-util")]
+    pub(crate) chaos: Option<Arc<ChaosOptions>>,
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub(crate) qos: Arc<Qos>,
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub(crate) priority: Priority,
+    // Shared by the clones: held for read by the writes in flight, for write by
+    // `drop_guarded`.
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub(crate) writers: Arc<RwLock<()>>,
+    // Shared by the clones: the counters of `op_stats`.
+    pub(crate) operations: Arc<OpCounters>,
+    // Shared by the clones: whether the index of the derived files is created.
+    pub(crate) derived_indexed: Arc<AtomicBool>,
 }
 
 impl GridFSBucket {
@@ -23,9 +126,106 @@ impl GridFSBucket {
      */
     pub fn new(db: Database, options: Option<GridFSBucketOptions>) -> GridFSBucket {
         GridFSBucket {
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            qos: Arc::new(Qos::new(&options.clone().unwrap_or_default())),
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            priority: Priority::Interactive,
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            writers: Arc::new(RwLock::new(())),
+            operations: Arc::new(OpCounters::default()),
+            derived_indexed: Arc::new(AtomicBool::new(false)),
             db,
             options,
             never_write: true,
+            #[cfg(feature = "test-util")]
+            chaos: None,
+        }
+    }
+
+    /**
+     * Create a new GridFSBucket object on @db with the given @options, once they are
+     * validated by [`GridFSBucketOptions::validate`] and the namespaces of the bucket fit
+     * in @db.
+     *
+     * # Errors
+     *
+     * Raise [`GridFSError::InvalidChunkSize`] when the chunk size of @options is out of bounds.
+     * Raise [`GridFSError::InvalidBucketName`] when the bucket name of @options doesn't make
+     * valid collection names in @db.
+     */
+    pub fn try_new(
+        db: Database,
+        options: Option<GridFSBucketOptions>,
+    ) -> Result<GridFSBucket, GridFSError> {
+        if let Some(options) = &options {
+            options.validate()?;
+            bucket_name::check(&options.bucket_name, db.name())?;
+        }
+        Ok(GridFSBucket::new(db, options))
+    }
+
+    /**
+     * Create a new GridFSBucket object on @db with the options of this bucket.
+     * The indexes are checked again before the first write, unless @db is the
+     * database of this bucket.
+     */
+    pub fn with_database(&self, db: Database) -> GridFSBucket {
+        let never_write = self.never_write || db.name() != self.db.name();
+        let derived_indexed = match db.name() == self.db.name() {
+            true => self.derived_indexed.clone(),
+            false => Arc::new(AtomicBool::new(false)),
+        };
+        GridFSBucket {
+            db,
+            never_write,
+            derived_indexed,
+            ..self.clone()
+        }
+    }
+
+    /**
+     * The files collection of the bucket, for the queries not covered by the bucket API.
+     */
+    pub fn files_collection(&self) -> Collection<Document> {
+        self.db
+            .collection(&(self.options.clone().unwrap_or_default().bucket_name + ".files"))
+    }
+
+    /**
+     * The chunks collection of the bucket, for the queries not covered by the bucket API.
+     *
+     * The chunks of the files uploaded with the
+     * [`chunks_collection`](crate::options::GridFSUploadOptions::chunks_collection) upload
+     * option are in the collection named by their files collection document instead.
+     */
+    pub fn chunks_collection(&self) -> Collection<Document> {
+        self.db
+            .collection(&(self.options.clone().unwrap_or_default().bucket_name + ".chunks"))
+    }
+
+    /// The current time, from the [`Clock`](crate::clock::Clock) of the bucket.
+    pub(crate) fn now(&self) -> DateTime {
+        match self
+            .options
+            .as_ref()
+            .and_then(|options| options.clock.as_ref())
+        {
+            Some(clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    /// Applies the filename policy of the bucket to @filename. Returns the filename to store.
+    pub(crate) fn checked_filename(&self, filename: &str) -> Result<String, GridFSError> {
+        match self
+            .options
+            .as_ref()
+            .and_then(|options| options.filename_policy.as_ref())
+        {
+            Some(policy) => policy
+                .apply(filename)
+                .map_err(|violation| GridFSError::InvalidFilename(filename.to_string(), violation)),
+            None => Ok(filename.to_string()),
         }
     }
 }
@@ -33,8 +233,9 @@ impl GridFSBucket {
 #[cfg(test)]
 mod tests {
     use super::{GridFSBucket, GridFSBucketOptions};
+    use crate::{bucket_name, BucketNameViolation, GridFSError};
     use mongodb::Client;
-    use mongodb::{error::Error, Database};
+    use mongodb::Database;
     use uuid::Uuid;
     fn db_name_new() -> String {
         "test_".to_owned()
@@ -44,7 +245,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn grid_f_s_bucket_new() -> Result<(), Error> {
+    async fn grid_f_s_bucket_new() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
             &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
         )
@@ -63,4 +264,97 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn grid_f_s_bucket_try_new() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let db: Database = client.database(&db_name_new());
+        let options = GridFSBucketOptions::builder()
+            .bucket_name(bucket_name::escape("acme.com"))
+            .build();
+        assert!(GridFSBucket::try_new(db.clone(), Some(options)).is_ok());
+
+        // Fits without the database, but not with it.
+        let options = GridFSBucketOptions::builder()
+            .bucket_name("a".repeat(200))
+            .build();
+        assert!(options.validate().is_ok());
+        assert!(matches!(
+            GridFSBucket::try_new(db, Some(options)),
+            Err(GridFSError::InvalidBucketName(
+                _,
+                BucketNameViolation::TooLong(173)
+            ))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn grid_f_s_bucket_collections() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(db.clone(), None);
+        assert_eq!(bucket.files_collection().name(), "fs.files");
+        assert_eq!(bucket.chunks_collection().name(), "fs.chunks");
+
+        let bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .bucket_name("tenant".into())
+                    .build(),
+            ),
+        );
+        assert_eq!(bucket.files_collection().name(), "tenant.files");
+        assert_eq!(bucket.chunks_collection().name(), "tenant.chunks");
+        assert_eq!(bucket.files_collection().namespace().db, dbname);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn grid_f_s_bucket_with_database() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .bucket_name("tenant".into())
+                    .chunk_size_bytes(4)
+                    .build(),
+            ),
+        );
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        assert!(!bucket.never_write);
+
+        let other_dbname = db_name_new();
+        let other_db: Database = client.database(&other_dbname);
+        let other = bucket.with_database(other_db.clone());
+        assert_eq!(other.db.name(), other_dbname);
+        assert!(other.never_write);
+        let options = other.options.clone().unwrap();
+        assert_eq!(options.bucket_name, "tenant");
+        assert_eq!(options.chunk_size_bytes, 4);
+
+        let same = bucket.with_database(db.clone());
+        assert!(!same.never_write);
+
+        db.drop(None).await?;
+        other_db.drop(None).await?;
+        Ok(())
+    }
 }
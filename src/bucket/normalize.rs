@@ -0,0 +1,377 @@
+use crate::{
+    bucket::{status::STATUS_FIELD, GridFSBucket},
+    FileStatus, GridFSError,
+};
+use bson::{doc, oid::ObjectId, Bson, DateTime, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::options::{FindOptions, UpdateOptions};
+use std::fmt::{Display, Formatter};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// The deprecated fields of the files collection documents, moved to their metadata.
+const DEPRECATED_FIELDS: [&str; 2] = ["contentType", "aliases"];
+
+/// A change of a files collection document by [`GridFSBucket::normalize_legacy_documents`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "change", rename_all = "snake_case"))]
+pub enum LegacyChange {
+    /// The number `field`, stored as a `from`, e.g. a `double`, is stored with the type the
+    /// crate writes: an int64 `length`, an int32 `chunkSize`.
+    NumberType {
+        field: &'static str,
+        from: &'static str,
+    },
+    /// The missing `uploadDate` of a complete file is set to the creation date of its id.
+    UploadDate { date: DateTime },
+    /// The deprecated `field`, `contentType` or `aliases`, is moved to the metadata of the
+    /// file, as advised by the spec.
+    MovedToMetadata { field: &'static str },
+}
+
+/// `length: double to int64`, `uploadDate set to 2020-10-21T01:23:45Z`, `contentType moved to
+/// metadata`.
+impl Display for LegacyChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LegacyChange::NumberType { field, from } => {
+                let to = match *field {
+                    "length" => "int64",
+                    _ => "int32",
+                };
+                write!(f, "{}: {} to {}", field, from, to)
+            }
+            LegacyChange::UploadDate { date } => match date.try_to_rfc3339_string() {
+                Ok(date) => write!(f, "uploadDate set to {}", date),
+                Err(_) => write!(f, "uploadDate set"),
+            },
+            LegacyChange::MovedToMetadata { field } => write!(f, "{} moved to metadata", field),
+        }
+    }
+}
+
+/// A files collection document rewritten by [`GridFSBucket::normalize_legacy_documents`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NormalizedFile {
+    pub id: ObjectId,
+    pub changes: Vec<LegacyChange>,
+}
+
+/// The outcome of [`GridFSBucket::normalize_legacy_documents`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NormalizeReport {
+    /// The files rewritten, with their changes.
+    pub files: Vec<NormalizedFile>,
+    /// The number of legacy files left as they are: with a number that isn't an integer, or
+    /// deleted during the job.
+    pub files_skipped: u64,
+}
+
+/// `n files normalized (n changes), n skipped`.
+impl Display for NormalizeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files normalized ({} changes), {} skipped",
+            self.files.len(),
+            self.files
+                .iter()
+                .map(|file| file.changes.len())
+                .sum::<usize>(),
+            self.files_skipped
+        )
+    }
+}
+
+/// The integer stored in the double @value.
+fn integer(value: f64) -> Option<i64> {
+    (value.is_finite() && value.fract() == 0.0).then_some(value as i64)
+}
+
+/// The changes bringing the files collection document @file to the layout written by the
+/// crate, with the update applying them. The update is empty when there is no change.
+fn legacy_changes(file: &Document, id: ObjectId) -> (Vec<LegacyChange>, Document) {
+    let mut changes = vec![];
+    let mut set = Document::new();
+    let mut unset = Document::new();
+
+    let length = match file.get("length") {
+        Some(Bson::Double(length)) => integer(*length).map(|length| (length, "double")),
+        Some(Bson::Int32(length)) => Some((*length as i64, "int32")),
+        _ => None,
+    };
+    if let Some((length, from)) = length.filter(|(length, _)| *length >= 0) {
+        set.insert("length", length);
+        changes.push(LegacyChange::NumberType {
+            field: "length",
+            from,
+        });
+    }
+    let chunk_size = match file.get("chunkSize") {
+        Some(Bson::Double(chunk_size)) => {
+            integer(*chunk_size).map(|chunk_size| (chunk_size, "double"))
+        }
+        Some(Bson::Int64(chunk_size)) => Some((*chunk_size, "int64")),
+        _ => None,
+    };
+    if let Some((chunk_size, from)) =
+        chunk_size.filter(|(chunk_size, _)| (1..=i32::MAX as i64).contains(chunk_size))
+    {
+        set.insert("chunkSize", chunk_size as i32);
+        changes.push(LegacyChange::NumberType {
+            field: "chunkSize",
+            from,
+        });
+    }
+
+    // The files without length are uploads in progress: they have no upload date yet.
+    if file.contains_key("length") && matches!(file.get("uploadDate"), None | Some(Bson::Null)) {
+        let date = id.timestamp();
+        set.insert("uploadDate", date);
+        changes.push(LegacyChange::UploadDate { date });
+    }
+
+    let metadata = match file.get("metadata") {
+        None | Some(Bson::Null) => Some(None),
+        Some(Bson::Document(metadata)) => Some(Some(metadata)),
+        // Not a document: the fields can't be moved into it.
+        Some(_) => None,
+    };
+    if let Some(metadata) = metadata {
+        let mut created = Document::new();
+        for field in DEPRECATED_FIELDS {
+            let value = match file.get(field) {
+                Some(value) => value,
+                None => continue,
+            };
+            match metadata.and_then(|metadata| metadata.get(field)) {
+                // Already in the metadata: the deprecated field is dropped.
+                Some(moved) if moved == value => {}
+                // A different value in the metadata: both are kept.
+                Some(_) => continue,
+                None if metadata.is_some() => {
+                    set.insert(format!("metadata.{}", field), value.clone());
+                }
+                None => {
+                    created.insert(field, value.clone());
+                }
+            }
+            unset.insert(field, "");
+            changes.push(LegacyChange::MovedToMetadata { field });
+        }
+        if !created.is_empty() {
+            set.insert("metadata", created);
+        }
+    }
+
+    let mut update = Document::new();
+    if !set.is_empty() {
+        update.insert("$set", set);
+    }
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+    (changes, update)
+}
+
+impl GridFSBucket {
+    /**
+    Rewrites the files collection documents written by older drivers into the layout the
+    crate writes, and reports each change: the `length` and `chunkSize` stored as doubles,
+    or with another integer type, are converted, the missing `uploadDate` of the complete
+    files is set to the creation date of their id, and the deprecated `contentType` and
+    `aliases` fields are moved to the metadata of the files.
+
+    The documents are rewritten one at a time; the job can be stopped and run again. A
+    number that isn't an integer is left as it is, as is a deprecated field whose metadata
+    already holds a different value.
+
+    # Errors
+
+    Raise [`GridFSError::InvalidFile`] when the id of a file isn't an ObjectId.
+    */
+    pub async fn normalize_legacy_documents(&self) -> Result<NormalizeReport, GridFSError> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let filter = doc! {
+            STATUS_FIELD:{"$ne":FileStatus::Deleting.as_str()},
+            "$or":[
+                {"length":{"$type":["double", "int"]}},
+                {"chunkSize":{"$type":["double", "long"]}},
+                {"length":{"$exists":true}, "uploadDate":Bson::Null},
+                {"contentType":{"$exists":true}},
+                {"aliases":{"$exists":true}},
+            ],
+        };
+        let find_options = FindOptions::builder().sort(doc! {"_id":1}).build();
+        let mut cursor = self.files_collection().find(filter, find_options).await?;
+        let update_options = UpdateOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+
+        let mut report = NormalizeReport::default();
+        while let Some(file) = cursor.next().await {
+            let file = file?;
+            let id = file
+                .get_object_id("_id")
+                .map_err(|_| GridFSError::InvalidFile("_id isn't an ObjectId".into()))?;
+            let (changes, update) = legacy_changes(&file, id);
+            if changes.is_empty() {
+                report.files_skipped += 1;
+                continue;
+            }
+            let updated = self
+                .files_collection()
+                .update_one(doc! {"_id":id}, update, update_options.clone())
+                .await?;
+            match updated.matched_count {
+                0 => report.files_skipped += 1,
+                _ => report.files.push(NormalizedFile { id, changes }),
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{legacy_changes, LegacyChange};
+    use crate::{bucket::GridFSBucket, options::GridFSBucketOptions, GridFSError};
+    use bson::{doc, oid::ObjectId, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn legacy_document_changes() {
+        let id = ObjectId::parse_str("5f8f8c2b1b7156c9d1a2b3c4").unwrap();
+        let file = doc! {
+            "_id":id, "length":9.0, "chunkSize":261120.0, "contentType":"text/plain",
+            "aliases":["a"], "metadata":{"aliases":["b"]},
+        };
+        let (changes, update) = legacy_changes(&file, id);
+        let date = id.timestamp();
+        assert_eq!(
+            changes,
+            [
+                LegacyChange::NumberType {
+                    field: "length",
+                    from: "double"
+                },
+                LegacyChange::NumberType {
+                    field: "chunkSize",
+                    from: "double"
+                },
+                LegacyChange::UploadDate { date },
+                LegacyChange::MovedToMetadata {
+                    field: "contentType"
+                },
+            ]
+        );
+        assert_eq!(
+            update,
+            doc! {
+                "$set":{"length":9_i64, "chunkSize":261120, "uploadDate":date,
+                "metadata.contentType":"text/plain"},
+                "$unset":{"contentType":""},
+            }
+        );
+        assert_eq!(changes[0].to_string(), "length: double to int64");
+        assert_eq!(changes[3].to_string(), "contentType moved to metadata");
+
+        let (changes, update) = legacy_changes(&doc! {"contentType":"text/plain"}, id);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            update,
+            doc! {"$set":{"metadata":{"contentType":"text/plain"}}, "$unset":{"contentType":""}}
+        );
+
+        // Modern, or in progress, or not an integer.
+        for file in [
+            doc! {"length":9_i64, "chunkSize":4, "uploadDate":bson::DateTime::now()},
+            doc! {"chunkSize":4},
+            doc! {"length":9.5, "chunkSize":4, "uploadDate":bson::DateTime::now()},
+        ] {
+            let (changes, update) = legacy_changes(&file, id);
+            assert!(changes.is_empty());
+            assert!(update.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn normalize_legacy_documents() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("modern.txt", "test data".as_bytes(), None)
+            .await?;
+        let files = db.collection::<Document>("fs.files");
+        // As written by a decade-old driver.
+        files
+            .update_one(
+                doc! {"_id":id},
+                doc! {
+                    "$set":{"length":9.0, "chunkSize":4.0, "contentType":"text/plain"},
+                    "$unset":{"uploadDate":""},
+                },
+                None,
+            )
+            .await?;
+
+        let report = bucket.normalize_legacy_documents().await?;
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.files[0].id, id);
+        assert_eq!(report.files[0].changes.len(), 4);
+        assert_eq!(
+            report.to_string(),
+            "1 files normalized (4 changes), 0 skipped"
+        );
+        let file = files.find_one(doc! {"_id":id}, None).await?.unwrap();
+        assert_eq!(file.get_i64("length"), Ok(9));
+        assert_eq!(file.get_i32("chunkSize"), Ok(4));
+        assert_eq!(file.get_datetime("uploadDate"), Ok(&id.timestamp()));
+        assert!(file.get("contentType").is_none());
+        assert_eq!(
+            file.get_document("metadata")
+                .unwrap()
+                .get_str("contentType"),
+            Ok("text/plain")
+        );
+        let mut content = vec![];
+        let mut cursor = bucket.open_download_stream(id).await?;
+        while let Some(chunk) = cursor.next().await {
+            content.extend(chunk?);
+        }
+        assert_eq!(content, b"test data");
+
+        assert!(bucket.normalize_legacy_documents().await?.files.is_empty());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{
+    bucket::{chunk_stream::ChunkStream, GridFSBucket},
+    display::HumanSize,
+    GridFSError,
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// The counters of the uploads and the downloads of a bucket, shared by its clones.
+#[derive(Debug, Default)]
+pub(crate) struct OpCounters {
+    started: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl OpCounters {
+    /// Counts a new operation, which ends when the returned [`Operation`] is dropped.
+    pub(crate) fn start(self: &Arc<Self>) -> Operation {
+        self.started.fetch_add(1, Ordering::Relaxed);
+        Operation {
+            counters: self.clone(),
+            failed: false,
+        }
+    }
+
+    /// A snapshot of the counters.
+    fn stats(&self) -> OpStats {
+        // Read the ends first, so the operations in flight never look negative.
+        let completed = self.completed.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        let started = self.started.load(Ordering::Relaxed);
+        OpStats {
+            started,
+            completed,
+            failed,
+            in_flight: started.saturating_sub(completed + failed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// An upload or a download in flight. It's counted as completed when dropped, unless it
+/// [failed](Operation::fail).
+#[derive(Debug)]
+pub(crate) struct Operation {
+    counters: Arc<OpCounters>,
+    failed: bool,
+}
+
+impl Operation {
+    /// Marks the operation as failed.
+    pub(crate) fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    /// Counts @bytes written by an upload.
+    pub(crate) fn bytes_in(&self, bytes: u64) {
+        self.counters.bytes_in.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Counts @bytes read by a download.
+    pub(crate) fn bytes_out(&self, bytes: u64) {
+        self.counters.bytes_out.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        let ended = match self.failed {
+            true => &self.counters.failed,
+            false => &self.counters.completed,
+        };
+        ended.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the activity of a bucket and its clones since its creation, returned by
+/// [`GridFSBucket::op_stats`]. The operations are the uploads and the downloads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OpStats {
+    /// The number of operations started.
+    pub started: u64,
+    /// The number of operations ended without error. A download ends when its stream is
+    /// dropped, even before its end.
+    pub completed: u64,
+    /// The number of operations ended with an error.
+    pub failed: u64,
+    /// The number of operations in flight.
+    pub in_flight: u64,
+    /// The number of bytes of the completed uploads.
+    pub bytes_in: u64,
+    /// The number of bytes of the chunks read by the downloads.
+    pub bytes_out: u64,
+}
+
+/// `n started, n completed, n failed, n in flight, size in, size out`.
+impl Display for OpStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} started, {} completed, {} failed, {} in flight, {} in, {} out",
+            self.started,
+            self.completed,
+            self.failed,
+            self.in_flight,
+            HumanSize(self.bytes_in),
+            HumanSize(self.bytes_out)
+        )
+    }
+}
+
+impl GridFSBucket {
+    /// The counters of the uploads and the downloads of the bucket and its clones, e.g. for
+    /// a health endpoint. The counters are kept in memory, from the creation of the bucket.
+    pub fn op_stats(&self) -> OpStats {
+        self.operations.stats()
+    }
+
+    /// Counts the download @opening: it ends when its chunk stream is dropped.
+    pub(crate) async fn counted_download<T>(
+        &self,
+        opening: impl Future<Output = Result<(ChunkStream, T), GridFSError>>,
+    ) -> Result<(ChunkStream, T), GridFSError> {
+        let mut operation = self.operations.start();
+        match opening.await {
+            Ok((chunks, file)) => Ok((chunks.with_operation(operation), file)),
+            Err(error) => {
+                operation.fail();
+                Err(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OpCounters, OpStats};
+    use crate::{bucket::GridFSBucket, options::GridFSBucketOptions, GridFSError};
+    use bson::oid::ObjectId;
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    use std::sync::Arc;
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn op_counters() {
+        let counters = Arc::new(OpCounters::default());
+        let upload = counters.start();
+        upload.bytes_in(1536);
+        drop(upload);
+        let mut download = counters.start();
+        download.bytes_out(10);
+        download.fail();
+        drop(download);
+        let _in_flight = counters.start();
+
+        let stats = counters.stats();
+        assert_eq!(
+            stats,
+            OpStats {
+                started: 3,
+                completed: 1,
+                failed: 1,
+                in_flight: 1,
+                bytes_in: 1536,
+                bytes_out: 10,
+            }
+        );
+        assert_eq!(
+            stats.to_string(),
+            "3 started, 1 completed, 1 failed, 1 in flight, 1.5 KiB in, 10 B out"
+        );
+    }
+
+    #[tokio::test]
+    async fn op_stats() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        assert_eq!(bucket.op_stats(), OpStats::default());
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert_eq!(bucket.op_stats().in_flight, 1);
+        while let Some(chunk) = cursor.next().await {
+            chunk?;
+        }
+        drop(cursor);
+        assert!(bucket.open_download_stream(ObjectId::new()).await.is_err());
+        assert_eq!(
+            bucket.op_stats(),
+            OpStats {
+                started: 3,
+                completed: 2,
+                failed: 1,
+                in_flight: 0,
+                bytes_in: 9,
+                bytes_out: 9,
+            }
+        );
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
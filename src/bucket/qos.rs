@@ -0,0 +1,306 @@
+use crate::{
+    bucket::GridFSBucket,
+    options::{GridFSBucketOptions, Priority, PriorityLimits},
+    GridFSError,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{Notify, OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+
+#[derive(Debug)]
+struct Class {
+    semaphore: Option<Arc<Semaphore>>,
+    max_bytes_per_second: Option<u64>,
+}
+
+impl Class {
+    fn new(limits: &PriorityLimits) -> Class {
+        Class {
+            semaphore: limits
+                .max_concurrent
+                .map(|max_concurrent| Arc::new(Semaphore::new(max_concurrent))),
+            max_bytes_per_second: limits.max_bytes_per_second,
+        }
+    }
+}
+
+/// The operations in flight and the state of the shutdown of a bucket.
+#[derive(Debug, Default)]
+struct Lifecycle {
+    closed: AtomicBool,
+    aborted: AtomicBool,
+    in_flight: AtomicUsize,
+    drained: Notify,
+}
+
+impl Lifecycle {
+    /// Waits until no operation is in flight.
+    async fn drained(&self) {
+        loop {
+            let drained = self.drained.notified();
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            drained.await;
+        }
+    }
+}
+
+/// The limits of the priority classes and the operations in flight, shared by the clones of
+/// a bucket.
+#[derive(Debug)]
+pub(crate) struct Qos {
+    interactive: Class,
+    batch: Class,
+    lifecycle: Arc<Lifecycle>,
+}
+
+impl Qos {
+    pub(crate) fn new(options: &GridFSBucketOptions) -> Qos {
+        Qos {
+            interactive: Class::new(&options.interactive_limits),
+            batch: Class::new(&options.batch_limits),
+            lifecycle: Arc::default(),
+        }
+    }
+
+    /**
+     * Waits for a slot of the @priority class. The slot is released when dropped.
+     *
+     * # Errors
+     *
+     * Raise [`GridFSError::ShuttingDown`] when the bucket is shut down.
+     */
+    pub(crate) async fn acquire(&self, priority: Priority) -> Result<Slot, GridFSError> {
+        // Counted before the check, so a shutdown either waits for the slot or rejects it.
+        self.lifecycle.in_flight.fetch_add(1, Ordering::SeqCst);
+        let mut slot = Slot {
+            _permit: None,
+            throttle: None,
+            lifecycle: self.lifecycle.clone(),
+        };
+        if self.is_closed() {
+            return Err(GridFSError::ShuttingDown());
+        }
+        let class = match priority {
+            Priority::Interactive => &self.interactive,
+            Priority::Batch => &self.batch,
+        };
+        if let Some(semaphore) = &class.semaphore {
+            slot._permit = semaphore.clone().acquire_owned().await.ok();
+            if self.is_closed() {
+                return Err(GridFSError::ShuttingDown());
+            }
+        }
+        slot.throttle = class.max_bytes_per_second.map(|limit| Throttle {
+            started: Instant::now(),
+            bytes: 0,
+            limit,
+        });
+        Ok(slot)
+    }
+
+    /// Rejects the new operations, and waits @grace for the ones in flight, then aborts them
+    /// and waits @grace again. See [`GridFSBucket::shutdown`].
+    pub(crate) async fn shutdown(&self, grace: Duration) -> Result<(), GridFSError> {
+        let lifecycle = &self.lifecycle;
+        lifecycle.closed.store(true, Ordering::SeqCst);
+        if tokio::time::timeout(grace, lifecycle.drained())
+            .await
+            .is_ok()
+        {
+            return Ok(());
+        }
+        lifecycle.aborted.store(true, Ordering::SeqCst);
+        tokio::time::timeout(grace, lifecycle.drained())
+            .await
+            .map_err(|_| GridFSError::BucketBusy())
+    }
+
+    /// Whether the bucket is shut down: it doesn't accept new operations.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.lifecycle.closed.load(Ordering::SeqCst)
+    }
+
+    /// Whether the operations in flight are aborted by the shutdown.
+    pub(crate) fn is_aborted(&self) -> bool {
+        self.lifecycle.aborted.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug)]
+struct Throttle {
+    started: Instant,
+    bytes: u64,
+    limit: u64,
+}
+
+/// A running operation of a priority class.
+#[derive(Debug)]
+pub(crate) struct Slot {
+    _permit: Option<OwnedSemaphorePermit>,
+    throttle: Option<Throttle>,
+    lifecycle: Arc<Lifecycle>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if self.lifecycle.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.lifecycle.drained.notify_waiters();
+        }
+    }
+}
+
+impl Slot {
+    /// Whether the operation is aborted by the shutdown of the bucket: it stops at its next
+    /// chunk, and an upload removes what it has written.
+    pub(crate) fn is_aborted(&self) -> bool {
+        self.lifecycle.aborted.load(Ordering::SeqCst)
+    }
+
+    /// Accounts for @bytes transferred. Returns how long to wait to stay under the
+    /// throughput of the class.
+    pub(crate) fn delay(&mut self, bytes: usize) -> Option<Duration> {
+        let throttle = self.throttle.as_mut()?;
+        throttle.bytes += bytes as u64;
+        let expected = Duration::from_secs_f64(throttle.bytes as f64 / throttle.limit.max(1) as f64);
+        expected
+            .checked_sub(throttle.started.elapsed())
+            .filter(|delay| !delay.is_zero())
+    }
+}
+
+impl GridFSBucket {
+    /**
+     * Returns a clone of the bucket whose uploads and downloads belong to the @priority
+     * class, and are limited by its [`PriorityLimits`]. The clones of a bucket share the
+     * concurrency limits, so a batch job and the interactive users of one bucket don't
+     * compete for the same slots.
+     */
+    pub fn with_priority(&self, priority: Priority) -> GridFSBucket {
+        GridFSBucket {
+            priority,
+            ..self.clone()
+        }
+    }
+
+    /**
+    Shuts the bucket and its clones down, e.g. when the container is stopped: the new
+    uploads and downloads raise [`GridFSError::ShuttingDown`], and the ones in flight get
+    @grace to end. The operations still in flight then are aborted at their next chunk: the
+    aborted uploads remove their chunks, the aborted downloads end with
+    [`GridFSError::ShuttingDown`]. The background jobs of the bucket, like the
+    [warmer](GridFSBucket::start_warmer) or the deletions of
+    [`GridFSBucket::delete_async`], stop as well: the interrupted deletions are completed by
+    [`GridFSBucket::purge_deleted`] after the restart.
+
+    # Errors
+
+    Raise [`GridFSError::BucketBusy`] when operations are still in flight @grace after they
+    were aborted, e.g. a download stream which isn't polled anymore.
+    */
+    pub async fn shutdown(&self, grace: Duration) -> Result<(), GridFSError> {
+        self.qos.shutdown(grace).await
+    }
+
+    /// Whether the bucket is shut down by [`GridFSBucket::shutdown`].
+    pub fn is_shut_down(&self) -> bool {
+        self.qos.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Qos;
+    use crate::{
+        options::{GridFSBucketOptions, Priority, PriorityLimits},
+        GridFSError,
+    };
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn throttle() {
+        let qos = Qos::new(
+            &GridFSBucketOptions::builder()
+                .batch_limits(
+                    PriorityLimits::builder()
+                        .max_bytes_per_second(Some(1000))
+                        .build(),
+                )
+                .build(),
+        );
+        let mut slot = qos.acquire(Priority::Batch).await.unwrap();
+        assert_eq!(slot.delay(500), Some(Duration::from_millis(500)));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(slot.delay(500), None);
+
+        let mut slot = qos.acquire(Priority::Interactive).await.unwrap();
+        assert_eq!(slot.delay(500), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_concurrent() {
+        let qos = Qos::new(
+            &GridFSBucketOptions::builder()
+                .batch_limits(PriorityLimits::builder().max_concurrent(Some(1)).build())
+                .build(),
+        );
+        let slot = qos.acquire(Priority::Batch).await.unwrap();
+        let waiting = tokio::time::timeout(Duration::from_secs(1), qos.acquire(Priority::Batch));
+        assert!(waiting.await.is_err());
+        let _interactive = qos.acquire(Priority::Interactive).await.unwrap();
+
+        drop(slot);
+        let waiting = tokio::time::timeout(Duration::from_secs(1), qos.acquire(Priority::Batch));
+        assert!(waiting.await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown() {
+        let qos = Qos::new(&GridFSBucketOptions::default());
+        let slot = qos.acquire(Priority::Interactive).await.unwrap();
+        let ending = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            drop(slot);
+        });
+        assert!(qos.shutdown(Duration::from_secs(5)).await.is_ok());
+        ending.await.unwrap();
+        assert!(qos.is_closed());
+        assert!(!qos.is_aborted());
+        assert!(matches!(
+            qos.acquire(Priority::Batch).await,
+            Err(GridFSError::ShuttingDown())
+        ));
+        assert!(qos.shutdown(Duration::from_secs(5)).await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_aborts() {
+        let qos = Qos::new(&GridFSBucketOptions::default());
+        let slot = qos.acquire(Priority::Interactive).await.unwrap();
+        let stuck = qos.acquire(Priority::Batch).await.unwrap();
+        let aborting = tokio::spawn(async move {
+            // Ends once aborted, like an upload at its next chunk.
+            while !slot.is_aborted() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        });
+        assert!(matches!(
+            qos.shutdown(Duration::from_secs(1)).await,
+            Err(GridFSError::BucketBusy())
+        ));
+        aborting.await.unwrap();
+        assert!(qos.is_aborted());
+        assert!(stuck.is_aborted());
+        drop(stuck);
+        assert!(qos.shutdown(Duration::from_secs(1)).await.is_ok());
+    }
+}
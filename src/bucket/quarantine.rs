@@ -0,0 +1,232 @@
+use crate::{
+    bucket::{
+        status::{with_status, STATUS_FIELD},
+        GridFSBucket,
+    },
+    FileInfo, FileStatus, GridFSError,
+};
+use bson::{doc, oid::ObjectId, DateTime};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::options::{FindOptions, UpdateOptions};
+use std::convert::TryFrom;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// Field of the files collection document of a quarantined file, holding the reason and the
+/// date of the quarantine.
+pub(crate) const QUARANTINE_FIELD: &str = "quarantine";
+
+/// A file set aside by [`GridFSBucket::quarantine`], returned by
+/// [`GridFSBucket::list_quarantined`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct QuarantinedFile {
+    pub file: FileInfo,
+    /// Why the file was quarantined.
+    pub reason: Option<String>,
+    /// When the file was quarantined.
+    pub quarantined_at: Option<DateTime>,
+}
+
+impl GridFSBucket {
+    /**
+    Quarantines the corrupted file @id for the @reason: the file and its chunks are kept
+    for triage, but the readers don't see it anymore. [`GridFSBucket::publish`] releases it,
+    [`GridFSBucket::delete`] removes it.
+
+    # Errors
+
+    Raise [`GridFSError::FileNotFound`] when no uploaded file has the id @id.
+    */
+    pub async fn quarantine(&self, id: ObjectId, reason: &str) -> Result<(), GridFSError> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let update_options = UpdateOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        let update_result = self
+            .files_collection()
+            .update_one(
+                doc! {
+                    "_id":id,
+                    "length":{"$exists":true},
+                    STATUS_FIELD:{"$ne":FileStatus::Deleting.as_str()},
+                },
+                doc! {"$set":{
+                    STATUS_FIELD:FileStatus::Quarantined.as_str(),
+                    QUARANTINE_FIELD:{"reason":reason, "date":self.now()},
+                }},
+                update_options,
+            )
+            .await?;
+        if update_result.matched_count == 0 {
+            return Err(GridFSError::FileNotFound());
+        }
+        Ok(())
+    }
+
+    /// Lists the quarantined files, the most recently quarantined first.
+    pub async fn list_quarantined(&self) -> Result<Vec<QuarantinedFile>, GridFSError> {
+        let find_options = FindOptions::builder()
+            .sort(doc! {QUARANTINE_FIELD.to_string() + ".date":-1})
+            .build();
+        let mut cursor = self
+            .files_collection()
+            .find(with_status(doc! {}, FileStatus::Quarantined), find_options)
+            .await?;
+        let mut quarantined = vec![];
+        while let Some(document) = cursor.next().await {
+            let document = document?;
+            let quarantine = document
+                .get_document(QUARANTINE_FIELD)
+                .cloned()
+                .unwrap_or_default();
+            quarantined.push(QuarantinedFile {
+                file: FileInfo::try_from(document)?,
+                reason: quarantine.get_str("reason").ok().map(str::to_string),
+                quarantined_at: quarantine.get_datetime("date").ok().copied(),
+            });
+        }
+        Ok(quarantined)
+    }
+
+    /// Quarantines the file @id in the background when the bucket quarantines the corrupted
+    /// files and @error is a corruption.
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub(crate) fn quarantine_on(&self, id: ObjectId, error: &GridFSError) {
+        let corrupted = matches!(
+            error,
+            GridFSError::InvalidChunk(_, _) | GridFSError::ChecksumMismatch { .. }
+        );
+        let enabled = self
+            .options
+            .as_ref()
+            .is_some_and(|options| options.quarantine_corrupt);
+        if corrupted && enabled {
+            let bucket = self.clone();
+            let reason = error.to_string();
+            tokio::spawn(async move {
+                let _ = bucket.quarantine(id, &reason).await;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{
+        options::{GridFSBucketOptions, GridFSFindOptions},
+        FileStatus, GridFSError,
+    };
+    use bson::{doc, oid::ObjectId, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn quarantine() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        bucket.quarantine(id, "bad chunk").await?;
+        assert!(matches!(
+            bucket.open_download_stream(id).await,
+            Err(GridFSError::FileNotFound())
+        ));
+        let mut cursor = bucket.find(doc! {}, GridFSFindOptions::default()).await?;
+        assert!(cursor.next().await.is_none());
+        let quarantined = bucket.list_quarantined().await?;
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].file.id, id);
+        assert_eq!(quarantined[0].file.status, FileStatus::Quarantined);
+        assert_eq!(quarantined[0].reason.as_deref(), Some("bad chunk"));
+        assert!(quarantined[0].quarantined_at.is_some());
+
+        // Released.
+        bucket.publish(id).await?;
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test data");
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! {"_id":id}, None)
+            .await?
+            .unwrap();
+        assert!(file.get("quarantine").is_none());
+        assert!(bucket.list_quarantined().await?.is_empty());
+
+        assert!(matches!(
+            bucket.quarantine(ObjectId::new(), "bad chunk").await,
+            Err(GridFSError::FileNotFound())
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[tokio::test]
+    async fn quarantine_corrupt_download() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .quarantine_corrupt(true)
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        db.collection::<Document>("fs.chunks")
+            .update_one(
+                doc! {"files_id":id},
+                doc! {"$set":{"data":"not binary"}},
+                None,
+            )
+            .await?;
+
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert!(matches!(
+            cursor.next().await,
+            Some(Err(GridFSError::InvalidChunk(0, _)))
+        ));
+        for _ in 0..50 {
+            if !bucket.list_quarantined().await?.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let quarantined = bucket.list_quarantined().await?;
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].file.id, id);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
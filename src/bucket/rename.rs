@@ -1,14 +1,28 @@
-use crate::bucket::GridFSBucket;
+use crate::{bucket::GridFSBucket, options::GridFSBucketOptions, GridFSError};
 use bson::{doc, oid::ObjectId, Document};
-use mongodb::{error::Result, options::UpdateOptions, results::UpdateResult};
+use mongodb::{
+    error::Result,
+    options::{UpdateOptions, WriteConcern},
+    results::UpdateResult,
+};
 
 impl GridFSBucket {
     /**
     Renames the stored file with the specified @id.
     [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#renaming-stored-files)
 
+    # Errors
+
+    Raise [`GridFSError::InvalidFilename`] when @new_filename breaks the
+    [`FilenamePolicy`](crate::options::FilenamePolicy) of the bucket.
      */
-    pub async fn rename(&self, id: ObjectId, new_filename: &str) -> Result<UpdateResult> {
+    pub async fn rename(
+        &self,
+        id: ObjectId,
+        new_filename: &str,
+    ) -> std::result::Result<UpdateResult, GridFSError> {
+        let new_filename = self.checked_filename(new_filename)?;
+        let _writer = self.write_guard().await;
         let dboptions = self.options.clone().unwrap_or_default();
         let bucket_name = dboptions.bucket_name;
         let file_collection = bucket_name + ".files";
@@ -25,17 +39,87 @@ impl GridFSBucket {
                 update_options,
             )
             .await
+            .map_err(GridFSError::from)
+    }
+
+    async fn rename_collection(
+        &self,
+        from: &str,
+        to: &str,
+        write_concern: &Option<WriteConcern>,
+    ) -> Result<Document> {
+        let dbname = self.db.name();
+        let mut command = doc! {
+            "renameCollection": format!("{}.{}", dbname, from),
+            "to": format!("{}.{}", dbname, to),
+            "dropTarget": false,
+        };
+        if let Some(write_concern) = write_concern {
+            command.insert("writeConcern", bson::to_bson(write_concern)?);
+        }
+        // renameCollection is an admin command.
+        self.db
+            .collection::<Document>(from)
+            .client()
+            .database("admin")
+            .run_command(command, None)
+            .await
+    }
+
+    /**
+    Renames the bucket to @new_name: the `.files` and `.chunks` collections are renamed with
+    `renameCollection`, without copying the files. The bucket then uses the new name.
+
+    Fails if a collection of the new bucket already exists. If the chunks collection can't
+    be renamed, the files collection gets its former name back.
+     */
+    pub async fn rename_bucket(&mut self, new_name: &str) -> Result<()> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let bucket_name = dboptions.bucket_name.clone();
+        let write_concern = dboptions.write_concern.clone();
+
+        let old_files = bucket_name.clone() + ".files";
+        let new_files = new_name.to_string() + ".files";
+        self.rename_collection(&old_files, &new_files, &write_concern)
+            .await?;
+        if let Err(error) = self
+            .rename_collection(
+                &(bucket_name + ".chunks"),
+                &(new_name.to_string() + ".chunks"),
+                &write_concern,
+            )
+            .await
+        {
+            let _ = self
+                .rename_collection(&new_files, &old_files, &write_concern)
+                .await;
+            return Err(error);
+        }
+
+        self.options = Some(GridFSBucketOptions {
+            bucket_name: new_name.into(),
+            ..dboptions
+        });
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::GridFSBucket;
-    use crate::{options::GridFSBucketOptions, GridFSError};
+    use crate::{
+        options::{FilenamePolicy, GridFSBucketOptions, UnicodeNormalization},
+        FilenameViolation, GridFSError,
+    };
     use bson::doc;
     use bson::Document;
+    #[cfg(feature = "async-std-runtime")]
+    use futures::StreamExt;
     use mongodb::Client;
     use mongodb::Database;
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
     use uuid::Uuid;
     fn db_name_new() -> String {
         "test_".to_owned()
@@ -72,4 +156,118 @@ mod tests {
         db.drop(None).await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn rename_with_filename_policy() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .filename_policy(Some(
+                        FilenamePolicy::builder()
+                            .normalization(Some(UnicodeNormalization::Nfc))
+                            .reject_path_traversal(true)
+                            .build(),
+                    ))
+                    .build(),
+            ),
+        );
+        let result = bucket
+            .upload_from_stream("../test.txt", "test data".as_bytes(), None)
+            .await;
+        assert!(matches!(
+            result,
+            Err(GridFSError::InvalidFilename(
+                _,
+                FilenameViolation::PathTraversal
+            ))
+        ));
+        let id = bucket
+            .upload_from_stream("cafe\u{301}.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let result = bucket.rename(id, "/etc/passwd").await;
+        assert!(matches!(
+            result,
+            Err(GridFSError::InvalidFilename(
+                _,
+                FilenameViolation::PathTraversal
+            ))
+        ));
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_str("filename").unwrap(), "caf\u{e9}.txt");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rename_a_bucket() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        bucket.rename_bucket("archive").await?;
+
+        let names = db.list_collection_names(None).await?;
+        assert!(names.contains(&"archive.files".to_string()));
+        assert!(names.contains(&"archive.chunks".to_string()));
+        assert!(!names.contains(&"fs.files".to_string()));
+        assert!(!names.contains(&"fs.chunks".to_string()));
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test data");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rename_a_bucket_to_an_existing_bucket() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        let mut archive = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .bucket_name("archive".into())
+                    .build(),
+            ),
+        );
+        archive
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        assert!(bucket.rename_bucket("archive").await.is_err());
+        let names = db.list_collection_names(None).await?;
+        assert!(names.contains(&"fs.files".to_string()));
+        assert!(names.contains(&"fs.chunks".to_string()));
+
+        db.drop(None).await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,365 @@
+use crate::{
+    bucket::{
+        inline::INLINE_FIELD,
+        routing::{chunk_collection_of, chunk_filter},
+        upload::ReadSource,
+        GridFSBucket,
+    },
+    display::{HumanSize, ShortId},
+    options::GridFSUploadOptions,
+    FileInfo, GridFSError,
+};
+use bson::{doc, oid::ObjectId, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::io::AsyncRead;
+use mongodb::options::{
+    Acknowledgment, CountOptions, FindOneOptions, ReadConcern, ReadPreference, SelectionCriteria,
+};
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::io::AsyncRead;
+
+/// The acknowledgements of an upload, returned by [`GridFSBucket::upload_from_stream_verbose`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UploadReport {
+    /// The id of the uploaded file.
+    pub id: ObjectId,
+    /// The length of the uploaded file, in bytes.
+    pub length: u64,
+    /// The number of chunk inserts the upload made.
+    pub chunks_written: u64,
+    /// Whether the server acknowledged the writes. `false` with a `w: 0` write concern:
+    /// the inserts were sent, but their outcome is unknown.
+    pub acknowledged: bool,
+    /// Whether the files collection document was read back from the primary.
+    pub file_found: bool,
+    /// The number of chunks read back from the primary.
+    pub chunks_found: u64,
+    /// The timings of the upload, with [`GridFSUploadOptions::diagnostics`].
+    pub diagnostics: Option<UploadDiagnostics>,
+}
+
+impl UploadReport {
+    /// Whether the files collection document and every chunk were read back.
+    pub fn is_complete(&self) -> bool {
+        self.file_found && self.chunks_found == self.chunks_written
+    }
+}
+
+/// `…id: size in n chunks, found/n chunks read back`, followed by the missing files
+/// collection document and the unacknowledged writes.
+impl Display for UploadReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} in {} chunks, {}/{} chunks read back",
+            ShortId(self.id),
+            HumanSize(self.length),
+            self.chunks_written,
+            self.chunks_found,
+            self.chunks_written
+        )?;
+        if !self.file_found {
+            write!(f, ", file missing")?;
+        }
+        if !self.acknowledged {
+            write!(f, ", unacknowledged")?;
+        }
+        Ok(())
+    }
+}
+
+/// The upper bounds of the buckets of a [`LatencyHistogram`], in milliseconds. The last
+/// bucket holds the latencies above the last bound.
+const LATENCY_BOUNDS_MS: [u64; 10] = [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000];
+
+/// The distribution of the latencies of the chunk inserts of an upload.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LatencyHistogram {
+    /// The number of latencies of at most 1ms, 2ms, 5ms, 10ms, 20ms, 50ms, 100ms, 200ms,
+    /// 500ms and 1s in each bucket, then of the latencies above 1s.
+    pub buckets: [u64; 11],
+    /// The number of latencies recorded.
+    pub count: u64,
+    /// The sum of the latencies recorded.
+    pub total: Duration,
+    /// The largest latency recorded.
+    pub max: Duration,
+}
+
+impl LatencyHistogram {
+    /// Counts the @latency in its bucket.
+    pub fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BOUNDS_MS
+            .iter()
+            .position(|bound| latency <= Duration::from_millis(*bound))
+            .unwrap_or(LATENCY_BOUNDS_MS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    /// The mean of the latencies recorded, zero without latency.
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.total / count as u32,
+        }
+    }
+}
+
+/// `count inserts, mean …, max …: ≤1ms count, ≤2ms count, …, >1s count`, without the
+/// empty buckets.
+impl Display for LatencyHistogram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} inserts, mean {:?}, max {:?}",
+            self.count,
+            self.mean(),
+            self.max
+        )?;
+        let mut separator = ": ";
+        for (i, count) in self.buckets.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            match LATENCY_BOUNDS_MS.get(i) {
+                Some(bound) => write!(
+                    f,
+                    "{}≤{:?} {}",
+                    separator,
+                    Duration::from_millis(*bound),
+                    count
+                )?,
+                None => write!(
+                    f,
+                    "{}>{:?} {}",
+                    separator,
+                    Duration::from_millis(LATENCY_BOUNDS_MS[i - 1]),
+                    count
+                )?,
+            }
+            separator = ", ";
+        }
+        Ok(())
+    }
+}
+
+/// The timings of an upload, to tell the stalls of its source from those of the server.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UploadDiagnostics {
+    /// The duration of each phase of the upload, in order, as reported to the
+    /// [`SlowOpLogger`](crate::slow_op::SlowOpLogger).
+    pub phases: Vec<(&'static str, Duration)>,
+    /// The time spent waiting for the source to yield the chunks.
+    pub read_wait: Duration,
+    /// The time spent waiting for chunk inserts to complete, with the queue of inserts full
+    /// or at the end of the source.
+    pub write_wait: Duration,
+    /// The latencies of the chunk inserts.
+    pub insert_latency: LatencyHistogram,
+}
+
+impl GridFSBucket {
+    /**
+      Uploads a user file like [`GridFSBucket::upload_from_stream`], then reads the file
+      back from the primary with a majority read concern.
+
+      Returns the [`UploadReport`] of the upload, to diagnose the deployments where the
+      writes are acknowledged but the data is missing afterwards, e.g. after a failover.
+      The read back costs a query and a count.
+
+      With [`GridFSUploadOptions::diagnostics`], the report includes the
+      [`UploadDiagnostics`] of the upload: a source slower than the server shows as read
+      wait, a server slower than the source as write wait and long chunk inserts.
+    */
+    pub async fn upload_from_stream_verbose(
+        &mut self,
+        filename: &str,
+        source: impl AsyncRead + Unpin,
+        options: Option<GridFSUploadOptions>,
+    ) -> Result<UploadReport, GridFSError> {
+        let chunk_size = options
+            .as_ref()
+            .and_then(|options| options.chunk_size_bytes);
+        let (id, _, diagnostics) = self
+            .upload_chunks_with_diagnostics(None, filename, ReadSource(source), options)
+            .await?;
+
+        let dboptions = self.options.clone().unwrap_or_default();
+        let chunk_size = chunk_size.unwrap_or(dboptions.chunk_size_bytes) as u64;
+        let acknowledged = [
+            dboptions.write_concern_of_files(),
+            dboptions.write_concern_of_chunks(),
+        ]
+        .iter()
+        .all(|write_concern| {
+            !matches!(
+                write_concern
+                    .as_ref()
+                    .and_then(|write_concern| write_concern.w.as_ref()),
+                Some(Acknowledgment::Nodes(0))
+            )
+        });
+        let bucket_name = dboptions.bucket_name;
+        let files = self
+            .db
+            .collection::<Document>(&(bucket_name.clone() + ".files"));
+        let primary = SelectionCriteria::ReadPreference(ReadPreference::Primary);
+
+        let find_one_options = FindOneOptions::builder()
+            .selection_criteria(primary.clone())
+            .read_concern(ReadConcern::majority())
+            .build();
+        let file = files.find_one(doc! {"_id":id}, find_one_options).await?;
+        let file_document = file.clone().unwrap_or_default();
+        let chunks = self
+            .db
+            .collection::<Document>(&chunk_collection_of(&file_document, &bucket_name));
+        let file = file.map(FileInfo::try_from).transpose()?;
+        let count_options = CountOptions::builder()
+            .selection_criteria(primary)
+            .read_concern(ReadConcern::majority())
+            .build();
+        let chunks_found = chunks
+            .count_documents(
+                chunk_filter(&file_document, id, self.chunk_shard_key()),
+                count_options,
+            )
+            .await?;
+
+        let length = file.as_ref().map_or(0, |file| file.length);
+        // An inlined file has no chunks.
+        let chunks_written = match file_document.contains_key(INLINE_FIELD) {
+            true => 0,
+            false => length.div_ceil(chunk_size),
+        };
+        Ok(UploadReport {
+            id,
+            length,
+            chunks_written,
+            acknowledged,
+            file_found: file.is_some(),
+            chunks_found,
+            diagnostics,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GridFSBucket, LatencyHistogram, UploadReport};
+    use crate::{
+        options::{GridFSBucketOptions, GridFSUploadOptions},
+        GridFSError,
+    };
+    use bson::oid::ObjectId;
+    use mongodb::{Client, Database};
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn upload_report_display() {
+        let mut report = UploadReport {
+            id: ObjectId::parse_str("5f8f8c44b54764421b7156c9").unwrap(),
+            length: 3 * 1024 * 1024,
+            chunks_written: 13,
+            acknowledged: true,
+            file_found: true,
+            chunks_found: 13,
+            diagnostics: None,
+        };
+        assert_eq!(
+            report.to_string(),
+            "…1b7156c9: 3.0 MiB in 13 chunks, 13/13 chunks read back"
+        );
+        report.acknowledged = false;
+        report.file_found = false;
+        report.chunks_found = 0;
+        assert_eq!(
+            report.to_string(),
+            "…1b7156c9: 3.0 MiB in 13 chunks, 0/13 chunks read back, file missing, unacknowledged"
+        );
+    }
+
+    #[test]
+    fn latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.mean(), Duration::ZERO);
+        for millis in [1, 3, 4, 30, 2500] {
+            histogram.record(Duration::from_millis(millis));
+        }
+        assert_eq!(histogram.buckets, [1, 0, 2, 0, 0, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(histogram.count, 5);
+        assert_eq!(histogram.mean(), Duration::from_micros(507_600));
+        assert_eq!(histogram.max, Duration::from_millis(2500));
+        assert_eq!(
+            histogram.to_string(),
+            "5 inserts, mean 507.6ms, max 2.5s: ≤1ms 1, ≤5ms 2, ≤50ms 1, >1s 1"
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_from_stream_verbose() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let report = bucket
+            .upload_from_stream_verbose("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        assert_eq!(report.length, 9);
+        assert_eq!(report.chunks_written, 3);
+        assert!(report.acknowledged);
+        assert_eq!(report.chunks_found, 3);
+        assert!(report.is_complete());
+        assert_eq!(report.diagnostics, None);
+
+        let report = bucket
+            .upload_from_stream_verbose(
+                "test.txt",
+                "test data".as_bytes(),
+                Some(GridFSUploadOptions::builder().diagnostics(true).build()),
+            )
+            .await?;
+        let diagnostics = report.diagnostics.unwrap();
+        assert_eq!(diagnostics.insert_latency.count, 3);
+        let phases: Vec<&str> = diagnostics.phases.iter().map(|(phase, _)| *phase).collect();
+        assert_eq!(
+            phases,
+            [
+                "queue",
+                "index check",
+                "files insert",
+                "chunk writes",
+                "finalize"
+            ]
+        );
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{
+    bucket::{status::STATUS_FIELD, upload::check_chunk_size, GridFSBucket},
+    options::GridFSUploadOptions,
+    FileStatus, GridFSError,
+};
+use bson::{doc, oid::ObjectId, Document};
+use mongodb::options::{DeleteOptions, InsertOneOptions};
+
+/// Filter of the reservation of the file @id: a pending files collection document, without
+/// length.
+pub(crate) fn reservation(id: ObjectId) -> Document {
+    doc! {"_id":id, STATUS_FIELD:FileStatus::Pending.as_str(), "length":{"$exists":false}}
+}
+
+impl GridFSBucket {
+    /**
+    Reserves an id for a file of @filename whose content doesn't exist yet, so the id can be
+    exchanged with another system before the upload.
+
+    The reservation is a files collection document in a `pending` state, which the readers
+    never see. It is completed by [`GridFSBucket::upload_from_stream_with_id`] with the
+    returned id, or cancelled by [`GridFSBucket::abort`]. The filename and the options of
+    the upload replace those of the reservation.
+    */
+    pub async fn reserve_id(
+        &mut self,
+        filename: &str,
+        options: Option<GridFSUploadOptions>,
+    ) -> Result<ObjectId, GridFSError> {
+        let filename = self.checked_filename(filename)?;
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let bucket_name = dboptions.bucket_name.clone();
+        let file_collection = bucket_name.clone() + ".files";
+        let chunk_collection = bucket_name + ".chunks";
+        let files = self.db.collection::<Document>(&file_collection);
+        self.ensure_file_index(&files, &file_collection, &chunk_collection)
+            .await?;
+
+        let mut chunk_size = dboptions.chunk_size_bytes;
+        let id = ObjectId::new();
+        let mut file_document =
+            doc! {"_id":id, "filename":filename, STATUS_FIELD:FileStatus::Pending.as_str()};
+        if let Some(options) = options {
+            if let Some(chunk_size_bytes) = options.chunk_size_bytes {
+                chunk_size = chunk_size_bytes;
+            }
+            if let Some(metadata) = options.metadata {
+                file_document.insert("metadata", metadata);
+            }
+        }
+        check_chunk_size(chunk_size)?;
+        file_document.insert("chunkSize", chunk_size);
+
+        let insert_options = InsertOneOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        files.insert_one(file_document, insert_options).await?;
+        Ok(id)
+    }
+
+    /**
+    Cancels the reservation of the file @id made by [`GridFSBucket::reserve_id`].
+
+    # Errors
+
+    Raise [`GridFSError::FileNotFound`] when @id isn't a pending reservation.
+    */
+    pub async fn abort(&self, id: ObjectId) -> Result<(), GridFSError> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let files = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name.clone() + ".files"));
+
+        let delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        let delete_result = files.delete_one(reservation(id), delete_options).await?;
+        if delete_result.deleted_count == 0 {
+            return Err(GridFSError::FileNotFound());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{
+        options::{GridFSBucketOptions, GridFSFindOptions},
+        GridFSError,
+    };
+    use bson::{doc, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn reserve_id() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket.reserve_id("test.txt", None).await?;
+
+        // The readers don't see the reservation.
+        assert!(matches!(
+            bucket.open_download_stream(id).await,
+            Err(GridFSError::FileNotFound())
+        ));
+        assert!(matches!(
+            bucket.file_info(id).await,
+            Err(GridFSError::FileNotFound())
+        ));
+        let mut cursor = bucket.find(doc! {}, GridFSFindOptions::default()).await?;
+        assert!(cursor.next().await.is_none());
+
+        bucket
+            .upload_from_stream_with_id(id, "test.txt", "test data".as_bytes(), None)
+            .await?;
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test data");
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert!(file.get("status").is_none());
+
+        // A completed file can't be aborted, nor uploaded again.
+        assert!(matches!(
+            bucket.abort(id).await,
+            Err(GridFSError::FileNotFound())
+        ));
+        assert!(matches!(
+            bucket
+                .upload_from_stream_with_id(id, "test.txt", "test data".as_bytes(), None)
+                .await,
+            Err(GridFSError::AlreadyExists { .. })
+        ));
+
+        let id = bucket.reserve_id("aborted.txt", None).await?;
+        bucket.abort(id).await?;
+        assert_eq!(
+            db.collection::<Document>("fs.files")
+                .count_documents(doc! { "_id": id }, None)
+                .await?,
+            0
+        );
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
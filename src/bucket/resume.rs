@@ -0,0 +1,375 @@
+#[cfg(any(feature = "md5", feature = "sha256"))]
+use crate::bucket::checksum::{upload_algorithms, Checksum};
+use crate::{
+    bucket::{
+        chunk_stream::chunk_data,
+        routing::{chunk_collection_of, chunk_filter},
+        status::STATUS_FIELD,
+        upload::read_chunk,
+        GridFSBucket,
+    },
+    encryption::chunk_data_field,
+    file_info::get_number,
+    FileStatus, GridFSError,
+};
+use bson::{doc, oid::ObjectId, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::{io::AsyncRead, StreamExt};
+use mongodb::{
+    error::Error,
+    options::{DeleteOptions, FindOptions, InsertOneOptions, UpdateOptions},
+    Collection,
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::io::AsyncRead;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// An interrupted upload, returned by [`GridFSBucket::inspect_partial`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PartialUpload {
+    pub id: ObjectId,
+    pub filename: Option<String>,
+    pub chunk_size: u32,
+    /// The number of chunks persisted: the chunks `0` to `chunks - 1` are stored, full and
+    /// readable.
+    pub chunks: u32,
+    /// The number of bytes persisted in these chunks: the offset of the source of
+    /// [`GridFSBucket::continue_upload`].
+    pub bytes_persisted: u64,
+}
+
+/// A partial upload, with the collection and the filter of its chunks.
+struct Partial {
+    file: Document,
+    chunks: Collection<Document>,
+    filter: Document,
+    upload: PartialUpload,
+}
+
+impl GridFSBucket {
+    /// Reads the files collection document of the interrupted upload @id, then its chunks in
+    /// order up to the first missing, short or malformed one. @on_chunk is called with the
+    /// data of the persisted chunks.
+    async fn scan_partial(
+        &self,
+        id: ObjectId,
+        mut on_chunk: impl FnMut(&[u8]),
+    ) -> Result<Partial, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let bucket_name = dboptions.bucket_name;
+        let file = self
+            .db
+            .collection::<Document>(&(bucket_name.clone() + ".files"))
+            .find_one(
+                doc! {"_id":id, STATUS_FIELD:{"$ne":FileStatus::Deleting.as_str()}},
+                None,
+            )
+            .await?
+            .ok_or(GridFSError::FileNotFound())?;
+        if file.contains_key("length") {
+            return Err(GridFSError::AlreadyExists {
+                id: Some(id),
+                filename: None,
+            });
+        }
+        let chunk_size = get_number(&file, "chunkSize")
+            .filter(|chunk_size| (1..=u32::MAX as i64).contains(chunk_size))
+            .ok_or_else(|| GridFSError::InvalidFile("chunkSize isn't a chunk size".into()))?
+            as u32;
+        let chunks = self
+            .db
+            .collection::<Document>(&chunk_collection_of(&file, &bucket_name));
+        let filter = chunk_filter(&file, id, self.chunk_shard_key());
+
+        let find_options = FindOptions::builder().sort(doc! {"n":1}).build();
+        let mut cursor = chunks.find(filter.clone(), find_options).await?;
+        let mut upload = PartialUpload {
+            id,
+            filename: file.get_str("filename").ok().map(str::to_string),
+            chunk_size,
+            chunks: 0,
+            bytes_persisted: 0,
+        };
+        while let Some(chunk) = cursor.next().await {
+            let chunk = chunk?;
+            let n = upload.chunks as i64;
+            if get_number(&chunk, "n") != Some(n) {
+                break;
+            }
+            match chunk_data(chunk, n) {
+                Ok(data) if data.len() == chunk_size as usize => {
+                    on_chunk(&data);
+                    upload.chunks += 1;
+                    upload.bytes_persisted += data.len() as u64;
+                }
+                _ => break,
+            }
+        }
+        Ok(Partial {
+            file,
+            chunks,
+            filter,
+            upload,
+        })
+    }
+
+    /// Removes the upload of the file @id interrupted before its files collection document
+    /// was completed: its chunks, then its files collection document. A complete file is
+    /// kept. The caller holds the [`GridFSBucket::write_guard`].
+    pub(crate) async fn discard_partial(&self, id: ObjectId) -> Result<(), GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let files = self.files_collection();
+        let pending = doc! {"_id":id, "length":{"$exists":false}};
+        let file = match files.find_one(pending.clone(), None).await? {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let chunks = self
+            .db
+            .collection::<Document>(&chunk_collection_of(&file, &dboptions.bucket_name));
+        let delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        chunks
+            .delete_many(
+                chunk_filter(&file, id, self.chunk_shard_key()),
+                delete_options,
+            )
+            .await?;
+        let delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        files.delete_one(pending, delete_options).await?;
+        Ok(())
+    }
+
+    /**
+    Inspects the upload of the file @id, interrupted before its files collection document
+    was completed, e.g. by a crash: reports the bytes persisted in its chunks, to resume it
+    with [`GridFSBucket::continue_upload`]. The stored chunks are read.
+
+    # Errors
+
+    Raise [`GridFSError::FileNotFound`] when the file @id doesn't exist.
+    Raise [`GridFSError::AlreadyExists`] when the upload of the file @id is complete.
+    Raise [`GridFSError::InvalidFile`] when the files collection document is malformed.
+    */
+    pub async fn inspect_partial(&self, id: ObjectId) -> Result<PartialUpload, GridFSError> {
+        Ok(self.scan_partial(id, |_| {}).await?.upload)
+    }
+
+    /**
+    Resumes the interrupted upload of the file @id with @source, the rest of the content
+    from the offset `bytes_persisted` reported by [`GridFSBucket::inspect_partial`]: the
+    persisted chunks are checked again, the chunks after them removed, the rest of the
+    content appended and the files collection document completed. Returns the length of
+    the file.
+
+    The upload must be stopped: resuming an upload still in flight corrupts the file. The
+    chunks are written one at a time, without the content inspector nor the text extractor
+    of the bucket.
+
+    # Errors
+
+    Raise [`GridFSError::FileNotFound`] when the file @id doesn't exist.
+    Raise [`GridFSError::AlreadyExists`] when the upload of the file @id is complete.
+    Raise [`GridFSError::InvalidFile`] when the files collection document is malformed.
+    Raise [`GridFSError::FileTooLarge`] when the file needs more than 2^31 chunks.
+    */
+    pub async fn continue_upload(
+        &self,
+        id: ObjectId,
+        mut source: impl AsyncRead + Unpin,
+    ) -> Result<u64, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        let _slot = self.qos.acquire(self.priority).await?;
+        let _writer = self.write_guard().await;
+
+        #[cfg(any(feature = "md5", feature = "sha256"))]
+        let mut checksums: Vec<Checksum> = upload_algorithms(&dboptions)
+            .into_iter()
+            .filter_map(Checksum::new)
+            .collect();
+        let Partial {
+            file,
+            chunks,
+            filter,
+            upload,
+        } = self
+            .scan_partial(id, |_data| {
+                #[cfg(any(feature = "md5", feature = "sha256"))]
+                for checksum in checksums.iter_mut() {
+                    checksum.update(_data);
+                }
+            })
+            .await?;
+
+        let mut stray = filter.clone();
+        stray.insert("n", doc! {"$gte":upload.chunks as i64});
+        let delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        chunks.delete_many(stray, delete_options).await?;
+
+        let insert_options = InsertOneOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        let expire_at = file.get_datetime("expireAt").ok().copied();
+        let mut length = upload.bytes_persisted;
+        let mut n = upload.chunks;
+        loop {
+            let mut data = vec![0; upload.chunk_size as usize];
+            let read = read_chunk(&mut source, &mut data)
+                .await
+                .map_err(Error::from)?;
+            if read == 0 {
+                break;
+            }
+            if n > i32::MAX as u32 {
+                return Err(GridFSError::FileTooLarge {
+                    max_length: (i32::MAX as u64 + 1) * upload.chunk_size as u64,
+                });
+            }
+            data.truncate(read);
+            #[cfg(any(feature = "md5", feature = "sha256"))]
+            for checksum in checksums.iter_mut() {
+                checksum.update(&data);
+            }
+            let mut chunk = filter.clone();
+            chunk.insert("n", n as i32);
+            let data = chunk_data_field(
+                dboptions.chunk_encryption.as_ref(),
+                dboptions.chunk_binary_subtype.into(),
+                data,
+            )
+            .await?;
+            chunk.insert("data", data);
+            if let Some(expire_at) = expire_at {
+                chunk.insert("expireAt", expire_at);
+            }
+            chunks.insert_one(chunk, insert_options.clone()).await?;
+            length += read as u64;
+            n += 1;
+        }
+
+        #[cfg_attr(not(any(feature = "md5", feature = "sha256")), allow(unused_mut))]
+        let mut update = doc! {"length":length as i64, "uploadDate":self.now()};
+        #[cfg(any(feature = "md5", feature = "sha256"))]
+        for checksum in checksums {
+            update.insert(checksum.algorithm().field(), checksum.finalize());
+        }
+        let update_options = UpdateOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        self.files_collection()
+            .update_one(
+                doc! {"_id":id, "length":{"$exists":false}},
+                doc! {"$set":update},
+                update_options,
+            )
+            .await?;
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GridFSBucket, PartialUpload};
+    use crate::{options::GridFSBucketOptions, GridFSError};
+    use bson::{doc, oid::ObjectId, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn continue_upload() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let content = b"0123456789ab";
+        let id = bucket
+            .upload_from_stream("test.txt", &content[..], None)
+            .await?;
+        let files = db.collection::<Document>("fs.files");
+        let chunks = db.collection::<Document>("fs.chunks");
+        let md5 = files
+            .find_one(doc! {"_id":id}, None)
+            .await?
+            .unwrap()
+            .get_str("md5")
+            .ok()
+            .map(str::to_string);
+
+        assert!(matches!(
+            bucket.inspect_partial(id).await,
+            Err(GridFSError::AlreadyExists { .. })
+        ));
+
+        // Interrupted: the chunk 1 is missing, the chunk 2 is written.
+        files
+            .update_one(
+                doc! {"_id":id},
+                doc! {"$unset":{"length":"", "uploadDate":"", "md5":""}},
+                None,
+            )
+            .await?;
+        chunks.delete_one(doc! {"files_id":id, "n":1}, None).await?;
+        assert!(matches!(
+            bucket.open_download_stream(id).await,
+            Err(GridFSError::FileNotFound())
+        ));
+        let partial = bucket.inspect_partial(id).await?;
+        assert_eq!(
+            partial,
+            PartialUpload {
+                id,
+                filename: Some("test.txt".into()),
+                chunk_size: 4,
+                chunks: 1,
+                bytes_persisted: 4,
+            }
+        );
+
+        let length = bucket
+            .continue_upload(id, &content[partial.bytes_persisted as usize..])
+            .await?;
+        assert_eq!(length, 12);
+        let mut downloaded = vec![];
+        let mut cursor = bucket.open_download_stream(id).await?;
+        while let Some(chunk) = cursor.next().await {
+            downloaded.extend(chunk?);
+        }
+        assert_eq!(downloaded, content);
+        let file = files.find_one(doc! {"_id":id}, None).await?.unwrap();
+        assert_eq!(file.get_str("md5").ok().map(str::to_string), md5);
+        assert_eq!(chunks.count_documents(doc! {"files_id":id}, None).await?, 3);
+
+        assert!(matches!(
+            bucket.inspect_partial(ObjectId::new()).await,
+            Err(GridFSError::FileNotFound())
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
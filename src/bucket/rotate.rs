@@ -0,0 +1,323 @@
+use crate::{
+    bucket::{
+        inline::INLINE_FIELD,
+        routing::{chunk_collection_of, chunk_filter, CHUNKS_COLLECTION_FIELD},
+        status::STATUS_FIELD,
+        tier::TIER_FIELD,
+        GridFSBucket,
+    },
+    encryption::{chunk_data_field, ChunkEncryption, ENCRYPTION_KEY_FIELD},
+    options::GridFSDownloadOptions,
+    FileInfo, FileStatus, GridFSError,
+};
+use bson::{doc, Binary, Bson, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::{
+    options::{DeleteOptions, FindOptions, InsertOneOptions, UpdateOptions},
+    Collection,
+};
+use std::convert::TryFrom;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// The data key @key_id in hexadecimal, in the name of the chunks collections.
+fn key_hex(key_id: &Binary) -> String {
+    key_id
+        .bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl GridFSBucket {
+    /**
+    Re-encrypts the stored files matching @filter whose chunks are encrypted with the data
+    key @old_key with the data key of @new_key. Returns the number of files re-encrypted.
+
+    The chunks of a file are read, decrypted by the driver, encrypted with @new_key and
+    written to another chunks collection: the chunks collection of the bucket, or
+    `<bucket_name>.chunks.<new key id in hexadecimal>` for the files already there. The files
+    collection document is then switched to the new chunks and key in a single update, so
+    the readers see either the former or the new chunks, and the former chunks are removed.
+    The content of an inlined file is re-encrypted in its files collection document.
+
+    The files record the data key of their chunks: an interrupted rotation is resumed by
+    running it again, the files already re-encrypted being left out. The files whose upload
+    is in progress and the tiered files are left out too, as is a file changed during its
+    copy. The database of the bucket must belong to a client configured with
+    auto-encryption, which decrypts the chunks read.
+
+    # Errors
+
+    Raise [`GridFSError::InvalidChunk`] when a chunk is read still encrypted.
+    Raise [`GridFSError::EncryptionFailed`] when the chunks can't be encrypted with @new_key.
+    */
+    pub async fn rotate_keys(
+        &self,
+        old_key: &Binary,
+        new_key: &ChunkEncryption,
+        filter: Document,
+    ) -> Result<u64, GridFSError> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let bucket_name = dboptions.bucket_name.clone();
+        let bucket_chunks = bucket_name.clone() + ".chunks";
+        let files = self.files_collection();
+        let find_options = FindOptions::builder().sort(doc! {"_id":1}).build();
+        let mut cursor = files
+            .find(
+                doc! {"$and":[
+                    filter,
+                    {
+                        ENCRYPTION_KEY_FIELD:old_key.clone(),
+                        "length":{"$exists":true},
+                        TIER_FIELD:{"$exists":false},
+                        STATUS_FIELD:{"$ne":FileStatus::Deleting.as_str()},
+                    },
+                ]},
+                find_options,
+            )
+            .await?;
+
+        let insert_options = InsertOneOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        let update_options = UpdateOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        let delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        let subtype = dboptions.chunk_binary_subtype.into();
+
+        let mut rotated = 0;
+        while let Some(document) = cursor.next().await {
+            let document = document?;
+            let file = FileInfo::try_from(document.clone())?;
+            let (mut chunks, _) = self
+                .open_chunk_stream(file.id, &GridFSDownloadOptions::default())
+                .await?;
+            // The swap only applies to the file as it was copied.
+            let swap = doc! {
+                "_id":file.id,
+                ENCRYPTION_KEY_FIELD:old_key.clone(),
+                "length":document.get("length").cloned().unwrap_or(Bson::Null),
+                CHUNKS_COLLECTION_FIELD:document
+                    .get(CHUNKS_COLLECTION_FIELD)
+                    .cloned()
+                    .unwrap_or_else(|| Bson::Document(doc! {"$exists":false})),
+            };
+
+            if document.contains_key(INLINE_FIELD) {
+                let data = chunks.next().await.transpose()?.unwrap_or_default();
+                let data = chunk_data_field(Some(new_key), subtype, data).await?;
+                let update = doc! {"$set":{
+                    INLINE_FIELD:data,
+                    ENCRYPTION_KEY_FIELD:new_key.key_id.clone(),
+                }};
+                if files
+                    .update_one(swap, update, update_options.clone())
+                    .await?
+                    .matched_count
+                    == 1
+                {
+                    rotated += 1;
+                }
+                continue;
+            }
+
+            let former_collection = chunk_collection_of(&document, &bucket_name);
+            let target_collection = if former_collection == bucket_chunks {
+                format!("{}.chunks.{}", bucket_name, key_hex(&new_key.key_id))
+            } else {
+                bucket_chunks.clone()
+            };
+            self.ensure_chunks_index(&target_collection).await?;
+            let target: Collection<Document> = self.db.collection(&target_collection);
+            let chunk_base = chunk_filter(&document, file.id, self.chunk_shard_key());
+            // The chunks left by an interrupted copy of the file.
+            target
+                .delete_many(chunk_base.clone(), delete_options.clone())
+                .await?;
+
+            let mut n = 0;
+            while let Some(data) = chunks.next().await {
+                let mut chunk = chunk_base.clone();
+                chunk.insert("n", n);
+                chunk.insert(
+                    "data",
+                    chunk_data_field(Some(new_key), subtype, data?).await?,
+                );
+                if let Some(expire_at) = file.expire_at {
+                    chunk.insert("expireAt", expire_at);
+                }
+                target.insert_one(chunk, insert_options.clone()).await?;
+                n += 1;
+            }
+
+            let update = if target_collection == bucket_chunks {
+                doc! {
+                    "$set":{ENCRYPTION_KEY_FIELD:new_key.key_id.clone()},
+                    "$unset":{CHUNKS_COLLECTION_FIELD:""},
+                }
+            } else {
+                doc! {"$set":{
+                    ENCRYPTION_KEY_FIELD:new_key.key_id.clone(),
+                    CHUNKS_COLLECTION_FIELD:&target_collection,
+                }}
+            };
+            let swapped = files
+                .update_one(swap, update, update_options.clone())
+                .await?
+                .matched_count
+                == 1;
+            let stale_collection = if swapped {
+                former_collection
+            } else {
+                target_collection
+            };
+            self.db
+                .collection::<Document>(&stale_collection)
+                .delete_many(chunk_base, delete_options.clone())
+                .await?;
+            if swapped {
+                rotated += 1;
+            }
+        }
+        Ok(rotated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::key_hex;
+    use crate::{
+        bucket::GridFSBucket,
+        encryption::{ChunkEncryption, ChunkEncryptor, ENCRYPTION_KEY_FIELD},
+        options::GridFSBucketOptions,
+        GridFSError,
+    };
+    use bson::{doc, spec::BinarySubtype, Binary, Bson, Document};
+    use futures_util::future::BoxFuture;
+    use mongodb::{Client, Database};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    /// Encrypts by reversing the data.
+    struct Reverse;
+
+    impl ChunkEncryptor for Reverse {
+        fn encrypt<'a>(
+            &'a self,
+            _key_id: &'a Binary,
+            mut data: Vec<u8>,
+        ) -> BoxFuture<'a, Result<Binary, String>> {
+            Box::pin(async move {
+                data.reverse();
+                Ok(Binary {
+                    subtype: BinarySubtype::Encrypted,
+                    bytes: data,
+                })
+            })
+        }
+    }
+
+    fn key_id(byte: u8) -> Binary {
+        Binary {
+            subtype: BinarySubtype::Uuid,
+            bytes: vec![byte; 16],
+        }
+    }
+
+    #[test]
+    fn key_hex_of_key_id() {
+        assert_eq!(key_hex(&key_id(0xa7)), "a7".repeat(16));
+    }
+
+    #[tokio::test]
+    async fn rotate_keys() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        let other = bucket
+            .upload_from_stream("other.txt", "test data".as_bytes(), None)
+            .await?;
+        // Stored in the clear, as read back through auto-encryption.
+        let files = db.collection::<Document>("fs.files");
+        files
+            .update_many(
+                doc! {},
+                doc! {"$set":{ENCRYPTION_KEY_FIELD:key_id(1)}},
+                None,
+            )
+            .await?;
+
+        let new_key = ChunkEncryption::new(key_id(2), Arc::new(Reverse));
+        let rotated = bucket
+            .rotate_keys(&key_id(1), &new_key, doc! {"filename":"test.txt"})
+            .await?;
+        assert_eq!(rotated, 1);
+
+        let file = files.find_one(doc! {"_id":id}, None).await?.unwrap();
+        assert_eq!(
+            file.get(ENCRYPTION_KEY_FIELD),
+            Some(&Bson::Binary(key_id(2)))
+        );
+        let collection = format!("fs.chunks.{}", "02".repeat(16));
+        assert_eq!(file.get_str("chunksCollection"), Ok(collection.as_str()));
+        let chunks = db.collection::<Document>(&collection);
+        let chunk = chunks
+            .find_one(doc! {"files_id":id, "n":0}, None)
+            .await?
+            .unwrap();
+        assert_eq!(
+            chunk.get("data"),
+            Some(&Bson::Binary(Binary {
+                subtype: BinarySubtype::Encrypted,
+                bytes: b"tset".to_vec()
+            }))
+        );
+        assert_eq!(chunks.count_documents(doc! {"files_id":id}, None).await?, 3);
+        let former = db.collection::<Document>("fs.chunks");
+        assert_eq!(former.count_documents(doc! {"files_id":id}, None).await?, 0);
+        assert_eq!(
+            former
+                .count_documents(doc! {"files_id":other}, None)
+                .await?,
+            3
+        );
+
+        // Re-encrypted: left out, and the chunks can't be read without auto-encryption.
+        assert_eq!(
+            bucket
+                .rotate_keys(&key_id(1), &new_key, doc! {"filename":"test.txt"})
+                .await?,
+            0
+        );
+        assert!(matches!(
+            bucket.rotate_keys(&key_id(2), &new_key, doc! {}).await,
+            Err(GridFSError::InvalidChunk(0, _))
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
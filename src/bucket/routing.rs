@@ -0,0 +1,193 @@
+use bson::{doc, oid::ObjectId, Document};
+
+/// Field of the files collection document of a file whose chunks are stored out of the
+/// chunks collection of the bucket, holding the name of their collection.
+pub(crate) const CHUNKS_COLLECTION_FIELD: &str = "chunksCollection";
+
+/// The name of the collection of the chunks of the @file of the bucket @bucket_name.
+pub(crate) fn chunk_collection_of(file: &Document, bucket_name: &str) -> String {
+    match file.get_str(CHUNKS_COLLECTION_FIELD) {
+        Ok(collection_name) => collection_name.to_owned(),
+        Err(_) => bucket_name.to_owned() + ".chunks",
+    }
+}
+
+/// The projection of the fields of a files collection document locating its chunks: the
+/// chunks collection and the value of the @shard_key.
+pub(crate) fn chunk_routing_projection(shard_key: Option<&str>) -> Document {
+    let mut projection = doc! {CHUNKS_COLLECTION_FIELD:1};
+    if let Some(shard_key) = shard_key {
+        projection.insert(shard_key, 1);
+    }
+    projection
+}
+
+/// The keys of the index of the chunks: `{files_id: 1, n: 1}`, prefixed by the @shard_key
+/// field of the bucket if any.
+pub(crate) fn chunks_index_keys(shard_key: Option<&str>) -> Document {
+    let mut keys = Document::new();
+    if let Some(shard_key) = shard_key {
+        keys.insert(shard_key, 1);
+    }
+    keys.insert("files_id", 1);
+    keys.insert("n", 1);
+    keys
+}
+
+/// The filter of the chunks of the @file @files_id. With a @shard_key, the filter holds the
+/// shard key value of the file, so the query targets the shard of the chunks.
+pub(crate) fn chunk_filter(file: &Document, files_id: ObjectId, shard_key: Option<&str>) -> Document {
+    let mut filter = Document::new();
+    if let Some((shard_key, value)) =
+        shard_key.and_then(|shard_key| Some((shard_key, file.get(shard_key)?)))
+    {
+        filter.insert(shard_key, value.clone());
+    }
+    filter.insert("files_id", files_id);
+    filter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_collection_of, chunk_filter, chunks_index_keys};
+    use crate::{
+        options::{GridFSBucketOptions, GridFSUploadOptions},
+        GridFSBucket, GridFSError,
+    };
+    use bson::{doc, oid::ObjectId, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn chunk_collection() {
+        assert_eq!(chunk_collection_of(&doc! {}, "fs"), "fs.chunks");
+        assert_eq!(
+            chunk_collection_of(&doc! {"chunksCollection":"fs.chunks.big"}, "fs"),
+            "fs.chunks.big"
+        );
+    }
+
+    #[test]
+    fn shard_key() {
+        let id = ObjectId::new();
+        assert_eq!(chunks_index_keys(None), doc! {"files_id":1, "n":1});
+        assert_eq!(
+            chunks_index_keys(Some("tenant")),
+            doc! {"tenant":1, "files_id":1, "n":1}
+        );
+        assert_eq!(
+            chunk_filter(&doc! {"tenant":"a"}, id, None),
+            doc! {"files_id":id}
+        );
+        assert_eq!(
+            chunk_filter(&doc! {"tenant":"a"}, id, Some("tenant")),
+            doc! {"tenant":"a", "files_id":id}
+        );
+        assert_eq!(
+            chunk_filter(&doc! {}, id, Some("tenant")),
+            doc! {"files_id":id}
+        );
+    }
+
+    #[tokio::test]
+    async fn routed_chunks() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket
+            .upload_from_stream(
+                "test.txt",
+                "test data".as_bytes(),
+                Some(
+                    GridFSUploadOptions::builder()
+                        .chunks_collection(Some("fs.chunks.big".into()))
+                        .build(),
+                ),
+            )
+            .await?;
+        let shared = db.collection::<Document>("fs.chunks");
+        let routed = db.collection::<Document>("fs.chunks.big");
+        assert_eq!(shared.count_documents(doc! {}, None).await?, 0);
+        assert_eq!(routed.count_documents(doc! {"files_id":id}, None).await?, 1);
+
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test data");
+        assert_eq!(bucket.read_range(id, 5, 4, None).await?, b"data");
+
+        bucket.delete(id).await?;
+        assert_eq!(routed.count_documents(doc! {}, None).await?, 0);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chunk_shard_key() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(4)
+                    .chunk_shard_key(Some("tenant".into()))
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream(
+                "test.txt",
+                "test data".as_bytes(),
+                Some(
+                    GridFSUploadOptions::builder()
+                        .shard_key_value(Some("acme".into()))
+                        .build(),
+                ),
+            )
+            .await?;
+        let chunks = db.collection::<Document>("fs.chunks");
+        assert_eq!(
+            chunks
+                .count_documents(doc! {"tenant":"acme", "files_id":id}, None)
+                .await?,
+            3
+        );
+        let index_names = chunks.list_index_names().await?;
+        assert!(index_names.contains(&"fs.chunks_index".to_string()));
+        let mut indexes = chunks.list_indexes(None).await?;
+        while let Some(index) = indexes.next().await {
+            let index = index?;
+            if index.options.and_then(|options| options.name) == Some("fs.chunks_index".into()) {
+                assert_eq!(index.keys, chunks_index_keys(Some("tenant")));
+            }
+        }
+
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test");
+        assert_eq!(bucket.touch_chunks(id).await?, 3);
+        bucket.delete(id).await?;
+        assert_eq!(chunks.count_documents(doc! {}, None).await?, 0);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use crate::bucket::GridFSBucket;
+use prometheus::{IntGauge, Opts, Registry};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Handle on a running sampler started by [`GridFSBucket::start_stats_sampler`].
+///
+/// The sampler stops when the handle is dropped. The gauges stay registered with their last
+/// values.
+pub struct StatsSampler {
+    task: JoinHandle<()>,
+}
+
+impl Drop for StatsSampler {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The gauges of a bucket, labelled with the name of the bucket.
+#[derive(Clone)]
+struct Gauges {
+    files: IntGauge,
+    bytes: IntGauge,
+    orphan_chunks: IntGauge,
+}
+
+impl Gauges {
+    fn new(bucket_name: &str) -> prometheus::Result<Gauges> {
+        let gauge = |name: &str, help: &str| {
+            IntGauge::with_opts(Opts::new(name, help).const_label("bucket", bucket_name))
+        };
+        Ok(Gauges {
+            files: gauge("gridfs_files", "Number of files in the bucket.")?,
+            bytes: gauge(
+                "gridfs_bytes",
+                "Total length of the files in the bucket, in bytes.",
+            )?,
+            orphan_chunks: gauge(
+                "gridfs_orphan_chunks_estimate",
+                "Estimated number of chunks not belonging to any file.",
+            )?,
+        })
+    }
+
+    fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.files.clone()))?;
+        registry.register(Box::new(self.bytes.clone()))?;
+        registry.register(Box::new(self.orphan_chunks.clone()))
+    }
+}
+
+impl GridFSBucket {
+    /**
+    Registers the gauges `gridfs_files`, `gridfs_bytes` and `gridfs_orphan_chunks_estimate`,
+    labelled with the name of the bucket, in @registry and updates them from
+    [`GridFSBucket::stats`] every @interval, in a background task.
+
+    A failed sampling leaves the gauges at their last values. The sampler stops when the
+    bucket is [shut down](GridFSBucket::shutdown).
+
+    # Errors
+
+    Raise a [`prometheus::Error`] when the gauges of the bucket are already registered.
+    */
+    pub fn start_stats_sampler(
+        &self,
+        registry: &Registry,
+        interval: Duration,
+    ) -> prometheus::Result<StatsSampler> {
+        let bucket_name = self.options.clone().unwrap_or_default().bucket_name;
+        let gauges = Gauges::new(&bucket_name)?;
+        gauges.register(registry)?;
+
+        let bucket = self.clone();
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if bucket.is_shut_down() {
+                    break;
+                }
+                if let Ok(stats) = bucket.stats().await {
+                    gauges.files.set(stats.files as i64);
+                    gauges.bytes.set(stats.bytes as i64);
+                    gauges
+                        .orphan_chunks
+                        .set(stats.orphan_chunks_estimate as i64);
+                }
+            }
+        });
+        Ok(StatsSampler { task })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Gauges;
+    use prometheus::Registry;
+
+    #[test]
+    fn register_gauges() {
+        let registry = Registry::new();
+        let gauges = Gauges::new("fs").unwrap();
+        gauges.register(&registry).unwrap();
+        gauges.bytes.set(9);
+
+        let families = registry.gather();
+        assert_eq!(families.len(), 3);
+        let bytes = families
+            .iter()
+            .find(|family| family.name() == "gridfs_bytes")
+            .unwrap();
+        assert_eq!(bytes.get_metric()[0].get_gauge().get_value(), 9.0);
+        assert_eq!(bytes.get_metric()[0].get_label()[0].value(), "fs");
+
+        // The gauges of a bucket are registered once.
+        assert!(Gauges::new("fs").unwrap().register(&registry).is_err());
+        assert!(Gauges::new("other").unwrap().register(&registry).is_ok());
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{bucket::GridFSBucket, options::GridFSUploadOptions, GridFSError};
+use bson::oid::ObjectId;
+use std::panic::resume_unwind;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    task::JoinSet,
+};
+use tokio_stream::StreamExt;
+
+/// The result of a transfer of a [`BucketScope`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ScopedTransfer {
+    /// The file was uploaded with this id.
+    Uploaded(ObjectId),
+    /// The file `id` was downloaded: `length` bytes were written.
+    Downloaded { id: ObjectId, length: u64 },
+}
+
+/// A group of uploads and downloads running in the background, started by
+/// [`GridFSBucket::scope`].
+///
+/// The transfers are awaited together with [`BucketScope::join_next`] or
+/// [`BucketScope::join_all`]. They are cancelled together by [`BucketScope::cancel`], or when
+/// the scope is dropped: no transfer outlives its scope.
+pub struct BucketScope {
+    bucket: GridFSBucket,
+    transfers: JoinSet<Result<ScopedTransfer, GridFSError>>,
+}
+
+impl GridFSBucket {
+    /**
+    Opens a scope on the bucket: the uploads and downloads spawned in the scope run in the
+    background until they are awaited, and are cancelled when the scope is dropped.
+    Requires a tokio runtime.
+
+    # Examples
+
+    ```rust,no_run
+    # use mongodb::Client;
+    use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket, GridFSError};
+    #
+    # #[tokio::main]
+    # async fn main() -> Result<(), GridFSError> {
+    #     let client = Client::with_uri_str("mongodb://localhost:27017/").await?;
+    #     let db = client.database("test");
+    let bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+    let mut scope = bucket.scope();
+    scope.spawn_upload("a.txt", "first file".as_bytes(), None);
+    scope.spawn_upload("b.txt", "second file".as_bytes(), None);
+    for transfer in scope.join_all().await {
+        println!("{:?}", transfer?);
+    }
+    #     Ok(())
+    # }
+    ```
+    */
+    pub fn scope(&self) -> BucketScope {
+        BucketScope {
+            bucket: self.clone(),
+            transfers: JoinSet::new(),
+        }
+    }
+}
+
+impl BucketScope {
+    /// Spawns the upload of @source as @filename, see [`GridFSBucket::upload_from_stream`].
+    pub fn spawn_upload<R>(&mut self, filename: &str, source: R, options: Option<GridFSUploadOptions>)
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let mut bucket = self.bucket.clone();
+        let filename = filename.to_string();
+        self.transfers.spawn(async move {
+            bucket
+                .upload_from_stream(&filename, source, options)
+                .await
+                .map(ScopedTransfer::Uploaded)
+        });
+    }
+
+    /// Spawns the download of the file @id into @destination, see
+    /// [`GridFSBucket::open_download_stream`].
+    pub fn spawn_download<W>(&mut self, id: ObjectId, mut destination: W)
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let bucket = self.bucket.clone();
+        self.transfers.spawn(async move {
+            let mut stream = bucket.open_download_stream(id).await?;
+            let mut length = 0;
+            while let Some(data) = stream.next().await {
+                let data = data?;
+                destination
+                    .write_all(&data)
+                    .await
+                    .map_err(mongodb::error::Error::from)?;
+                length += data.len() as u64;
+            }
+            destination
+                .flush()
+                .await
+                .map_err(mongodb::error::Error::from)?;
+            Ok(ScopedTransfer::Downloaded { id, length })
+        });
+    }
+
+    /// The number of transfers of the scope not awaited yet.
+    pub fn len(&self) -> usize {
+        self.transfers.len()
+    }
+
+    /// Whether all the transfers of the scope are awaited.
+    pub fn is_empty(&self) -> bool {
+        self.transfers.is_empty()
+    }
+
+    /// Waits for the next transfer to complete and returns its result, in completion order.
+    /// The cancelled transfers are skipped.
+    ///
+    /// Returns `None` once all the transfers are awaited.
+    pub async fn join_next(&mut self) -> Option<Result<ScopedTransfer, GridFSError>> {
+        while let Some(joined) = self.transfers.join_next().await {
+            match joined {
+                Ok(result) => return Some(result),
+                Err(error) if error.is_panic() => resume_unwind(error.into_panic()),
+                Err(_) => continue,
+            }
+        }
+        None
+    }
+
+    /// Waits for all the transfers of the scope, and returns their results in completion
+    /// order.
+    pub async fn join_all(mut self) -> Vec<Result<ScopedTransfer, GridFSError>> {
+        let mut results = Vec::with_capacity(self.len());
+        while let Some(result) = self.join_next().await {
+            results.push(result);
+        }
+        results
+    }
+
+    /// Cancels the transfers of the scope. An upload cancelled before its completion leaves
+    /// its chunks and a files collection document without `length`.
+    pub fn cancel(&mut self) {
+        self.transfers.abort_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScopedTransfer;
+    use crate::{bucket::GridFSBucket, options::GridFSBucketOptions, GridFSError};
+    use bson::{doc, Document};
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn scope() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let mut scope = bucket.scope();
+        scope.spawn_upload("a.txt", "first file".as_bytes(), None);
+        scope.spawn_upload("b.txt", "second file".as_bytes(), None);
+        scope.spawn_download(id, tokio::io::sink());
+        assert_eq!(scope.len(), 3);
+        let results = scope.join_all().await;
+        assert_eq!(results.len(), 3);
+        assert!(results
+            .iter()
+            .any(|result| matches!(result, Ok(ScopedTransfer::Downloaded { length: 9, .. }))));
+        assert_eq!(
+            results
+                .iter()
+                .filter(|result| matches!(result, Ok(ScopedTransfer::Uploaded(_))))
+                .count(),
+            2
+        );
+
+        // An endless upload is cancelled with its scope.
+        let mut scope = bucket.scope();
+        scope.spawn_upload("endless.txt", tokio::io::repeat(b'x'), None);
+        scope.cancel();
+        assert!(scope.join_next().await.is_none());
+        assert!(scope.is_empty());
+        let files = db.collection::<Document>("fs.files");
+        assert_eq!(
+            files
+                .count_documents(doc! {"length":{"$exists":true}}, None)
+                .await?,
+            3
+        );
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    bucket::{inline::INLINE_FIELD, status::visible, GridFSBucket},
+    display::HumanSize,
+    file_info::get_number,
+    GridFSError,
+};
+use bson::{doc, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::options::{AggregateOptions, EstimatedDocumentCountOptions};
+use std::fmt::{Display, Formatter};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// Statistics of a bucket, returned by [`GridFSBucket::stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BucketStats {
+    /// Number of files.
+    pub files: u64,
+    /// Total length of the files, in bytes.
+    pub bytes: u64,
+    /// Number of chunks, estimated from the metadata of the chunks collection.
+    pub chunks: u64,
+    /// Number of chunks which don't belong to any file, estimated as the difference between
+    /// [`BucketStats::chunks`] and the number of chunks the files should have.
+    pub orphan_chunks_estimate: u64,
+}
+
+/// `n files, size, n chunks`, followed by the estimate of the orphan chunks if any.
+impl Display for BucketStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files, {}, {} chunks",
+            self.files,
+            HumanSize(self.bytes),
+            self.chunks
+        )?;
+        if self.orphan_chunks_estimate > 0 {
+            write!(f, " (~{} orphan)", self.orphan_chunks_estimate)?;
+        }
+        Ok(())
+    }
+}
+
+impl GridFSBucket {
+    /**
+    Returns the [`BucketStats`] of the bucket: the files are aggregated, the chunks are counted
+    from the metadata of the chunks collection.
+
+    The reads honor the `selection_criteria` and the `read_concern` of the bucket options.
+    */
+    pub async fn stats(&self) -> Result<BucketStats, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let selection_criteria = dboptions.read_selection_criteria();
+        let bucket_name = dboptions.bucket_name;
+        let files = self
+            .db
+            .collection::<Document>(&(bucket_name.clone() + ".files"));
+        let chunks = self.db.collection::<Document>(&(bucket_name + ".chunks"));
+
+        let aggregate_options = AggregateOptions::builder()
+            .selection_criteria(selection_criteria.clone())
+            .read_concern(dboptions.read_concern.clone())
+            .build();
+        let mut cursor = files
+            .aggregate(
+                vec![
+                    doc! {"$match":visible(doc! {})},
+                    doc! {"$group":{
+                        "_id":null,
+                        "files":{"$sum":1},
+                        "bytes":{"$sum":"$length"},
+                        "chunks":{"$sum":{"$cond":[
+                            {"$and":[
+                                {"$gt":["$chunkSize",0]},
+                                {"$eq":[{"$type":format!("${}", INLINE_FIELD)},"missing"]},
+                            ]},
+                            {"$ceil":{"$divide":["$length","$chunkSize"]}},
+                            0
+                        ]}},
+                    }},
+                ],
+                aggregate_options,
+            )
+            .await?;
+        let totals = cursor.next().await.transpose()?.unwrap_or_default();
+
+        let count_options = EstimatedDocumentCountOptions::builder()
+            .selection_criteria(selection_criteria)
+            .read_concern(dboptions.read_concern)
+            .build();
+        let chunk_count = chunks.estimated_document_count(count_options).await?;
+        let expected_chunks = get_number(&totals, "chunks").unwrap_or(0).max(0) as u64;
+
+        Ok(BucketStats {
+            files: get_number(&totals, "files").unwrap_or(0).max(0) as u64,
+            bytes: get_number(&totals, "bytes").unwrap_or(0).max(0) as u64,
+            chunks: chunk_count,
+            orphan_chunks_estimate: chunk_count.saturating_sub(expected_chunks),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BucketStats, GridFSBucket};
+    use crate::{options::GridFSBucketOptions, GridFSError};
+    use bson::{doc, oid::ObjectId, Document};
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn stats_display() {
+        let mut stats = BucketStats {
+            files: 2,
+            bytes: 1536,
+            chunks: 8,
+            orphan_chunks_estimate: 0,
+        };
+        assert_eq!(stats.to_string(), "2 files, 1.5 KiB, 8 chunks");
+        stats.orphan_chunks_estimate = 3;
+        assert_eq!(stats.to_string(), "2 files, 1.5 KiB, 8 chunks (~3 orphan)");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn stats_serialize() {
+        let stats = BucketStats {
+            files: 2,
+            bytes: 1536,
+            chunks: 8,
+            orphan_chunks_estimate: 0,
+        };
+        assert_eq!(
+            bson::to_document(&stats).unwrap(),
+            doc! {"files":2_i64, "bytes":1536_i64, "chunks":8_i64, "orphan_chunks_estimate":0_i64}
+        );
+    }
+
+    #[tokio::test]
+    async fn stats() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        assert_eq!(bucket.stats().await?, BucketStats::default());
+
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("test2.txt", "test".as_bytes(), None)
+            .await?;
+        db.collection::<Document>("fs.chunks")
+            .insert_one(
+                doc! {"files_id":ObjectId::new(), "n":0, "data":bson::Binary{subtype:bson::spec::BinarySubtype::Generic, bytes:vec![0]}},
+                None,
+            )
+            .await?;
+
+        let stats = bucket.stats().await?;
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.bytes, 13);
+        assert_eq!(stats.chunks, 5);
+        assert_eq!(stats.orphan_chunks_estimate, 1);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{
+    bucket::{quarantine::QUARANTINE_FIELD, GridFSBucket},
+    FileStatus, GridFSError,
+};
+use bson::{doc, oid::ObjectId, Bson, Document};
+use mongodb::options::UpdateOptions;
+
+/// Field of the files collection document holding the [`FileStatus`] of the file.
+pub(crate) const STATUS_FIELD: &str = "status";
+
+/// Restricts @filter to the files in the state @status. The files stored without state
+/// are available.
+pub(crate) fn with_status(filter: Document, status: FileStatus) -> Document {
+    let with_status = match status {
+        FileStatus::Available => doc! {STATUS_FIELD:{"$in":[Bson::Null, status.as_str()]}},
+        _ => doc! {STATUS_FIELD:status.as_str()},
+    };
+    if filter.is_empty() {
+        with_status
+    } else {
+        doc! {"$and":[filter, with_status]}
+    }
+}
+
+/// Restricts @filter to the files the readers see: the available ones.
+pub(crate) fn visible(filter: Document) -> Document {
+    with_status(filter, FileStatus::Available)
+}
+
+impl GridFSBucket {
+    /**
+    Publishes the uploaded file @id: it becomes available to the readers. Releases a
+    quarantined file.
+
+    # Errors
+
+    Raise [`GridFSError::FileNotFound`] when no uploaded file has the id @id.
+    */
+    pub async fn publish(&self, id: ObjectId) -> Result<(), GridFSError> {
+        self.set_status(id, FileStatus::Available).await
+    }
+
+    /**
+    Archives the uploaded file @id: it is kept, but the readers don't see it anymore until it
+    is published again.
+
+    # Errors
+
+    Raise [`GridFSError::FileNotFound`] when no uploaded file has the id @id.
+    */
+    pub async fn archive(&self, id: ObjectId) -> Result<(), GridFSError> {
+        self.set_status(id, FileStatus::Archived).await
+    }
+
+    async fn set_status(&self, id: ObjectId, status: FileStatus) -> Result<(), GridFSError> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let files = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name.clone() + ".files"));
+
+        let update_options = UpdateOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        // The reservations, without length, have no content to publish, and the files being
+        // deleted have lost a part of it.
+        let update_result = files
+            .update_one(
+                doc! {
+                    "_id":id,
+                    "length":{"$exists":true},
+                    STATUS_FIELD:{"$ne":FileStatus::Deleting.as_str()},
+                },
+                doc! {"$set":{STATUS_FIELD:status.as_str()}, "$unset":{QUARANTINE_FIELD:""}},
+                update_options,
+            )
+            .await?;
+        if update_result.matched_count == 0 {
+            return Err(GridFSError::FileNotFound());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{visible, with_status, GridFSBucket};
+    use crate::{
+        options::{GridFSBucketOptions, GridFSFindOptions, GridFSUploadOptions},
+        FileStatus, GridFSError,
+    };
+    use bson::{doc, Bson};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn status_filter() {
+        assert_eq!(
+            visible(doc! {}),
+            doc! {"status":{"$in":[Bson::Null, "available"]}}
+        );
+        assert_eq!(
+            with_status(doc! {"filename":"test.txt"}, FileStatus::Archived),
+            doc! {"$and":[{"filename":"test.txt"}, {"status":"archived"}]}
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_archive() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket
+            .upload_from_stream(
+                "test.txt",
+                "test data".as_bytes(),
+                Some(
+                    GridFSUploadOptions::builder()
+                        .status(Some(FileStatus::Pending))
+                        .build(),
+                ),
+            )
+            .await?;
+        assert!(matches!(
+            bucket.open_download_stream(id).await,
+            Err(GridFSError::FileNotFound())
+        ));
+
+        bucket.publish(id).await?;
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test data");
+        assert_eq!(bucket.file_info(id).await?.status, FileStatus::Available);
+
+        bucket.archive(id).await?;
+        let mut cursor = bucket.find(doc! {}, GridFSFindOptions::default()).await?;
+        assert!(cursor.next().await.is_none());
+        let mut cursor = bucket
+            .find(
+                doc! {},
+                GridFSFindOptions::builder()
+                    .status(Some(FileStatus::Archived))
+                    .build(),
+            )
+            .await?;
+        assert_eq!(cursor.next().await.unwrap()?.get_object_id("_id"), Ok(id));
+
+        // A reservation can't be published before its upload.
+        let reserved = bucket.reserve_id("reserved.txt", None).await?;
+        assert!(matches!(
+            bucket.publish(reserved).await,
+            Err(GridFSError::FileNotFound())
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{
+    bucket::{
+        chunk_stream::DocumentStream,
+        inline::INLINE_FIELD,
+        routing::{chunk_collection_of, chunk_filter},
+        status::visible,
+        GridFSBucket,
+    },
+    encryption::chunk_data_field,
+    file_info::get_number,
+    options::GridFSDownloadOptions,
+    FileInfo, GridFSError,
+};
+use bson::{doc, oid::ObjectId, Binary, DateTime, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use futures_util::stream::iter;
+use mongodb::options::{DeleteOptions, InsertManyOptions, UpdateOptions};
+use std::{convert::TryFrom, time::Duration};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// Field of the files collection document of a tiered file, holding the key of its content
+/// in the tier backend.
+pub(crate) const TIER_FIELD: &str = "tierKey";
+
+impl GridFSBucket {
+    /**
+    Exports the content of the files uploaded more than @age ago to the
+    [`tier_backend`](crate::options::GridFSBucketOptions::tier_backend) of the bucket, and
+    removes their chunks. Returns the ids of the tiered files.
+
+    The files collection document of a tiered file stays, with the key of its content in the
+    backend: the file is still found, and its downloads fetch the content from the backend.
+    A file is tiered once its content is stored in the backend: when the backend fails, the
+    file stays as it is.
+
+    # Errors
+
+    Raise [`GridFSError::TierFailed`] when the bucket has no tier backend, or the backend
+    fails to store a file.
+    */
+    pub async fn tier_files(&self, age: Duration) -> Result<Vec<ObjectId>, GridFSError> {
+        let _writer = self.write_guard().await;
+        let dboptions = self.options.clone().unwrap_or_default();
+        let backend = dboptions
+            .tier_backend
+            .clone()
+            .ok_or_else(|| GridFSError::TierFailed {
+                reason: "no tier backend".into(),
+            })?;
+        let bucket_name = dboptions.bucket_name.clone();
+        let files = self
+            .db
+            .collection::<Document>(&(bucket_name.clone() + ".files"));
+
+        let cutoff = DateTime::from_millis(
+            self.now().timestamp_millis() - age.as_millis().min(i64::MAX as u128) as i64,
+        );
+        let mut cursor = files
+            .find(
+                visible(doc! {
+                    "uploadDate":{"$lt":cutoff},
+                    "length":{"$exists":true},
+                    TIER_FIELD:{"$exists":false},
+                    INLINE_FIELD:{"$exists":false},
+                }),
+                None,
+            )
+            .await?;
+        let update_options = UpdateOptions::builder()
+            .write_concern(dboptions.write_concern_of_files())
+            .build();
+        let delete_options = DeleteOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        let mut tiered = vec![];
+        while let Some(file) = cursor.next().await {
+            let document = file?;
+            let file = FileInfo::try_from(document.clone())?;
+            let chunks = self
+                .db
+                .collection::<Document>(&chunk_collection_of(&document, &bucket_name));
+            let filter = chunk_filter(&document, file.id, self.chunk_shard_key());
+            let (mut stream, _) = self
+                .open_chunk_stream(file.id, &GridFSDownloadOptions::default())
+                .await?;
+            let mut content = Vec::with_capacity(file.length as usize);
+            while let Some(data) = stream.next().await {
+                content.extend(data?);
+            }
+            let key = backend
+                .export(&file, content)
+                .await
+                .map_err(|reason| GridFSError::TierFailed { reason })?;
+            files
+                .update_one(
+                    doc! {"_id":file.id},
+                    doc! {"$set":{TIER_FIELD:key}},
+                    update_options.clone(),
+                )
+                .await?;
+            chunks.delete_many(filter, delete_options.clone()).await?;
+            tiered.push(file.id);
+        }
+        Ok(tiered)
+    }
+
+    /// Fetches the content of the tiered @file @id from the tier backend, as a stream of
+    /// chunks documents. With `rehydrate_tiered`, the chunks are written back in the bucket,
+    /// on a best effort basis: a failed write leaves the file tiered.
+    pub(crate) async fn fetch_tiered(
+        &self,
+        file: &Document,
+        id: ObjectId,
+        key: &str,
+    ) -> Result<DocumentStream, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let backend = dboptions
+            .tier_backend
+            .clone()
+            .ok_or_else(|| GridFSError::TierFailed {
+                reason: "no tier backend".into(),
+            })?;
+        let content = backend
+            .fetch(key)
+            .await
+            .map_err(|reason| GridFSError::TierFailed { reason })?;
+
+        let chunk_size = get_number(file, "chunkSize").unwrap_or(0).max(1) as usize;
+        let subtype = dboptions.chunk_binary_subtype.into();
+        let filter = chunk_filter(file, id, self.chunk_shard_key());
+        let chunk_documents: Vec<Document> = content
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(n, data)| {
+                let mut chunk = filter.clone();
+                chunk.insert("n", n as i32);
+                chunk.insert(
+                    "data",
+                    Binary {
+                        subtype,
+                        bytes: data.to_vec(),
+                    },
+                );
+                chunk
+            })
+            .collect();
+
+        // Not rehydrated while a drop of the bucket is pending.
+        let writer = match dboptions.rehydrate_tiered {
+            true => self.try_write_guard(),
+            false => None,
+        };
+        if writer.is_some() {
+            let bucket_name = dboptions.bucket_name.clone();
+            let insert_options = InsertManyOptions::builder()
+                .write_concern(dboptions.write_concern_of_chunks())
+                .build();
+            // The chunks returned to the download stay in the clear.
+            let mut rehydrated_documents = Ok(chunk_documents.clone());
+            if let Some(encryption) = &dboptions.chunk_encryption {
+                let mut encrypted = vec![];
+                for (mut chunk, data) in chunk_documents
+                    .iter()
+                    .cloned()
+                    .zip(content.chunks(chunk_size))
+                {
+                    match chunk_data_field(Some(encryption), subtype, data.to_vec()).await {
+                        Ok(data) => chunk.insert("data", data),
+                        Err(error) => {
+                            rehydrated_documents = Err(error);
+                            break;
+                        }
+                    };
+                    encrypted.push(chunk);
+                }
+                rehydrated_documents = rehydrated_documents.map(|_| encrypted);
+            }
+            let rehydrated = match rehydrated_documents {
+                Ok(documents) => self
+                    .db
+                    .collection::<Document>(&chunk_collection_of(file, &bucket_name))
+                    .insert_many(documents, insert_options)
+                    .await
+                    .is_ok(),
+                Err(_) => false,
+            };
+            if rehydrated || chunk_documents.is_empty() {
+                let update_options = UpdateOptions::builder()
+                    .write_concern(dboptions.write_concern_of_files())
+                    .build();
+                let _ = self
+                    .db
+                    .collection::<Document>(&(bucket_name + ".files"))
+                    .update_one(
+                        doc! {"_id":id},
+                        doc! {"$unset":{TIER_FIELD:""}},
+                        update_options,
+                    )
+                    .await;
+            }
+        }
+        Ok(Box::pin(iter(chunk_documents.into_iter().map(Ok))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TIER_FIELD;
+    use crate::{
+        bucket::GridFSBucket, options::GridFSBucketOptions, tier::TierBackend, FileInfo, GridFSError,
+    };
+    use bson::{doc, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use futures_util::future::BoxFuture;
+    use mongodb::{Client, Database};
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[derive(Default)]
+    struct MemoryBackend(Mutex<HashMap<String, Vec<u8>>>);
+
+    impl TierBackend for MemoryBackend {
+        fn export<'a>(
+            &'a self,
+            file: &'a FileInfo,
+            content: Vec<u8>,
+        ) -> BoxFuture<'a, Result<String, String>> {
+            Box::pin(async move {
+                let key = file.id.to_hex();
+                self.0.lock().unwrap().insert(key.clone(), content);
+                Ok(key)
+            })
+        }
+
+        fn fetch<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, String>> {
+            Box::pin(async move {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .get(key)
+                    .cloned()
+                    .ok_or_else(|| format!("{} not found", key))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn tier_files() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let backend = Arc::new(MemoryBackend::default());
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(4)
+                    .tier_backend(Some(backend.clone()))
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        assert!(bucket
+            .tier_files(Duration::from_secs(3600))
+            .await?
+            .is_empty());
+        assert_eq!(bucket.tier_files(Duration::ZERO).await?, vec![id]);
+        let chunks = db.collection::<Document>("fs.chunks");
+        assert_eq!(chunks.count_documents(doc! {}, None).await?, 0);
+
+        let mut cursor = bucket.open_download_stream(id).await?;
+        let mut content = vec![];
+        while let Some(data) = cursor.next().await {
+            content.extend(data?);
+        }
+        assert_eq!(content, b"test data");
+        assert_eq!(chunks.count_documents(doc! {}, None).await?, 0);
+
+        // Rehydrated by the download.
+        let bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(4)
+                    .tier_backend(Some(backend))
+                    .rehydrate_tiered(true)
+                    .build(),
+            ),
+        );
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert_eq!(cursor.next().await.unwrap()?, b"test");
+        assert_eq!(chunks.count_documents(doc! {}, None).await?, 3);
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! {"_id":id}, None)
+            .await?
+            .unwrap();
+        assert!(file.get(TIER_FIELD).is_none());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -1,16 +1,253 @@
-use crate::bucket::GridFSBucket;
-use crate::options::GridFSUploadOptions;
+#[cfg(any(feature = "md5", feature = "sha256"))]
+use crate::bucket::checksum::{upload_algorithms, Checksum};
+#[cfg(feature = "md5")]
+use crate::bucket::verify::{chunk_digest, verify_written_chunks};
+use crate::bucket::{
+    inline::INLINE_FIELD,
+    report::UploadDiagnostics,
+    reserve::reservation,
+    routing::{chunks_index_keys, CHUNKS_COLLECTION_FIELD},
+    status::STATUS_FIELD,
+    GridFSBucket,
+};
+#[cfg(any(feature = "md5", feature = "sha256"))]
+use crate::options::ChecksumAlgorithm;
+use crate::options::{GridFSUploadOptions, UploadProgress};
+use crate::{
+    encryption::{chunk_data_field, ENCRYPTION_KEY_FIELD},
+    file_info::get_number,
+    is_duplicate_key, is_duplicate_key_on,
+    slow_op::PhaseTimer,
+    GridFSError,
+};
 use bson::{doc, oid::ObjectId, DateTime, Document};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use bytes::Bytes;
 #[cfg(feature = "async-std-runtime")]
-use futures::io::{AsyncRead, AsyncReadExt};
-use md5::{Digest, Md5};
+use futures::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt};
+use futures_util::{
+    future::{select, Either},
+    stream::{FuturesUnordered, StreamExt},
+};
 use mongodb::{
     error::Error,
-    options::{FindOneOptions, InsertOneOptions, UpdateOptions},
+    options::{DeleteOptions, FindOneOptions, InsertOneOptions, ReplaceOptions, UpdateOptions},
     Collection,
 };
+use std::{
+    pin::{pin, Pin},
+    time::Instant,
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::{sync::mpsc, task::JoinHandle};
+
+/// The largest chunk size: with its other fields, a chunk document must fit in the 16MiB
+/// limit of a BSON document.
+const MAX_CHUNK_SIZE: u32 = 15 * 1024 * 1024;
+/// The largest `n` of a chunk: the spec stores it as an int32.
+const MAX_CHUNK_N: u32 = i32::MAX as u32;
+/// The number of frames the channel of [`GridFSBucket::channel_upload`] holds before the
+/// producers wait.
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+const CHANNEL_UPLOAD_FRAMES: usize = 16;
+
+/// Checks that @chunk_size is more than 0 and at most 15MiB, so a chunk fits in a document.
+pub(crate) fn check_chunk_size(chunk_size: u32) -> Result<(), GridFSError> {
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(GridFSError::InvalidChunkSize(chunk_size));
+    }
+    Ok(())
+}
+
+/// Fills @buffer with the bytes of @source.
+///
+/// A read may return less bytes than requested, so the source is read until the buffer is
+/// full or the source is exhausted. The returned size is only smaller than the buffer for
+/// the last chunk of the source, and is 0 once the source is exhausted.
+pub(crate) async fn read_chunk(
+    source: &mut (impl AsyncRead + Unpin),
+    buffer: &mut [u8],
+) -> std::io::Result<usize> {
+    let mut chunk_read_size = 0;
+    while chunk_read_size < buffer.len() {
+        let step_read_size = source.read(&mut buffer[chunk_read_size..]).await?;
+        if step_read_size == 0 {
+            break;
+        }
+        chunk_read_size += step_read_size;
+    }
+    Ok(chunk_read_size)
+}
+
+/// Whether the index @keys starts with @fields, in this order and ascending.
+fn is_ascending_index_on(keys: &Document, fields: &[&str]) -> bool {
+    keys.len() >= fields.len()
+        && keys
+            .keys()
+            .zip(fields)
+            .all(|(key, field)| key == field && get_number(keys, key) == Some(1))
+}
+
+/// A source of the chunks of an upload.
+pub(crate) trait ChunkSource {
+    /// Reads the next chunk of at most @size bytes. The chunk is only smaller than @size at
+    /// the end of the source, and is empty once the source is exhausted.
+    async fn next_chunk(&mut self, size: usize) -> std::io::Result<Vec<u8>>;
+
+    /// Called once the files collection document of the upload is written: from then on,
+    /// the upload owns a document with its id.
+    fn file_written(&mut self) {}
+}
+
+/// Serves the bytes read ahead of a source, then the rest of the source.
+struct HeadSource<S> {
+    head: Vec<u8>,
+    source: S,
+}
+
+impl<S: ChunkSource> ChunkSource for HeadSource<S> {
+    async fn next_chunk(&mut self, size: usize) -> std::io::Result<Vec<u8>> {
+        if self.head.is_empty() {
+            return self.source.next_chunk(size).await;
+        }
+        let mut chunk: Vec<u8> = self.head.drain(..size.min(self.head.len())).collect();
+        if chunk.len() < size {
+            chunk.extend(self.source.next_chunk(size - chunk.len()).await?);
+        }
+        Ok(chunk)
+    }
+
+    fn file_written(&mut self) {
+        self.source.file_written();
+    }
+}
+
+pub(crate) struct ReadSource<R>(pub(crate) R);
+
+impl<R: AsyncRead + Unpin> ChunkSource for ReadSource<R> {
+    async fn next_chunk(&mut self, size: usize) -> std::io::Result<Vec<u8>> {
+        let mut chunk = vec![0; size];
+        let chunk_read_size = read_chunk(&mut self.0, &mut chunk).await?;
+        chunk.truncate(chunk_read_size);
+        Ok(chunk)
+    }
+}
+
+/// Copies the chunks straight from the buffer of the reader, without an intermediate read.
+struct BufReadSource<R>(R);
+
+impl<R: AsyncBufRead + Unpin> ChunkSource for BufReadSource<R> {
+    async fn next_chunk(&mut self, size: usize) -> std::io::Result<Vec<u8>> {
+        let mut chunk = Vec::with_capacity(size);
+        while chunk.len() < size {
+            let buffer = self.0.fill_buf().await?;
+            if buffer.is_empty() {
+                break;
+            }
+            let consumed = buffer.len().min(size - chunk.len());
+            chunk.extend_from_slice(&buffer[..consumed]);
+            Pin::new(&mut self.0).consume(consumed);
+        }
+        Ok(chunk)
+    }
+}
+
+/// Cuts the chunks from the frames received on a channel. The source is exhausted once every
+/// sender is dropped.
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+struct ChannelSource {
+    receiver: mpsc::Receiver<Bytes>,
+    /// The part of the last frame not in a chunk yet.
+    pending: Bytes,
+}
+
 #[cfg(any(feature = "default", feature = "tokio-runtime"))]
-use tokio::io::{AsyncRead, AsyncReadExt};
+impl ChunkSource for ChannelSource {
+    async fn next_chunk(&mut self, size: usize) -> std::io::Result<Vec<u8>> {
+        let mut chunk = Vec::with_capacity(size);
+        while chunk.len() < size {
+            if self.pending.is_empty() {
+                match self.receiver.recv().await {
+                    Some(frame) => self.pending = frame,
+                    None => break,
+                }
+            }
+            let consumed = self.pending.len().min(size - chunk.len());
+            chunk.extend_from_slice(&self.pending.split_to(consumed));
+        }
+        Ok(chunk)
+    }
+}
+
+/// The checksums of the uploaded chunks.
+#[cfg(any(feature = "md5", feature = "sha256"))]
+enum ChunkDigest {
+    Inline(Vec<Checksum>),
+    /// Each chunk is hashed on the blocking thread pool while it's inserted.
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    Offloaded(JoinHandle<Vec<Checksum>>),
+}
+
+#[cfg(any(feature = "md5", feature = "sha256"))]
+fn update_all(checksums: &mut [Checksum], chunk: &[u8]) {
+    for checksum in checksums {
+        checksum.update(chunk);
+    }
+}
+
+#[cfg(any(feature = "md5", feature = "sha256"))]
+impl ChunkDigest {
+    /// The digest of the @algorithms checksums.
+    #[cfg_attr(feature = "async-std-runtime", allow(unused_variables))]
+    fn new(offload: bool, algorithms: &[ChecksumAlgorithm]) -> ChunkDigest {
+        let checksums = algorithms
+            .iter()
+            .copied()
+            .filter_map(Checksum::new)
+            .collect();
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        if offload {
+            return ChunkDigest::Offloaded(tokio::task::spawn_blocking(move || checksums));
+        }
+        ChunkDigest::Inline(checksums)
+    }
+
+    async fn update(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        match self {
+            ChunkDigest::Inline(checksums) => update_all(checksums, chunk),
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            ChunkDigest::Offloaded(handle) => {
+                let mut checksums = (&mut *handle).await.map_err(std::io::Error::other)?;
+                let chunk = chunk.to_vec();
+                *handle = tokio::task::spawn_blocking(move || {
+                    update_all(&mut checksums, &chunk);
+                    checksums
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The fields of the checksums in the files collection document.
+    async fn finalize(self) -> std::io::Result<Document> {
+        let checksums = match self {
+            ChunkDigest::Inline(checksums) => checksums,
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            ChunkDigest::Offloaded(handle) => handle.await.map_err(std::io::Error::other)?,
+        };
+        Ok(checksums
+            .into_iter()
+            .map(|checksum| {
+                (
+                    checksum.algorithm().field().to_string(),
+                    checksum.finalize().into(),
+                )
+            })
+            .collect())
+    }
+}
 
 impl GridFSBucket {
     async fn create_files_index(&self, collection_name: &str) -> Result<Document, Error> {
@@ -24,7 +261,11 @@ impl GridFSBucket {
                             "filename":1,
                             "uploadDate":1.0
                         },
-                        "name": collection_name.to_owned()+"_index",
+                        "name": self
+                            .options
+                            .as_ref()
+                            .and_then(|options| options.files_index_name.clone())
+                            .unwrap_or(collection_name.to_owned() + "_index"),
                 }]},
                 None,
             )
@@ -32,6 +273,43 @@ impl GridFSBucket {
     }
 
     async fn create_chunks_index(&self, collection_name: &str) -> Result<Document, Error> {
+        self.db
+            .run_command(
+                doc! {
+                "createIndexes": collection_name,
+                "indexes": [
+                    {
+                        "key": chunks_index_keys(self.chunk_shard_key()),
+                        "name": self
+                            .options
+                            .as_ref()
+                            .and_then(|options| options.chunks_index_name.clone())
+                            .unwrap_or(collection_name.to_owned() + "_index"),
+                }]},
+                None,
+            )
+            .await
+    }
+
+    async fn create_expire_index(&self, collection_name: &str) -> Result<Document, Error> {
+        self.db
+            .run_command(
+                doc! {
+                "createIndexes": collection_name,
+                "indexes": [
+                    {
+                        "key": {
+                            "expireAt":1
+                        },
+                        "name": collection_name.to_owned()+"_expire_index",
+                        "expireAfterSeconds": 0,
+                }]},
+                None,
+            )
+            .await
+    }
+
+    async fn create_unique_filename_index(&self, collection_name: &str) -> Result<Document, Error> {
         self.db
             .run_command(
                 doc! {
@@ -39,24 +317,74 @@ impl GridFSBucket {
                 "indexes": [
                     {
                         "key": {
-                             "files_id":1,
-                             "n":1
+                            "filename":1
                         },
-                        "name": collection_name.to_owned()+"_index",
+                        "name": collection_name.to_owned()+"_unique_filename_index",
+                        "unique": true,
+                        "partialFilterExpression": {"filename":{"$type":"string"}},
                 }]},
                 None,
             )
             .await
     }
 
+    /// Whether the collection @collection_name has an index starting with @fields, in this
+    /// order and ascending, whatever its name. Other drivers name the indexes differently
+    /// and write their directions as int32, int64 or double: their indexes are recognized,
+    /// so no duplicate is created.
+    pub(crate) async fn has_ascending_index(
+        &self,
+        collection_name: &str,
+        fields: &[&str],
+    ) -> Result<bool, GridFSError> {
+        let mut indexes = self
+            .db
+            .collection::<Document>(collection_name)
+            .list_indexes(None)
+            .await?;
+        while let Some(index) = indexes.next().await {
+            if is_ascending_index_on(&index?.keys, fields) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The chunk shard key of the bucket, if any.
+    pub(crate) fn chunk_shard_key(&self) -> Option<&str> {
+        self.options
+            .as_ref()
+            .and_then(|options| options.chunk_shard_key.as_deref())
+    }
+
+    /// The fields of the index of the chunks, in order.
+    pub(crate) fn chunks_index_fields(&self) -> Vec<&str> {
+        self.chunk_shard_key()
+            .into_iter()
+            .chain(["files_id", "n"])
+            .collect()
+    }
+
+    /// Ensure the chunks collection @collection_name, other than the one of the bucket, has
+    /// the index of the chunks. Checked before each upload or migration to the collection.
+    pub(crate) async fn ensure_chunks_index(&self, collection_name: &str) -> Result<(), GridFSError> {
+        if !self
+            .has_ascending_index(collection_name, &self.chunks_index_fields())
+            .await?
+        {
+            self.create_chunks_index(collection_name).await?;
+        }
+        Ok(())
+    }
+
     /// Ensure the index of fs.files collection is created before first write operation.
     /// [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#before-write-operations)
-    async fn ensure_file_index(
+    pub(crate) async fn ensure_file_index(
         &mut self,
         files: &Collection<Document>,
         file_collection: &str,
         chunk_collection: &str,
-    ) -> Result<(), Error> {
+    ) -> Result<(), GridFSError> {
         if self.never_write {
             if files
                 .find_one(
@@ -75,46 +403,12 @@ impl GridFSBucket {
                         .list_collection_names(doc! {"name":file_collection})
                         .await?;
                     if is_collection_exists.is_empty() {
-                        self.db
-                            .create_collection(&file_collection, None)
-                            .await?
+                        self.db.create_collection(&file_collection, None).await?
                     }
 
-                    let indexes = self
-                        .db
-                        .run_command(doc! {"listIndexes":file_collection}, None)
+                    let have_index = self
+                        .has_ascending_index(file_collection, &["filename", "uploadDate"])
                         .await?;
-                    let mut have_index = false;
-                    for index in indexes
-                        .get_document("cursor")
-                        .unwrap()
-                        .get_array("firstBatch")
-                        .unwrap()
-                    {
-                        let key = index.as_document().unwrap().get_document("key").unwrap();
-                        let filename = key.get_i32("filename");
-                        let upload_date = key.get_i32("uploadDate");
-                        let filename_f = key.get_f64("filename");
-                        let upload_date_f = key.get_f64("uploadDate");
-
-                        match (filename, upload_date, filename_f, upload_date_f) {
-                            (Ok(1), Ok(1), _, _) => {
-                                have_index = true;
-                            }
-                            (_, _, Ok(x), Ok(y))
-                                if (x - 1.0).abs() < 0.0001 && (y - 1.0).abs() < 0.0001 =>
-                            {
-                                have_index = true;
-                            }
-                            (Ok(1), _, _, Ok(x)) if (x - 1.0).abs() < 0.0001 => {
-                                have_index = true;
-                            }
-                            (_, Ok(1), Ok(x), _) if (x - 1.0).abs() < 0.0001 => {
-                                have_index = true;
-                            }
-                            _ => {}
-                        }
-                    }
                     if !have_index {
                         self.create_files_index(file_collection).await?;
                     }
@@ -125,58 +419,58 @@ impl GridFSBucket {
                         .list_collection_names(doc! {"name":chunk_collection})
                         .await?;
                     if is_collection_exists.is_empty() {
-                        self.db
-                            .create_collection(&chunk_collection, None)
-                            .await?
+                        self.db.create_collection(&chunk_collection, None).await?
                     }
 
-                    let indexes = self
-                        .db
-                        .run_command(doc! {"listIndexes":chunk_collection}, None)
+                    let have_index = self
+                        .has_ascending_index(chunk_collection, &self.chunks_index_fields())
                         .await?;
-                    let mut have_index = false;
-                    for index in indexes
-                        .get_document("cursor")
-                        .unwrap()
-                        .get_array("firstBatch")
-                        .unwrap()
-                    {
-                        let key = index.as_document().unwrap().get_document("key").unwrap();
-                        let files_id = key.get_i32("files_id");
-                        let n = key.get_i32("n");
-                        let files_id_f = key.get_f64("files_id");
-                        let n_f = key.get_f64("n");
-
-                        match (files_id, n, files_id_f, n_f) {
-                            (Ok(1), Ok(1), _, _) => {
-                                have_index = true;
-                            }
-                            (_, _, Ok(x), Ok(y))
-                                if (x - 1.0).abs() < 0.0001 && (y - 1.0).abs() < 0.0001 =>
-                            {
-                                have_index = true;
-                            }
-                            (Ok(1), _, _, Ok(x)) if (x - 1.0).abs() < 0.0001 => {
-                                have_index = true;
-                            }
-                            (_, Ok(1), Ok(x), _) if (x - 1.0).abs() < 0.0001 => {
-                                have_index = true;
-                            }
-                            _ => {}
-                        }
-                    }
                     if !have_index {
                         self.create_chunks_index(chunk_collection).await?;
                     }
                 }
             }
+            let expire_after = self
+                .options
+                .as_ref()
+                .and_then(|options| options.expire_after);
+            if expire_after.is_some() {
+                self.create_expire_index(file_collection).await?;
+                self.create_expire_index(chunk_collection).await?;
+            }
+            if self
+                .options
+                .as_ref()
+                .is_some_and(|options| options.unique_filenames)
+            {
+                self.create_unique_filename_index(file_collection).await?;
+            }
+            if let Some(options) = self
+                .options
+                .as_ref()
+                .filter(|options| !options.files_indexes.is_empty())
+            {
+                files
+                    .create_indexes(options.files_indexes.clone(), None)
+                    .await?;
+            }
+            #[cfg(feature = "content-search")]
+            if let Some(options) = self
+                .options
+                .as_ref()
+                .filter(|options| options.text_extractor.is_some())
+            {
+                let content_collection = options.bucket_name.clone() + ".content";
+                self.create_content_index(&content_collection).await?;
+            }
             self.never_write = false;
         }
         Ok(())
     }
 
     /**
-      Uploads a user file to a GridFS bucket. The driver generates the file id.
+      Uploads a user file to a GridFS bucket. The driver generates the file id, unless the
+      [`file_id`](GridFSUploadOptions::file_id) upload option is set.
 
       Reads the contents of the user file from the @source Stream and uploads it
       as chunks in the chunks collection. After all the chunks have been uploaded,
@@ -190,8 +484,8 @@ impl GridFSBucket {
       # Examples
        ```
        # use mongodb::Client;
-       # use mongodb::{error::Error, Database};
-       use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket};
+       # use mongodb::Database;
+       use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket, GridFSError};
        # use uuid::Uuid;
        #
        # fn db_name_new() -> String {
@@ -202,7 +496,7 @@ impl GridFSBucket {
        # }
        #
        # #[tokio::main]
-       # async fn main() -> Result<(), Error> {
+       # async fn main() -> Result<(), GridFSError> {
        #    let client = Client::with_uri_str(&std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string())).await?;
        #    let dbname = db_name_new();
        #    let db: Database = client.database(&dbname);
@@ -211,260 +505,1665 @@ impl GridFSBucket {
            .upload_from_stream("test.txt", "stream your data here".as_bytes(), None)
            .await?;
        #     println!("{}", id);
-       #     db.drop(None).await
+       #     db.drop(None).await?;
+       #     Ok(())
+       # }
+       ```
+
+      # Errors
+
+      Raise [`GridFSError::AlreadyExists`] when the [`file_id`](GridFSUploadOptions::file_id)
+      upload option is the id of a stored file.
+    */
+    pub async fn upload_from_stream(
+        &mut self,
+        filename: &str,
+        source: impl AsyncRead + Unpin,
+        options: Option<GridFSUploadOptions>,
+    ) -> Result<ObjectId, GridFSError> {
+        if let Some(id) = options.as_ref().and_then(|options| options.file_id) {
+            self.upload_from_stream_with_id(id, filename, source, options)
+                .await?;
+            return Ok(id);
+        }
+        self.upload_chunks(None, filename, ReadSource(source), options)
+            .await
+    }
+
+    /**
+      Uploads a user file to a GridFS bucket with the application supplied @id.
+
+      Behaves like [`GridFSBucket::upload_from_stream`], but the files collection document
+      gets @id instead of a generated id. Completes the reservation of @id made by
+      [`GridFSBucket::reserve_id`], if any.
+      [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#file-upload)
+
+      # Errors
+
+      Raise [`GridFSError::AlreadyExists`] when a stored file already has the @id, or the
+      @filename when [`GridFSBucketOptions::unique_filenames`](crate::options::GridFSBucketOptions::unique_filenames)
+      is set.
+    */
+    pub async fn upload_from_stream_with_id(
+        &mut self,
+        id: ObjectId,
+        filename: &str,
+        source: impl AsyncRead + Unpin,
+        options: Option<GridFSUploadOptions>,
+    ) -> Result<(), GridFSError> {
+        self.upload_chunks_with_id(id, filename, ReadSource(source), options)
+            .await
+    }
+
+    /// Uploads the chunks of @source as the file @id, see
+    /// [`GridFSBucket::upload_from_stream_with_id`].
+    pub(crate) async fn upload_chunks_with_id(
+        &mut self,
+        id: ObjectId,
+        filename: &str,
+        source: impl ChunkSource,
+        options: Option<GridFSUploadOptions>,
+    ) -> Result<(), GridFSError> {
+        match self
+            .upload_chunks(Some(id), filename, source, options)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(GridFSError::MongoError(error)) if is_duplicate_key(&error) => {
+                let on_filename = match is_duplicate_key_on(&error, "filename") {
+                    Some(on_filename) => on_filename,
+                    // Not reported by the server: the filename is taken when the id is free.
+                    None => {
+                        self.files_collection()
+                            .count_documents(doc! {"_id":id}, None)
+                            .await?
+                            == 0
+                    }
+                };
+                Err(match on_filename {
+                    true => GridFSError::AlreadyExists {
+                        id: None,
+                        filename: Some(filename.to_string()),
+                    },
+                    false => GridFSError::AlreadyExists {
+                        id: Some(id),
+                        filename: None,
+                    },
+                })
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /**
+      Uploads a user file to a GridFS bucket from a buffered @source. The driver generates
+      the file id.
+
+      Behaves like [`GridFSBucket::upload_from_stream`], but the chunks are filled from the
+      buffer of the reader with `fill_buf`/`consume` instead of being read into an
+      intermediate slice. Prefer it for sources which are already buffered, like a
+      `BufReader` over a local file.
+
+      Returns the id of the uploaded file.
+      # Examples
+       ```
+       # use mongodb::Client;
+       # use mongodb::Database;
+       use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket, GridFSError};
+       # use uuid::Uuid;
+       #
+       # fn db_name_new() -> String {
+       #     "test_".to_owned()
+       #         + Uuid::new_v4()
+       #             .hyphenated()
+       #             .encode_lower(&mut Uuid::encode_buffer())
+       # }
+       #
+       # #[tokio::main]
+       # async fn main() -> Result<(), GridFSError> {
+       #    let client = Client::with_uri_str(&std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string())).await?;
+       #    let dbname = db_name_new();
+       #    let db: Database = client.database(&dbname);
+       let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+       let id = bucket
+           .upload_from_buf_reader("test.txt", "stream your data here".as_bytes(), None)
+           .await?;
+       #     println!("{}", id);
+       #     db.drop(None).await?;
+       #     Ok(())
        # }
        ```
     */
-    pub async fn upload_from_stream<'a>(
+    pub async fn upload_from_buf_reader(
+        &mut self,
+        filename: &str,
+        source: impl AsyncBufRead + Unpin,
+        options: Option<GridFSUploadOptions>,
+    ) -> Result<ObjectId, GridFSError> {
+        self.upload_chunks(None, filename, BufReadSource(source), options)
+            .await
+    }
+
+    /**
+    Uploads a file from the byte frames sent on the returned channel, in a background task.
+    Requires a tokio runtime.
+
+    The upload is committed once every [`mpsc::Sender`] is dropped: the returned handle then
+    resolves to the id of the file. The channel is bounded: the producers wait while the
+    upload is behind.
+
+    # Examples
+
+    ```rust,no_run
+    # use mongodb::Client;
+    use bytes::Bytes;
+    use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket, GridFSError};
+    #
+    # #[tokio::main]
+    # async fn main() -> Result<(), GridFSError> {
+    #     let client = Client::with_uri_str("mongodb://localhost:27017/").await?;
+    #     let db = client.database("test");
+    let bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+    let (sender, upload) = bucket.channel_upload("test.txt", None);
+    tokio::spawn(async move {
+        for frame in ["stream ", "your ", "data"] {
+            sender.send(Bytes::from(frame)).await.unwrap();
+        }
+    });
+    let id = upload.await.unwrap()?;
+    #     println!("{}", id);
+    #     Ok(())
+    # }
+    ```
+
+    # Errors
+
+    The handle resolves to the errors of [`GridFSBucket::upload_from_stream`].
+    */
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub fn channel_upload(
+        &self,
+        filename: &str,
+        options: Option<GridFSUploadOptions>,
+    ) -> (
+        mpsc::Sender<Bytes>,
+        JoinHandle<Result<ObjectId, GridFSError>>,
+    ) {
+        let (sender, receiver) = mpsc::channel(CHANNEL_UPLOAD_FRAMES);
+        let mut bucket = self.clone();
+        let filename = filename.to_string();
+        let upload = tokio::spawn(async move {
+            let source = ChannelSource {
+                receiver,
+                pending: Bytes::new(),
+            };
+            bucket.upload_chunks(None, &filename, source, options).await
+        });
+        (sender, upload)
+    }
+
+    /// Uploads the chunks of @source, counted by [`GridFSBucket::op_stats`].
+    async fn upload_chunks(
+        &mut self,
+        id: Option<ObjectId>,
+        filename: &str,
+        source: impl ChunkSource,
+        options: Option<GridFSUploadOptions>,
+    ) -> Result<ObjectId, GridFSError> {
+        self.upload_chunks_with_diagnostics(id, filename, source, options)
+            .await
+            .map(|(files_id, _, _)| files_id)
+    }
+
+    /// Uploads the chunks of @source like [`GridFSBucket::upload_chunks`]. Returns the id and
+    /// the length of the file, and its diagnostics when [`GridFSUploadOptions::diagnostics`]
+    /// is set.
+    pub(crate) async fn upload_chunks_with_diagnostics(
+        &mut self,
+        id: Option<ObjectId>,
+        filename: &str,
+        source: impl ChunkSource,
+        options: Option<GridFSUploadOptions>,
+    ) -> Result<(ObjectId, u64, Option<UploadDiagnostics>), GridFSError> {
+        let mut operation = self.operations.start();
+        match self.write_chunks(id, filename, source, options).await {
+            Ok((files_id, length, diagnostics)) => {
+                operation.bytes_in(length);
+                Ok((files_id, length, diagnostics))
+            }
+            Err(error) => {
+                operation.fail();
+                Err(error)
+            }
+        }
+    }
+
+    /// Uploads the chunks of @source. Returns the id and the length of the file, and its
+    /// diagnostics when requested by @options.
+    async fn write_chunks(
         &mut self,
+        id: Option<ObjectId>,
         filename: &str,
-        mut source: impl AsyncRead + Unpin,
+        source: impl ChunkSource,
         options: Option<GridFSUploadOptions>,
-    ) -> Result<ObjectId, Error> {
+    ) -> Result<(ObjectId, u64, Option<UploadDiagnostics>), GridFSError> {
+        let mut timer = PhaseTimer::start();
+        let filename = self.checked_filename(filename)?;
         let dboptions = self.options.clone().unwrap_or_default();
         let mut chunk_size: u32 = dboptions.chunk_size_bytes;
-        let bucket_name = dboptions.bucket_name;
+        let max_in_flight = dboptions.in_flight_chunks();
+        let bucket_name = dboptions.bucket_name.clone();
         let file_collection = bucket_name.clone() + ".files";
-        let disable_md5 = dboptions.disable_md5;
+        #[cfg(any(feature = "md5", feature = "sha256"))]
+        let checksum_algorithms = upload_algorithms(&dboptions);
         let chunk_collection = bucket_name + ".chunks";
         let mut progress_tick = None;
+        let mut size_hint = None;
+        let mut expected_length = None;
+        let mut routed_collection = None;
+        let mut shard_key_value = None;
+        let mut upload_date = None;
+        let mut diagnostics = None;
+        let mut id = id;
         if let Some(options) = options.clone() {
             if let Some(chunk_size_bytes) = options.chunk_size_bytes {
                 chunk_size = chunk_size_bytes;
             }
             progress_tick = options.progress_tick;
+            size_hint = options.size_hint;
+            if options.strict_length {
+                expected_length = options.size_hint;
+            }
+            routed_collection = options.chunks_collection;
+            shard_key_value = options.shard_key_value;
+            upload_date = options.upload_date;
+            diagnostics = options.diagnostics.then(UploadDiagnostics::default);
+            id = id.or(options.file_id);
         }
+        check_chunk_size(chunk_size)?;
         let files = self.db.collection(&file_collection);
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        let mut slot = self.qos.acquire(self.priority).await?;
+        let _writer = self.write_guard().await;
+        timer.phase("queue");
 
         self.ensure_file_index(&files, &file_collection, &chunk_collection)
             .await?;
+        if let Some(routed_collection) = &routed_collection {
+            self.ensure_chunks_index(routed_collection).await?;
+        }
+        timer.phase("index check");
 
-        let mut file_document = doc! {"filename":filename,
+        let mut inspection = dboptions
+            .content_inspector
+            .as_ref()
+            .map(|inspector| inspector.start(&filename));
+        #[cfg(feature = "content-search")]
+        let mut extraction = dboptions
+            .text_extractor
+            .as_ref()
+            .and_then(|extractor| extractor.start(&filename));
+        let mut file_document = doc! {"filename":filename.clone(),
         "chunkSize":chunk_size};
+        if let Some(id) = id {
+            file_document.insert("_id", id);
+        }
+        let expire_at = dboptions.expire_after.map(|expire_after| {
+            DateTime::from_system_time(self.now().to_system_time() + expire_after)
+        });
+        if let Some(expire_at) = expire_at {
+            file_document.insert("expireAt", expire_at);
+        }
+        if let Some(encryption) = &dboptions.chunk_encryption {
+            file_document.insert(ENCRYPTION_KEY_FIELD, encryption.key_id.clone());
+        }
+        if let Some(routed_collection) = &routed_collection {
+            file_document.insert(CHUNKS_COLLECTION_FIELD, routed_collection);
+        }
+        let shard_key = dboptions.chunk_shard_key.clone().zip(shard_key_value);
+        if let Some((shard_key, value)) = &shard_key {
+            file_document.insert(shard_key, value.clone());
+        }
         if let Some(options) = options {
             if let Some(metadata) = options.metadata {
                 file_document.insert("metadata", metadata);
             }
+            if let Some(status) = options.status {
+                file_document.insert(STATUS_FIELD, status.as_str());
+            }
         }
-        let mut insert_option = InsertOneOptions::default();
-        if let Some(write_concern) = dboptions.write_concern.clone() {
-            insert_option.write_concern = Some(write_concern);
+        #[cfg(feature = "otel")]
+        {
+            let mut metadata = file_document
+                .get_document("metadata")
+                .cloned()
+                .unwrap_or_default();
+            crate::otel::inject_current(&mut metadata);
+            if !metadata.is_empty() {
+                file_document.insert("metadata", metadata);
+            }
         }
-        let insert_file_result = files
-            .insert_one(file_document, Some(insert_option.clone()))
-            .await?;
-
-        let files_id = insert_file_result.inserted_id.as_object_id().unwrap();
 
-        let mut md5 = Md5::default();
-        let chunks = self.db.collection(&chunk_collection);
-        let mut vecbuf: Vec<u8> = vec![0; chunk_size as usize];
-        let mut length: usize = 0;
-        let mut n: u32 = 0;
-        loop {
-            let chunk_read_size = {
-                let mut chunk_read_size = 0;
-                loop {
-                    let buffer = &mut vecbuf[chunk_read_size..];
-                    let step_read_size = source.read(buffer).await?;
-                    if step_read_size == 0 {
-                        break;
-                    }
-                    chunk_read_size += step_read_size;
+        // A file of at most one chunk under the inline threshold is stored in its files
+        // collection document, complete: the file is written in one round trip.
+        let mut source = HeadSource {
+            head: vec![],
+            source,
+        };
+        let inlined = match dboptions.inline_threshold {
+            Some(threshold) => {
+                let threshold = threshold.min(chunk_size) as usize;
+                let head = source
+                    .source
+                    .next_chunk(threshold + 1)
+                    .await
+                    .map_err(Error::from)?;
+                if head.len() <= threshold {
+                    Some(head)
+                } else {
+                    source.head = head;
+                    None
                 }
-                if chunk_read_size == 0 {
-                    break;
+            }
+            None => None,
+        };
+        let inlined_length = inlined.as_ref().map(|data| data.len() as u64);
+        if let (Some(expected), Some(actual)) = (expected_length, inlined_length) {
+            if actual != expected {
+                return Err(GridFSError::LengthMismatch { expected, actual });
+            }
+        }
+        if let Some(data) = inlined {
+            if let Some(mut inspection) = inspection.take() {
+                if !data.is_empty() {
+                    inspection
+                        .inspect(&data)
+                        .await
+                        .map_err(|reason| GridFSError::ContentRejected { reason })?;
                 }
-                chunk_read_size
-            };
-            let bin: Vec<u8> = Vec::from(&vecbuf[..chunk_read_size]);
-            md5.update(&bin);
-            chunks
-                .insert_one(
-                    doc! {"files_id":files_id,
-                    "n":n,
-                    "data": bson::Binary{subtype: bson::spec::BinarySubtype::Generic, bytes:bin}},
-                    Some(insert_option.clone()),
+                inspection
+                    .finish()
+                    .await
+                    .map_err(|reason| GridFSError::ContentRejected { reason })?;
+            }
+            #[cfg(feature = "content-search")]
+            if let Some(extraction) = extraction.as_mut() {
+                extraction.update(&data);
+            }
+            file_document.insert("length", data.len() as i64);
+            file_document.insert("uploadDate", upload_date.unwrap_or_else(|| self.now()));
+            #[cfg(any(feature = "md5", feature = "sha256"))]
+            {
+                let mut digest = ChunkDigest::new(false, &checksum_algorithms);
+                digest.update(&data).await.map_err(Error::from)?;
+                file_document.extend(digest.finalize().await.map_err(Error::from)?);
+            }
+            if !data.is_empty() {
+                let data = chunk_data_field(
+                    dboptions.chunk_encryption.as_ref(),
+                    dboptions.chunk_binary_subtype.into(),
+                    data,
                 )
                 .await?;
-            length += chunk_read_size;
+                file_document.insert(INLINE_FIELD, data);
+            }
+        }
+
+        let mut insert_option = InsertOneOptions::default();
+        if let Some(write_concern) = dboptions.write_concern_of_files() {
+            insert_option.write_concern = Some(write_concern);
+        }
+        let chunk_insert_option = InsertOneOptions::builder()
+            .write_concern(dboptions.write_concern_of_chunks())
+            .build();
+        // An upload with the id of a reservation completes it.
+        let reserved = match id {
+            Some(id) => {
+                let replace_options = ReplaceOptions::builder()
+                    .write_concern(dboptions.write_concern_of_files())
+                    .build();
+                files
+                    .replace_one(reservation(id), &file_document, replace_options)
+                    .await?
+                    .matched_count
+                    == 1
+            }
+            None => false,
+        };
+        let files_id = match id {
+            Some(id) if reserved => id,
+            _ => files
+                .insert_one(file_document, Some(insert_option))
+                .await?
+                .inserted_id
+                .as_object_id()
+                .unwrap(),
+        };
+        timer.phase("files insert");
+        source.file_written();
+        if let Some(length) = inlined_length {
+            #[cfg(feature = "content-search")]
+            if let Some(text) = extraction.and_then(|extraction| extraction.finish()) {
+                self.store_content(files_id, text, dboptions.write_concern.clone())
+                    .await?;
+            }
+            timer.phase("finalize");
+            if let Some(diagnostics) = diagnostics.as_mut() {
+                diagnostics.phases = timer.phases().to_vec();
+            }
+            timer.finish(&dboptions, "upload", files_id, &filename, length);
+            return Ok((files_id, length, diagnostics));
+        }
+
+        #[cfg(all(
+            any(feature = "md5", feature = "sha256"),
+            any(feature = "default", feature = "tokio-runtime")
+        ))]
+        let offload_digest = dboptions.offload_digest;
+        #[cfg(all(
+            any(feature = "md5", feature = "sha256"),
+            feature = "async-std-runtime"
+        ))]
+        let offload_digest = false;
+        #[cfg(any(feature = "md5", feature = "sha256"))]
+        let mut digest = (!checksum_algorithms.is_empty())
+            .then(|| ChunkDigest::new(offload_digest, &checksum_algorithms));
+        let chunks = self
+            .db
+            .collection(routed_collection.as_ref().unwrap_or(&chunk_collection));
+        let chunk_binary_subtype = dboptions.chunk_binary_subtype;
+        #[cfg(feature = "md5")]
+        let mut written_digests = dboptions.verify_on_write.as_ref().map(|_| vec![]);
+        let mut rejection = None;
+        let mut length: u64 = 0;
+        let mut bytes_read: u64 = 0;
+        let mut chunks_done: u64 = 0;
+        let report_progress = |length: u64, chunks_done: u64| {
+            if let Some(progress_tick) = &progress_tick {
+                progress_tick.update(length as usize);
+                progress_tick.progress(UploadProgress::new(
+                    length,
+                    chunks_done,
+                    size_hint,
+                    chunk_size,
+                ));
+            }
+        };
+        let mut n: u32 = 0;
+        // Inserts are driven while the next chunk is read. The reader waits for a slot
+        // when the queue is full, so a slow cluster slows the upload down instead of
+        // buffering the source in memory.
+        let mut in_flight = FuturesUnordered::new();
+        loop {
+            let read_started = Instant::now();
+            let bin = {
+                let mut read = pin!(source.next_chunk(chunk_size as usize));
+                loop {
+                    if in_flight.is_empty() {
+                        break read.await.map_err(Error::from)?;
+                    }
+                    match select(read.as_mut(), in_flight.next()).await {
+                        Either::Left((bin, _)) => break bin.map_err(Error::from)?,
+                        Either::Right((inserted, _)) => {
+                            if let Some(inserted) = inserted {
+                                let (size, latency) = inserted?;
+                                length += size as u64;
+                                if let Some(diagnostics) = diagnostics.as_mut() {
+                                    diagnostics.insert_latency.record(latency);
+                                }
+                            }
+                            chunks_done += 1;
+                            report_progress(length, chunks_done);
+                        }
+                    }
+                }
+            };
+            if let Some(diagnostics) = diagnostics.as_mut() {
+                diagnostics.read_wait += read_started.elapsed();
+            }
+            if bin.is_empty() {
+                break;
+            }
+            let chunk_read_size = bin.len();
+            bytes_read += chunk_read_size as u64;
+            if let Some(expected) = expected_length.filter(|expected| bytes_read > *expected) {
+                rejection = Some(GridFSError::LengthMismatch {
+                    expected,
+                    actual: bytes_read,
+                });
+                break;
+            }
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            if slot.is_aborted() {
+                rejection = Some(GridFSError::ShuttingDown());
+                break;
+            }
+            if let Some(inspection) = inspection.as_mut() {
+                if let Err(reason) = inspection.inspect(&bin).await {
+                    rejection = Some(GridFSError::ContentRejected { reason });
+                    break;
+                }
+            }
+            if n > MAX_CHUNK_N {
+                rejection = Some(GridFSError::FileTooLarge {
+                    max_length: (MAX_CHUNK_N as u64 + 1) * chunk_size as u64,
+                });
+                break;
+            }
+            #[cfg(feature = "content-search")]
+            if let Some(extraction) = extraction.as_mut() {
+                extraction.update(&bin);
+            }
+            #[cfg(any(feature = "md5", feature = "sha256"))]
+            if let Some(digest) = digest.as_mut() {
+                digest.update(&bin).await.map_err(Error::from)?;
+            }
+            #[cfg(feature = "md5")]
+            if let Some(written_digests) = written_digests.as_mut() {
+                written_digests.push(chunk_digest(&bin));
+            }
+            #[cfg(feature = "test-util")]
+            if let Some(chaos) = &self.chaos {
+                chaos.delay().await;
+                chaos.inject_chunk_insert_failure(n)?;
+            }
+            let mut chunk = Document::new();
+            if let Some((shard_key, value)) = &shard_key {
+                chunk.insert(shard_key, value.clone());
+            }
+            chunk.insert("files_id", files_id);
+            chunk.insert("n", n);
+            match chunk_data_field(
+                dboptions.chunk_encryption.as_ref(),
+                chunk_binary_subtype.into(),
+                bin,
+            )
+            .await
+            {
+                Ok(data) => chunk.insert("data", data),
+                Err(error) => {
+                    rejection = Some(error);
+                    break;
+                }
+            };
+            if let Some(expire_at) = expire_at {
+                chunk.insert("expireAt", expire_at);
+            }
+            let insert = chunks.insert_one(chunk, chunk_insert_option.clone());
+            in_flight.push(async move {
+                let started = Instant::now();
+                insert.await.map(|_| (chunk_read_size, started.elapsed()))
+            });
             n += 1;
             if let Some(ref progress_tick) = progress_tick {
-                progress_tick.update(length);
+                progress_tick.queue_depth(in_flight.len());
             };
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            if let Some(delay) = slot.delay(chunk_read_size) {
+                tokio::time::sleep(delay).await;
+            }
+            let write_started = Instant::now();
+            while in_flight.len() >= max_in_flight {
+                if let Some(inserted) = in_flight.next().await {
+                    let (size, latency) = inserted?;
+                    length += size as u64;
+                    if let Some(diagnostics) = diagnostics.as_mut() {
+                        diagnostics.insert_latency.record(latency);
+                    }
+                    chunks_done += 1;
+                    report_progress(length, chunks_done);
+                }
+            }
+            if let Some(diagnostics) = diagnostics.as_mut() {
+                diagnostics.write_wait += write_started.elapsed();
+            }
+        }
+        if let (None, Some(expected)) = (&rejection, expected_length) {
+            if bytes_read != expected {
+                rejection = Some(GridFSError::LengthMismatch {
+                    expected,
+                    actual: bytes_read,
+                });
+            }
+        }
+        if let (None, Some(inspection)) = (&rejection, inspection) {
+            rejection = inspection
+                .finish()
+                .await
+                .err()
+                .map(|reason| GridFSError::ContentRejected { reason });
+        }
+        if rejection.is_none() {
+            let write_started = Instant::now();
+            while let Some(inserted) = in_flight.next().await {
+                let (size, latency) = inserted?;
+                length += size as u64;
+                if let Some(diagnostics) = diagnostics.as_mut() {
+                    diagnostics.insert_latency.record(latency);
+                }
+                chunks_done += 1;
+                report_progress(length, chunks_done);
+            }
+            if let Some(diagnostics) = diagnostics.as_mut() {
+                diagnostics.write_wait += write_started.elapsed();
+            }
+            #[cfg(feature = "md5")]
+            if let (Some(verification), Some(written_digests)) =
+                (&dboptions.verify_on_write, &written_digests)
+            {
+                let mut filter = doc! {"files_id":files_id};
+                if let Some((shard_key, value)) = &shard_key {
+                    filter.insert(shard_key, value.clone());
+                }
+                rejection =
+                    verify_written_chunks(&chunks, filter, verification, length, written_digests)
+                        .await
+                        .err();
+            }
+        }
+        if let Some(error) = rejection {
+            // The file isn't committed yet: it is removed with the chunks already written.
+            while in_flight.next().await.is_some() {}
+            let delete_options = DeleteOptions::builder()
+                .write_concern(dboptions.write_concern_of_chunks())
+                .build();
+            chunks
+                .delete_many(doc! {"files_id":files_id}, delete_options)
+                .await?;
+            let delete_options = DeleteOptions::builder()
+                .write_concern(dboptions.write_concern_of_files())
+                .build();
+            files
+                .delete_one(doc! {"_id":files_id}, delete_options)
+                .await?;
+            return Err(error);
+        }
+        timer.phase("chunk writes");
+
+        #[cfg_attr(not(any(feature = "md5", feature = "sha256")), allow(unused_mut))]
+        let mut update = doc! {
+            "length": length as i64,
+            "uploadDate": upload_date.unwrap_or_else(|| self.now()),
+        };
+        #[cfg(any(feature = "md5", feature = "sha256"))]
+        if let Some(digest) = digest {
+            update.extend(digest.finalize().await.map_err(Error::from)?);
+        }
+        let mut update_option = UpdateOptions::default();
+        if let Some(write_concern) = dboptions.write_concern_of_files() {
+            update_option.write_concern = Some(write_concern);
+        }
+        files
+            .update_one(
+                doc! {"_id":files_id},
+                doc! {"$set":update},
+                Some(update_option),
+            )
+            .await?;
+
+        #[cfg(feature = "content-search")]
+        if let Some(text) = extraction.and_then(|extraction| extraction.finish()) {
+            self.store_content(files_id, text, dboptions.write_concern.clone())
+                .await?;
+        }
+        timer.phase("finalize");
+        if let Some(diagnostics) = diagnostics.as_mut() {
+            diagnostics.phases = timer.phases().to_vec();
+        }
+        timer.finish(&dboptions, "upload", files_id, &filename, length);
+
+        Ok((files_id, length, diagnostics))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use super::read_chunk;
+    use super::{check_chunk_size, is_ascending_index_on, GridFSBucket, MAX_CHUNK_SIZE};
+    use crate::{
+        clock::Clock,
+        inspector::{ContentInspection, ContentInspector},
+        options::{GridFSBucketOptions, GridFSUploadOptions, ProgressUpdate, UploadProgress},
+        GridFSError, GridFSErrorCode,
+    };
+    use bson::{doc, oid::ObjectId, DateTime, Document};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use bytes::Bytes;
+    #[cfg(feature = "async-std-runtime")]
+    use futures::StreamExt;
+    use futures_util::future::BoxFuture;
+    use mongodb::{error::Error, options::IndexOptions, Client, Database, IndexModel};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use proptest::prelude::*;
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use std::io::Write;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+        time::Duration,
+    };
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio::io::{AsyncRead, ReadBuf};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    fn generate_large_text(size: usize) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(size);
+        for i in 0..size {
+            buffer.push((i % 8) as u8);
+        }
+        buffer
+    }
+
+    #[tokio::test]
+    async fn upload_from_stream() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        assert_eq!(id.to_hex(), id.to_hex());
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_str("filename").unwrap(), "test.txt");
+        assert_eq!(file.get_i32("chunkSize").unwrap(), 261120);
+        assert_eq!(file.get_i64("length").unwrap(), 9);
+        assert_eq!(
+            file.get_str("md5").unwrap(),
+            "eb733a00c0c9d336e65691a37ab54293"
+        );
+
+        let chunks: Vec<Result<Document, Error>> = db
+            .collection("fs.chunks")
+            .find(doc! { "files_id": id }, None)
+            .await?
+            .collect()
+            .await;
+
+        assert_eq!(chunks[0].as_ref().unwrap().get_i32("n").unwrap(), 0);
+        assert_eq!(
+            chunks[0]
+                .as_ref()
+                .unwrap()
+                .get_binary_generic("data")
+                .unwrap(),
+            &vec![116_u8, 101, 115, 116, 32, 100, 97, 116, 97]
+        );
+
+        db.drop(None).await?;
+        Ok(())
+        //Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_from_stream_chunk_size() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(8).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data 1234567890".as_bytes(), None)
+            .await?;
+
+        assert_eq!(id.to_hex(), id.to_hex());
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_str("filename").unwrap(), "test.txt");
+        assert_eq!(file.get_i32("chunkSize").unwrap(), 8);
+        assert_eq!(file.get_i64("length").unwrap(), 20);
+        assert_eq!(
+            file.get_str("md5").unwrap(),
+            "5e75d6271a7cfc3d9b79116be261eb21"
+        );
+
+        let chunks: Vec<Result<Document, Error>> = db
+            .collection::<Document>("fs.chunks")
+            .find(doc! { "files_id": id }, None)
+            .await?
+            .collect()
+            .await;
+
+        assert_eq!(chunks[0].as_ref().unwrap().get_i32("n").unwrap(), 0);
+        assert_eq!(
+            chunks[0]
+                .as_ref()
+                .unwrap()
+                .get_binary_generic("data")
+                .unwrap(),
+            &vec![116_u8, 101, 115, 116, 32, 100, 97, 116]
+        );
+
+        assert_eq!(chunks[1].as_ref().unwrap().get_i32("n").unwrap(), 1);
+        assert_eq!(
+            chunks[1]
+                .as_ref()
+                .unwrap()
+                .get_binary_generic("data")
+                .unwrap(),
+            &vec![97_u8, 32, 49, 50, 51, 52, 53, 54]
+        );
+
+        assert_eq!(chunks[2].as_ref().unwrap().get_i32("n").unwrap(), 2);
+        assert_eq!(
+            chunks[2]
+                .as_ref()
+                .unwrap()
+                .get_binary_generic("data")
+                .unwrap(),
+            &vec![55_u8, 56, 57, 48]
+        );
+
+        db.drop(None).await?;
+        Ok(())
+        // Ok(())
+    }
+
+    #[test]
+    fn chunk_size_bounds() {
+        assert!(check_chunk_size(1).is_ok());
+        assert!(check_chunk_size(MAX_CHUNK_SIZE).is_ok());
+        for chunk_size in [0, MAX_CHUNK_SIZE + 1, u32::MAX] {
+            assert!(matches!(
+                check_chunk_size(chunk_size),
+                Err(GridFSError::InvalidChunkSize(size)) if size == chunk_size
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_invalid_chunk_size() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(0).build()),
+        );
+
+        // Checked before any write.
+        assert!(matches!(
+            bucket
+                .upload_from_stream("test.txt", "test data".as_bytes(), None)
+                .await,
+            Err(GridFSError::InvalidChunkSize(0))
+        ));
+        assert!(matches!(
+            bucket
+                .upload_from_stream(
+                    "test.txt",
+                    "test data".as_bytes(),
+                    Some(
+                        GridFSUploadOptions::builder()
+                            .chunk_size_bytes(Some(16 * 1024 * 1024))
+                            .build()
+                    ),
+                )
+                .await,
+            Err(GridFSError::InvalidChunkSize(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn ascending_index_on() {
+        let fields = ["filename", "uploadDate"];
+        assert!(is_ascending_index_on(
+            &doc! {"filename":1, "uploadDate":1},
+            &fields
+        ));
+        assert!(is_ascending_index_on(
+            &doc! {"filename":1_i64, "uploadDate":1.0, "metadata.tenant":1},
+            &fields
+        ));
+        assert!(!is_ascending_index_on(
+            &doc! {"uploadDate":1, "filename":1},
+            &fields
+        ));
+        assert!(!is_ascending_index_on(
+            &doc! {"filename":1, "uploadDate":-1},
+            &fields
+        ));
+        assert!(!is_ascending_index_on(&doc! {"filename":1}, &fields));
+    }
+
+    #[tokio::test]
+    async fn upload_other_driver_indexes() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        for (collection, keys, name) in [
+            (
+                "fs.files",
+                doc! {"filename":1_i64, "uploadDate":1_i64},
+                "filename_1_uploadDate_1",
+            ),
+            (
+                "fs.chunks",
+                doc! {"files_id":1.0, "n":1.0},
+                "files_id_1_n_1",
+            ),
+        ] {
+            db.run_command(
+                doc! {"createIndexes":collection, "indexes":[{"key":keys, "name":name}]},
+                None,
+            )
+            .await?;
+        }
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        for collection in ["fs.files", "fs.chunks"] {
+            let index_names = db
+                .collection::<Document>(collection)
+                .list_index_names()
+                .await?;
+            assert_eq!(index_names.len(), 2, "{:?}", index_names);
         }
 
-        let mut update = doc! { "length": length as i64, "uploadDate": DateTime::now() };
-        if !disable_md5 {
-            update.insert("md5", format!("{:02x}", md5.finalize()));
-        }
-        let mut update_option = UpdateOptions::default();
-        if let Some(write_concern) = dboptions.write_concern {
-            update_option.write_concern = Some(write_concern);
-        }
-        files
-            .update_one(
-                doc! {"_id":files_id},
-                doc! {"$set":update},
-                Some(update_option),
-            )
-            .await?;
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_custom_indexes() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .files_index_name(Some("files_by_name".into()))
+                    .chunks_index_name(Some("chunks_by_file".into()))
+                    .files_indexes(vec![
+                        IndexModel::builder()
+                            .keys(doc! {"metadata.tenant":1})
+                            .build(),
+                        IndexModel::builder()
+                            .keys(doc! {"uploadDate":-1})
+                            .options(IndexOptions::builder().name("recent".to_string()).build())
+                            .build(),
+                    ])
+                    .build(),
+            ),
+        );
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let files_indexes = db
+            .collection::<Document>("fs.files")
+            .list_index_names()
+            .await?;
+        for name in ["files_by_name", "metadata.tenant_1", "recent"] {
+            assert!(files_indexes.contains(&name.to_string()), "{}", name);
+        }
+        let chunks_indexes = db
+            .collection::<Document>("fs.chunks")
+            .list_index_names()
+            .await?;
+        assert!(chunks_indexes.contains(&"chunks_by_file".to_string()));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_unique_filenames() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .unique_filenames(true)
+                    .build(),
+            ),
+        );
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        bucket
+            .upload_from_stream("other.txt", "other data".as_bytes(), None)
+            .await?;
+
+        let result = bucket
+            .upload_from_stream("test.txt", "new data".as_bytes(), None)
+            .await;
+        assert!(matches!(
+            result.map_err(|error| error.code()),
+            Err(GridFSErrorCode::DuplicateKey)
+        ));
+        let result = bucket
+            .upload_from_stream_with_id(ObjectId::new(), "test.txt", "new data".as_bytes(), None)
+            .await;
+        assert!(matches!(
+            result,
+            Err(GridFSError::AlreadyExists { id: None, filename: Some(filename) }) if filename == "test.txt"
+        ));
+        assert_eq!(
+            db.collection::<Document>("fs.files")
+                .count_documents(doc! {"filename":"test.txt"}, None)
+                .await?,
+            1
+        );
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    struct SignatureInspector;
+
+    struct SignatureInspection {
+        length: usize,
+    }
+
+    impl ContentInspector for SignatureInspector {
+        fn start(&self, _filename: &str) -> Box<dyn ContentInspection> {
+            Box::new(SignatureInspection { length: 0 })
+        }
+    }
+
+    impl ContentInspection for SignatureInspection {
+        fn inspect<'a>(&'a mut self, chunk: &'a [u8]) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async move {
+                self.length += chunk.len();
+                if chunk.windows(5).any(|window| window == b"EICAR") {
+                    return Err("signature found".into());
+                }
+                Ok(())
+            })
+        }
+
+        fn finish(self: Box<Self>) -> BoxFuture<'static, Result<(), String>> {
+            Box::pin(async move {
+                if self.length > 16 {
+                    return Err("too large".into());
+                }
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_content_inspector() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(4)
+                    .content_inspector(Some(Arc::new(SignatureInspector)))
+                    .build(),
+            ),
+        );
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let result = bucket
+            .upload_from_stream("virus.txt", "data EICAR data".as_bytes(), None)
+            .await;
+        assert!(
+            matches!(result, Err(GridFSError::ContentRejected { reason }) if reason == "signature found")
+        );
+        let result = bucket
+            .upload_from_stream("large.txt", "test data test data".as_bytes(), None)
+            .await;
+        assert!(
+            matches!(result, Err(GridFSError::ContentRejected { reason }) if reason == "too large")
+        );
+
+        assert_eq!(
+            db.collection::<Document>("fs.files")
+                .count_documents(doc! {}, None)
+                .await?,
+            1
+        );
+        assert_eq!(
+            db.collection::<Document>("fs.chunks")
+                .count_documents(doc! {}, None)
+                .await?,
+            3
+        );
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_from_stream_with_id() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = ObjectId::new();
+        bucket
+            .upload_from_stream_with_id(id, "test.txt", "test data".as_bytes(), None)
+            .await?;
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_str("filename").unwrap(), "test.txt");
+        assert_eq!(file.get_i64("length").unwrap(), 9);
+
+        let result = bucket
+            .upload_from_stream_with_id(id, "other.txt", "other data".as_bytes(), None)
+            .await;
+        assert!(matches!(
+            result,
+            Err(GridFSError::AlreadyExists { id: Some(existing), filename: None }) if existing == id
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    struct FixedClock(DateTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime {
+            self.0
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_with_clock() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let now = DateTime::from_millis(1_000_000_000_000);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .expire_after(Some(std::time::Duration::from_secs(60)))
+                    .clock(Some(Arc::new(FixedClock(now))))
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_datetime("uploadDate").unwrap(), &now);
+        assert_eq!(
+            file.get_datetime("expireAt").unwrap().timestamp_millis(),
+            now.timestamp_millis() + 60_000
+        );
+        // Not expired yet for the bucket.
+        bucket.open_download_stream(id).await?;
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_from_stream_with_file_id_and_upload_date() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let id = ObjectId::new();
+        let upload_date = DateTime::from_millis(1_000_000_000_000);
+        let options = GridFSUploadOptions::builder()
+            .file_id(Some(id))
+            .upload_date(Some(upload_date))
+            .build();
+        assert_eq!(
+            bucket
+                .upload_from_stream("test.txt", "test data".as_bytes(), Some(options.clone()))
+                .await?,
+            id
+        );
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_datetime("uploadDate").unwrap(), &upload_date);
+
+        let result = bucket
+            .upload_from_stream("other.txt", "other data".as_bytes(), Some(options))
+            .await;
+        assert!(matches!(
+            result,
+            Err(GridFSError::AlreadyExists { id: Some(existing), filename: None }) if existing == id
+        ));
 
-        Ok(files_id)
+        db.drop(None).await?;
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::GridFSBucket;
-    use crate::options::GridFSBucketOptions;
-    use bson::{doc, Document};
-    #[cfg(feature = "async-std-runtime")]
-    use futures::StreamExt;
-    use mongodb::{error::Error, Client, Database};
-    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
-    use std::io::Write;
-    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
-    use tokio_stream::StreamExt;
-    use uuid::Uuid;
-    fn db_name_new() -> String {
-        "test_".to_owned()
-            + Uuid::new_v4()
-                .hyphenated()
-                .encode_lower(&mut Uuid::encode_buffer())
-    }
     #[cfg(any(feature = "default", feature = "tokio-runtime"))]
-    fn generate_large_text(size: usize) -> Vec<u8> {
-        let mut buffer = Vec::new();
-        buffer.reserve(size);
-        for i in 0..size {
-            buffer.push((i % 8) as u8);
-        }
-        buffer
-    }
-
     #[tokio::test]
-    async fn upload_from_stream() -> Result<(), Error> {
+    async fn upload_from_buf_reader() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
             &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
         )
         .await?;
         let dbname = db_name_new();
         let db: Database = client.database(&dbname);
-        let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(8).build()),
+        );
+        // A buffer smaller than a chunk: each chunk spans several fill_buf calls.
+        let source = tokio::io::BufReader::with_capacity(3, "test data 1234567890".as_bytes());
         let id = bucket
-            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .upload_from_buf_reader("test.txt", source, None)
             .await?;
 
-        assert_eq!(id.to_hex(), id.to_hex());
         let file = db
             .collection::<Document>("fs.files")
             .find_one(doc! { "_id": id }, None)
             .await?
             .unwrap();
-        assert_eq!(file.get_str("filename").unwrap(), "test.txt");
-        assert_eq!(file.get_i32("chunkSize").unwrap(), 261120);
-        assert_eq!(file.get_i64("length").unwrap(), 9);
+        assert_eq!(file.get_i64("length").unwrap(), 20);
         assert_eq!(
             file.get_str("md5").unwrap(),
-            "eb733a00c0c9d336e65691a37ab54293"
+            "5e75d6271a7cfc3d9b79116be261eb21"
         );
 
         let chunks: Vec<Result<Document, Error>> = db
-            .collection("fs.chunks")
+            .collection::<Document>("fs.chunks")
             .find(doc! { "files_id": id }, None)
             .await?
             .collect()
             .await;
-
-        assert_eq!(chunks[0].as_ref().unwrap().get_i32("n").unwrap(), 0);
+        assert_eq!(chunks.len(), 3);
         assert_eq!(
-            chunks[0]
+            chunks[1]
                 .as_ref()
                 .unwrap()
                 .get_binary_generic("data")
                 .unwrap(),
-            &vec![116_u8, 101, 115, 116, 32, 100, 97, 116, 97]
+            &vec![97_u8, 32, 49, 50, 51, 52, 53, 54]
         );
 
-        db.drop(None).await
-        //Ok(())
+        db.drop(None).await?;
+        Ok(())
     }
 
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
     #[tokio::test]
-    async fn upload_from_stream_chunk_size() -> Result<(), Error> {
+    async fn channel_upload() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
             &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
         )
         .await?;
         let dbname = db_name_new();
         let db: Database = client.database(&dbname);
-        let mut bucket = GridFSBucket::new(
+        let bucket = GridFSBucket::new(
             db.clone(),
             Some(GridFSBucketOptions::builder().chunk_size_bytes(8).build()),
         );
+        let (sender, upload) = bucket.channel_upload("test.txt", None);
+        // Frames smaller and larger than a chunk.
+        tokio::spawn(async move {
+            for frame in ["test ", "data 1234567890", "", "abc"] {
+                sender.send(Bytes::from(frame)).await.unwrap();
+            }
+        });
+        let id = upload.await.unwrap()?;
+
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_i64("length").unwrap(), 23);
+        let chunks: Vec<Document> = db
+            .collection::<Document>("fs.chunks")
+            .find(doc! { "files_id": id }, None)
+            .await?
+            .collect::<Result<_, Error>>()
+            .await?;
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].get_binary_generic("data").unwrap(), b"a 123456");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[tokio::test]
+    async fn shutdown_aborts_upload() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let (sender, upload) = bucket.channel_upload("test.txt", None);
+        sender.send(Bytes::from("test")).await.unwrap();
+        let shutdown = {
+            let bucket = bucket.clone();
+            tokio::spawn(async move { bucket.shutdown(Duration::from_millis(200)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        // The upload notices the abort at its next chunk.
+        let _ = sender.send(Bytes::from("data")).await;
+        assert!(matches!(
+            upload.await.unwrap(),
+            Err(GridFSError::ShuttingDown())
+        ));
+        shutdown.await.unwrap()?;
+        assert!(bucket.is_shut_down());
+        assert_eq!(
+            db.collection::<Document>("fs.files")
+                .count_documents(doc! {}, None)
+                .await?,
+            0
+        );
+        assert_eq!(
+            db.collection::<Document>("fs.chunks")
+                .count_documents(doc! {}, None)
+                .await?,
+            0
+        );
+        assert!(matches!(
+            bucket
+                .clone()
+                .upload_from_stream("test.txt", "test".as_bytes(), None)
+                .await,
+            Err(GridFSError::ShuttingDown())
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[tokio::test]
+    async fn upload_from_stream_offload_digest() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(8)
+                    .offload_digest(true)
+                    .build(),
+            ),
+        );
         let id = bucket
             .upload_from_stream("test.txt", "test data 1234567890".as_bytes(), None)
             .await?;
 
-        assert_eq!(id.to_hex(), id.to_hex());
         let file = db
             .collection::<Document>("fs.files")
             .find_one(doc! { "_id": id }, None)
             .await?
             .unwrap();
-        assert_eq!(file.get_str("filename").unwrap(), "test.txt");
-        assert_eq!(file.get_i32("chunkSize").unwrap(), 8);
-        assert_eq!(file.get_i64("length").unwrap(), 20);
         assert_eq!(
             file.get_str("md5").unwrap(),
             "5e75d6271a7cfc3d9b79116be261eb21"
         );
 
-        let chunks: Vec<Result<Document, Error>> = db
-            .collection::<Document>("fs.chunks")
-            .find(doc! { "files_id": id }, None)
-            .await?
-            .collect()
-            .await;
+        db.drop(None).await?;
+        Ok(())
+    }
 
-        assert_eq!(chunks[0].as_ref().unwrap().get_i32("n").unwrap(), 0);
-        assert_eq!(
-            chunks[0]
-                .as_ref()
-                .unwrap()
-                .get_binary_generic("data")
-                .unwrap(),
-            &vec![116_u8, 101, 115, 116, 32, 100, 97, 116]
+    #[derive(Default)]
+    struct QueueRecorder {
+        position: AtomicUsize,
+        max_depth: AtomicUsize,
+    }
+
+    impl ProgressUpdate for QueueRecorder {
+        fn update(&self, position: usize) {
+            self.position.fetch_max(position, Ordering::SeqCst);
+        }
+
+        fn queue_depth(&self, depth: usize) {
+            self.max_depth.fetch_max(depth, Ordering::SeqCst);
+        }
+    }
+
+    #[derive(Default)]
+    struct ProgressRecorder {
+        progresses: Mutex<Vec<UploadProgress>>,
+    }
+
+    impl ProgressUpdate for ProgressRecorder {
+        fn update(&self, _position: usize) {}
+
+        fn progress(&self, progress: UploadProgress) {
+            self.progresses.lock().unwrap().push(progress);
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_from_stream_progress_totals() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
         );
+        let recorder = Arc::new(ProgressRecorder::default());
+        bucket
+            .upload_from_stream(
+                "test.txt",
+                "test data".as_bytes(),
+                Some(
+                    GridFSUploadOptions::builder()
+                        .progress_tick(Some(recorder.clone()))
+                        .size_hint(Some(9))
+                        .build(),
+                ),
+            )
+            .await?;
 
-        assert_eq!(chunks[1].as_ref().unwrap().get_i32("n").unwrap(), 1);
+        let progresses = recorder.progresses.lock().unwrap().clone();
+        assert_eq!(progresses.len(), 3);
+        assert!(progresses
+            .iter()
+            .all(|progress| progress.total_bytes == Some(9)
+                && progress.chunk_count_estimate == Some(3)));
+        let last = progresses.last().unwrap();
+        assert_eq!((last.bytes_done, last.chunk_index), (9, 3));
+        assert_eq!(last.percent(), Some(100.0));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_from_stream_strict_length() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let strict = |size_hint| {
+            Some(
+                GridFSUploadOptions::builder()
+                    .size_hint(Some(size_hint))
+                    .strict_length(true)
+                    .build(),
+            )
+        };
+        bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), strict(9))
+            .await?;
+        assert!(matches!(
+            bucket
+                .upload_from_stream("truncated.txt", "test data".as_bytes(), strict(20))
+                .await,
+            Err(GridFSError::LengthMismatch {
+                expected: 20,
+                actual: 9
+            })
+        ));
+        // Stopped at the first chunk past the expected length.
+        assert!(matches!(
+            bucket
+                .upload_from_stream("longer.txt", "test data".as_bytes(), strict(2))
+                .await,
+            Err(GridFSError::LengthMismatch {
+                expected: 2,
+                actual: 4
+            })
+        ));
         assert_eq!(
-            chunks[1]
-                .as_ref()
-                .unwrap()
-                .get_binary_generic("data")
-                .unwrap(),
-            &vec![97_u8, 32, 49, 50, 51, 52, 53, 54]
+            db.collection::<Document>("fs.files")
+                .count_documents(doc! {}, None)
+                .await?,
+            1
+        );
+        assert_eq!(
+            db.collection::<Document>("fs.chunks")
+                .count_documents(doc! {}, None)
+                .await?,
+            3
         );
 
-        assert_eq!(chunks[2].as_ref().unwrap().get_i32("n").unwrap(), 2);
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_from_stream_max_in_flight_chunks() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(2)
+                    .max_in_flight_chunks(3)
+                    .build(),
+            ),
+        );
+        let recorder = Arc::new(QueueRecorder::default());
+        let id = bucket
+            .upload_from_stream(
+                "test.txt",
+                "test data 1234567890".as_bytes(),
+                Some(
+                    GridFSUploadOptions::builder()
+                        .progress_tick(Some(recorder.clone()))
+                        .build(),
+                ),
+            )
+            .await?;
+
+        assert_eq!(recorder.position.load(Ordering::SeqCst), 20);
+        assert!(recorder.max_depth.load(Ordering::SeqCst) <= 3);
+
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_i64("length").unwrap(), 20);
         assert_eq!(
-            chunks[2]
-                .as_ref()
-                .unwrap()
-                .get_binary_generic("data")
-                .unwrap(),
-            &vec![55_u8, 56, 57, 48]
+            file.get_str("md5").unwrap(),
+            "5e75d6271a7cfc3d9b79116be261eb21"
         );
+        let count = db
+            .collection::<Document>("fs.chunks")
+            .count_documents(doc! { "files_id": id }, None)
+            .await?;
+        assert_eq!(count, 10);
 
-        db.drop(None).await
-        // Ok(())
+        db.drop(None).await?;
+        Ok(())
     }
 
     #[cfg(any(feature = "default", feature = "tokio-runtime"))]
     #[tokio::test]
-    async fn upload_from_stream_chunk_size_from_tokio_file() -> Result<(), Error> {
+    async fn upload_from_stream_chunk_size_from_tokio_file() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
             &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
         )
@@ -486,7 +2185,9 @@ mod tests {
         file.write_all(large_text.as_slice()).unwrap();
         file.flush().unwrap();
 
-        let async_file = tokio::fs::File::open(file.path()).await?;
+        let async_file = tokio::fs::File::open(file.path())
+            .await
+            .map_err(Error::from)?;
         let id = bucket
             .upload_from_stream("test.txt", async_file, None)
             .await?;
@@ -515,26 +2216,23 @@ mod tests {
         let n_chunks: usize = chunks.len();
         assert_eq!(n_chunks, (text_len - 1) / chunk_size + 1);
 
-        for i in 0..n_chunks {
-            assert_eq!(chunks[i].as_ref().unwrap().get_i32("n").unwrap(), i as i32);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.as_ref().unwrap().get_i32("n").unwrap(), i as i32);
 
-            let chunk = chunks[i]
-                .as_ref()
-                .unwrap()
-                .get_binary_generic("data")
-                .unwrap();
+            let chunk = chunk.as_ref().unwrap().get_binary_generic("data").unwrap();
             let start = i * chunk_size;
             let end = start + std::cmp::min(chunk.len(), chunk_size);
             assert_eq!(chunk, &large_text[start..end]);
         }
 
-        db.drop(None).await
+        db.drop(None).await?;
+        Ok(())
         // Ok(())
     }
 
     #[cfg(any(feature = "default", feature = "tokio-runtime"))]
     #[tokio::test]
-    async fn upload_from_stream_chunk_size_from_align_tokio_file() -> Result<(), Error> {
+    async fn upload_from_stream_chunk_size_from_align_tokio_file() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
             &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
         )
@@ -556,7 +2254,9 @@ mod tests {
         file.write_all(large_text.as_slice()).unwrap();
         file.flush().unwrap();
 
-        let async_file = tokio::fs::File::open(file.path()).await?;
+        let async_file = tokio::fs::File::open(file.path())
+            .await
+            .map_err(Error::from)?;
         let id = bucket
             .upload_from_stream("test.txt", async_file, None)
             .await?;
@@ -585,25 +2285,22 @@ mod tests {
         let n_chunks: usize = chunks.len();
         assert_eq!(n_chunks, (text_len - 1) / chunk_size + 1);
 
-        for i in 0..n_chunks {
-            assert_eq!(chunks[i].as_ref().unwrap().get_i32("n").unwrap(), i as i32);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.as_ref().unwrap().get_i32("n").unwrap(), i as i32);
 
-            let chunk = chunks[i]
-                .as_ref()
-                .unwrap()
-                .get_binary_generic("data")
-                .unwrap();
+            let chunk = chunk.as_ref().unwrap().get_binary_generic("data").unwrap();
             let start = i * chunk_size;
             let end = start + std::cmp::min(chunk.len(), chunk_size);
             assert_eq!(chunk, &large_text[start..end]);
         }
 
-        db.drop(None).await
+        db.drop(None).await?;
+        Ok(())
         // Ok(())
     }
 
     #[tokio::test]
-    async fn ensure_files_index_before_write() -> Result<(), Error> {
+    async fn ensure_files_index_before_write() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
             &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
         )
@@ -647,24 +2344,25 @@ mod tests {
                 (_, _, Ok(x), Ok(y)) if x == 1.0 && y == 1.0 => {
                     have_index = true;
                 }
-                (Ok(1), _, _, Ok(x)) if x == 1.0 => {
+                (Ok(1), _, _, Ok(1.0)) => {
                     have_index = true;
                 }
-                (_, Ok(1), Ok(x), _) if x == 1.0 => {
+                (_, Ok(1), Ok(1.0), _) => {
                     have_index = true;
                 }
                 _ => {}
             }
         }
 
-        assert_eq!(have_index, true, "should found a file index");
+        assert!(have_index, "should found a file index");
 
-        db.drop(None).await
+        db.drop(None).await?;
+        Ok(())
         // Ok(())
     }
 
     #[tokio::test]
-    async fn ensure_chunks_index_before_write() -> Result<(), Error> {
+    async fn ensure_chunks_index_before_write() -> Result<(), GridFSError> {
         let client = Client::with_uri_str(
             &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
         )
@@ -709,17 +2407,87 @@ mod tests {
                 (_, _, Ok(x), Ok(y)) if x == 1.0 && y == 1.0 => {
                     have_chunks_index = true;
                 }
-                (Ok(1), _, _, Ok(x)) if x == 1.0 => {
+                (Ok(1), _, _, Ok(1.0)) => {
                     have_chunks_index = true;
                 }
-                (_, Ok(1), Ok(x), _) if x == 1.0 => {
+                (_, Ok(1), Ok(1.0), _) => {
                     have_chunks_index = true;
                 }
                 _ => {}
             }
         }
-        assert_eq!(have_chunks_index, true, "should found a chunk index");
-        db.drop(None).await
+        assert!(have_chunks_index, "should found a chunk index");
+        db.drop(None).await?;
+        Ok(())
         // Ok(())
     }
+
+    /// A reader returning at most `max_step` bytes per read, and `Pending` once every
+    /// `pending_every + 1` polls when `pending_every` isn't 0.
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    struct ShortReader {
+        data: Vec<u8>,
+        position: usize,
+        max_step: usize,
+        pending_every: usize,
+        polls: usize,
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    impl AsyncRead for ShortReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            self.polls += 1;
+            if self.pending_every > 0 && self.polls.is_multiple_of(self.pending_every + 1) {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let step = self
+                .max_step
+                .min(buf.remaining())
+                .min(self.data.len() - self.position);
+            let start = self.position;
+            buf.put_slice(&self.data[start..start + step]);
+            self.position += step;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    proptest! {
+        #[test]
+        fn read_chunk_round_trip(
+            data in proptest::collection::vec(any::<u8>(), 0..4096),
+            chunk_size in 1usize..512,
+            max_step in 1usize..600,
+            pending_every in 0usize..4,
+        ) {
+            let mut source = ShortReader { data: data.clone(), position: 0, max_step, pending_every, polls: 0 };
+            let chunks: Vec<Vec<u8>> = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    let mut chunks = vec![];
+                    let mut buffer = vec![0; chunk_size];
+                    loop {
+                        let size = read_chunk(&mut source, &mut buffer).await.unwrap();
+                        if size == 0 {
+                            break;
+                        }
+                        chunks.push(buffer[..size].to_vec());
+                    }
+                    chunks
+                });
+
+            prop_assert_eq!(chunks.len(), data.len().div_ceil(chunk_size));
+            if let Some((last, full_chunks)) = chunks.split_last() {
+                prop_assert!(full_chunks.iter().all(|chunk| chunk.len() == chunk_size));
+                prop_assert!(!last.is_empty() && last.len() <= chunk_size);
+            }
+            prop_assert_eq!(chunks.concat(), data);
+        }
+    }
 }
@@ -0,0 +1,151 @@
+use crate::{bucket::GridFSBucket, options::UploadManyOptions, GridFSError};
+use bson::{oid::ObjectId, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::io::AsyncRead;
+use futures_util::stream::{Stream, StreamExt};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::io::AsyncRead;
+
+impl GridFSBucket {
+    /**
+      Uploads the files of @items, pairs of a filename and a source, with at most
+      [`UploadManyOptions::concurrency`] uploads in flight.
+
+      Returns the filename and the result of the upload of each file, in the order of
+      @items: a failed upload doesn't stop the others.
+
+      # Examples
+
+       ```rust
+       # use mongodb::Client;
+       # use mongodb::{error::Error, Database};
+       use futures_util::stream;
+       use mongodb_gridfs::{options::{GridFSBucketOptions, UploadManyOptions}, GridFSBucket, GridFSError};
+       # use uuid::Uuid;
+
+       # fn db_name_new() -> String {
+       #     "test_".to_owned()
+       #         + Uuid::new_v4()
+       #             .hyphenated()
+       #             .encode_lower(&mut Uuid::encode_buffer())
+       # }
+       #
+       # #[tokio::main]
+       # async fn main() -> Result<(), GridFSError> {
+       #     let client = Client::with_uri_str(&std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string())).await?;
+       #     let dbname = db_name_new();
+       #     let db: Database = client.database(&dbname);
+       let mut bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+       let items = stream::iter(vec![
+           ("a.txt".to_string(), "first file".as_bytes()),
+           ("b.txt".to_string(), "second file".as_bytes()),
+       ]);
+       for (filename, result) in bucket.upload_many(items, UploadManyOptions::default()).await? {
+           println!("{}: {:?}", filename, result);
+       }
+       #     db.drop(None).await?;
+       #     Ok(())
+       # }
+       ```
+
+      # Errors
+
+      Raise [`GridFSError::MongoError`] when the indexes of the bucket can't be checked before
+      the uploads.
+    */
+    pub async fn upload_many<S, R>(
+        &mut self,
+        items: S,
+        options: UploadManyOptions,
+    ) -> Result<Vec<(String, Result<ObjectId, GridFSError>)>, GridFSError>
+    where
+        S: Stream<Item = (String, R)>,
+        R: AsyncRead + Unpin,
+    {
+        let bucket_name = self.options.clone().unwrap_or_default().bucket_name;
+        let file_collection = bucket_name.clone() + ".files";
+        let chunk_collection = bucket_name + ".chunks";
+        let files = self.db.collection::<Document>(&file_collection);
+        // Checked once for all the uploads.
+        self.ensure_file_index(&files, &file_collection, &chunk_collection)
+            .await?;
+
+        let bucket = &*self;
+        let upload_options = &options.upload_options;
+        Ok(items
+            .map(|(filename, source)| async move {
+                let result = bucket
+                    .clone()
+                    .upload_from_stream(&filename, source, upload_options.clone())
+                    .await;
+                (filename, result)
+            })
+            .buffered(options.concurrency.max(1))
+            .collect()
+            .await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{
+        options::{FilenamePolicy, GridFSBucketOptions, UploadManyOptions},
+        GridFSError,
+    };
+    use futures_util::stream;
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn upload_many() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .filename_policy(Some(FilenamePolicy::builder().max_length(Some(8)).build()))
+                    .build(),
+            ),
+        );
+        let items = stream::iter(vec![
+            ("a.txt".to_string(), "first file".as_bytes()),
+            ("too_long.txt".to_string(), "second file".as_bytes()),
+            ("c.txt".to_string(), "third file".as_bytes()),
+        ]);
+        let results = bucket
+            .upload_many(items, UploadManyOptions::builder().concurrency(2).build())
+            .await?;
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, "a.txt");
+        assert_eq!(
+            bucket
+                .file_info(*results[0].1.as_ref().unwrap())
+                .await?
+                .length,
+            10
+        );
+        assert!(matches!(
+            results[1].1,
+            Err(GridFSError::InvalidFilename(..))
+        ));
+        assert_eq!(results[2].0, "c.txt");
+        assert!(results[2].1.is_ok());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
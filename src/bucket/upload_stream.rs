@@ -0,0 +1,580 @@
+use crate::{
+    bucket::{upload::ChunkSource, GridFSBucket},
+    options::{GridFSUploadOptions, ProgressUpdate, UploadProgress},
+    GridFSError,
+};
+use bson::oid::ObjectId;
+use futures_util::{future::BoxFuture, io::AsyncWrite};
+use std::{
+    future::poll_fn,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+/// The bytes written to a [`GridFSUploadStream`] and not cut in a chunk yet.
+#[derive(Default)]
+struct WriteBuffer {
+    data: Vec<u8>,
+    /// The size of the chunk the upload waits for, 0 while it doesn't wait for the writer.
+    wanted: usize,
+    closed: bool,
+    /// Set by [`GridFSUploadStream::abort`] and the drop of the stream: the writes are refused.
+    aborted: bool,
+    /// Whether the upload wrote its files collection document: only then does the abort
+    /// remove the document with the id of the stream, which is another writer's otherwise.
+    file_written: bool,
+    /// The number of chunk inserts queued by the upload, and done.
+    queued: u64,
+    inserted: u64,
+    /// The number of bytes of the chunks inserted.
+    persisted: u64,
+}
+
+impl WriteBuffer {
+    /// Whether the upload waits for the writer without insert in flight: it can be stopped
+    /// without leaving a chunk behind.
+    fn is_idle(&self) -> bool {
+        self.wanted > 0 && self.queued == self.inserted
+    }
+}
+
+/// Tracks the inserts of the upload of a [`GridFSUploadStream`] in its buffer, and forwards
+/// their progress to the `progress_tick` of the upload options.
+struct InsertTracker {
+    buffer: Arc<Mutex<WriteBuffer>>,
+    progress_tick: Option<Arc<dyn ProgressUpdate + Send + Sync>>,
+}
+
+impl ProgressUpdate for InsertTracker {
+    fn update(&self, position: usize) {
+        if let Some(progress_tick) = &self.progress_tick {
+            progress_tick.update(position);
+        }
+    }
+
+    fn queue_depth(&self, depth: usize) {
+        // Called once a chunk is queued, the inserts done are already counted.
+        {
+            let mut buffer = self.buffer.lock().unwrap();
+            buffer.queued = buffer.inserted + depth as u64;
+        }
+        if let Some(progress_tick) = &self.progress_tick {
+            progress_tick.queue_depth(depth);
+        }
+    }
+
+    fn progress(&self, progress: UploadProgress) {
+        {
+            let mut buffer = self.buffer.lock().unwrap();
+            buffer.inserted = progress.chunk_index;
+            buffer.persisted = progress.bytes_done;
+        }
+        if let Some(progress_tick) = &self.progress_tick {
+            progress_tick.progress(progress);
+        }
+    }
+}
+
+type Upload = BoxFuture<'static, Result<(), GridFSError>>;
+
+/// Drives the @upload of a stream until it's done or idle, see [`WriteBuffer::is_idle`].
+async fn settle(upload: &mut Upload, buffer: &Mutex<WriteBuffer>) {
+    poll_fn(|cx| match upload.as_mut().poll(cx) {
+        Poll::Ready(_) => Poll::Ready(()),
+        Poll::Pending if buffer.lock().unwrap().is_idle() => Poll::Ready(()),
+        Poll::Pending => Poll::Pending,
+    })
+    .await
+}
+
+/// Cuts the chunks of the upload from the bytes written to its [`GridFSUploadStream`].
+struct WriterSource(Arc<Mutex<WriteBuffer>>);
+
+impl ChunkSource for WriterSource {
+    async fn next_chunk(&mut self, size: usize) -> io::Result<Vec<u8>> {
+        // Not woken: the stream polls the upload again once it has buffered the bytes.
+        poll_fn(|_| {
+            let mut buffer = self.0.lock().unwrap();
+            if buffer.data.len() < size && !buffer.closed {
+                buffer.wanted = size;
+                return Poll::Pending;
+            }
+            buffer.wanted = 0;
+            let length = size.min(buffer.data.len());
+            Poll::Ready(Ok(buffer.data.drain(..length).collect()))
+        })
+        .await
+    }
+
+    fn file_written(&mut self) {
+        self.0.lock().unwrap().file_written = true;
+    }
+}
+
+/// A writer uploading the bytes written to it as a stored file, returned by
+/// [`GridFSBucket::open_upload_stream`].
+///
+/// The writes are buffered into chunk-sized inserts, and the upload is driven by the calls
+/// on the stream: a write waits while the inserts in flight are full. The files collection
+/// document is finalized by `close` (`shutdown` for the tokio `AsyncWrite`), which writes
+/// the last, partial, chunk. A flush only waits until the complete chunks are handed to the
+/// inserts: it doesn't wait for the inserts in flight, so the flushed bytes aren't durable.
+/// [`GridFSUploadStream::checkpoint`] waits for them.
+///
+/// The errors of the upload are reported by the writes and the close as [`io::Error`]s
+/// wrapping the [`GridFSError`]. A stream dropped before it's closed is aborted in the
+/// background, like with [`GridFSUploadStream::abort`], when it's dropped in a tokio
+/// runtime. Otherwise the upload is abandoned like an interrupted
+/// [`GridFSBucket::upload_from_stream`]: the partial file stays, hidden from the readers,
+/// until it's resumed with [`GridFSBucket::continue_upload`] or removed by the caller.
+pub struct GridFSUploadStream {
+    id: ObjectId,
+    bucket: GridFSBucket,
+    buffer: Arc<Mutex<WriteBuffer>>,
+    /// The upload, None once it's done.
+    upload: Option<Upload>,
+    /// Whether the upload was polled: an upload never polled wrote nothing.
+    started: bool,
+}
+
+impl GridFSUploadStream {
+    /// The id of the uploaded file.
+    pub fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    /**
+    Aborts the upload: the writes are refused, and once the inserts in flight are done, the
+    chunks already written and the files collection document of the upload are removed. The
+    file uploaded by a closed stream is complete, and stays. A file of another writer with
+    the id of the stream, e.g. a reservation of [`GridFSBucket::reserve_id`] the upload
+    didn't complete yet, stays too.
+
+    # Errors
+
+    Raise [`GridFSError::MongoError`] when the chunks or the files collection document
+    can't be removed.
+    */
+    pub async fn abort(&mut self) -> Result<(), GridFSError> {
+        self.buffer.lock().unwrap().aborted = true;
+        if let Some(mut upload) = self.upload.take().filter(|_| self.started) {
+            settle(&mut upload, &self.buffer).await;
+        }
+        if !self.buffer.lock().unwrap().file_written {
+            return Ok(());
+        }
+        let _writer = self.bucket.write_guard().await;
+        self.bucket.discard_partial(self.id).await
+    }
+
+    /**
+    Waits until the chunks cut from the bytes written so far are inserted, with the write
+    concern of the chunks of the bucket. Returns the byte offset of the content persisted:
+    the bytes written after it don't fill a chunk yet, and stay buffered.
+
+    The offset is the `bytes_persisted` of [`GridFSBucket::inspect_partial`], from which a
+    client resumes an interrupted upload with [`GridFSBucket::continue_upload`]. It's 0
+    while nothing is persisted: with an
+    [`inline_threshold`](crate::options::GridFSBucketOptions::inline_threshold), the files
+    collection document is only written once the content is longer than the threshold.
+
+    # Errors
+
+    The checkpoint fails with the errors of the upload, like the writes, and when the
+    stream is closed or aborted.
+    */
+    pub async fn checkpoint(&mut self) -> io::Result<u64> {
+        poll_fn(|cx| {
+            if let Poll::Ready(result) = self.poll_upload(cx) {
+                result?;
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "the upload is finished",
+                )));
+            }
+            let buffer = self.buffer.lock().unwrap();
+            match buffer.is_idle() {
+                true => Poll::Ready(Ok(buffer.persisted)),
+                false => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Drives the upload: ready with its result once it's done, and then ready.
+    fn poll_upload(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.started = true;
+        let result = match self.upload.as_mut() {
+            Some(upload) => match upload.as_mut().poll(cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Poll::Pending,
+            },
+            None if self.buffer.lock().unwrap().aborted => {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "the upload is aborted",
+                )))
+            }
+            None => return Poll::Ready(Ok(())),
+        };
+        self.upload = None;
+        Poll::Ready(result.map_err(io::Error::other))
+    }
+
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        if let Poll::Ready(result) = self.poll_upload(cx) {
+            // The upload only ends before the close on an error.
+            result?;
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the upload is finished",
+            )));
+        }
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.closed {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the upload stream is closed",
+            )));
+        }
+        // While the upload doesn't wait for the bytes of a chunk, it waits for an insert,
+        // which wakes the writer.
+        let free = buffer.wanted.saturating_sub(buffer.data.len());
+        if free == 0 {
+            return Poll::Pending;
+        }
+        let written = free.min(buf.len());
+        buffer.data.extend_from_slice(&buf[..written]);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Poll::Ready(result) = self.poll_upload(cx) {
+            return Poll::Ready(result);
+        }
+        match self.buffer.lock().unwrap().wanted > 0 {
+            true => Poll::Ready(Ok(())),
+            false => Poll::Pending,
+        }
+    }
+
+    fn poll_close_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.buffer.lock().unwrap().closed = true;
+        self.poll_upload(cx)
+    }
+}
+
+impl AsyncWrite for GridFSUploadStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write_buf(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush_buf(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_close_buf(cx)
+    }
+}
+
+impl Drop for GridFSUploadStream {
+    fn drop(&mut self) {
+        #[cfg_attr(
+            not(any(feature = "default", feature = "tokio-runtime")),
+            allow(unused_variables, unused_mut)
+        )]
+        let mut upload = match self.upload.take().filter(|_| self.started) {
+            Some(upload) => upload,
+            None => return,
+        };
+        self.buffer.lock().unwrap().aborted = true;
+        #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let (bucket, buffer, id) = (self.bucket.clone(), self.buffer.clone(), self.id);
+            runtime.spawn(async move {
+                settle(&mut upload, &buffer).await;
+                drop(upload);
+                if !buffer.lock().unwrap().file_written {
+                    return;
+                }
+                // Best effort: the partial file stays when it can't be removed.
+                let _writer = bucket.write_guard().await;
+                let _ = bucket.discard_partial(id).await;
+            });
+        }
+    }
+}
+
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+impl tokio::io::AsyncWrite for GridFSUploadStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write_buf(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush_buf(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_close_buf(cx)
+    }
+}
+
+impl GridFSBucket {
+    /**
+    Opens a [`GridFSUploadStream`] to which the application writes the contents of a user
+    file uploaded as @filename. The driver generates the file id, unless the
+    [`file_id`](GridFSUploadOptions::file_id) upload option is set: it's known upfront with
+    [`GridFSUploadStream::id`].
+
+    The stream implements `futures::io::AsyncWrite`, and `tokio::io::AsyncWrite` with the
+    tokio runtime, to pipe the data of other async sources without an intermediate reader.
+    The upload is complete once the stream is closed.
+    [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#file-upload)
+
+    # Errors
+
+    The writes and the close of the stream fail with the errors of
+    [`GridFSBucket::upload_from_stream_with_id`].
+    */
+    pub fn open_upload_stream(
+        &self,
+        filename: &str,
+        options: Option<GridFSUploadOptions>,
+    ) -> GridFSUploadStream {
+        let mut options = options.unwrap_or_else(|| GridFSUploadOptions::builder().build());
+        let id = options.file_id.unwrap_or_default();
+        let buffer = Arc::new(Mutex::new(WriteBuffer::default()));
+        options.progress_tick = Some(Arc::new(InsertTracker {
+            buffer: buffer.clone(),
+            progress_tick: options.progress_tick.take(),
+        }));
+        let source = WriterSource(buffer.clone());
+        let mut bucket = self.clone();
+        let filename = filename.to_string();
+        GridFSUploadStream {
+            id,
+            bucket: self.clone(),
+            buffer,
+            upload: Some(Box::pin(async move {
+                bucket
+                    .upload_chunks_with_id(id, &filename, source, Some(options))
+                    .await
+            })),
+            started: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{
+        options::{GridFSBucketOptions, GridFSUploadOptions},
+        GridFSError,
+    };
+    use bson::{doc, oid::ObjectId, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use futures_util::io::AsyncWriteExt;
+    use mongodb::{error::Error, Client, Database};
+    use std::time::Duration;
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn open_upload_stream() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+
+        let mut stream = bucket.open_upload_stream("test.txt", None);
+        for data in ["te", "st da", "ta"] {
+            stream
+                .write_all(data.as_bytes())
+                .await
+                .map_err(Error::from)?;
+        }
+        stream.close().await.map_err(Error::from)?;
+        let id = stream.id();
+
+        let mut download = bucket.open_download_stream(id).await?;
+        let mut content = vec![];
+        while let Some(data) = download.next().await {
+            content.extend(data?);
+        }
+        assert_eq!(content, b"test data");
+
+        // The id of an upload option.
+        let file_id = ObjectId::new();
+        let options = GridFSUploadOptions::builder()
+            .file_id(Some(file_id))
+            .build();
+        let mut stream = bucket.open_upload_stream("empty.txt", Some(options.clone()));
+        assert_eq!(stream.id(), file_id);
+        stream.close().await.map_err(Error::from)?;
+        assert!(bucket.open_download_stream(file_id).await.is_ok());
+
+        // The errors of the upload fail the stream.
+        let mut stream = bucket.open_upload_stream("other.txt", Some(options));
+        let error = stream.close().await.unwrap_err();
+        assert!(matches!(
+            error.get_ref().and_then(|error| error.downcast_ref()),
+            Some(GridFSError::AlreadyExists { .. })
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_stream_abort() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let files = db.collection::<Document>("fs.files");
+        let chunks = db.collection::<Document>("fs.chunks");
+
+        let mut stream = bucket.open_upload_stream("test.txt", None);
+        stream.write_all(b"test data").await.map_err(Error::from)?;
+        let id = stream.id();
+        assert_eq!(files.count_documents(doc! {"_id":id}, None).await?, 1);
+        stream.abort().await?;
+        assert_eq!(files.count_documents(doc! {"_id":id}, None).await?, 0);
+        assert_eq!(chunks.count_documents(doc! {"files_id":id}, None).await?, 0);
+        // The writes are refused.
+        assert!(stream.write_all(b"more").await.is_err());
+        assert!(stream.close().await.is_err());
+
+        // A dropped stream is aborted in the background.
+        let mut stream = bucket.open_upload_stream("dropped.txt", None);
+        stream.write_all(b"test data").await.map_err(Error::from)?;
+        let id = stream.id();
+        drop(stream);
+        let mut remaining = 1;
+        for _ in 0..50 {
+            remaining = files.count_documents(doc! {"_id":id}, None).await?;
+            if remaining == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(remaining, 0);
+        assert_eq!(chunks.count_documents(doc! {"files_id":id}, None).await?, 0);
+
+        // The file of a closed stream stays.
+        let mut stream = bucket.open_upload_stream("closed.txt", None);
+        stream.write_all(b"test data").await.map_err(Error::from)?;
+        stream.close().await.map_err(Error::from)?;
+        stream.abort().await?;
+        assert!(bucket.open_download_stream(stream.id()).await.is_ok());
+
+        // A stream whose id is taken leaves the file of the other writer.
+        let mut first = bucket.open_upload_stream("first.txt", None);
+        first.write_all(b"test data").await.map_err(Error::from)?;
+        let options = GridFSUploadOptions::builder()
+            .file_id(Some(first.id()))
+            .build();
+        let mut second = bucket.open_upload_stream("second.txt", Some(options));
+        assert!(second.write_all(b"test data").await.is_err());
+        second.abort().await?;
+        assert_eq!(
+            files.count_documents(doc! {"_id":first.id()}, None).await?,
+            1
+        );
+        first.abort().await?;
+
+        // And the reservation of its id.
+        let reserved = bucket.clone().reserve_id("reserved.txt", None).await?;
+        let options = GridFSUploadOptions::builder()
+            .file_id(Some(reserved))
+            .build();
+        let mut stream = bucket.open_upload_stream("test.txt", Some(options));
+        stream.abort().await?;
+        assert_eq!(
+            files
+                .count_documents(doc! {"_id":reserved, "status":"pending"}, None)
+                .await?,
+            1
+        );
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_stream_checkpoint() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let content = b"0123456789ab";
+
+        let mut stream = bucket.open_upload_stream("test.txt", None);
+        assert_eq!(stream.checkpoint().await.map_err(Error::from)?, 0);
+        stream
+            .write_all(&content[..10])
+            .await
+            .map_err(Error::from)?;
+        let offset = stream.checkpoint().await.map_err(Error::from)?;
+        assert_eq!(offset, 8);
+        let id = stream.id();
+        // The process is killed after the checkpoint: the stream is neither closed nor
+        // dropped.
+        std::mem::forget(stream);
+
+        assert_eq!(bucket.inspect_partial(id).await?.bytes_persisted, offset);
+        let length = bucket
+            .continue_upload(id, &content[offset as usize..])
+            .await?;
+        assert_eq!(length, 12);
+        let mut download = bucket.open_download_stream(id).await?;
+        let mut downloaded = vec![];
+        while let Some(data) = download.next().await {
+            downloaded.extend(data?);
+        }
+        assert_eq!(downloaded, content);
+
+        // A closed stream has no checkpoint.
+        let mut stream = bucket.open_upload_stream("closed.txt", None);
+        stream.close().await.map_err(Error::from)?;
+        assert!(stream.checkpoint().await.is_err());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,479 @@
+use crate::{bucket::chunk_stream::chunk_data, options::WriteVerification, GridFSError};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use crate::{
+    bucket::{status::visible, tier::TIER_FIELD, GridFSBucket},
+    display::HumanSize,
+    options::{GridFSDownloadOptions, VerifyOptions},
+    FileInfo,
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use bson::oid::ObjectId;
+use bson::{doc, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use md5::{Digest, Md5};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use mongodb::options::UpdateOptions;
+use mongodb::{
+    options::{FindOptions, ReadPreference, SelectionCriteria},
+    Collection,
+};
+use std::{
+    collections::{hash_map::RandomState, BTreeSet},
+    convert::TryFrom,
+    hash::{BuildHasher, Hasher},
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::time::Instant;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// The `_id` of the document of the `<bucket>.verify` collection holding the progress of
+/// [`GridFSBucket::verify_all`].
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+const VERIFY_PROGRESS_ID: &str = "verify_all";
+
+/// The MD5 checksum of a written chunk.
+pub(crate) type ChunkDigest = [u8; 16];
+
+/// The MD5 checksum of the chunk @data.
+pub(crate) fn chunk_digest(data: &[u8]) -> ChunkDigest {
+    Md5::digest(data).into()
+}
+
+/// The `n` of the chunks read back by the @verification of a file of @length bytes in
+/// @chunk_count chunks: all of them, or a random sample.
+fn chunks_to_verify(verification: &WriteVerification, length: u64, chunk_count: u32) -> Vec<u32> {
+    if length <= verification.full_read_max_length || verification.sample_chunks >= chunk_count {
+        return (0..chunk_count).collect();
+    }
+    let random = RandomState::new();
+    let mut sample = BTreeSet::new();
+    let mut draw = 0;
+    while sample.len() < verification.sample_chunks as usize {
+        let mut hasher = random.build_hasher();
+        hasher.write_u64(draw);
+        sample.insert((hasher.finish() % chunk_count as u64) as u32);
+        draw += 1;
+    }
+    sample.into_iter().collect()
+}
+
+/// Reads back from the primary the chunks of the file @filter selected by @verification,
+/// and compares them to the @digests of the written chunks.
+///
+/// # Errors
+///
+/// Raise [`GridFSError::WriteVerificationFailed`] when a chunk is missing or differs.
+pub(crate) async fn verify_written_chunks(
+    chunks: &Collection<Document>,
+    filter: Document,
+    verification: &WriteVerification,
+    length: u64,
+    digests: &[ChunkDigest],
+) -> Result<(), GridFSError> {
+    let sample = chunks_to_verify(verification, length, digests.len() as u32);
+    let mut filter = filter;
+    if sample.len() < digests.len() {
+        filter.insert("n", doc! {"$in":sample.clone()});
+    }
+    let find_options = FindOptions::builder()
+        .projection(doc! {"_id":0, "n":1, "data":1})
+        .selection_criteria(SelectionCriteria::ReadPreference(ReadPreference::Primary))
+        .build();
+    let mut cursor = chunks.find(filter, find_options).await?;
+    let mut verified = BTreeSet::new();
+    while let Some(chunk) = cursor.next().await {
+        let chunk = chunk?;
+        let n = chunk.get_i32("n").unwrap_or(-1);
+        let expected = usize::try_from(n).ok().and_then(|n| digests.get(n));
+        let n = n as u32;
+        match expected {
+            Some(expected) if *expected == chunk_digest(&chunk_data(chunk, n as i64)?) => {
+                verified.insert(n);
+            }
+            _ => return Err(GridFSError::WriteVerificationFailed { n }),
+        }
+    }
+    match sample.into_iter().find(|n| !verified.contains(n)) {
+        Some(n) => Err(GridFSError::WriteVerificationFailed { n }),
+        None => Ok(()),
+    }
+}
+
+/// Whether the file @id is in the sample of @ratio of the files.
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+fn sampled(id: ObjectId, ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        return true;
+    }
+    let digest = chunk_digest(&id.bytes());
+    let mut value = [0; 8];
+    value.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(value) as f64) < ratio * u64::MAX as f64
+}
+
+/// The outcome of [`GridFSBucket::verify_all`].
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VerifyReport {
+    /// The number of files whose content was hashed.
+    pub files_verified: u64,
+    /// The number of bytes of chunks read.
+    pub bytes_read: u64,
+    /// The number of files not verified: out of the sample, tiered, without checksum, or
+    /// deleted during the job.
+    pub files_skipped: u64,
+    /// The files whose content doesn't match their checksum, or with a malformed chunk.
+    pub corrupted: Vec<ObjectId>,
+    /// The id of the last file the job went through.
+    pub resume_token: Option<ObjectId>,
+}
+
+/// `n files verified (size read), n skipped, n corrupted`.
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+impl Display for VerifyReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files verified ({} read), {} skipped, {} corrupted",
+            self.files_verified,
+            HumanSize(self.bytes_read),
+            self.files_skipped,
+            self.corrupted.len()
+        )
+    }
+}
+
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+impl GridFSBucket {
+    /**
+    Re-hashes the content of the stored files, in the order of their ids, and compares it
+    with their MD5 checksum, like the scrubbing of a filesystem. Returns the
+    [`VerifyReport`] of the job. Requires a tokio runtime.
+
+    The job is meant to run in a background task for as long as needed: its reads are
+    limited to [`VerifyOptions::max_bytes_per_sec`], and its progress is saved in the
+    `<bucket>.verify` collection after each file. A job started again after an interruption
+    resumes after the last file it went through; the progress is cleared once every file is
+    gone through. The corrupted files are quarantined when
+    [`GridFSBucketOptions::quarantine_corrupt`](crate::options::GridFSBucketOptions::quarantine_corrupt)
+    is set.
+
+    # Errors
+
+    Raise [`GridFSError::MongoError`] when the files can't be read or the progress saved.
+    */
+    pub async fn verify_all(&self, options: VerifyOptions) -> Result<VerifyReport, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let progress = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name.clone() + ".verify"));
+        let resume_token = match options.resume_token {
+            Some(resume_token) => Some(resume_token),
+            None => progress
+                .find_one(doc! {"_id":VERIFY_PROGRESS_ID}, None)
+                .await?
+                .and_then(|progress| progress.get_object_id("resumeToken").ok()),
+        };
+        let mut filter = doc! {"length":{"$exists":true}};
+        if let Some(resume_token) = resume_token {
+            filter.insert("_id", doc! {"$gt":resume_token});
+        }
+        let find_options = FindOptions::builder()
+            .sort(doc! {"_id":1})
+            .selection_criteria(dboptions.read_selection_criteria())
+            .no_cursor_timeout(true)
+            .build();
+        let mut cursor = self
+            .files_collection()
+            .find(visible(filter), find_options)
+            .await?;
+        let update_options = UpdateOptions::builder()
+            .upsert(true)
+            .write_concern(dboptions.write_concern)
+            .build();
+
+        let mut report = VerifyReport {
+            resume_token,
+            ..VerifyReport::default()
+        };
+        let start = Instant::now();
+        while let Some(document) = cursor.next().await {
+            let document = document?;
+            let file = FileInfo::try_from(document.clone())?;
+            let verified = match &file.md5 {
+                Some(expected)
+                    if document.get_str(TIER_FIELD).is_err()
+                        && sampled(file.id, options.sample_ratio) =>
+                {
+                    self.verify_file(&file, expected, &options, &mut report, start)
+                        .await
+                }
+                _ => Err(GridFSError::FileNotFound()),
+            };
+            match verified {
+                Ok(()) => report.files_verified += 1,
+                Err(GridFSError::FileNotFound()) => report.files_skipped += 1,
+                Err(GridFSError::InvalidChunk(_, _) | GridFSError::ChecksumMismatch { .. }) => {
+                    report.files_verified += 1;
+                    report.corrupted.push(file.id);
+                }
+                Err(error) => return Err(error),
+            }
+            report.resume_token = Some(file.id);
+            progress
+                .update_one(
+                    doc! {"_id":VERIFY_PROGRESS_ID},
+                    doc! {"$set":{"resumeToken":file.id, "updatedAt":self.now()}},
+                    update_options.clone(),
+                )
+                .await?;
+        }
+        progress
+            .delete_one(doc! {"_id":VERIFY_PROGRESS_ID}, None)
+            .await?;
+        Ok(report)
+    }
+
+    /// Hashes the content of @file and compares it with its @expected checksum, counting the
+    /// bytes read in @report, at the pace of @options since @start.
+    async fn verify_file(
+        &self,
+        file: &FileInfo,
+        expected: &str,
+        options: &VerifyOptions,
+        report: &mut VerifyReport,
+        start: Instant,
+    ) -> Result<(), GridFSError> {
+        let (mut chunks, _) = self
+            .open_chunk_stream(file.id, &GridFSDownloadOptions::default())
+            .await?;
+        let mut md5 = Md5::new();
+        while let Some(data) = chunks.next().await {
+            let data = data?;
+            md5.update(&data);
+            report.bytes_read += data.len() as u64;
+            if let Some(max_bytes_per_sec) = options.max_bytes_per_sec {
+                let due = Duration::from_secs_f64(
+                    report.bytes_read as f64 / max_bytes_per_sec.max(1) as f64,
+                );
+                tokio::time::sleep_until(start + due).await;
+            }
+        }
+        let actual = format!("{:02x}", md5.finalize());
+        if actual != expected.to_lowercase() {
+            let error = GridFSError::ChecksumMismatch {
+                expected: expected.to_lowercase(),
+                actual,
+            };
+            chunks.report(&error);
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_digest, chunks_to_verify, verify_written_chunks};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use super::{sampled, VERIFY_PROGRESS_ID};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use crate::options::VerifyOptions;
+    use crate::{
+        options::{GridFSBucketOptions, WriteVerification},
+        GridFSBucket, GridFSError,
+    };
+    use bson::{doc, oid::ObjectId, spec::BinarySubtype, Binary, Document};
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn chunks_to_verify_sample() {
+        let verification = WriteVerification::builder()
+            .full_read_max_length(16)
+            .sample_chunks(3)
+            .build();
+        assert_eq!(chunks_to_verify(&verification, 16, 4), vec![0, 1, 2, 3]);
+        assert_eq!(chunks_to_verify(&verification, 100, 3), vec![0, 1, 2]);
+
+        let sample = chunks_to_verify(&verification, 100, 25);
+        assert_eq!(sample.len(), 3);
+        assert!(sample.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(sample.iter().all(|n| *n < 25));
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[test]
+    fn sampled_ratio() {
+        let ids: Vec<ObjectId> = (0..1000).map(|_| ObjectId::new()).collect();
+        assert!(ids.iter().all(|id| sampled(*id, 1.0)));
+        assert!(!ids.iter().any(|id| sampled(*id, 0.0)));
+        let count = ids.iter().filter(|id| sampled(**id, 0.5)).count();
+        assert!((400..600).contains(&count));
+        assert!(ids.iter().all(|id| sampled(*id, 0.5) == sampled(*id, 0.5)));
+    }
+
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[tokio::test]
+    async fn verify_all() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let mut ids = vec![];
+        for content in ["test data", "other data", "more data"] {
+            ids.push(
+                bucket
+                    .upload_from_stream("test.txt", content.as_bytes(), None)
+                    .await?,
+            );
+        }
+        db.collection::<Document>("fs.chunks")
+            .update_one(
+                doc! {"files_id":ids[1], "n":0},
+                doc! {"$set":{"data":Binary{subtype: BinarySubtype::Generic, bytes: b"OTHE".to_vec()}}},
+                None,
+            )
+            .await?;
+
+        let report = bucket.verify_all(VerifyOptions::default()).await?;
+        assert_eq!(report.files_verified, 3);
+        assert_eq!(report.files_skipped, 0);
+        assert_eq!(report.bytes_read, 28);
+        assert_eq!(report.corrupted, vec![ids[1]]);
+        assert_eq!(report.resume_token, Some(ids[2]));
+        let progress = db.collection::<Document>("fs.verify");
+        assert_eq!(progress.count_documents(doc! {}, None).await?, 0);
+
+        // Resumed after an interruption.
+        progress
+            .insert_one(doc! {"_id":VERIFY_PROGRESS_ID, "resumeToken":ids[0]}, None)
+            .await?;
+        let report = bucket.verify_all(VerifyOptions::default()).await?;
+        assert_eq!(report.files_verified, 2);
+        let report = bucket
+            .verify_all(VerifyOptions::builder().resume_token(Some(ids[1])).build())
+            .await?;
+        assert_eq!(report.files_verified, 1);
+        assert!(report.corrupted.is_empty());
+
+        let report = bucket
+            .verify_all(
+                VerifyOptions::builder()
+                    .sample_ratio(0.0)
+                    .max_bytes_per_sec(Some(1000))
+                    .build(),
+            )
+            .await?;
+        assert_eq!(report.files_verified, 0);
+        assert_eq!(report.files_skipped, 3);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_on_write() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(4)
+                    .verify_on_write(Some(
+                        WriteVerification::builder()
+                            .full_read_max_length(0)
+                            .sample_chunks(2)
+                            .build(),
+                    ))
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        let files_id = ObjectId::new();
+        let chunks = db.collection::<Document>("fs.chunks");
+        assert_eq!(chunks.count_documents(doc! {"files_id":id}, None).await?, 3);
+
+        // A chunk corrupted on its way to the server, and a lost one.
+        chunks
+            .insert_many(
+                [(0, b"test"), (1, b" dxt")].map(|(n, bytes)| {
+                    doc! {
+                        "files_id":files_id,
+                        "n":n,
+                        "data":Binary{subtype:BinarySubtype::Generic, bytes:bytes.to_vec()},
+                    }
+                }),
+                None,
+            )
+            .await?;
+        let verification = WriteVerification::default();
+        let digests = [
+            chunk_digest(b"test"),
+            chunk_digest(b" dat"),
+            chunk_digest(b"a"),
+        ];
+        assert!(matches!(
+            verify_written_chunks(
+                &chunks,
+                doc! {"files_id":files_id},
+                &verification,
+                9,
+                &digests
+            )
+            .await,
+            Err(GridFSError::WriteVerificationFailed { n: 1 })
+        ));
+        assert!(matches!(
+            verify_written_chunks(
+                &chunks,
+                doc! {"files_id":files_id, "n":0},
+                &verification,
+                9,
+                &digests
+            )
+            .await,
+            Err(GridFSError::WriteVerificationFailed { n: 1 })
+        ));
+        assert!(verify_written_chunks(
+            &chunks,
+            doc! {"files_id":files_id, "n":0},
+            &verification,
+            4,
+            &digests[..1]
+        )
+        .await
+        .is_ok());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{
+    bucket::{
+        routing::{chunk_collection_of, chunk_filter, chunk_routing_projection, chunks_index_keys},
+        GridFSBucket,
+    },
+    GridFSError,
+};
+use bson::{doc, oid::ObjectId, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::StreamExt;
+use mongodb::options::{FindOneOptions, FindOptions, Hint};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use std::time::Duration;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::task::JoinHandle;
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio_stream::StreamExt;
+
+/// Handle on a running warmer started by [`GridFSBucket::start_warmer`].
+///
+/// The warmer stops when the handle is dropped.
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+pub struct Warmer {
+    task: JoinHandle<()>,
+}
+
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+impl Drop for Warmer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl GridFSBucket {
+    /**
+    Pulls the chunks of the file @id into the cache of the server before a burst of
+    downloads, and returns the number of chunks touched.
+
+    The chunks are walked through the index of the chunks and only their `_id` is
+    returned, so the server loads them without sending their data. The read honors the
+    `selection_criteria` of the bucket options: the warmed server is the one the downloads
+    read from.
+    */
+    pub async fn touch_chunks(&self, id: ObjectId) -> Result<u64, GridFSError> {
+        let dboptions = self.options.clone().unwrap_or_default();
+        let files = self
+            .db
+            .collection::<Document>(&(dboptions.bucket_name.clone() + ".files"));
+        let find_one_options = FindOneOptions::builder()
+            .projection(chunk_routing_projection(self.chunk_shard_key()))
+            .selection_criteria(dboptions.read_selection_criteria())
+            .build();
+        let file = files
+            .find_one(doc! {"_id":id}, find_one_options)
+            .await?
+            .unwrap_or_default();
+        let chunks = self
+            .db
+            .collection::<Document>(&chunk_collection_of(&file, &dboptions.bucket_name));
+
+        let find_options = FindOptions::builder()
+            .hint(Hint::Keys(chunks_index_keys(self.chunk_shard_key())))
+            .projection(doc! {"_id":1})
+            .selection_criteria(dboptions.read_selection_criteria())
+            .read_concern(dboptions.read_concern)
+            .build();
+        let mut cursor = chunks
+            .find(
+                chunk_filter(&file, id, self.chunk_shard_key()),
+                find_options,
+            )
+            .await?;
+        let mut touched = 0;
+        while let Some(chunk) = cursor.next().await {
+            chunk?;
+            touched += 1;
+        }
+        Ok(touched)
+    }
+
+    /**
+    Touches the chunks of the files @ids with [`GridFSBucket::touch_chunks`] every
+    @interval, in a background task, to keep them in the cache of the server.
+
+    A failed touch is retried at the next interval. The warmer stops when the bucket is
+    [shut down](GridFSBucket::shutdown).
+    */
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    pub fn start_warmer(&self, ids: Vec<ObjectId>, interval: Duration) -> Warmer {
+        let bucket = self.clone();
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if bucket.is_shut_down() {
+                    break;
+                }
+                for id in &ids {
+                    let _ = bucket.touch_chunks(*id).await;
+                }
+            }
+        });
+        Warmer { task }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridFSBucket;
+    use crate::{options::GridFSBucketOptions, GridFSError};
+    use bson::oid::ObjectId;
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[tokio::test]
+    async fn touch_chunks() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        assert_eq!(bucket.touch_chunks(id).await?, 3);
+        assert_eq!(bucket.touch_chunks(ObjectId::new()).await?, 0);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+//! The bucket names, checked against the rules of the MongoDB namespaces.
+//!
+//! The collections of a bucket are named after it, `<bucket>.files` and `<bucket>.chunks`
+//! and the other collections the bucket derives from its name, so the bucket name must make
+//! valid collection names: it isn't empty, has no `$` nor NUL character, no empty
+//! `.`-separated segment, doesn't start with `system.`, and the longest namespace
+//! `<database>.<bucket>.<suffix>` fits in the 255 bytes of the server.
+//!
+//! A name derived from user input, e.g. a tenant name, is made valid with [`escape`], which
+//! percent-encodes the characters a bucket name can't contain.
+use crate::{BucketNameViolation, GridFSError};
+
+/// The longest namespace, in bytes, accepted by the server.
+const MAX_NAMESPACE_LENGTH: usize = 255;
+
+/// The longest suffix the bucket appends to its name, the `.chunks.<key id>` of the chunks
+/// collections of a key rotation.
+const LONGEST_SUFFIX_LENGTH: usize = ".chunks.".len() + 32;
+
+/// The characters escaped by [`escape`].
+const ESCAPED: [char; 4] = ['%', '$', '.', '\0'];
+
+/// The longest bucket name, in bytes, in the database @db_name.
+pub fn max_length(db_name: &str) -> usize {
+    MAX_NAMESPACE_LENGTH.saturating_sub(db_name.len() + 1 + LONGEST_SUFFIX_LENGTH)
+}
+
+/// The rule of the namespaces broken by the bucket name @bucket_name in the database
+/// @db_name, None when the name is valid.
+pub fn violation(bucket_name: &str, db_name: &str) -> Option<BucketNameViolation> {
+    if bucket_name.is_empty() {
+        return Some(BucketNameViolation::Empty);
+    }
+    if let Some(c) = bucket_name.chars().find(|c| *c == '$' || *c == '\0') {
+        return Some(BucketNameViolation::ForbiddenCharacter(c));
+    }
+    if bucket_name.split('.').any(str::is_empty) {
+        return Some(BucketNameViolation::EmptySegment);
+    }
+    if bucket_name.starts_with("system.") {
+        return Some(BucketNameViolation::Reserved);
+    }
+    let max_length = max_length(db_name);
+    (bucket_name.len() > max_length).then_some(BucketNameViolation::TooLong(max_length))
+}
+
+/**
+ * Checks the bucket name @bucket_name in the database @db_name, see [`violation`].
+ *
+ * # Errors
+ *
+ * Raise [`GridFSError::InvalidBucketName`] when the name breaks a rule of the namespaces.
+ */
+pub fn check(bucket_name: &str, db_name: &str) -> Result<(), GridFSError> {
+    match violation(bucket_name, db_name) {
+        Some(violation) => Err(GridFSError::InvalidBucketName(
+            bucket_name.to_string(),
+            violation,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The bucket name for @name, with `%`, `$`, `.` and NUL percent-encoded as `%XX`: the escaped
+/// name of a non-empty @name is valid when it's short enough. Reversed by [`unescape`].
+pub fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match ESCAPED.contains(&c) {
+            true => escaped.push_str(&format!("%{:02X}", c as u32)),
+            false => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The name escaped in the bucket name @escaped by [`escape`], None when @escaped isn't an
+/// escaped name.
+pub fn unescape(escaped: &str) -> Option<String> {
+    let mut name = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            name.push(c);
+            continue;
+        }
+        let hex: String = chars.by_ref().take(2).collect();
+        let c = u8::from_str_radix(&hex, 16).ok().map(char::from)?;
+        if hex.len() != 2 || !ESCAPED.contains(&c) {
+            return None;
+        }
+        name.push(c);
+    }
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, escape, max_length, unescape, violation};
+    use crate::{BucketNameViolation, GridFSError};
+
+    #[test]
+    fn bucket_name_violation() {
+        assert_eq!(violation("fs", "test"), None);
+        assert_eq!(violation("images.thumbnails", "test"), None);
+        assert_eq!(violation("", "test"), Some(BucketNameViolation::Empty));
+        assert_eq!(
+            violation("a$b", "test"),
+            Some(BucketNameViolation::ForbiddenCharacter('$'))
+        );
+        assert_eq!(
+            violation("a\0b", "test"),
+            Some(BucketNameViolation::ForbiddenCharacter('\0'))
+        );
+        for name in [".fs", "fs.", "a..b"] {
+            assert_eq!(
+                violation(name, "test"),
+                Some(BucketNameViolation::EmptySegment)
+            );
+        }
+        assert_eq!(
+            violation("system.fs", "test"),
+            Some(BucketNameViolation::Reserved)
+        );
+        assert_eq!(violation("systems", "test"), None);
+
+        assert_eq!(max_length("test"), 210);
+        assert_eq!(violation(&"a".repeat(210), "test"), None);
+        assert_eq!(
+            violation(&"a".repeat(211), "test"),
+            Some(BucketNameViolation::TooLong(210))
+        );
+        assert!(matches!(
+            check("a$b", "test"),
+            Err(GridFSError::InvalidBucketName(name, BucketNameViolation::ForbiddenCharacter('$')))
+                if name == "a$b"
+        ));
+    }
+
+    #[test]
+    fn escape_bucket_name() {
+        assert_eq!(escape("images"), "images");
+        assert_eq!(escape("acme.com"), "acme%2Ecom");
+        assert_eq!(escape("100%$"), "100%25%24");
+        assert_eq!(escape("system.fs"), "system%2Efs");
+        assert_eq!(escape("a\0"), "a%00");
+        assert_eq!(escape("..été"), "%2E%2Eété");
+        for name in ["acme.com", "100%$", "system.fs", "a\0", "..été", ""] {
+            assert_eq!(unescape(&escape(name)).as_deref(), Some(name));
+        }
+        for name in ["acme.com", "100%$", "system.fs", "a\0", "..été"] {
+            assert_eq!(violation(&escape(name), "test"), None);
+        }
+        assert_eq!(unescape("a%2"), None);
+        assert_eq!(unescape("a%41"), None);
+        assert_eq!(unescape("a%zz"), None);
+    }
+}
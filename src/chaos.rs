@@ -0,0 +1,206 @@
+//! Fault injection for resilience testing. Requires the `test-util` feature and a tokio runtime.
+//!
+//! A [`ChaosBucket`] behaves like the [`GridFSBucket`] it wraps, except for the failures
+//! described by its [`ChaosOptions`]. Applications use it to test their retry and cleanup
+//! logic around GridFS.
+//!
+//! ```rust,no_run
+//! use mongodb_gridfs::{chaos::ChaosBucket, options::ChaosOptions, GridFSBucket};
+//! # use mongodb::Client;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), mongodb::error::Error> {
+//! # let db = Client::with_uri_str("mongodb://localhost:27017/").await?.database("test");
+//! let mut bucket = ChaosBucket::new(
+//!     GridFSBucket::new(db, None),
+//!     ChaosOptions::builder().fail_chunk_insert(Some(1)).build(),
+//! );
+//! let result = bucket
+//!     .upload_from_stream("test.txt", "test data".as_bytes(), None)
+//!     .await;
+//! # Ok(())
+//! # }
+//! ```
+use crate::{bucket::GridFSBucket, options::ChaosOptions, GridFSError};
+use std::{
+    io,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+/// A [`GridFSBucket`] injecting the failures of its [`ChaosOptions`].
+#[derive(Clone, Debug)]
+pub struct ChaosBucket {
+    bucket: GridFSBucket,
+}
+
+impl ChaosBucket {
+    /// Wraps @bucket, injecting the failures described by @options.
+    pub fn new(mut bucket: GridFSBucket, options: ChaosOptions) -> ChaosBucket {
+        bucket.chaos = Some(Arc::new(options));
+        ChaosBucket { bucket }
+    }
+
+    /// Returns the wrapped bucket, without failure injection.
+    pub fn into_inner(mut self) -> GridFSBucket {
+        self.bucket.chaos = None;
+        self.bucket
+    }
+}
+
+impl Deref for ChaosBucket {
+    type Target = GridFSBucket;
+
+    fn deref(&self) -> &GridFSBucket {
+        &self.bucket
+    }
+}
+
+impl DerefMut for ChaosBucket {
+    fn deref_mut(&mut self) -> &mut GridFSBucket {
+        &mut self.bucket
+    }
+}
+
+impl ChaosOptions {
+    pub(crate) async fn delay(&self) {
+        if let Some(latency) = self.latency {
+            tokio::time::sleep(latency).await;
+        }
+    }
+
+    pub(crate) fn inject_chunk_insert_failure(&self, n: u32) -> Result<(), mongodb::error::Error> {
+        if self.fail_chunk_insert == Some(n) {
+            return Err(
+                io::Error::other(format!("injected failure of the insert of chunk {}", n)).into(),
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn inject_download_failure(&self, yielded: usize) -> Result<(), GridFSError> {
+        if self.fail_download_after == Some(yielded) {
+            let error: mongodb::error::Error = io::Error::other(format!(
+                "injected download failure after {} chunks",
+                yielded
+            ))
+            .into();
+            return Err(error.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChaosBucket;
+    use crate::{
+        options::{ChaosOptions, GridFSBucketOptions},
+        GridFSBucket, GridFSError,
+    };
+    use bson::{doc, Document};
+    use mongodb::{Client, Database};
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn inject_failures() {
+        let options = ChaosOptions::builder()
+            .fail_chunk_insert(Some(2))
+            .fail_download_after(Some(1))
+            .build();
+        assert!(options.inject_chunk_insert_failure(0).is_ok());
+        assert!(options.inject_chunk_insert_failure(2).is_err());
+        assert!(options.inject_download_failure(0).is_ok());
+        assert!(options.inject_download_failure(1).is_err());
+    }
+
+    #[tokio::test]
+    async fn chaos_upload_and_download() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let mut chaos = ChaosBucket::new(
+            bucket,
+            ChaosOptions::builder()
+                .fail_chunk_insert(Some(1))
+                .fail_download_after(Some(1))
+                .build(),
+        );
+
+        let result = chaos
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await;
+        assert!(result.is_err());
+        let count = db
+            .collection::<Document>("fs.chunks")
+            .count_documents(doc! {}, None)
+            .await?;
+        assert_eq!(count, 1, "Only the first chunk should be inserted");
+
+        let mut bucket = chaos.into_inner();
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        let chaos = ChaosBucket::new(
+            bucket,
+            ChaosOptions::builder().fail_download_after(Some(1)).build(),
+        );
+        let mut cursor = chaos.open_download_stream(id).await?;
+        assert!(cursor.next().await.unwrap().is_ok());
+        assert!(cursor.next().await.unwrap().is_err());
+
+        db.drop(None).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_resumes_after_failure() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(4)
+                    .download_retries(1)
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        let chaos = ChaosBucket::new(
+            bucket,
+            ChaosOptions::builder().fail_download_after(Some(1)).build(),
+        );
+
+        let mut content = vec![];
+        let mut cursor = chaos.open_download_stream(id).await?;
+        while let Some(chunk) = cursor.next().await {
+            content.extend(chunk?);
+        }
+        assert_eq!(content, b"test data");
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+//! The source of the current time of a bucket.
+//!
+//! The bucket reads the time from the [`Clock`] set in
+//! [`GridFSBucketOptions::clock`](crate::options::GridFSBucketOptions::clock): for the
+//! `uploadDate` and the `expireAt` of the uploaded files, the expiry checks of the
+//! downloads, and the age of the files in [`GridFSBucket::tier_files`](crate::GridFSBucket::tier_files).
+//! A fixed or offset clock gives deterministic tests and backdated migrations without
+//! patching the system clock.
+use bson::DateTime;
+use std::fmt::{Debug, Formatter, Result};
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    /// The current time.
+    fn now(&self) -> DateTime;
+}
+
+/// The system clock, used by the buckets without [`Clock`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime {
+        DateTime::now()
+    }
+}
+
+impl Debug for dyn Clock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Clock")
+    }
+}
@@ -0,0 +1,43 @@
+//! Text extraction for the search of the content of the files. Requires the
+//! `content-search` feature.
+//!
+//! A [`TextExtractor`] set in
+//! [`GridFSBucketOptions::text_extractor`](crate::options::GridFSBucketOptions::text_extractor)
+//! starts a [`TextExtraction`] for every upload. The extraction receives the chunks of the
+//! file as they are read; its text is stored in the `<bucket>.content` collection, under a
+//! text index, once the file is committed.
+//! [`GridFSBucket::search_content`](crate::GridFSBucket::search_content) then finds the files
+//! from their text.
+use crate::FileInfo;
+use std::fmt::{Debug, Formatter, Result};
+
+/// Starts the text extraction of each upload of a bucket.
+pub trait TextExtractor: Send + Sync {
+    /// Starts the extraction of the upload of @filename. Returns `None` when the file has no
+    /// text representation, e.g. because of its type.
+    fn start(&self, filename: &str) -> Option<Box<dyn TextExtraction>>;
+}
+
+impl Debug for dyn TextExtractor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "TextExtractor")
+    }
+}
+
+/// The text extraction of one upload.
+pub trait TextExtraction: Send {
+    /// Reads the next @chunk of the file.
+    fn update(&mut self, chunk: &[u8]);
+
+    /// Returns the text of the file, once every chunk has been read.
+    fn finish(self: Box<Self>) -> Option<String>;
+}
+
+/// A file found by [`GridFSBucket::search_content`](crate::GridFSBucket::search_content).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentMatch {
+    /// The found file.
+    pub file: FileInfo,
+    /// The relevance of the file for the query.
+    pub score: f64,
+}
@@ -0,0 +1,52 @@
+use bson::oid::ObjectId;
+use std::fmt::{Display, Formatter, Result};
+
+/// A number of bytes in binary units: `512 B`, `1.5 KiB`, `3.0 MiB`.
+pub(crate) struct HumanSize(pub(crate) u64);
+
+impl Display for HumanSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// The last 8 hexadecimal digits of an id, its counter: enough to tell the files of a
+/// listing apart.
+pub(crate) struct ShortId(pub(crate) ObjectId);
+
+impl Display for ShortId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "…{}", &self.0.to_hex()[16..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HumanSize, ShortId};
+    use bson::oid::ObjectId;
+
+    #[test]
+    fn human_size() {
+        assert_eq!(HumanSize(0).to_string(), "0 B");
+        assert_eq!(HumanSize(1023).to_string(), "1023 B");
+        assert_eq!(HumanSize(1536).to_string(), "1.5 KiB");
+        assert_eq!(HumanSize(3 * 1024 * 1024).to_string(), "3.0 MiB");
+        assert_eq!(HumanSize(u64::MAX).to_string(), "16384.0 PiB");
+    }
+
+    #[test]
+    fn short_id() {
+        let id = ObjectId::parse_str("5f8f8c44b54764421b7156c9").unwrap();
+        assert_eq!(ShortId(id).to_string(), "…1b7156c9");
+    }
+}
@@ -0,0 +1,212 @@
+//! Client-side field level encryption (CSFLE) of the chunks.
+//!
+//! With a [`ChunkEncryption`] set in
+//! [`GridFSBucketOptions::chunk_encryption`](crate::options::GridFSBucketOptions::chunk_encryption),
+//! the `data` field of every chunk written by the bucket is explicitly encrypted with the data
+//! key of its key id, e.g. by a [`ChunkEncryptor`] calling the `ClientEncryption::encrypt` of
+//! the driver, and is stored as binary data of subtype 6. The files collection documents stay
+//! in the clear: the length and the checksum are those of the plain content. They record the
+//! key id of their chunks, so [`GridFSBucket::rotate_keys`](crate::GridFSBucket::rotate_keys)
+//! re-encrypts the files of a data key with another one.
+//!
+//! The chunks are decrypted by the driver when the database of the bucket belongs to a client
+//! configured with auto-encryption, e.g. with `bypass_auto_encryption` set when only the
+//! explicit encryption is used: the downloads then read the plain content. A chunk read still
+//! encrypted raises [`GridFSError::InvalidChunk`].
+use crate::GridFSError;
+use bson::{spec::BinarySubtype, Binary};
+use futures_util::future::BoxFuture;
+use std::{
+    fmt::{Debug, Formatter, Result},
+    sync::Arc,
+};
+
+/// Encrypts the data of the chunks, usually with the key vault and the KMS providers of a
+/// `ClientEncryption`.
+pub trait ChunkEncryptor: Send + Sync {
+    /// Encrypts the @data of a chunk with the data key @key_id. Returns the encrypted value,
+    /// binary data of subtype [`BinarySubtype::Encrypted`], or the reason of the failure.
+    fn encrypt<'a>(
+        &'a self,
+        key_id: &'a Binary,
+        data: Vec<u8>,
+    ) -> BoxFuture<'a, std::result::Result<Binary, String>>;
+}
+
+impl Debug for dyn ChunkEncryptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "ChunkEncryptor")
+    }
+}
+
+/// The explicit encryption of the `data` field of the chunks of a bucket.
+#[derive(Clone, Debug)]
+pub struct ChunkEncryption {
+    /// The id of the data key, a UUID, encrypting the chunks.
+    pub key_id: Binary,
+    /// Encrypts the data of the chunks with the data key.
+    pub encryptor: Arc<dyn ChunkEncryptor>,
+}
+
+impl ChunkEncryption {
+    /// Encrypts the chunks with the data key @key_id, by the @encryptor.
+    pub fn new(key_id: Binary, encryptor: Arc<dyn ChunkEncryptor>) -> Self {
+        ChunkEncryption { key_id, encryptor }
+    }
+}
+
+/// Field of the files collection document of a file with encrypted chunks, holding the id of
+/// their data key.
+pub(crate) const ENCRYPTION_KEY_FIELD: &str = "encryptionKeyId";
+
+/// The `data` field of a chunk holding @bytes: binary data of @subtype, encrypted when the
+/// bucket has an @encryption.
+pub(crate) async fn chunk_data_field(
+    encryption: Option<&ChunkEncryption>,
+    subtype: BinarySubtype,
+    bytes: Vec<u8>,
+) -> std::result::Result<Binary, GridFSError> {
+    match encryption {
+        None => Ok(Binary { subtype, bytes }),
+        Some(encryption) => {
+            let data = encryption
+                .encryptor
+                .encrypt(&encryption.key_id, bytes)
+                .await
+                .map_err(|reason| GridFSError::EncryptionFailed { reason })?;
+            if data.subtype != BinarySubtype::Encrypted {
+                return Err(GridFSError::EncryptionFailed {
+                    reason: format!("binary subtype {:?} isn't encrypted", data.subtype),
+                });
+            }
+            Ok(data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_data_field, ChunkEncryption, ChunkEncryptor};
+    use crate::{
+        bucket::GridFSBucket, file_info::get_number, options::GridFSBucketOptions, GridFSError,
+        GridFSErrorCode,
+    };
+    use bson::{doc, spec::BinarySubtype, Binary, Bson, Document};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use futures_util::future::BoxFuture;
+    use mongodb::{Client, Database};
+    use std::sync::Arc;
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    struct Reverse(BinarySubtype);
+
+    impl ChunkEncryptor for Reverse {
+        fn encrypt<'a>(
+            &'a self,
+            key_id: &'a Binary,
+            mut data: Vec<u8>,
+        ) -> BoxFuture<'a, Result<Binary, String>> {
+            Box::pin(async move {
+                data.reverse();
+                data.extend(&key_id.bytes);
+                Ok(Binary {
+                    subtype: self.0,
+                    bytes: data,
+                })
+            })
+        }
+    }
+
+    fn key_id() -> Binary {
+        Binary {
+            subtype: BinarySubtype::Uuid,
+            bytes: vec![7; 16],
+        }
+    }
+
+    #[tokio::test]
+    async fn chunk_data_field_encrypts() -> Result<(), GridFSError> {
+        assert_eq!(
+            chunk_data_field(None, BinarySubtype::Generic, vec![1, 2, 3]).await?,
+            Binary {
+                subtype: BinarySubtype::Generic,
+                bytes: vec![1, 2, 3]
+            }
+        );
+
+        let encryption = ChunkEncryption::new(key_id(), Arc::new(Reverse(BinarySubtype::Encrypted)));
+        let data = chunk_data_field(Some(&encryption), BinarySubtype::Generic, vec![1, 2, 3]).await?;
+        assert_eq!(data.subtype, BinarySubtype::Encrypted);
+        assert_eq!(data.bytes[..3], [3, 2, 1]);
+
+        let encryption = ChunkEncryption::new(key_id(), Arc::new(Reverse(BinarySubtype::Generic)));
+        let error = chunk_data_field(Some(&encryption), BinarySubtype::Generic, vec![1])
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), GridFSErrorCode::Encryption);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_encrypted_chunks() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut bucket = GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .chunk_size_bytes(4)
+                    .chunk_encryption(Some(ChunkEncryption::new(
+                        key_id(),
+                        Arc::new(Reverse(BinarySubtype::Encrypted)),
+                    )))
+                    .build(),
+            ),
+        );
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+
+        let chunk = db
+            .collection::<Document>("fs.chunks")
+            .find_one(doc! {"files_id":id, "n":0}, None)
+            .await?
+            .unwrap();
+        let data = match chunk.get("data") {
+            Some(Bson::Binary(data)) => data.clone(),
+            data => panic!("data is {:?}", data),
+        };
+        assert_eq!(data.subtype, BinarySubtype::Encrypted);
+        assert_eq!(data.bytes[..4], *b"tset");
+        let file = db
+            .collection::<Document>("fs.files")
+            .find_one(doc! {"_id":id}, None)
+            .await?
+            .unwrap();
+        assert_eq!(get_number(&file, "length"), Some(9));
+
+        // Without auto-encryption, the client can't decrypt the chunks.
+        let mut cursor = bucket.open_download_stream(id).await?;
+        assert!(matches!(
+            cursor.next().await,
+            Some(Err(GridFSError::InvalidChunk(0, _)))
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
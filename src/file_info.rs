@@ -0,0 +1,308 @@
+use crate::{
+    display::{HumanSize, ShortId},
+    GridFSError,
+};
+use bson::{oid::ObjectId, Bson, DateTime, Document};
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+    time::SystemTime,
+};
+
+/// Reads the number stored at @key. Other drivers store numbers as int32, int64 or
+/// double: all of them are accepted. Returns `None` when @key is missing or isn't a number.
+pub(crate) fn get_number(document: &Document, key: &str) -> Option<i64> {
+    match document.get(key) {
+        Some(Bson::Int32(value)) => Some(*value as i64),
+        Some(Bson::Int64(value)) => Some(*value),
+        Some(Bson::Double(value)) if value.is_finite() => Some(*value as i64),
+        _ => None,
+    }
+}
+
+/// Whether the file or chunk @document has an `expireAt` date before @now.
+pub(crate) fn is_expired(document: &Document, now: DateTime) -> bool {
+    match document.get_datetime("expireAt") {
+        Ok(expire_at) => *expire_at <= now,
+        Err(_) => false,
+    }
+}
+
+/// The files collection document of a stored file.
+/// [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#definitions)
+///
+/// `length` and `chunkSize` are decoded whatever their numeric type, so files written by
+/// other drivers are readable.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FileInfo {
+    /// The id of the file.
+    pub id: ObjectId,
+    /// The length of the file in bytes.
+    pub length: u64,
+    /// The size of the chunks of the file in bytes.
+    pub chunk_size: u32,
+    /// The date the file was uploaded. `None` while the upload is in progress.
+    pub upload_date: Option<DateTime>,
+    /// The name of the file. Optional in the spec.
+    pub filename: Option<String>,
+    /// The MD5 checksum of the file, when computed at upload.
+    pub md5: Option<String>,
+    /// The user data of the file.
+    pub metadata: Option<Document>,
+    /// The date the file expires, for buckets with
+    /// [`GridFSBucketOptions::expire_after`](crate::options::GridFSBucketOptions::expire_after).
+    pub expire_at: Option<DateTime>,
+    /// The state of the file. The files stored without state are available.
+    pub status: FileStatus,
+}
+
+/// The state of a file, managed by the crate in the `status` field of the files collection
+/// document. Only the available files are seen by the readers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum FileStatus {
+    /// Reserved by [`GridFSBucket::reserve_id`](crate::GridFSBucket::reserve_id), or uploaded
+    /// but not published yet.
+    Pending,
+    /// Published: seen by the readers.
+    #[default]
+    Available,
+    /// Archived: kept, but not seen by the readers anymore.
+    Archived,
+    /// Deleted by [`GridFSBucket::delete_async`](crate::GridFSBucket::delete_async): hidden
+    /// from the readers while its chunks are removed.
+    Deleting,
+    /// Corrupted, set aside by [`GridFSBucket::quarantine`](crate::GridFSBucket::quarantine):
+    /// kept for triage, but not seen by the readers.
+    Quarantined,
+}
+
+impl FileStatus {
+    /// The value of the `status` field for this state.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileStatus::Pending => "pending",
+            FileStatus::Available => "available",
+            FileStatus::Archived => "archived",
+            FileStatus::Deleting => "deleting",
+            FileStatus::Quarantined => "quarantined",
+        }
+    }
+}
+
+/// `name (…id, size, uploaded date)`, e.g. `report.pdf (…1b7156c9, 1.5 MiB, uploaded
+/// 2020-10-21T01:23:45Z)`. The state of the files other than available follows.
+impl Display for FileInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}, {}, ",
+            self.filename.as_deref().unwrap_or("<unnamed>"),
+            ShortId(self.id),
+            HumanSize(self.length)
+        )?;
+        match self.upload_date.map(|date| date.try_to_rfc3339_string()) {
+            Some(Ok(date)) => write!(f, "uploaded {}", date)?,
+            Some(Err(_)) => write!(f, "uploaded")?,
+            None => write!(f, "upload in progress")?,
+        }
+        match self.status {
+            FileStatus::Available => write!(f, ")"),
+            status => write!(f, ", {})", status.as_str()),
+        }
+    }
+}
+
+impl FileInfo {
+    /// The date the file was uploaded, as a [`SystemTime`].
+    pub fn upload_system_time(&self) -> Option<SystemTime> {
+        self.upload_date.map(DateTime::to_system_time)
+    }
+
+    /// The date the file expires, as a [`SystemTime`].
+    pub fn expire_system_time(&self) -> Option<SystemTime> {
+        self.expire_at.map(DateTime::to_system_time)
+    }
+
+    /// The date the file was uploaded, as a `time` date. Requires the `time` feature.
+    #[cfg(feature = "time")]
+    pub fn upload_offset_date_time(&self) -> Option<time::OffsetDateTime> {
+        self.upload_date.map(DateTime::to_time_0_3)
+    }
+
+    /// The date the file expires, as a `time` date. Requires the `time` feature.
+    #[cfg(feature = "time")]
+    pub fn expire_offset_date_time(&self) -> Option<time::OffsetDateTime> {
+        self.expire_at.map(DateTime::to_time_0_3)
+    }
+
+    /// The trace context of the upload of the file, stored with the `otel` feature.
+    #[cfg(feature = "otel")]
+    pub fn trace_context(&self) -> Option<opentelemetry::Context> {
+        let metadata = self.metadata.as_ref()?;
+        opentelemetry::global::get_text_map_propagator(|propagator| {
+            crate::otel::extract(propagator, metadata)
+        })
+    }
+}
+
+impl TryFrom<Document> for FileInfo {
+    type Error = GridFSError;
+
+    fn try_from(document: Document) -> Result<Self, Self::Error> {
+        let id = document
+            .get_object_id("_id")
+            .map_err(|_| GridFSError::InvalidFile("_id isn't an ObjectId".into()))?;
+        let length = match get_number(&document, "length") {
+            Some(length) if length >= 0 => length as u64,
+            _ => return Err(GridFSError::InvalidFile("invalid length".into())),
+        };
+        let chunk_size = match get_number(&document, "chunkSize") {
+            Some(chunk_size) if chunk_size > 0 && chunk_size <= u32::MAX as i64 => chunk_size as u32,
+            _ => return Err(GridFSError::InvalidFile("invalid chunkSize".into())),
+        };
+        Ok(FileInfo {
+            id,
+            length,
+            chunk_size,
+            upload_date: document.get_datetime("uploadDate").ok().copied(),
+            filename: document.get_str("filename").ok().map(str::to_string),
+            md5: document.get_str("md5").ok().map(str::to_string),
+            metadata: document.get_document("metadata").ok().cloned(),
+            expire_at: document.get_datetime("expireAt").ok().copied(),
+            status: match document.get_str("status") {
+                Ok("pending") => FileStatus::Pending,
+                Ok("archived") => FileStatus::Archived,
+                Ok("deleting") => FileStatus::Deleting,
+                Ok("quarantined") => FileStatus::Quarantined,
+                _ => FileStatus::Available,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_number, is_expired, FileInfo, FileStatus};
+    use crate::GridFSError;
+    use bson::{doc, oid::ObjectId, DateTime};
+    use std::{
+        convert::TryFrom,
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn get_number_any_type() {
+        let document = doc! {"int32": 4_i32, "int64": 5_i64, "double": 6.0, "string": "7"};
+        assert_eq!(get_number(&document, "int32"), Some(4));
+        assert_eq!(get_number(&document, "int64"), Some(5));
+        assert_eq!(get_number(&document, "double"), Some(6));
+        assert_eq!(get_number(&document, "string"), None);
+        assert_eq!(get_number(&document, "missing"), None);
+    }
+
+    #[test]
+    fn file_info_from_other_drivers() {
+        let id = ObjectId::new();
+        for (length, chunk_size) in [
+            (bson::Bson::Int32(9), bson::Bson::Int32(4)),
+            (bson::Bson::Int64(9), bson::Bson::Int64(4)),
+            (bson::Bson::Double(9.0), bson::Bson::Double(4.0)),
+        ] {
+            let info = FileInfo::try_from(
+                doc! {"_id": id, "length": length, "chunkSize": chunk_size, "filename": "test.txt"},
+            )
+            .unwrap();
+            assert_eq!(info.id, id);
+            assert_eq!(info.length, 9);
+            assert_eq!(info.chunk_size, 4);
+            assert_eq!(info.filename.as_deref(), Some("test.txt"));
+            assert_eq!(info.upload_date, None);
+        }
+    }
+
+    #[test]
+    fn file_info_invalid() {
+        let id = ObjectId::new();
+        assert!(matches!(
+            FileInfo::try_from(doc! {"_id": id, "chunkSize": 4}),
+            Err(GridFSError::InvalidFile(_))
+        ));
+        assert!(matches!(
+            FileInfo::try_from(doc! {"_id": id, "length": 9, "chunkSize": 0}),
+            Err(GridFSError::InvalidFile(_))
+        ));
+        assert!(matches!(
+            FileInfo::try_from(doc! {"_id": "id", "length": 9, "chunkSize": 4}),
+            Err(GridFSError::InvalidFile(_))
+        ));
+    }
+
+    #[test]
+    fn file_info_display() {
+        let id = ObjectId::parse_str("5f8f8c44b54764421b7156c9").unwrap();
+        let mut info = FileInfo::try_from(doc! {
+            "_id": id,
+            "length": 1536,
+            "chunkSize": 4,
+            "filename": "test.txt",
+            "uploadDate": DateTime::from_millis(1_603_243_425_000),
+        })
+        .unwrap();
+        assert_eq!(
+            info.to_string(),
+            "test.txt (…1b7156c9, 1.5 KiB, uploaded 2020-10-21T01:23:45Z)"
+        );
+        info.filename = None;
+        info.upload_date = None;
+        info.status = FileStatus::Pending;
+        assert_eq!(
+            info.to_string(),
+            "<unnamed> (…1b7156c9, 1.5 KiB, upload in progress, pending)"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn file_info_serialize() {
+        let id = ObjectId::new();
+        let mut info = FileInfo::try_from(doc! {"_id": id, "length": 9, "chunkSize": 4}).unwrap();
+        info.status = FileStatus::Pending;
+        let document = bson::to_document(&info).unwrap();
+        assert_eq!(document.get_object_id("id").unwrap(), id);
+        assert_eq!(document.get_i64("length").unwrap(), 9);
+        assert_eq!(document.get_str("status").unwrap(), "pending");
+    }
+
+    #[test]
+    fn file_info_dates() {
+        let upload_date = DateTime::from_millis(1_603_243_425_000);
+        let info = FileInfo::try_from(
+            doc! {"_id": ObjectId::new(), "length": 9, "chunkSize": 4, "uploadDate": upload_date},
+        )
+        .unwrap();
+        assert_eq!(
+            info.upload_system_time(),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_603_243_425))
+        );
+        assert_eq!(info.expire_system_time(), None);
+        #[cfg(feature = "time")]
+        assert_eq!(
+            info.upload_offset_date_time()
+                .map(|date| date.unix_timestamp()),
+            Some(1_603_243_425)
+        );
+    }
+
+    #[test]
+    fn expired() {
+        let past = DateTime::from_millis(DateTime::now().timestamp_millis() - 1000);
+        let future = DateTime::from_millis(DateTime::now().timestamp_millis() + 60_000);
+        let now = DateTime::now();
+        assert!(is_expired(&doc! {"expireAt": past}, now));
+        assert!(!is_expired(&doc! {"expireAt": future}, now));
+        assert!(!is_expired(&doc! {}, now));
+    }
+}
@@ -0,0 +1,313 @@
+//! Deterministic test fixtures. Requires the `test-util` feature.
+//!
+//! [`Fixtures`] seeds a bucket with files of given lengths and content [`Pattern`]s, and
+//! optionally damages some of them with a [`Corruption`], so the error paths of an
+//! application, like a missing chunk or a wrong checksum, are exercised reproducibly. The
+//! content of a file only depends on its length and pattern.
+//!
+//! ```rust,no_run
+//! use mongodb_gridfs::{
+//!     fixtures::{Corruption, Fixtures, Pattern},
+//!     GridFSBucket,
+//! };
+//! # use mongodb::Client;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), mongodb_gridfs::GridFSError> {
+//! # let db = Client::with_uri_str("mongodb://localhost:27017/").await?.database("test");
+//! let bucket = GridFSBucket::new(db, None);
+//! let files = Fixtures::new()
+//!     .files(10, 1024 * 1024, Pattern::Seeded(42))
+//!     .corrupted_file("broken.bin", 1024, Pattern::Counter, Corruption::MissingChunk(0))
+//!     .seed(&bucket)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::{bucket::GridFSBucket, GridFSError};
+use bson::{doc, oid::ObjectId, spec::BinarySubtype, Binary};
+
+/// The content of a fixture file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    /// Every byte is this value.
+    Repeat(u8),
+    /// The byte at offset `i` is `i % 256`.
+    Counter,
+    /// Pseudo-random bytes generated from the seed.
+    Seeded(u64),
+}
+
+impl Pattern {
+    /// The @length bytes of content of this pattern.
+    pub fn content(&self, length: usize) -> Vec<u8> {
+        match *self {
+            Pattern::Repeat(byte) => vec![byte; length],
+            Pattern::Counter => (0..length).map(|i| i as u8).collect(),
+            Pattern::Seeded(seed) => {
+                // xorshift64*, never seeded with 0.
+                let mut state = seed | 1;
+                (0..length)
+                    .map(|_| {
+                        state ^= state >> 12;
+                        state ^= state << 25;
+                        state ^= state >> 27;
+                        (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The damage done to a fixture file once it's uploaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// The chunk `n` is deleted.
+    MissingChunk(u32),
+    /// The last byte of the chunk `n` is removed.
+    TruncatedChunk(u32),
+    /// The `length` of the files collection document is one byte more than the content.
+    WrongLength,
+    /// The `md5` of the files collection document doesn't match the content.
+    BadMd5,
+}
+
+/// A file to seed.
+#[derive(Clone, Debug)]
+struct FixtureFile {
+    filename: String,
+    length: usize,
+    pattern: Pattern,
+    corruption: Option<Corruption>,
+}
+
+/// A file seeded by [`Fixtures::seed`].
+#[derive(Clone, Debug)]
+pub struct SeededFile {
+    pub id: ObjectId,
+    pub filename: String,
+    /// The uploaded content, before any corruption.
+    pub content: Vec<u8>,
+    pub corruption: Option<Corruption>,
+}
+
+/// The files to seed in a bucket, in order.
+#[derive(Clone, Debug, Default)]
+pub struct Fixtures {
+    files: Vec<FixtureFile>,
+}
+
+impl Fixtures {
+    /// No files.
+    pub fn new() -> Fixtures {
+        Fixtures::default()
+    }
+
+    /// Adds the file @filename of @length bytes of @pattern.
+    pub fn file(mut self, filename: &str, length: usize, pattern: Pattern) -> Fixtures {
+        self.files.push(FixtureFile {
+            filename: filename.to_string(),
+            length,
+            pattern,
+            corruption: None,
+        });
+        self
+    }
+
+    /// Adds @count files of @length bytes of @pattern, named `file-0` to `file-<count - 1>`.
+    pub fn files(mut self, count: usize, length: usize, pattern: Pattern) -> Fixtures {
+        for i in 0..count {
+            self = self.file(&format!("file-{}", i), length, pattern);
+        }
+        self
+    }
+
+    /// Adds the file @filename of @length bytes of @pattern, damaged by @corruption.
+    pub fn corrupted_file(
+        mut self,
+        filename: &str,
+        length: usize,
+        pattern: Pattern,
+        corruption: Corruption,
+    ) -> Fixtures {
+        self = self.file(filename, length, pattern);
+        if let Some(file) = self.files.last_mut() {
+            file.corruption = Some(corruption);
+        }
+        self
+    }
+
+    /**
+    Uploads the files in @bucket, then applies their corruptions. Returns the seeded files,
+    in order.
+
+    # Errors
+
+    Raise [`GridFSError::MongoError`] when an upload or a corruption fails.
+    */
+    pub async fn seed(&self, bucket: &GridFSBucket) -> Result<Vec<SeededFile>, GridFSError> {
+        let mut seeded = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            let content = file.pattern.content(file.length);
+            let id = bucket
+                .clone()
+                .upload_from_stream(&file.filename, content.as_slice(), None)
+                .await?;
+            if let Some(corruption) = file.corruption {
+                corrupt(bucket, id, corruption).await?;
+            }
+            seeded.push(SeededFile {
+                id,
+                filename: file.filename.clone(),
+                content,
+                corruption: file.corruption,
+            });
+        }
+        Ok(seeded)
+    }
+}
+
+/// Damages the uploaded file @id with @corruption.
+async fn corrupt(
+    bucket: &GridFSBucket,
+    id: ObjectId,
+    corruption: Corruption,
+) -> Result<(), GridFSError> {
+    let files = bucket.files_collection();
+    let chunks = bucket.chunks_collection();
+    match corruption {
+        Corruption::MissingChunk(n) => {
+            chunks
+                .delete_one(doc! {"files_id":id, "n":n as i32}, None)
+                .await?;
+        }
+        Corruption::TruncatedChunk(n) => {
+            let filter = doc! {"files_id":id, "n":n as i32};
+            if let Some(chunk) = chunks.find_one(filter.clone(), None).await? {
+                let mut data = chunk
+                    .get_binary_generic("data")
+                    .map_err(|_| GridFSError::InvalidChunk(n as i64, "data isn't binary".into()))?
+                    .clone();
+                data.pop();
+                chunks
+                    .update_one(
+                        filter,
+                        doc! {"$set":{"data":Binary{subtype: BinarySubtype::Generic, bytes: data}}},
+                        None,
+                    )
+                    .await?;
+            }
+        }
+        Corruption::WrongLength => {
+            files
+                .update_one(doc! {"_id":id}, doc! {"$inc":{"length":1_i64}}, None)
+                .await?;
+        }
+        Corruption::BadMd5 => {
+            files
+                .update_one(
+                    doc! {"_id":id},
+                    doc! {"$set":{"md5":"00000000000000000000000000000000"}},
+                    None,
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Corruption, Fixtures, Pattern};
+    use crate::{bucket::GridFSBucket, options::GridFSBucketOptions, GridFSError};
+    use bson::{doc, Document};
+    use mongodb::{Client, Database};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    #[test]
+    fn pattern_content() {
+        assert_eq!(Pattern::Repeat(7).content(3), vec![7, 7, 7]);
+        assert_eq!(Pattern::Counter.content(258)[255..], [255, 0, 1]);
+        assert_eq!(
+            Pattern::Seeded(1).content(64),
+            Pattern::Seeded(1).content(64)
+        );
+        assert_ne!(
+            Pattern::Seeded(1).content(64),
+            Pattern::Seeded(2).content(64)
+        );
+    }
+
+    #[tokio::test]
+    async fn seed() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let seeded = Fixtures::new()
+            .files(2, 10, Pattern::Counter)
+            .corrupted_file(
+                "missing",
+                10,
+                Pattern::Repeat(1),
+                Corruption::MissingChunk(1),
+            )
+            .corrupted_file(
+                "truncated",
+                10,
+                Pattern::Repeat(1),
+                Corruption::TruncatedChunk(0),
+            )
+            .corrupted_file("length", 10, Pattern::Repeat(1), Corruption::WrongLength)
+            .corrupted_file("md5", 10, Pattern::Repeat(1), Corruption::BadMd5)
+            .seed(&bucket)
+            .await?;
+        assert_eq!(seeded.len(), 6);
+        assert_eq!(seeded[1].filename, "file-1");
+        assert_eq!(seeded[1].content, Pattern::Counter.content(10));
+
+        let files = db.collection::<Document>("fs.files");
+        let chunks = db.collection::<Document>("fs.chunks");
+        assert_eq!(
+            chunks
+                .count_documents(doc! {"files_id":seeded[2].id}, None)
+                .await?,
+            2
+        );
+        let chunk = chunks
+            .find_one(doc! {"files_id":seeded[3].id, "n":0}, None)
+            .await?
+            .unwrap();
+        assert_eq!(chunk.get_binary_generic("data").unwrap().len(), 3);
+        let file = files
+            .find_one(doc! {"_id":seeded[4].id}, None)
+            .await?
+            .unwrap();
+        assert_eq!(file.get_i64("length").unwrap(), 11);
+        let file = files
+            .find_one(doc! {"_id":seeded[5].id}, None)
+            .await?
+            .unwrap();
+        assert_eq!(
+            file.get_str("md5").unwrap(),
+            "00000000000000000000000000000000"
+        );
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,858 @@
+//! Read-only FUSE mount of a bucket. Requires the `fuse` feature, Linux and the tokio
+//! runtime. Experimental.
+//!
+//! [`mount`] presents the files of a bucket as a file system: the path of a file is its
+//! filename, whose `/` separated segments are directories, and only the latest revision of
+//! a filename is listed. A read fetches the chunks covering the read range with
+//! [`GridFSBucket::read_range`], so the files aren't downloaded whole, e.g. to inspect the
+//! data of a production bucket with the usual tools. The listing is read again from the
+//! files collection when it's older than a second.
+//!
+//! The kernel protocol is served on `/dev/fuse` without libfuse. The file system is mounted
+//! with the `mount` system call when the process has the `CAP_SYS_ADMIN` capability, and
+//! with the `fusermount3` or `fusermount` helper of the FUSE package otherwise.
+use crate::{
+    bucket::status::visible,
+    file_info::{get_number, is_expired},
+    GridFSBucket, GridFSError,
+};
+use bson::{doc, oid::ObjectId, DateTime, Document};
+use mongodb::options::FindOptions;
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+    ffi::CString,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    mem,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{AsRawFd, FromRawFd, RawFd},
+        net::UnixStream,
+        process::CommandExt,
+    },
+    path::{Path, PathBuf},
+    process::Command,
+    ptr,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tokio::runtime::Handle;
+use tokio_stream::StreamExt;
+
+/// How long the kernel and the session keep the listing.
+const REFRESH: Duration = Duration::from_secs(1);
+/// The inode of the mountpoint.
+const ROOT: u64 = 1;
+/// The largest write the kernel sends: none with a read-only mount.
+const MAX_WRITE: u32 = 128 * 1024;
+/// The kernel protocol version, the minor one is the oldest with the current layouts.
+const KERNEL_VERSION: u32 = 7;
+const KERNEL_MINOR_VERSION: u32 = 23;
+const MAX_KERNEL_MINOR_VERSION: u32 = 31;
+
+const IN_HEADER_LENGTH: usize = 40;
+const OUT_HEADER_LENGTH: usize = 16;
+const DIRENT_LENGTH: usize = 24;
+
+const FUSE_LOOKUP: u32 = 1;
+const FUSE_FORGET: u32 = 2;
+const FUSE_GETATTR: u32 = 3;
+const FUSE_OPEN: u32 = 14;
+const FUSE_READ: u32 = 15;
+const FUSE_STATFS: u32 = 17;
+const FUSE_RELEASE: u32 = 18;
+const FUSE_FSYNC: u32 = 20;
+const FUSE_FLUSH: u32 = 25;
+const FUSE_INIT: u32 = 26;
+const FUSE_OPENDIR: u32 = 27;
+const FUSE_READDIR: u32 = 28;
+const FUSE_RELEASEDIR: u32 = 29;
+const FUSE_FSYNCDIR: u32 = 30;
+const FUSE_ACCESS: u32 = 34;
+const FUSE_INTERRUPT: u32 = 36;
+const FUSE_DESTROY: u32 = 38;
+const FUSE_BATCH_FORGET: u32 = 42;
+
+/// A node of the mounted tree.
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    /// The inodes of the entries of a directory, by name.
+    Directory(BTreeMap<String, u64>),
+    /// The latest revision of a filename.
+    File {
+        id: ObjectId,
+        length: u64,
+        upload_date: DateTime,
+    },
+}
+
+/// A file of the bucket, listed in the tree.
+struct Entry {
+    filename: String,
+    id: ObjectId,
+    length: u64,
+    upload_date: DateTime,
+}
+
+/// The directories and the files of the bucket, by inode.
+struct Tree {
+    nodes: HashMap<u64, Node>,
+    /// The inodes of the paths, kept while the file system is mounted.
+    inodes: HashMap<String, u64>,
+}
+
+/// The path segments of @filename, without the empty and the relative ones. None when a
+/// segment isn't a valid file name.
+fn segments(filename: &str) -> Option<Vec<&str>> {
+    let segments: Vec<&str> = filename
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != "." && *segment != "..")
+        .collect();
+    let valid = !segments.is_empty()
+        && segments
+            .iter()
+            .all(|segment| segment.len() <= 255 && !segment.contains('\0'));
+    valid.then_some(segments)
+}
+
+impl Tree {
+    fn new() -> Self {
+        let mut tree = Tree {
+            nodes: HashMap::new(),
+            inodes: HashMap::new(),
+        };
+        tree.inode("");
+        tree.nodes.insert(ROOT, Node::Directory(BTreeMap::new()));
+        tree
+    }
+
+    /// The inode of @path, allocated on its first use.
+    fn inode(&mut self, path: &str) -> u64 {
+        let next = self.inodes.len() as u64 + ROOT;
+        *self.inodes.entry(path.to_string()).or_insert(next)
+    }
+
+    /// Replaces the nodes with the @entries, sorted by filename and latest revision first.
+    /// A filename which is also the directory of other filenames is hidden by the directory.
+    fn rebuild(&mut self, entries: &[Entry]) {
+        let mut nodes = HashMap::new();
+        nodes.insert(ROOT, Node::Directory(BTreeMap::new()));
+        let paths: Vec<(Vec<&str>, &Entry)> = entries
+            .iter()
+            .filter_map(|entry| segments(&entry.filename).map(|segments| (segments, entry)))
+            .collect();
+
+        let mut files = Vec::new();
+        for (segments, entry) in &paths {
+            let (name, directories) = segments.split_last().unwrap();
+            let mut parent = ROOT;
+            let mut path = String::new();
+            for directory in directories {
+                path = path + "/" + directory;
+                let inode = self.inode(&path);
+                if let Some(Node::Directory(children)) = nodes.get_mut(&parent) {
+                    children.insert(directory.to_string(), inode);
+                }
+                nodes
+                    .entry(inode)
+                    .or_insert_with(|| Node::Directory(BTreeMap::new()));
+                parent = inode;
+            }
+            files.push((parent, path + "/" + name, *name, *entry));
+        }
+        for (parent, path, name, entry) in files {
+            let inode = self.inode(&path);
+            if let Some(Node::Directory(children)) = nodes.get_mut(&parent) {
+                if children.contains_key(name) {
+                    continue;
+                }
+                children.insert(name.to_string(), inode);
+            }
+            nodes.insert(
+                inode,
+                Node::File {
+                    id: entry.id,
+                    length: entry.length,
+                    upload_date: entry.upload_date,
+                },
+            );
+        }
+        self.nodes = nodes;
+    }
+}
+
+fn read_u32(body: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(
+        body.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(body: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_ne_bytes(
+        body.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// A request of the kernel.
+#[derive(Debug, PartialEq)]
+struct Request<'a> {
+    opcode: u32,
+    unique: u64,
+    nodeid: u64,
+    body: &'a [u8],
+}
+
+impl<'a> Request<'a> {
+    fn parse(message: &'a [u8]) -> Option<Self> {
+        let length = read_u32(message, 0)? as usize;
+        Some(Request {
+            opcode: read_u32(message, 4)?,
+            unique: read_u64(message, 8)?,
+            nodeid: read_u64(message, 16)?,
+            body: message.get(IN_HEADER_LENGTH..length)?,
+        })
+    }
+}
+
+/// The reply to the request @unique: its body, or the errno of the failure.
+fn encode_reply(unique: u64, reply: &Result<Vec<u8>, i32>) -> Vec<u8> {
+    let (error, body) = match reply {
+        Ok(body) => (0, body.as_slice()),
+        Err(errno) => (-errno, &[][..]),
+    };
+    let mut message = Vec::with_capacity(OUT_HEADER_LENGTH + body.len());
+    message.extend(((OUT_HEADER_LENGTH + body.len()) as u32).to_ne_bytes());
+    message.extend(error.to_ne_bytes());
+    message.extend(unique.to_ne_bytes());
+    message.extend(body);
+    message
+}
+
+/// Appends the directory entry @name to a `READDIR` reply, if it fits in @size bytes.
+fn encode_dirent(
+    out: &mut Vec<u8>,
+    size: usize,
+    inode: u64,
+    next: u64,
+    kind: u32,
+    name: &str,
+) -> bool {
+    let length = (DIRENT_LENGTH + name.len()).div_ceil(8) * 8;
+    if out.len() + length > size {
+        return false;
+    }
+    out.extend(inode.to_ne_bytes());
+    out.extend(next.to_ne_bytes());
+    out.extend((name.len() as u32).to_ne_bytes());
+    out.extend(kind.to_ne_bytes());
+    out.extend(name.as_bytes());
+    out.resize(out.len() + length - DIRENT_LENGTH - name.len(), 0);
+    true
+}
+
+/// The errno of a failed read.
+fn errno(error: &GridFSError) -> i32 {
+    match error {
+        GridFSError::FileNotFound() | GridFSError::FileExpired() => libc::ENOENT,
+        _ => libc::EIO,
+    }
+}
+
+/// The file system served to the kernel.
+struct Session {
+    bucket: GridFSBucket,
+    runtime: Handle,
+    tree: Tree,
+    refreshed: Option<Instant>,
+    /// The files opened by the kernel, by file handle.
+    handles: HashMap<u64, ObjectId>,
+    next_handle: u64,
+    uid: u32,
+    gid: u32,
+    mounted_at: DateTime,
+}
+
+impl Session {
+    /// Lists the latest revision of the filenames again when the listing is outdated.
+    fn refresh(&mut self) -> Result<(), i32> {
+        if self
+            .refreshed
+            .is_some_and(|refreshed| refreshed.elapsed() < REFRESH)
+        {
+            return Ok(());
+        }
+        let bucket = self.bucket.clone();
+        let entries = self
+            .runtime
+            .block_on(async move {
+                let find_options = FindOptions::builder()
+                    .sort(doc! {"filename":1, "uploadDate":-1})
+                    .projection(doc! {"filename":1, "length":1, "uploadDate":1, "expireAt":1})
+                    .build();
+                let mut cursor = bucket
+                    .files_collection()
+                    .find(visible(doc! {}), find_options)
+                    .await?;
+                let now = bucket.now();
+                let mut entries = Vec::new();
+                while let Some(file) = cursor.next().await {
+                    let file: Document = file?;
+                    if is_expired(&file, now) {
+                        continue;
+                    }
+                    if let (Ok(filename), Ok(id), Ok(upload_date)) = (
+                        file.get_str("filename"),
+                        file.get_object_id("_id"),
+                        file.get_datetime("uploadDate"),
+                    ) {
+                        entries.push(Entry {
+                            filename: filename.to_string(),
+                            id,
+                            length: get_number(&file, "length").unwrap_or(0).max(0) as u64,
+                            upload_date: *upload_date,
+                        });
+                    }
+                }
+                Ok::<_, GridFSError>(entries)
+            })
+            .map_err(|error| errno(&error))?;
+        self.tree.rebuild(&entries);
+        self.refreshed = Some(Instant::now());
+        Ok(())
+    }
+
+    fn node(&self, inode: u64) -> Result<&Node, i32> {
+        self.tree.nodes.get(&inode).ok_or(libc::ENOENT)
+    }
+
+    /// The `fuse_attr` of the node @inode.
+    fn attr(&self, inode: u64, node: &Node) -> Vec<u8> {
+        let (size, mode, nlink, date) = match node {
+            Node::Directory(_) => (0, libc::S_IFDIR | 0o555, 2, self.mounted_at),
+            Node::File {
+                length,
+                upload_date,
+                ..
+            } => (*length, libc::S_IFREG | 0o444, 1, *upload_date),
+        };
+        let millis = date.timestamp_millis();
+        let (seconds, nanos) = (
+            millis.div_euclid(1000) as u64,
+            (millis.rem_euclid(1000) * 1_000_000) as u32,
+        );
+        let mut attr = Vec::with_capacity(88);
+        for value in [inode, size, size.div_ceil(512), seconds, seconds, seconds] {
+            attr.extend(value.to_ne_bytes());
+        }
+        for value in [
+            nanos, nanos, nanos, mode, nlink, self.uid, self.gid, 0, 4096, 0,
+        ] {
+            attr.extend(value.to_ne_bytes());
+        }
+        attr
+    }
+
+    /// The `fuse_entry_out` of the node @inode.
+    fn entry(&self, inode: u64) -> Result<Vec<u8>, i32> {
+        let mut out = Vec::with_capacity(128);
+        for value in [inode, 0, REFRESH.as_secs(), REFRESH.as_secs()] {
+            out.extend(value.to_ne_bytes());
+        }
+        out.extend([0; 8]);
+        out.extend(self.attr(inode, self.node(inode)?));
+        Ok(out)
+    }
+
+    fn init(&self, body: &[u8]) -> Result<Vec<u8>, i32> {
+        let (major, minor, max_readahead) = (
+            read_u32(body, 0).ok_or(libc::EINVAL)?,
+            read_u32(body, 4).ok_or(libc::EINVAL)?,
+            read_u32(body, 8).ok_or(libc::EINVAL)?,
+        );
+        if major != KERNEL_VERSION || minor < KERNEL_MINOR_VERSION {
+            return Err(libc::EPROTO);
+        }
+        let mut out = Vec::with_capacity(64);
+        for value in [major, minor.min(MAX_KERNEL_MINOR_VERSION), max_readahead, 0] {
+            out.extend(value.to_ne_bytes());
+        }
+        // max_background and congestion_threshold.
+        out.extend(16_u16.to_ne_bytes());
+        out.extend(12_u16.to_ne_bytes());
+        // max_write and time_gran.
+        out.extend(MAX_WRITE.to_ne_bytes());
+        out.extend(1_u32.to_ne_bytes());
+        out.resize(64, 0);
+        Ok(out)
+    }
+
+    fn lookup(&mut self, parent: u64, body: &[u8]) -> Result<Vec<u8>, i32> {
+        let name = body.split(|byte| *byte == 0).next().unwrap_or_default();
+        let name = std::str::from_utf8(name).map_err(|_| libc::ENOENT)?;
+        self.refresh()?;
+        match self.node(parent)? {
+            Node::Directory(children) => {
+                let inode = *children.get(name).ok_or(libc::ENOENT)?;
+                self.entry(inode)
+            }
+            Node::File { .. } => Err(libc::ENOTDIR),
+        }
+    }
+
+    fn getattr(&mut self, inode: u64) -> Result<Vec<u8>, i32> {
+        self.refresh()?;
+        let mut out = Vec::with_capacity(104);
+        out.extend(REFRESH.as_secs().to_ne_bytes());
+        out.extend([0; 8]);
+        out.extend(self.attr(inode, self.node(inode)?));
+        Ok(out)
+    }
+
+    fn open(&mut self, inode: u64, body: &[u8]) -> Result<Vec<u8>, i32> {
+        let flags = read_u32(body, 0).ok_or(libc::EINVAL)? as i32;
+        if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            return Err(libc::EROFS);
+        }
+        let id = match self.node(inode)? {
+            Node::File { id, .. } => *id,
+            Node::Directory(_) => return Err(libc::EISDIR),
+        };
+        self.next_handle += 1;
+        self.handles.insert(self.next_handle, id);
+        let mut out = self.next_handle.to_ne_bytes().to_vec();
+        out.extend([0; 8]);
+        Ok(out)
+    }
+
+    fn read(&self, body: &[u8]) -> Result<Vec<u8>, i32> {
+        let (handle, offset, size) = (
+            read_u64(body, 0).ok_or(libc::EINVAL)?,
+            read_u64(body, 8).ok_or(libc::EINVAL)?,
+            read_u32(body, 16).ok_or(libc::EINVAL)?,
+        );
+        let id = *self.handles.get(&handle).ok_or(libc::EBADF)?;
+        self.runtime
+            .block_on(self.bucket.read_range(id, offset, size as usize, None))
+            .map_err(|error| errno(&error))
+    }
+
+    fn readdir(&self, inode: u64, body: &[u8]) -> Result<Vec<u8>, i32> {
+        let (offset, size) = (
+            read_u64(body, 8).ok_or(libc::EINVAL)?,
+            read_u32(body, 16).ok_or(libc::EINVAL)? as usize,
+        );
+        let children = match self.node(inode)? {
+            Node::Directory(children) => children,
+            Node::File { .. } => return Err(libc::ENOTDIR),
+        };
+        let directories = [(".", inode), ("..", inode)];
+        let entries = directories
+            .iter()
+            .copied()
+            .chain(children.iter().map(|(name, inode)| (name.as_str(), *inode)));
+        let mut out = Vec::new();
+        for (index, (name, inode)) in entries.enumerate().skip(offset as usize) {
+            let kind = match self.tree.nodes.get(&inode) {
+                Some(Node::File { .. }) => libc::DT_REG,
+                _ => libc::DT_DIR,
+            };
+            if !encode_dirent(&mut out, size, inode, index as u64 + 1, kind as u32, name) {
+                break;
+            }
+        }
+        Ok(out)
+    }
+
+    fn statfs(&self) -> Vec<u8> {
+        let mut out = vec![0; 40];
+        for value in [4096_u32, 255, 4096] {
+            out.extend(value.to_ne_bytes());
+        }
+        out.resize(80, 0);
+        out
+    }
+
+    /// The reply to the request @opcode, None when the kernel doesn't wait for a reply.
+    fn dispatch(&mut self, request: &Request) -> Option<Result<Vec<u8>, i32>> {
+        let (inode, body) = (request.nodeid, request.body);
+        Some(match request.opcode {
+            FUSE_FORGET | FUSE_BATCH_FORGET | FUSE_INTERRUPT => return None,
+            FUSE_INIT => self.init(body),
+            FUSE_LOOKUP => self.lookup(inode, body),
+            FUSE_GETATTR => self.getattr(inode),
+            FUSE_OPEN => self.open(inode, body),
+            FUSE_READ => self.read(body),
+            FUSE_RELEASE => {
+                if let Some(handle) = read_u64(body, 0) {
+                    self.handles.remove(&handle);
+                }
+                Ok(vec![])
+            }
+            FUSE_OPENDIR => match self.node(inode) {
+                Ok(Node::Directory(_)) => Ok(vec![0; 16]),
+                Ok(Node::File { .. }) => Err(libc::ENOTDIR),
+                Err(errno) => Err(errno),
+            },
+            FUSE_READDIR => self.readdir(inode, body),
+            FUSE_STATFS => Ok(self.statfs()),
+            FUSE_ACCESS => match read_u32(body, 0) {
+                Some(mask) if mask as i32 & libc::W_OK != 0 => Err(libc::EROFS),
+                _ => Ok(vec![]),
+            },
+            FUSE_RELEASEDIR | FUSE_FLUSH | FUSE_FSYNC | FUSE_FSYNCDIR | FUSE_DESTROY => Ok(vec![]),
+            _ => Err(libc::ENOSYS),
+        })
+    }
+
+    /// Serves the requests read from @device until the file system is unmounted.
+    fn serve(mut self, mut device: File) -> io::Result<()> {
+        let mut buffer = vec![0; MAX_WRITE as usize + 4096];
+        loop {
+            let length = match device.read(&mut buffer) {
+                Ok(length) => length,
+                // An interrupted request, or an interrupted read.
+                Err(error)
+                    if error.kind() == io::ErrorKind::Interrupted
+                        || error.raw_os_error() == Some(libc::ENOENT) =>
+                {
+                    continue
+                }
+                Err(error) if error.raw_os_error() == Some(libc::ENODEV) => return Ok(()),
+                Err(error) => return Err(error),
+            };
+            let request = Request::parse(&buffer[..length]).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "malformed FUSE request")
+            })?;
+            if let Some(reply) = self.dispatch(&request) {
+                match device.write_all(&encode_reply(request.unique, &reply)) {
+                    Err(error) if error.raw_os_error() == Some(libc::ENODEV) => return Ok(()),
+                    // The request was interrupted.
+                    Err(error) if error.raw_os_error() != Some(libc::ENOENT) => return Err(error),
+                    _ => {}
+                }
+            }
+            if request.opcode == FUSE_DESTROY {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)
+}
+
+/// Mounts a FUSE file system on @mountpoint with the `mount` system call. Returns the
+/// opened `/dev/fuse`.
+fn mount_device(mountpoint: &Path, uid: u32, gid: u32) -> io::Result<File> {
+    let device = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/fuse")?;
+    let options = CString::new(format!(
+        "fd={},rootmode=40000,user_id={},group_id={}",
+        device.as_raw_fd(),
+        uid,
+        gid
+    ))
+    .map_err(io::Error::other)?;
+    let target = c_path(mountpoint)?;
+    let (source, fstype) = (CString::new("gridfs")?, CString::new("fuse.gridfs")?);
+    // SAFETY: the strings are NUL terminated and outlive the call.
+    let result = unsafe {
+        libc::mount(
+            source.as_ptr(),
+            target.as_ptr(),
+            fstype.as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV | libc::MS_RDONLY,
+            options.as_ptr().cast(),
+        )
+    };
+    match result {
+        0 => Ok(device),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Receives the `/dev/fuse` descriptor sent by `fusermount` on @socket.
+fn receive_device(socket: &UnixStream) -> io::Result<File> {
+    let mut byte = [0_u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: byte.len(),
+    };
+    let mut control = [0_u64; 8];
+    // SAFETY: a zeroed msghdr is valid, the buffers outlive the call and the control
+    // message is only read when the kernel wrote one.
+    unsafe {
+        let mut message: libc::msghdr = mem::zeroed();
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr().cast();
+        message.msg_controllen = mem::size_of_val(&control) as _;
+        if libc::recvmsg(socket.as_raw_fd(), &mut message, 0) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let header = libc::CMSG_FIRSTHDR(&message);
+        if header.is_null() || (*header).cmsg_type != libc::SCM_RIGHTS {
+            return Err(io::Error::other("fusermount didn't send the FUSE device"));
+        }
+        let fd: RawFd = ptr::read_unaligned(libc::CMSG_DATA(header).cast());
+        Ok(File::from_raw_fd(fd))
+    }
+}
+
+/// Mounts a FUSE file system on @mountpoint with the first `fusermount` helper found.
+/// Returns the `/dev/fuse` it opened and the helper.
+fn mount_with_helper(mountpoint: &Path) -> io::Result<(File, &'static str)> {
+    let mut failure = io::Error::new(io::ErrorKind::NotFound, "fusermount isn't installed");
+    for helper in ["fusermount3", "fusermount"] {
+        let (socket, helper_socket) = UnixStream::pair()?;
+        let fd = helper_socket.as_raw_fd();
+        let mut command = Command::new(helper);
+        command
+            .args(["-o", "ro,nosuid,nodev,fsname=gridfs,subtype=gridfs", "--"])
+            .arg(mountpoint)
+            .env("_FUSE_COMMFD", fd.to_string());
+        // SAFETY: fcntl is async-signal-safe. The socket is inherited by the helper.
+        unsafe {
+            command.pre_exec(move || match libc::fcntl(fd, libc::F_SETFD, 0) {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            });
+        }
+        match command.status() {
+            Ok(status) if status.success() => {
+                drop(helper_socket);
+                return Ok((receive_device(&socket)?, helper));
+            }
+            Ok(status) => failure = io::Error::other(format!("{} failed: {}", helper, status)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => failure = error,
+        }
+    }
+    Err(failure)
+}
+
+/// A bucket mounted by [`mount`]. The file system is unmounted by [`Mount::unmount`], or
+/// lazily when the mount is dropped.
+pub struct Mount {
+    mountpoint: PathBuf,
+    /// The `fusermount` helper which mounted the file system, None when it was mounted with
+    /// the `mount` system call.
+    helper: Option<&'static str>,
+    session: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Mount {
+    /// The directory on which the bucket is mounted.
+    pub fn mountpoint(&self) -> &Path {
+        &self.mountpoint
+    }
+
+    /// Detaches the file system: it's unmounted once the files opened on it are closed.
+    fn detach(&self) -> io::Result<()> {
+        match self.helper {
+            Some(helper) => {
+                let status = Command::new(helper)
+                    .args(["-u", "-z", "--"])
+                    .arg(&self.mountpoint)
+                    .status()?;
+                match status.success() {
+                    true => Ok(()),
+                    false => Err(io::Error::other(format!("{} failed: {}", helper, status))),
+                }
+            }
+            None => {
+                let target = c_path(&self.mountpoint)?;
+                // SAFETY: the path is NUL terminated and outlives the call.
+                match unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) } {
+                    0 => Ok(()),
+                    _ => Err(io::Error::last_os_error()),
+                }
+            }
+        }
+    }
+
+    /**
+    Unmounts the file system, and waits for the end of its session once the files opened on
+    it are closed.
+
+    # Errors
+
+    Raise the [`io::Error`] of the unmount, or of the session with the kernel.
+    */
+    pub async fn unmount(mut self) -> io::Result<()> {
+        let session = match self.session.take() {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+        // Already unmounted when the session is over.
+        if let Err(error) = self.detach() {
+            if !session.is_finished() {
+                return Err(error);
+            }
+        }
+        tokio::task::spawn_blocking(move || session.join())
+            .await
+            .map_err(io::Error::other)?
+            .map_err(|_| io::Error::other("the FUSE session panicked"))?
+    }
+}
+
+impl Drop for Mount {
+    fn drop(&mut self) {
+        if self.session.is_some() {
+            let _ = self.detach();
+        }
+    }
+}
+
+/**
+Mounts the @bucket read-only on the directory @mountpoint: the path of a file is its
+filename. See the [module documentation](self). Must be called from a tokio runtime, which
+runs the queries of the file system.
+
+# Errors
+
+Raise the [`io::Error`] of the mount, e.g. when neither the `CAP_SYS_ADMIN` capability nor
+the `fusermount` helper is available.
+*/
+pub fn mount(bucket: GridFSBucket, mountpoint: impl AsRef<Path>) -> io::Result<Mount> {
+    let runtime = Handle::try_current().map_err(io::Error::other)?;
+    let mountpoint = mountpoint.as_ref().to_path_buf();
+    // SAFETY: getuid and getgid can't fail.
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let (device, helper) = match mount_device(&mountpoint, uid, gid) {
+        Ok(device) => (device, None),
+        Err(error) if error.raw_os_error() == Some(libc::EPERM) => {
+            let (device, helper) = mount_with_helper(&mountpoint)?;
+            (device, Some(helper))
+        }
+        Err(error) => return Err(error),
+    };
+    let session = Session {
+        mounted_at: bucket.now(),
+        bucket,
+        runtime,
+        tree: Tree::new(),
+        refreshed: None,
+        handles: HashMap::new(),
+        next_handle: 0,
+        uid,
+        gid,
+    };
+    let mut mount = Mount {
+        mountpoint,
+        helper,
+        session: None,
+    };
+    match thread::Builder::new()
+        .name("gridfs-fuse".to_string())
+        .spawn(move || session.serve(device))
+    {
+        Ok(session) => mount.session = Some(session),
+        Err(error) => {
+            let _ = mount.detach();
+            return Err(error);
+        }
+    }
+    Ok(mount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_dirent, encode_reply, segments, Entry, Node, Request, Tree, ROOT};
+    use bson::{oid::ObjectId, DateTime};
+
+    fn entry(filename: &str) -> Entry {
+        Entry {
+            filename: filename.to_string(),
+            id: ObjectId::new(),
+            length: 4,
+            upload_date: DateTime::from_millis(0),
+        }
+    }
+
+    fn children(tree: &Tree, inode: u64) -> Vec<&str> {
+        match &tree.nodes[&inode] {
+            Node::Directory(children) => children.keys().map(|name| name.as_str()).collect(),
+            Node::File { .. } => panic!("not a directory"),
+        }
+    }
+
+    #[test]
+    fn filename_segments() {
+        assert_eq!(segments("a/b.txt"), Some(vec!["a", "b.txt"]));
+        assert_eq!(segments("/a//./b.txt"), Some(vec!["a", "b.txt"]));
+        assert_eq!(segments("../a"), Some(vec!["a"]));
+        assert_eq!(segments("/"), None);
+        assert_eq!(segments(&"a".repeat(256)), None);
+        assert_eq!(segments("a\0b"), None);
+    }
+
+    #[test]
+    fn tree_rebuild() {
+        let mut tree = Tree::new();
+        let entries = vec![
+            entry("a"),
+            entry("a/b.txt"),
+            entry("c.txt"),
+            // An older revision.
+            entry("c.txt"),
+        ];
+        tree.rebuild(&entries);
+        // The directory hides the file.
+        assert_eq!(children(&tree, ROOT), vec!["a", "c.txt"]);
+        let directory = tree.inodes["/a"];
+        assert_eq!(children(&tree, directory), vec!["b.txt"]);
+        match &tree.nodes[&tree.inodes["/c.txt"]] {
+            Node::File { id, .. } => assert_eq!(*id, entries[2].id),
+            Node::Directory(_) => panic!("not a file"),
+        }
+
+        // The inodes are kept across the refreshes, and the removed files are gone.
+        tree.rebuild(&[entry("d.txt"), entry("a/b.txt")]);
+        assert_eq!(tree.inodes["/a"], directory);
+        assert_eq!(children(&tree, ROOT), vec!["a", "d.txt"]);
+        assert!(!tree.nodes.contains_key(&tree.inodes["/c.txt"]));
+    }
+
+    #[test]
+    fn protocol_messages() {
+        let mut message = vec![];
+        for value in [46_u32, 1] {
+            message.extend(value.to_ne_bytes());
+        }
+        for value in [7_u64, ROOT] {
+            message.extend(value.to_ne_bytes());
+        }
+        message.extend([0; 16]);
+        message.extend(b"a.txt\0");
+        message.extend([0; 2]);
+        assert_eq!(
+            Request::parse(&message),
+            Some(Request {
+                opcode: 1,
+                unique: 7,
+                nodeid: ROOT,
+                body: b"a.txt\0",
+            })
+        );
+        assert_eq!(Request::parse(&message[..20]), None);
+
+        let reply = encode_reply(7, &Err(libc::ENOENT));
+        assert_eq!(reply.len(), 16);
+        assert_eq!(reply[..4], 16_u32.to_ne_bytes());
+        assert_eq!(reply[4..8], (-libc::ENOENT).to_ne_bytes());
+        assert_eq!(encode_reply(7, &Ok(vec![1, 2])).len(), 18);
+
+        // The entries are aligned on 8 bytes, and stop at the requested size.
+        let mut out = vec![];
+        assert!(encode_dirent(&mut out, 48, 2, 1, 8, "a.txt"));
+        assert_eq!(out.len(), 32);
+        assert!(!encode_dirent(&mut out, 48, 3, 2, 8, "b.txt"));
+        assert_eq!(out.len(), 32);
+    }
+}
@@ -0,0 +1,360 @@
+//! A reference gRPC service on a bucket, with [tonic](https://docs.rs/tonic).
+//!
+//! [`GridFSService`] serves the `gridfs.GridFs` service:
+//! - `Upload` uploads a file from a stream of [`UploadRequest`] frames, the first one naming
+//!   the file, and returns its id;
+//! - `Download` streams the chunks of a file as [`DownloadResponse`] frames;
+//! - `List` streams the [`FileEntry`] of the files, optionally of a filename.
+//!
+//! The service and its client are generated in [`grid_fs_server`] and [`grid_fs_client`],
+//! without the transport of tonic: the server is mounted in the application's own tonic
+//! server or router. Requires the `examples-extra` feature and a tokio runtime.
+//!
+//! ```rust,no_run
+//! # use mongodb::Client;
+//! use mongodb_gridfs::{
+//!     grpc::{grid_fs_server::GridFsServer, GridFSService},
+//!     options::GridFSBucketOptions,
+//!     GridFSBucket, GridFSError,
+//! };
+//! #
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), GridFSError> {
+//! #     let client = Client::with_uri_str("mongodb://localhost:27017/").await?;
+//! #     let db = client.database("test");
+//! let bucket = GridFSBucket::new(db.clone(), Some(GridFSBucketOptions::default()));
+//! let server = GridFsServer::new(GridFSService::new(bucket));
+//! // e.g. tonic::transport::Server::builder().add_service(server).serve(address)
+//! #     Ok(())
+//! # }
+//! ```
+use crate::{options::GridFSFindOptions, FileInfo, GridFSBucket, GridFSError, GridFSErrorCode};
+use bson::{doc, oid::ObjectId};
+use bytes::Bytes;
+use std::{convert::TryFrom, pin::Pin};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+include!(concat!(env!("OUT_DIR"), "/gridfs.GridFs.rs"));
+
+/// A frame of an upload.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct UploadRequest {
+    /// The name of the file, in the first frame.
+    #[prost(string, tag = "1")]
+    pub filename: String,
+    /// The next bytes of the file.
+    #[prost(bytes = "bytes", tag = "2")]
+    pub data: Bytes,
+}
+
+/// The uploaded file.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct UploadResponse {
+    /// The id of the file, in hexadecimal.
+    #[prost(string, tag = "1")]
+    pub id: String,
+}
+
+/// The file to download.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DownloadRequest {
+    /// The id of the file, in hexadecimal.
+    #[prost(string, tag = "1")]
+    pub id: String,
+}
+
+/// A chunk of a download.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DownloadResponse {
+    /// The bytes of the chunk.
+    #[prost(bytes = "bytes", tag = "1")]
+    pub data: Bytes,
+}
+
+/// The files to list.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListRequest {
+    /// Only lists the revisions of this filename.
+    #[prost(string, optional, tag = "1")]
+    pub filename: Option<String>,
+}
+
+/// A listed file.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FileEntry {
+    /// The id of the file, in hexadecimal.
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, optional, tag = "2")]
+    pub filename: Option<String>,
+    #[prost(uint64, tag = "3")]
+    pub length: u64,
+    #[prost(uint32, tag = "4")]
+    pub chunk_size: u32,
+    /// The upload date, in milliseconds since the epoch.
+    #[prost(int64, optional, tag = "5")]
+    pub upload_date: Option<i64>,
+    #[prost(string, optional, tag = "6")]
+    pub md5: Option<String>,
+}
+
+impl From<FileInfo> for FileEntry {
+    fn from(file: FileInfo) -> Self {
+        FileEntry {
+            id: file.id.to_hex(),
+            filename: file.filename,
+            length: file.length,
+            chunk_size: file.chunk_size,
+            upload_date: file.upload_date.map(|date| date.timestamp_millis()),
+            md5: file.md5,
+        }
+    }
+}
+
+/// The gRPC status of @error.
+fn status(error: GridFSError) -> Status {
+    let message = error.to_string();
+    match error.code() {
+        GridFSErrorCode::FileNotFound
+        | GridFSErrorCode::FileExpired
+        | GridFSErrorCode::ParentNotFound => Status::not_found(message),
+        GridFSErrorCode::InvalidFilename
+        | GridFSErrorCode::InvalidBucketName
+        | GridFSErrorCode::InvalidChunkSize
+        | GridFSErrorCode::InvalidConfiguration
+        | GridFSErrorCode::InvalidPageToken
+        | GridFSErrorCode::LengthMismatch => Status::invalid_argument(message),
+        GridFSErrorCode::DuplicateKey => Status::already_exists(message),
+        GridFSErrorCode::ContentRejected => Status::failed_precondition(message),
+        GridFSErrorCode::FileTooLarge => Status::resource_exhausted(message),
+        GridFSErrorCode::BucketBusy | GridFSErrorCode::Network | GridFSErrorCode::ShuttingDown => {
+            Status::unavailable(message)
+        }
+        GridFSErrorCode::Authentication => Status::unauthenticated(message),
+        _ => Status::internal(message),
+    }
+}
+
+/// Parses the hexadecimal @id of a request.
+fn parse_id(id: &str) -> Result<ObjectId, Status> {
+    ObjectId::parse_str(id).map_err(|error| Status::invalid_argument(error.to_string()))
+}
+
+/// Serves the `gridfs.GridFs` gRPC service on a bucket.
+#[derive(Clone, Debug)]
+pub struct GridFSService {
+    bucket: GridFSBucket,
+}
+
+impl GridFSService {
+    /// Serves the files of @bucket.
+    pub fn new(bucket: GridFSBucket) -> Self {
+        GridFSService { bucket }
+    }
+
+    /// Uploads the file of the @frames, through [`GridFSBucket::channel_upload`]. The upload is
+    /// aborted when the stream of the client fails: the file is never committed.
+    async fn upload_frames<S>(&self, mut frames: S) -> Result<UploadResponse, Status>
+    where
+        S: Stream<Item = Result<UploadRequest, Status>> + Unpin,
+    {
+        let first = frames
+            .next()
+            .await
+            .transpose()?
+            .ok_or_else(|| Status::invalid_argument("the upload has no frame"))?;
+        if first.filename.is_empty() {
+            return Err(Status::invalid_argument("the first frame has no filename"));
+        }
+        let (sender, upload) = self.bucket.channel_upload(&first.filename, None);
+        let sent = async {
+            let mut frame = Some(first);
+            while let Some(UploadRequest { data, .. }) = frame {
+                if sender.send(data).await.is_err() {
+                    // The upload failed: its error is returned below.
+                    break;
+                }
+                frame = frames.next().await.transpose()?;
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(status) = sent {
+            upload.abort();
+            return Err(status);
+        }
+        drop(sender);
+        let id = upload
+            .await
+            .map_err(|error| Status::internal(error.to_string()))?
+            .map_err(status)?;
+        Ok(UploadResponse { id: id.to_hex() })
+    }
+}
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl grid_fs_server::GridFs for GridFSService {
+    async fn upload(
+        &self,
+        request: Request<Streaming<UploadRequest>>,
+    ) -> Result<Response<UploadResponse>, Status> {
+        self.upload_frames(request.into_inner())
+            .await
+            .map(Response::new)
+    }
+
+    type DownloadStream = ResponseStream<DownloadResponse>;
+
+    async fn download(
+        &self,
+        request: Request<DownloadRequest>,
+    ) -> Result<Response<Self::DownloadStream>, Status> {
+        let id = parse_id(&request.get_ref().id)?;
+        let chunks = self
+            .bucket
+            .open_download_stream_bytes(id, None)
+            .await
+            .map_err(status)?;
+        let frames = chunks.map(|data| data.map(|data| DownloadResponse { data }).map_err(status));
+        Ok(Response::new(Box::pin(frames)))
+    }
+
+    type ListStream = ResponseStream<FileEntry>;
+
+    async fn list(
+        &self,
+        request: Request<ListRequest>,
+    ) -> Result<Response<Self::ListStream>, Status> {
+        let filter = match &request.get_ref().filename {
+            Some(filename) => doc! {"filename":filename},
+            None => doc! {},
+        };
+        let files = self
+            .bucket
+            .find(filter, GridFSFindOptions::default())
+            .await
+            .map_err(|error| status(error.into()))?;
+        let entries = files.map(|document| {
+            let document = document.map_err(|error| status(error.into()))?;
+            FileInfo::try_from(document)
+                .map(FileEntry::from)
+                .map_err(status)
+        });
+        Ok(Response::new(Box::pin(entries)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        grid_fs_server::GridFs, status, DownloadRequest, FileEntry, GridFSService, ListRequest,
+        UploadRequest,
+    };
+    use crate::{bucket::GridFSBucket, options::GridFSBucketOptions, GridFSError};
+    use bson::oid::ObjectId;
+    use bytes::Bytes;
+    use futures_util::stream::iter;
+    use mongodb::{Client, Database};
+    use tokio_stream::StreamExt;
+    use tonic::{Code, Request, Status};
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    fn frame(filename: &str, data: &'static str) -> Result<UploadRequest, Status> {
+        Ok(UploadRequest {
+            filename: filename.into(),
+            data: Bytes::from(data),
+        })
+    }
+
+    #[test]
+    fn status_of_errors() {
+        assert_eq!(status(GridFSError::FileNotFound()).code(), Code::NotFound);
+        assert_eq!(
+            status(GridFSError::AlreadyExists {
+                id: None,
+                filename: Some("test.txt".into())
+            })
+            .code(),
+            Code::AlreadyExists
+        );
+        assert_eq!(
+            status(GridFSError::InvalidChunkSize(0)).code(),
+            Code::InvalidArgument
+        );
+    }
+
+    #[tokio::test]
+    async fn grpc_service() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let bucket = GridFSBucket::new(
+            db.clone(),
+            Some(GridFSBucketOptions::builder().chunk_size_bytes(4).build()),
+        );
+        let service = GridFSService::new(bucket.clone());
+
+        let frames = iter(vec![frame("test.txt", "test "), frame("", "data")]);
+        let id = service.upload_frames(frames).await.unwrap().id;
+
+        // A failed stream aborts the upload.
+        let frames = iter(vec![
+            frame("other.txt", "test "),
+            Err(Status::cancelled("gone")),
+        ]);
+        let error = service.upload_frames(frames).await.unwrap_err();
+        assert_eq!(error.code(), Code::Cancelled);
+        let frames = iter(vec![frame("", "test")]);
+        let error = service.upload_frames(frames).await.unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+
+        let entries: Vec<FileEntry> = service
+            .list(Request::new(ListRequest { filename: None }))
+            .await
+            .unwrap()
+            .into_inner()
+            .collect::<Result<_, Status>>()
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, id);
+        assert_eq!(entries[0].filename.as_deref(), Some("test.txt"));
+        assert_eq!(entries[0].length, 9);
+
+        let mut content = vec![];
+        let mut frames = service
+            .download(Request::new(DownloadRequest { id }))
+            .await
+            .unwrap()
+            .into_inner();
+        while let Some(frame) = frames.next().await {
+            content.extend(frame.unwrap().data);
+        }
+        assert_eq!(content, b"test data");
+
+        let error = service
+            .download(Request::new(DownloadRequest {
+                id: ObjectId::new().to_hex(),
+            }))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), Code::NotFound);
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
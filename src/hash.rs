@@ -0,0 +1,29 @@
+//! A stable hash, for the placements which must not change from a build to another.
+
+/// The 64-bit FNV-1a hash of @bytes, its bits spread by the finalizer of MurmurHash3: stable
+/// across platforms and releases, unlike the hashers of the standard library. Not meant to
+/// resist collisions crafted on purpose.
+pub(crate) fn stable_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stable_hash;
+
+    #[test]
+    fn stable_hash_values() {
+        assert_eq!(stable_hash(b""), 0xefd0_1f60_ba99_2926);
+        assert_eq!(stable_hash(b"fs#0"), 0xdc0c_e042_8d70_6445);
+        assert_ne!(stable_hash(b"fs#0"), stable_hash(b"fs#1"));
+    }
+}
@@ -0,0 +1,35 @@
+//! Content inspection of the uploads, e.g. virus scanning.
+//!
+//! A [`ContentInspector`] set in
+//! [`GridFSBucketOptions::content_inspector`](crate::options::GridFSBucketOptions::content_inspector)
+//! starts a [`ContentInspection`] for every upload. The inspection receives the chunks of the
+//! file as they are read, and can veto the upload until the file is committed: the chunks
+//! already written and the files collection document are then removed, and the upload raises
+//! [`GridFSError::ContentRejected`](crate::GridFSError::ContentRejected).
+use futures_util::future::BoxFuture;
+use std::fmt::{Debug, Formatter, Result};
+
+/// Starts the inspection of each upload of a bucket.
+pub trait ContentInspector: Send + Sync {
+    /// Starts the inspection of the upload of @filename.
+    fn start(&self, filename: &str) -> Box<dyn ContentInspection>;
+}
+
+impl Debug for dyn ContentInspector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "ContentInspector")
+    }
+}
+
+/// The inspection of one upload.
+pub trait ContentInspection: Send {
+    /// Inspects the next @chunk of the file. Returns the reason of the rejection to veto
+    /// the upload.
+    fn inspect<'a>(&'a mut self, chunk: &'a [u8]) -> BoxFuture<'a, std::result::Result<(), String>>;
+
+    /// Called once the whole file has been inspected, before it is committed. Returns the
+    /// reason of the rejection to veto the upload.
+    fn finish(self: Box<Self>) -> BoxFuture<'static, std::result::Result<(), String>> {
+        Box::pin(async { Ok(()) })
+    }
+}
@@ -7,8 +7,8 @@
 //! Uploading a document:
 //!  ```rust
 //!  # use mongodb::Client;
-//!  # use mongodb::{error::Error, Database};
-//!  use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket};
+//!  # use mongodb::Database;
+//!  use mongodb_gridfs::{options::GridFSBucketOptions, GridFSBucket, GridFSError};
 //!  # use uuid::Uuid;
 //!  
 //!  # fn db_name_new() -> String {
@@ -19,7 +19,7 @@
 //!  # }
 //!  #
 //!  # #[tokio::main]
-//!  # async fn main() -> Result<(), Error> {
+//!  # async fn main() -> Result<(), GridFSError> {
 //!  #    let client = Client::with_uri_str(&std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string())).await?;
 //!  #    let dbname = db_name_new();
 //!  #    let db: Database = client.database(&dbname);
@@ -28,7 +28,8 @@
 //!      .upload_from_stream("test.txt", "stream your data here".as_bytes(), None)
 //!      .await?;
 //!  #     println!("{}", id);
-//!  #     db.drop(None).await
+//!  #     db.drop(None).await?;
+//!  #     Ok(())
 //!  # }
 //!  ```
 //!  Downloading a document:
@@ -65,7 +66,7 @@
 //! #     println!("{}", id);
 //! #
 //! let mut cursor = bucket.open_download_stream(id).await?;
-//! let buffer = cursor.next().await.unwrap();
+//! let buffer = cursor.next().await.unwrap()?;
 //! #     println!("{:?}", buffer);
 //! #
 //! #     db.drop(None).await?;
@@ -77,41 +78,452 @@
 //! - default
 //! - async-std-runtime
 //! - tokio-runtime
+//!
+//! Optional features:
+//! - md5 (default): the MD5 checksums of the uploaded files, `GridFSBucketOptions::verify_on_write` and `GridFSBucket::verify_all`. Without it, the `md-5` crate isn't built and `disable_md5` is implied.
+//! - sha256: `ChecksumAlgorithm::Sha256`: the uploads store the SHA-256 checksum of the files besides the MD5 one, or instead of it without the md5 feature. `GridFSDownloadOptions::verify_checksum`, `GridFSBucket::backfill_checksums` and `GridFSBucket::copy_from_legacy` need md5 or sha256.
+//! - fuse: `fuse::mount` mounts a bucket read-only with FUSE, on Linux with the tokio runtime. Experimental.
+//! - watch-fs: `GridFSBucket::ingest_directory` uploads the files dropped in a directory.
+//! - test-util: `chaos::ChaosBucket` injects failures to test the error handling of applications, and `fixtures::Fixtures` seeds a bucket with deterministic, optionally corrupted, files.
+//! - test-harness: `test_harness::TestBucket`, a disposable bucket for integration tests.
+//! - otel: uploads store the OpenTelemetry trace context in the metadata of the files, and downloads link to it.
+//! - content-search: `content::TextExtractor` stores the text of the uploaded files, searched with `GridFSBucket::search_content`.
+//! - prometheus: `GridFSBucket::start_stats_sampler`, exposing the statistics of a bucket as gauges of a `prometheus` registry.
+//! - serde: the reports and results, like `BucketStats`, `OpStats`, `UploadReport`, `ReconcileReport` and `FileInfo`, implement `serde::Serialize`, and the options, like `GridFSBucketOptions`, `GridFSUploadOptions` and `GridFSDownloadOptions`, `serde::Serialize` and `serde::Deserialize`.
+//! - tracing: the slow operations of the `slow_op` module are emitted as `tracing` warnings.
+//! - time: `FileInfo::upload_offset_date_time` and `FileInfo::expire_offset_date_time` convert the dates of a file to `time::OffsetDateTime`.
+//! - examples-extra: `grpc::GridFSService`, a reference tonic service with the Upload, Download and List RPCs on a bucket.
 //! # Code Status
 //! | Feature                                     | Status  | Notes                                           |
 //! | ------------------------------------------- | ------- | ----------------------------------------------- |
 //! | GridFSUploadOptions                         | DONE    | `contentType` and `aliases` are not implemented |
 //! | GridFSBucketOption                          | DONE    | concerns not used when ensuring indexes         |
 //! | GridFSFindOptions                           | DONE    |                                                 |
-//! | GridFSDownloadByNameOptions                 | TODO    |                                                 |
+//! | GridFSDownloadByNameOptions                 | DONE    |                                                 |
 //! | GridFSBucket                                | DONE    |                                                 |
-//! | GridFSBucket . open_upload_stream           | DONE    |                                                 |
-//! | GridFSBucket . open_upload_stream_with_id   |         |                                                 |
+//! | GridFSBucket . open_upload_stream           | DONE    | returns an `AsyncWrite` `GridFSUploadStream`    |
+//! | GridFSBucket . open_upload_stream_with_id   | DONE    | with the `file_id` upload option                |
 //! | GridFSBucket . upload_from_stream           | NO      | No Implementation planned                         |
-//! | GridFSBucket . upload_from_stream_with_id   | NO      | No Implementation planned                         |
+//! | GridFSBucket . upload_from_stream_with_id   | DONE    |                                                 |
 //! | GridFSBucket . open_download_stream         | DONE    |                                                 |
 //! | GridFSBucket . download_to_stream           | NO      | No Implementation planned                         |
 //! | GridFSBucket . delete                       | DONE    |                                                 |
 //! | GridFSBucket . find                         | DONE    |                                                 |
 //! | GridFSBucket . rename                       | DONE    |                                                 |
 //! | GridFSBucket . drop                         | DONE    |                                                 |
-//! | GridFSBucket . open_download_stream_by_name |         |                                                 |
+//! | GridFSBucket . open_download_stream_by_name | DONE    |                                                 |
 //! | GridFSBucket . download_to_stream_by_name   |         |                                                 |
 //! | indexes                                     | DONE   |                                                 |
 
 pub mod bucket;
+pub mod bucket_name;
+#[cfg(feature = "test-util")]
+pub mod chaos;
+pub mod clock;
+#[cfg(feature = "content-search")]
+pub mod content;
+mod display;
+pub mod encryption;
+mod file_info;
+#[cfg(feature = "test-util")]
+pub mod fixtures;
+#[cfg(all(
+    feature = "fuse",
+    target_os = "linux",
+    any(feature = "default", feature = "tokio-runtime")
+))]
+pub mod fuse;
+#[cfg(all(
+    feature = "examples-extra",
+    any(feature = "default", feature = "tokio-runtime")
+))]
+pub mod grpc;
+mod hash;
+pub mod inspector;
 pub mod options;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod sharded;
+pub mod slow_op;
+pub mod storage_key;
+#[cfg(feature = "test-harness")]
+pub mod test_harness;
+pub mod tier;
+use bson::{oid::ObjectId, DateTime};
+use mongodb::error::{ErrorKind, WriteFailure, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR};
 use std::{
     error::Error,
     fmt::{Display, Formatter, Result},
 };
 
 pub use bucket::GridFSBucket;
+pub use file_info::{FileInfo, FileStatus};
 
 #[derive(Debug)]
 pub enum GridFSError {
     MongoError(mongodb::error::Error),
     FileNotFound(),
+    /// No stored file has the requested filename.
+    FilenameNotFound {
+        filename: String,
+    },
+    /// The filename is stored, but has no revision `revision`.
+    /// See [`GridFSDownloadByNameOptions`](options::GridFSDownloadByNameOptions).
+    RevisionNotFound {
+        filename: String,
+        revision: i32,
+    },
+    /// The filename is stored, but every revision was uploaded after the `date`.
+    /// See [`GridFSBucket::open_download_stream_as_of`](bucket::GridFSBucket::open_download_stream_as_of).
+    RevisionNotFoundAt {
+        filename: String,
+        date: DateTime,
+    },
+    /// The file has expired and is being removed by the server.
+    /// See [`GridFSBucketOptions::expire_after`](options::GridFSBucketOptions::expire_after).
+    FileExpired(),
+    /// The chunk `n` of a file can't be read: its `data` field is missing, isn't binary
+    /// data or has an unsupported binary subtype.
+    InvalidChunk(i64, String),
+    /// The files collection document of a file is malformed.
+    InvalidFile(String),
+    /// The filename breaks the
+    /// [`FilenamePolicy`](options::FilenamePolicy) of the bucket.
+    InvalidFilename(String, FilenameViolation),
+    /// The bucket name doesn't make valid collection names, see [`bucket_name`].
+    InvalidBucketName(String, BucketNameViolation),
+    /// The [`ContentInspector`](inspector::ContentInspector) of the bucket vetoed the upload.
+    ContentRejected {
+        reason: String,
+    },
+    /// A stored file already has the `id` or the `filename` of the uploaded file.
+    AlreadyExists {
+        id: Option<ObjectId>,
+        filename: Option<String>,
+    },
+    /// Writes are still in flight on the bucket.
+    /// See [`GridFSBucket::drop_guarded`](bucket::GridFSBucket::drop_guarded).
+    BucketBusy(),
+    /// The checksum of the downloaded chunks isn't the one stored in the file, e.g. its `md5`.
+    ChecksumMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// The chunk `n` of an upload, read back with
+    /// [`GridFSBucketOptions::verify_on_write`](options::GridFSBucketOptions::verify_on_write),
+    /// is missing or differs from the written one.
+    WriteVerificationFailed {
+        n: u32,
+    },
+    /// The chunk size isn't between 1 byte and 15MiB.
+    InvalidChunkSize(u32),
+    /// The uploaded file needs more than 2^31 chunks: it is longer than `max_length` bytes
+    /// with its chunk size.
+    FileTooLarge {
+        max_length: u64,
+    },
+    /// The source of an upload with
+    /// [`GridFSUploadOptions::strict_length`](options::GridFSUploadOptions::strict_length)
+    /// yielded `actual` bytes instead of the `expected` length. A longer source is stopped at
+    /// the first chunk past the expected length.
+    LengthMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    /// The [`TierBackend`](tier::TierBackend) of the bucket failed, or the bucket has none.
+    TierFailed {
+        reason: String,
+    },
+    /// The [`ChunkEncryptor`](encryption::ChunkEncryptor) of the bucket failed to encrypt a
+    /// chunk.
+    EncryptionFailed {
+        reason: String,
+    },
+    /// The bucket is shut down by [`GridFSBucket::shutdown`](bucket::GridFSBucket::shutdown):
+    /// it doesn't accept new operations, and the operations still in flight after the grace
+    /// period are aborted.
+    ShuttingDown(),
+    /// The configuration of
+    /// [`GridFSBucket::from_uri_with_bucket`](bucket::GridFSBucket::from_uri_with_bucket) is
+    /// missing or malformed.
+    InvalidConfiguration {
+        reason: String,
+    },
+    /// The parent document of an attachment isn't in the `collection`.
+    /// See [`GridFSBucket::attach`](bucket::GridFSBucket::attach).
+    ParentNotFound {
+        collection: String,
+    },
+    /// The token isn't a [`PageToken`](bucket::PageToken) of
+    /// [`GridFSBucket::list_files`](bucket::GridFSBucket::list_files).
+    InvalidPageToken(String),
+    /// The file system watcher of
+    /// [`GridFSBucket::ingest_directory`](bucket::GridFSBucket::ingest_directory) failed.
+    WatchError(WatchError),
+}
+
+/// The error of the file system watcher in a [`GridFSError::WatchError`]. Opaque, so the
+/// error doesn't depend on the `watch-fs` feature: its [`Error::source`] is the error of the
+/// watcher.
+#[derive(Debug)]
+pub struct WatchError(Box<dyn Error + Send + Sync>);
+
+impl Display for WatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for WatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.0.as_ref())
+    }
+}
+
+/// The rule of a [`FilenamePolicy`](options::FilenamePolicy) a filename breaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FilenameViolation {
+    /// The filename is longer than the maximum length, in characters.
+    TooLong(usize),
+    /// The filename contains a character which isn't allowed.
+    ForbiddenCharacter(char),
+    /// The filename is an absolute path or has a `..` component.
+    PathTraversal,
+}
+
+impl Display for FilenameViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            FilenameViolation::TooLong(max_length) => {
+                write!(f, "longer than {} characters", max_length)
+            }
+            FilenameViolation::ForbiddenCharacter(c) => write!(f, "forbidden character {:?}", c),
+            FilenameViolation::PathTraversal => write!(f, "path traversal"),
+        }
+    }
+}
+
+/// The rule of the namespaces a bucket name breaks, see [`bucket_name`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BucketNameViolation {
+    /// The bucket name is empty.
+    Empty,
+    /// The bucket name contains a `$` or a NUL character.
+    ForbiddenCharacter(char),
+    /// The bucket name starts or ends with a `.`, or has two consecutive ones.
+    EmptySegment,
+    /// The bucket name starts with `system.`, reserved by the server.
+    Reserved,
+    /// The bucket name is longer than the maximum length in its database, in bytes.
+    TooLong(usize),
+}
+
+impl Display for BucketNameViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            BucketNameViolation::Empty => write!(f, "empty"),
+            BucketNameViolation::ForbiddenCharacter(c) => write!(f, "forbidden character {:?}", c),
+            BucketNameViolation::EmptySegment => write!(f, "empty segment"),
+            BucketNameViolation::Reserved => write!(f, "reserved system. prefix"),
+            BucketNameViolation::TooLong(max_length) => {
+                write!(f, "longer than {} bytes", max_length)
+            }
+        }
+    }
+}
+
+/// The category of a [`GridFSError`], see [`GridFSError::code`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GridFSErrorCode {
+    /// The requested file doesn't exist.
+    FileNotFound,
+    /// The requested file has expired.
+    FileExpired,
+    /// A chunk of the file is malformed.
+    InvalidChunk,
+    /// The files collection document of the file is malformed.
+    InvalidFile,
+    /// The filename breaks the filename policy of the bucket.
+    InvalidFilename,
+    /// The bucket name doesn't make valid collection names.
+    InvalidBucketName,
+    /// The content inspector of the bucket vetoed the upload.
+    ContentRejected,
+    /// Writes are still in flight on the bucket.
+    BucketBusy,
+    /// The content of the file doesn't match its checksum.
+    ChecksumMismatch,
+    /// The chunk size is out of the bounds of the spec.
+    InvalidChunkSize,
+    /// The file is too large for its chunk size.
+    FileTooLarge,
+    /// The uploaded content isn't as long as declared.
+    LengthMismatch,
+    /// The tier backend of the bucket failed.
+    Tier,
+    /// The chunk encryptor of the bucket failed.
+    Encryption,
+    /// The configuration of the bucket is missing or malformed.
+    InvalidConfiguration,
+    /// The bucket is shut down.
+    ShuttingDown,
+    /// The page token of a listing is malformed.
+    InvalidPageToken,
+    /// The parent document of an attachment doesn't exist.
+    ParentNotFound,
+    /// No server is available, the connection failed or the primary changed.
+    Network,
+    /// A document with the same unique key already exists.
+    DuplicateKey,
+    /// The credentials were rejected.
+    Authentication,
+    /// The server rejected the operation for another reason.
+    Server,
+    /// The file system watcher failed.
+    Watch,
+    /// Any other error of the driver.
+    Other,
+}
+
+// Server error codes of a failover or a network failure, after which an operation may succeed.
+// https://github.com/mongodb/specifications/blob/master/source/retryable-reads/retryable-reads.md
+const RETRYABLE_CODES: [i32; 13] = [
+    6, 7, 89, 91, 134, 189, 262, 9001, 10107, 11600, 11602, 13435, 13436,
+];
+const DUPLICATE_KEY_CODES: [i32; 2] = [11000, 11001];
+
+/// Whether @error is a violation of a unique index.
+pub(crate) fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    server_code(error).is_some_and(|code| DUPLICATE_KEY_CODES.contains(&code))
+}
+
+/// Whether @error is a violation of a unique index on @field, from the `keyPattern` or the
+/// `keyValue` of its write error. None when the server didn't report them.
+pub(crate) fn is_duplicate_key_on(error: &mongodb::error::Error, field: &str) -> Option<bool> {
+    if !is_duplicate_key(error) {
+        return Some(false);
+    }
+    let details = match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => write_error.details.as_ref(),
+        ErrorKind::BulkWrite(failure) => failure
+            .write_errors
+            .as_ref()
+            .and_then(|write_errors| write_errors.first())
+            .and_then(|write_error| write_error.details.as_ref()),
+        _ => None,
+    }?;
+    ["keyPattern", "keyValue"]
+        .iter()
+        .find_map(|key| details.get_document(key).ok())
+        .map(|keys| keys.contains_key(field))
+}
+
+/// The server error code of an operation the user isn't authorized to run.
+const UNAUTHORIZED_CODE: i32 = 13;
+
+/// Whether @error is a refusal of the access control of the server.
+pub(crate) fn is_unauthorized(error: &mongodb::error::Error) -> bool {
+    server_code(error) == Some(UNAUTHORIZED_CODE)
+}
+
+/// The server error code of @error, if any.
+fn server_code(error: &mongodb::error::Error) -> Option<i32> {
+    match error.kind.as_ref() {
+        ErrorKind::Command(command_error) => Some(command_error.code),
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => Some(write_error.code),
+        ErrorKind::Write(WriteFailure::WriteConcernError(write_concern_error)) => {
+            Some(write_concern_error.code)
+        }
+        ErrorKind::BulkWrite(failure) => failure
+            .write_errors
+            .as_ref()
+            .and_then(|write_errors| write_errors.first())
+            .map(|write_error| write_error.code)
+            .or_else(|| {
+                failure
+                    .write_concern_error
+                    .as_ref()
+                    .map(|write_concern_error| write_concern_error.code)
+            }),
+        _ => None,
+    }
+}
+
+impl GridFSError {
+    /// The category of the error. The errors of the driver are classified from their kind
+    /// and server error code.
+    pub fn code(&self) -> GridFSErrorCode {
+        match self {
+            GridFSError::MongoError(error) => {
+                let code = server_code(error);
+                match error.kind.as_ref() {
+                    _ if code.is_some_and(|code| DUPLICATE_KEY_CODES.contains(&code)) => {
+                        GridFSErrorCode::DuplicateKey
+                    }
+                    ErrorKind::Io(_)
+                    | ErrorKind::ConnectionPoolCleared { .. }
+                    | ErrorKind::ServerSelection { .. }
+                    | ErrorKind::DnsResolve { .. } => GridFSErrorCode::Network,
+                    _ if code.is_some_and(|code| RETRYABLE_CODES.contains(&code)) => {
+                        GridFSErrorCode::Network
+                    }
+                    ErrorKind::Authentication { .. } => GridFSErrorCode::Authentication,
+                    ErrorKind::Command(_) | ErrorKind::Write(_) | ErrorKind::BulkWrite(_) => {
+                        GridFSErrorCode::Server
+                    }
+                    _ => GridFSErrorCode::Other,
+                }
+            }
+            GridFSError::FileNotFound()
+            | GridFSError::FilenameNotFound { .. }
+            | GridFSError::RevisionNotFound { .. }
+            | GridFSError::RevisionNotFoundAt { .. } => GridFSErrorCode::FileNotFound,
+            GridFSError::FileExpired() => GridFSErrorCode::FileExpired,
+            GridFSError::InvalidChunk(_, _) => GridFSErrorCode::InvalidChunk,
+            GridFSError::InvalidFile(_) => GridFSErrorCode::InvalidFile,
+            GridFSError::InvalidFilename(_, _) => GridFSErrorCode::InvalidFilename,
+            GridFSError::InvalidBucketName(_, _) => GridFSErrorCode::InvalidBucketName,
+            GridFSError::ContentRejected { .. } => GridFSErrorCode::ContentRejected,
+            GridFSError::AlreadyExists { .. } => GridFSErrorCode::DuplicateKey,
+            GridFSError::BucketBusy() => GridFSErrorCode::BucketBusy,
+            GridFSError::ChecksumMismatch { .. } => GridFSErrorCode::ChecksumMismatch,
+            GridFSError::WriteVerificationFailed { .. } => GridFSErrorCode::ChecksumMismatch,
+            GridFSError::InvalidChunkSize(_) => GridFSErrorCode::InvalidChunkSize,
+            GridFSError::FileTooLarge { .. } => GridFSErrorCode::FileTooLarge,
+            GridFSError::LengthMismatch { .. } => GridFSErrorCode::LengthMismatch,
+            GridFSError::TierFailed { .. } => GridFSErrorCode::Tier,
+            GridFSError::EncryptionFailed { .. } => GridFSErrorCode::Encryption,
+            GridFSError::InvalidConfiguration { .. } => GridFSErrorCode::InvalidConfiguration,
+            GridFSError::ShuttingDown() => GridFSErrorCode::ShuttingDown,
+            GridFSError::InvalidPageToken(_) => GridFSErrorCode::InvalidPageToken,
+            GridFSError::ParentNotFound { .. } => GridFSErrorCode::ParentNotFound,
+            GridFSError::WatchError(_) => GridFSErrorCode::Watch,
+        }
+    }
+
+    /// Whether the operation may succeed if tried again: network errors, failovers and
+    /// the errors labelled retryable by the server.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            GridFSError::MongoError(error) => {
+                error.contains_label(RETRYABLE_WRITE_ERROR)
+                    || error.contains_label(TRANSIENT_TRANSACTION_ERROR)
+                    || self.code() == GridFSErrorCode::Network
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the requested file doesn't exist, or has expired.
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self.code(),
+            GridFSErrorCode::FileNotFound | GridFSErrorCode::FileExpired
+        )
+    }
 }
 
 impl From<mongodb::error::Error> for GridFSError {
@@ -120,11 +532,41 @@ impl From<mongodb::error::Error> for GridFSError {
     }
 }
 
+#[cfg(feature = "watch-fs")]
+impl From<notify::Error> for GridFSError {
+    fn from(err: notify::Error) -> GridFSError {
+        GridFSError::WatchError(WatchError(Box::new(err)))
+    }
+}
+
 impl Error for GridFSError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             GridFSError::MongoError(e) => Some(e),
             GridFSError::FileNotFound() => None,
+            GridFSError::FilenameNotFound { .. } => None,
+            GridFSError::RevisionNotFound { .. } => None,
+            GridFSError::RevisionNotFoundAt { .. } => None,
+            GridFSError::FileExpired() => None,
+            GridFSError::InvalidChunk(_, _) => None,
+            GridFSError::InvalidFile(_) => None,
+            GridFSError::InvalidFilename(_, _) => None,
+            GridFSError::InvalidBucketName(_, _) => None,
+            GridFSError::ContentRejected { .. } => None,
+            GridFSError::AlreadyExists { .. } => None,
+            GridFSError::BucketBusy() => None,
+            GridFSError::ChecksumMismatch { .. } => None,
+            GridFSError::WriteVerificationFailed { .. } => None,
+            GridFSError::InvalidChunkSize(_) => None,
+            GridFSError::FileTooLarge { .. } => None,
+            GridFSError::LengthMismatch { .. } => None,
+            GridFSError::TierFailed { .. } => None,
+            GridFSError::EncryptionFailed { .. } => None,
+            GridFSError::InvalidConfiguration { .. } => None,
+            GridFSError::ShuttingDown() => None,
+            GridFSError::InvalidPageToken(_) => None,
+            GridFSError::ParentNotFound { .. } => None,
+            GridFSError::WatchError(e) => Some(e),
         }
     }
 
@@ -146,6 +588,205 @@ impl Display for GridFSError {
         match self {
             GridFSError::MongoError(me) => write!(f, "{}", me),
             GridFSError::FileNotFound() => write!(f, "File not found"),
+            GridFSError::FilenameNotFound { filename } => {
+                write!(f, "File not found: filename {}", filename)
+            }
+            GridFSError::RevisionNotFound { filename, revision } => {
+                write!(f, "Revision {} of {} not found", revision, filename)
+            }
+            GridFSError::RevisionNotFoundAt { filename, date } => {
+                write!(
+                    f,
+                    "No revision of {} uploaded at or before {}",
+                    filename,
+                    date.try_to_rfc3339_string()
+                        .unwrap_or_else(|_| date.to_string())
+                )
+            }
+            GridFSError::FileExpired() => write!(f, "File expired"),
+            GridFSError::InvalidChunk(n, reason) => write!(f, "Invalid chunk {}: {}", n, reason),
+            GridFSError::InvalidFile(reason) => write!(f, "Invalid file: {}", reason),
+            GridFSError::InvalidFilename(filename, violation) => {
+                write!(f, "Invalid filename {:?}: {}", filename, violation)
+            }
+            GridFSError::InvalidBucketName(bucket_name, violation) => {
+                write!(f, "Invalid bucket name {:?}: {}", bucket_name, violation)
+            }
+            GridFSError::ContentRejected { reason } => write!(f, "Content rejected: {}", reason),
+            GridFSError::AlreadyExists {
+                id: Some(id),
+                filename: _,
+            } => write!(f, "File already exists: id {}", id),
+            GridFSError::AlreadyExists {
+                id: None,
+                filename: Some(filename),
+            } => write!(f, "File already exists: filename {}", filename),
+            GridFSError::AlreadyExists {
+                id: None,
+                filename: None,
+            } => write!(f, "File already exists"),
+            GridFSError::BucketBusy() => write!(f, "Bucket busy"),
+            GridFSError::ChecksumMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "Checksum mismatch: expected {}, got {}",
+                    expected, actual
+                )
+            }
+            GridFSError::WriteVerificationFailed { n } => {
+                write!(
+                    f,
+                    "Write verification failed: chunk {} read back corrupted",
+                    n
+                )
+            }
+            GridFSError::InvalidChunkSize(chunk_size) => {
+                write!(
+                    f,
+                    "Invalid chunk size {}: not between 1 and 15MiB",
+                    chunk_size
+                )
+            }
+            GridFSError::FileTooLarge { max_length } => {
+                write!(f, "File too large: longer than {} bytes", max_length)
+            }
+            GridFSError::LengthMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "Length mismatch: expected {} bytes, got {}",
+                    expected, actual
+                )
+            }
+            GridFSError::TierFailed { reason } => write!(f, "Tier backend failed: {}", reason),
+            GridFSError::EncryptionFailed { reason } => {
+                write!(f, "Chunk encryption failed: {}", reason)
+            }
+            GridFSError::InvalidConfiguration { reason } => {
+                write!(f, "Invalid bucket configuration: {}", reason)
+            }
+            GridFSError::ShuttingDown() => write!(f, "The bucket is shutting down"),
+            GridFSError::InvalidPageToken(token) => write!(f, "Invalid page token {:?}", token),
+            GridFSError::ParentNotFound { collection } => {
+                write!(f, "Parent document not found in {}", collection)
+            }
+            GridFSError::WatchError(we) => write!(f, "{}", we),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{is_duplicate_key_on, BucketNameViolation, GridFSError, GridFSErrorCode, WatchError};
+    use bson::{doc, DateTime};
+    use mongodb::error::{ErrorKind, WriteError, WriteFailure};
+    use std::error::Error;
+    use std::io;
+
+    #[test]
+    fn error_code() {
+        let error = GridFSError::FileNotFound();
+        assert_eq!(error.code(), GridFSErrorCode::FileNotFound);
+        assert!(error.is_not_found());
+        assert!(!error.is_retryable());
+
+        let error = GridFSError::RevisionNotFound {
+            filename: "test.txt".into(),
+            revision: -3,
+        };
+        assert_eq!(error.code(), GridFSErrorCode::FileNotFound);
+        assert_eq!(error.to_string(), "Revision -3 of test.txt not found");
+        assert!(error.is_not_found());
+
+        let error = GridFSError::RevisionNotFoundAt {
+            filename: "test.txt".into(),
+            date: DateTime::from_millis(1_000),
+        };
+        assert_eq!(error.code(), GridFSErrorCode::FileNotFound);
+        assert_eq!(
+            error.to_string(),
+            "No revision of test.txt uploaded at or before 1970-01-01T00:00:01Z"
+        );
+        assert!(error.is_not_found());
+
+        let error = GridFSError::InvalidChunk(0, "data is missing".into());
+        assert_eq!(error.code(), GridFSErrorCode::InvalidChunk);
+        assert!(!error.is_not_found());
+        assert!(!error.is_retryable());
+
+        let error = GridFSError::AlreadyExists {
+            id: None,
+            filename: Some("test.txt".into()),
+        };
+        assert_eq!(error.code(), GridFSErrorCode::DuplicateKey);
+        assert_eq!(error.to_string(), "File already exists: filename test.txt");
+        assert!(!error.is_retryable());
+
+        let error = GridFSError::FileTooLarge {
+            max_length: 1 << 31,
+        };
+        assert_eq!(error.code(), GridFSErrorCode::FileTooLarge);
+        assert_eq!(
+            error.to_string(),
+            "File too large: longer than 2147483648 bytes"
+        );
+
+        let error = GridFSError::LengthMismatch {
+            expected: 9,
+            actual: 4,
+        };
+        assert_eq!(error.code(), GridFSErrorCode::LengthMismatch);
+        assert_eq!(
+            error.to_string(),
+            "Length mismatch: expected 9 bytes, got 4"
+        );
+        assert!(!error.is_retryable());
+
+        let error = GridFSError::InvalidBucketName("a..b".into(), BucketNameViolation::EmptySegment);
+        assert_eq!(error.code(), GridFSErrorCode::InvalidBucketName);
+        assert_eq!(
+            error.to_string(),
+            "Invalid bucket name \"a..b\": empty segment"
+        );
+        assert!(!error.is_retryable());
+
+        let error = GridFSError::ParentNotFound {
+            collection: "posts".into(),
+        };
+        assert_eq!(error.code(), GridFSErrorCode::ParentNotFound);
+        assert_eq!(error.to_string(), "Parent document not found in posts");
+        assert!(!error.is_not_found());
+
+        let error = GridFSError::WatchError(WatchError(Box::new(io::Error::other("inotify"))));
+        assert_eq!(error.code(), GridFSErrorCode::Watch);
+        assert_eq!(error.to_string(), "inotify");
+        assert!(error.source().and_then(Error::source).is_some());
+
+        let error: GridFSError = mongodb::error::Error::from(io::Error::other("reset")).into();
+        assert_eq!(error.code(), GridFSErrorCode::Network);
+        assert!(error.is_retryable());
+        assert!(!error.is_not_found());
+    }
+
+    #[test]
+    fn duplicate_key_on() {
+        let duplicate = |details: Option<bson::Document>| {
+            let mut write_error = doc! {"code":11000, "errmsg":"E11000 duplicate key error"};
+            if let Some(details) = details {
+                write_error.insert("errInfo", details);
+            }
+            let write_error: WriteError = bson::from_document(write_error).unwrap();
+            mongodb::error::Error::from(ErrorKind::Write(WriteFailure::WriteError(write_error)))
+        };
+        let error = duplicate(Some(
+            doc! {"keyPattern":{"filename":1}, "keyValue":{"filename":"test.txt"}},
+        ));
+        assert_eq!(is_duplicate_key_on(&error, "filename"), Some(true));
+        assert_eq!(is_duplicate_key_on(&error, "_id"), Some(false));
+        let error = duplicate(Some(doc! {"keyValue":{"_id":1}}));
+        assert_eq!(is_duplicate_key_on(&error, "filename"), Some(false));
+        assert_eq!(is_duplicate_key_on(&duplicate(None), "filename"), None);
+
+        let error = mongodb::error::Error::from(std::io::Error::other("reset"));
+        assert_eq!(is_duplicate_key_on(&error, "filename"), Some(false));
+    }
+}
@@ -1,16 +1,85 @@
-use bson::Document;
-use mongodb::options::{ReadConcern, ReadPreference, WriteConcern};
-use std::{sync::Arc, time::Duration};
+#[cfg(feature = "content-search")]
+use crate::content::TextExtractor;
+use crate::{
+    bucket::{check_chunk_size, CausalToken, GridFSBucket},
+    bucket_name,
+    clock::Clock,
+    encryption::ChunkEncryption,
+    inspector::ContentInspector,
+    slow_op::SlowOpLogger,
+    tier::TierBackend,
+    FileStatus, FilenameViolation, GridFSError,
+};
+use bson::{oid::ObjectId, spec::BinarySubtype, Bson, DateTime, Document};
+use futures_util::lock::Mutex;
+use mongodb::{
+    options::{
+        Collation, HedgedReadOptions, ReadConcern, ReadPreference, SelectionCriteria, WriteConcern,
+    },
+    ClientSession, IndexModel,
+};
+use std::{ops::Range, sync::Arc, time::Duration};
 use typed_builder::TypedBuilder;
+use unicode_normalization::UnicodeNormalization as _;
 
 // TODO: rethink the name of the trait
 // TODO: move the trait in another file
 pub trait ProgressUpdate {
     fn update(&self, position: usize);
+
+    /// Called with the number of chunk inserts in flight each time a chunk is queued.
+    /// The depth never exceeds [`GridFSBucketOptions::max_in_flight_chunks`].
+    fn queue_depth(&self, _depth: usize) {}
+
+    /// Called with the [`UploadProgress`] of the upload each time a chunk is written.
+    fn progress(&self, _progress: UploadProgress) {}
+}
+
+/// The progress of an upload, see [`ProgressUpdate::progress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct UploadProgress {
+    /// The number of bytes written.
+    pub bytes_done: u64,
+    /// The length of the file, from [`GridFSUploadOptions::size_hint`].
+    pub total_bytes: Option<u64>,
+    /// The number of chunks written.
+    pub chunk_index: u64,
+    /// The number of chunks of the file, estimated from [`GridFSUploadOptions::size_hint`].
+    pub chunk_count_estimate: Option<u64>,
+}
+
+impl UploadProgress {
+    pub(crate) fn new(
+        bytes_done: u64,
+        chunk_index: u64,
+        size_hint: Option<u64>,
+        chunk_size: u32,
+    ) -> Self {
+        UploadProgress {
+            bytes_done,
+            total_bytes: size_hint,
+            chunk_index,
+            chunk_count_estimate: size_hint
+                .map(|total_bytes| total_bytes.div_ceil(chunk_size as u64).max(chunk_index)),
+        }
+    }
+
+    /// The percentage of the bytes written, between 0 and 100, when the length of the file
+    /// is known.
+    pub fn percent(&self) -> Option<f64> {
+        self.total_bytes.map(|total_bytes| match total_bytes {
+            0 => 100.0,
+            _ => (self.bytes_done as f64 * 100.0 / total_bytes as f64).min(100.0),
+        })
+    }
 }
 
 /// [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#file-upload)
 #[derive(Clone, Default, TypedBuilder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct GridFSUploadOptions {
     /**
      * The number of bytes per chunk of this file. Defaults to the
@@ -54,14 +123,332 @@ pub struct GridFSUploadOptions {
      */
     // TODO: find a better name.
     #[builder(default = None)]
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub(crate) progress_tick: Option<Arc<dyn ProgressUpdate + Send + Sync>>, // TODO: test process_tick
+
+    /**
+     * The state of the file once uploaded. Files stored without state are available;
+     * a [`FileStatus::Pending`] file is hidden from the readers until
+     * [`GridFSBucket::publish`](crate::GridFSBucket::publish).
+     */
+    #[builder(default = None)]
+    pub(crate) status: Option<FileStatus>,
+
+    /**
+     * The expected length of the file in bytes, e.g. from the metadata of a local file or
+     * a `Content-Length` header. Gives the totals of the [`UploadProgress`].
+     */
+    #[builder(default = None)]
+    pub(crate) size_hint: Option<u64>,
+
+    /**
+     * When true, the upload fails with [`GridFSError::LengthMismatch`] when its source
+     * yields more or fewer bytes than the [`GridFSUploadOptions::size_hint`], e.g. a client
+     * upload cut short: the chunks already written are removed. Ignored without size hint.
+     * Defaults to false.
+     */
+    #[builder(default = false)]
+    pub(crate) strict_length: bool,
+
+    /**
+     * When true, the [`UploadReport`](crate::bucket::UploadReport) of
+     * [`GridFSBucket::upload_from_stream_verbose`](crate::GridFSBucket::upload_from_stream_verbose)
+     * includes the [`UploadDiagnostics`](crate::bucket::UploadDiagnostics) of the upload:
+     * the duration of its phases and the latency of its chunk inserts. Ignored by the
+     * other uploads. Defaults to false.
+     */
+    #[builder(default = false)]
+    pub(crate) diagnostics: bool,
+
+    /**
+     * The collection of the chunks of this file, instead of the chunks collection of the
+     * bucket, e.g. a dedicated `fs.chunks.<tenant>` collection so a huge tenant doesn't
+     * bloat the shared chunks collection and its index. The name is recorded in the files
+     * collection document, and the downloads and the deletes follow it. The collection is
+     * neither dropped nor cleared with the bucket.
+     */
+    #[builder(default = None)]
+    pub(crate) chunks_collection: Option<String>,
+
+    /**
+     * The value of the [`GridFSBucketOptions::chunk_shard_key`] field of this file, e.g.
+     * the tenant, stored in the files collection document and in every chunk. Ignored when
+     * the bucket has no chunk shard key.
+     */
+    #[builder(default = None)]
+    pub(crate) shard_key_value: Option<Bson>,
+
+    /**
+     * The id of the file, instead of a generated one, e.g. to keep the ids of the files
+     * migrated from another store. The id given to
+     * [`GridFSBucket::upload_from_stream_with_id`](crate::GridFSBucket::upload_from_stream_with_id)
+     * takes precedence.
+     */
+    #[builder(default = None)]
+    pub(crate) file_id: Option<ObjectId>,
+
+    /**
+     * The `uploadDate` of the file, instead of the end of the upload, e.g. to keep the
+     * timestamps of the files migrated from another store.
+     */
+    #[builder(default = None)]
+    pub(crate) upload_date: Option<DateTime>,
+}
+
+/// The binary subtype of the `data` field of the chunks written by a bucket.
+/// Downloads accept both subtypes whatever this setting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ChunkBinarySubtype {
+    /// Generic binary data (0x00), as written by the current drivers.
+    #[default]
+    Generic,
+    /// Old binary data (0x02), as written by some legacy drivers.
+    BinaryOld,
+}
+
+impl From<ChunkBinarySubtype> for BinarySubtype {
+    fn from(subtype: ChunkBinarySubtype) -> BinarySubtype {
+        match subtype {
+            ChunkBinarySubtype::Generic => BinarySubtype::Generic,
+            ChunkBinarySubtype::BinaryOld => BinarySubtype::BinaryOld,
+        }
+    }
+}
+
+/// The checksum of the files preferred by a bucket: stored by its uploads, verified first by
+/// its downloads, and added to the files without it by
+/// [`GridFSBucket::backfill_checksums`](crate::GridFSBucket::backfill_checksums).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ChecksumAlgorithm {
+    /// The `md5` field of the GridFS spec, read and written by every driver. Requires the
+    /// `md5` feature.
+    #[default]
+    Md5,
+    /// A `sha256` field, stored besides the `md5` one unless `disable_md5` is set or the
+    /// `md5` feature is off.
+    #[cfg(feature = "sha256")]
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// The field of the files collection document holding the checksum.
+    pub fn field(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "md5",
+            #[cfg(feature = "sha256")]
+            ChecksumAlgorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+/// The order in which the chunks of an upload are committed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ChunkOrdering {
+    /// Up to [`GridFSBucketOptions::max_in_flight_chunks`] chunks are inserted at once: a
+    /// chunk may be committed before the previous ones.
+    #[default]
+    Pipelined,
+    /// The chunk `n` is inserted once the chunk `n - 1` is acknowledged, whatever
+    /// [`GridFSBucketOptions::max_in_flight_chunks`]: the `n` of the chunks of a file are
+    /// committed in strictly increasing order.
+    Sequential,
+}
+
+/// The read-after-write check of the uploads, see [`GridFSBucketOptions::verify_on_write`].
+#[cfg(feature = "md5")]
+#[derive(Clone, Debug, PartialEq, Eq, TypedBuilder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WriteVerification {
+    /**
+     * The files up to this length, in bytes, are read back whole. Defaults to 16MiB.
+     */
+    #[builder(default = 16 * 1024 * 1024)]
+    pub full_read_max_length: u64,
+
+    /**
+     * The number of chunks, chosen at random, read back from the longer files.
+     * Defaults to 8.
+     */
+    #[builder(default = 8)]
+    pub sample_chunks: u32,
+}
+
+#[cfg(feature = "md5")]
+impl Default for WriteVerification {
+    fn default() -> Self {
+        WriteVerification::builder().build()
+    }
+}
+
+/// The options of [`GridFSBucket::verify_all`](crate::GridFSBucket::verify_all).
+#[cfg(all(feature = "md5", any(feature = "default", feature = "tokio-runtime")))]
+#[derive(Clone, Debug, PartialEq, TypedBuilder)]
+pub struct VerifyOptions {
+    /**
+     * The fraction of the files verified, between 0 and 1. The sample only depends on the
+     * ids of the files, so a resumed job verifies the same files. Defaults to 1: every file.
+     */
+    #[builder(default = 1.0)]
+    pub sample_ratio: f64,
+
+    /**
+     * The maximum number of bytes of chunks read per second. Defaults to no limit.
+     */
+    #[builder(default)]
+    pub max_bytes_per_sec: Option<u64>,
+
+    /**
+     * Resumes the job after the file of this id, instead of after the last file verified by
+     * an interrupted job, see [`VerifyReport::resume_token`](crate::bucket::VerifyReport::resume_token).
+     */
+    #[builder(default)]
+    pub resume_token: Option<ObjectId>,
+}
+
+#[cfg(all(feature = "md5", any(feature = "default", feature = "tokio-runtime")))]
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions::builder().build()
+    }
+}
+
+/// The priority class of the operations of a bucket.
+/// See [`GridFSBucket::with_priority`](crate::GridFSBucket::with_priority).
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// User-facing operations.
+    #[default]
+    Interactive,
+    /// Background operations, like migrations, which shouldn't slow down the interactive ones.
+    Batch,
+}
+
+/// The limits of the operations of a [`Priority`] class.
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+#[derive(Clone, Debug, Default, TypedBuilder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PriorityLimits {
+    /**
+     * The maximum number of uploads and downloads of the class running at once,
+     * across the clones of the bucket. The other ones wait for a slot. Unlimited
+     * when None.
+     */
+    #[builder(default)]
+    pub max_concurrent: Option<usize>,
+
+    /**
+     * The maximum throughput of each upload and download of the class, in bytes per
+     * second. Unlimited when None.
+     */
+    #[builder(default)]
+    pub max_bytes_per_second: Option<u64>,
+}
+
+/// A Unicode normalization form, see [`FilenamePolicy::normalization`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum UnicodeNormalization {
+    /// Canonical composition.
+    Nfc,
+    /// Canonical decomposition.
+    Nfd,
+    /// Compatibility composition.
+    Nfkc,
+    /// Compatibility decomposition.
+    Nfkd,
+}
+
+/// The rules the filenames of a bucket follow, checked on upload and on rename.
+/// See [`GridFSBucketOptions::filename_policy`].
+#[derive(Clone, Debug, Default, TypedBuilder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct FilenamePolicy {
+    /**
+     * The maximum length of a filename, in characters, after normalization.
+     */
+    #[builder(default)]
+    pub max_length: Option<usize>,
+
+    /**
+     * Whether a character is allowed in a filename. Every character is allowed when None.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub allowed_characters: Option<fn(char) -> bool>,
+
+    /**
+     * The normalization applied to the filenames before they are checked and stored.
+     */
+    #[builder(default)]
+    pub normalization: Option<UnicodeNormalization>,
+
+    /**
+     * Reject the absolute paths and the filenames with a `..` component, which would
+     * escape a directory when the files are written to a file system. Defaults to false.
+     */
+    #[builder(default)]
+    pub reject_path_traversal: bool,
+}
+
+impl FilenamePolicy {
+    /// Normalizes and checks @filename. Returns the filename to store.
+    pub fn apply(&self, filename: &str) -> Result<String, FilenameViolation> {
+        let filename: String = match self.normalization {
+            None => filename.to_string(),
+            Some(UnicodeNormalization::Nfc) => filename.nfc().collect(),
+            Some(UnicodeNormalization::Nfd) => filename.nfd().collect(),
+            Some(UnicodeNormalization::Nfkc) => filename.nfkc().collect(),
+            Some(UnicodeNormalization::Nfkd) => filename.nfkd().collect(),
+        };
+        if let Some(max_length) = self.max_length {
+            if filename.chars().count() > max_length {
+                return Err(FilenameViolation::TooLong(max_length));
+            }
+        }
+        if let Some(allowed_characters) = self.allowed_characters {
+            if let Some(c) = filename.chars().find(|c| !allowed_characters(*c)) {
+                return Err(FilenameViolation::ForbiddenCharacter(c));
+            }
+        }
+        if self.reject_path_traversal
+            && (filename.starts_with(['/', '\\'])
+                || filename.get(1..2) == Some(":")
+                || filename
+                    .split(['/', '\\'])
+                    .any(|component| component == ".."))
+        {
+            return Err(FilenameViolation::PathTraversal);
+        }
+        Ok(filename)
+    }
 }
 
 /// [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#configurable-gridfsbucket-class)
+///
+/// With the `serde` feature, the options are (de)serializable, e.g. from a configuration
+/// file: the concerns and the read preference have the representation of the driver, like
+/// `{w: "majority", wtimeout: 1000}` or `{mode: "secondaryPreferred"}`, the durations are in
+/// milliseconds, and the missing fields take their default value. The plug-ins, like the
+/// content inspector or the clock, are skipped.
 #[derive(Clone, Debug, TypedBuilder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct GridFSBucketOptions {
     /**
      * The bucket name. Defaults to 'fs'.
+     * A name derived from user input is made valid with
+     * [`bucket_name::escape`](crate::bucket_name::escape).
      */
     #[builder(default = "fs".into())]
     pub bucket_name: String,
@@ -78,6 +465,21 @@ pub struct GridFSBucketOptions {
     #[builder(default)]
     pub write_concern: Option<WriteConcern>,
 
+    /**
+     * The write concern of the files collection documents, e.g. a majority so a file is only
+     * visible once durable. Defaults to `write_concern`.
+     */
+    #[builder(default)]
+    pub files_write_concern: Option<WriteConcern>,
+
+    /**
+     * The write concern of the chunks, e.g. `w: 1` for the throughput of the uploads, with
+     * `verify_on_write` or the `md5` of the files to catch a lost chunk. Defaults to
+     * `write_concern`.
+     */
+    #[builder(default)]
+    pub chunks_write_concern: Option<WriteConcern>,
+
     /**
      * The read concern. Defaults to the read concern of the database.
      */
@@ -90,15 +492,291 @@ pub struct GridFSBucketOptions {
     #[builder(default)]
     pub read_preference: Option<ReadPreference>,
 
+    /**
+     * The server selection criteria of the reads, for tag sets, max staleness or a
+     * custom predicate. Takes precedence over the read preference when set.
+     * Defaults to the read preference.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "selection_criteria"))]
+    pub selection_criteria: Option<SelectionCriteria>,
+
     /**
      * TRANSITIONAL: This option is provided for backwards compatibility.
      * It MUST be supported while a driver supports MD5 and MUST be removed
      * (or made into a no-op) when a driver removes MD5 support entirely.
      * When true, the GridFS implementation will not compute MD5 checksums
-     * of uploaded files. Defaults to false.
+     * of uploaded files. Defaults to false. Implied when the `md5` feature is off.
      */
     #[builder(default = false)]
     pub disable_md5: bool,
+
+    /**
+     * The checksum preferred by the bucket. The downloads verifying their checksum check it
+     * when the file has it, and otherwise the checksum the file has, e.g. the `md5` of a
+     * file written by another driver. Defaults to [`ChecksumAlgorithm::Md5`].
+     */
+    #[builder(default)]
+    pub checksum_algorithm: ChecksumAlgorithm,
+
+    /**
+     * The number of times a download re-issues the chunks query, starting from the
+     * last yielded chunk, when the chunks cursor fails (e.g. on a primary failover).
+     * Defaults to 0.
+     */
+    #[builder(default = 0)]
+    pub download_retries: u32,
+
+    /**
+     * When true, a download reading from the secondaries which doesn't find the file reads
+     * it again, with its chunks, from the primary: a file uploaded just before isn't
+     * replicated yet. The file is only missing when the primary doesn't have it either.
+     * Defaults to false.
+     */
+    #[builder(default = false)]
+    pub primary_fallback: bool,
+
+    /**
+     * When true, the chunk reads of the downloads from the secondaries are hedged: on a
+     * sharded cluster, each read is sent to two members of the shard, and the first answer
+     * is used. Defaults to false.
+     */
+    #[builder(default = false)]
+    pub hedged_chunk_reads: bool,
+
+    /**
+     * The maximum number of chunk inserts in flight during an upload. The source is
+     * read ahead while the previous chunks are inserted, and reading pauses when the
+     * queue is full, so an upload holds at most this number of chunks in memory.
+     * Defaults to 1: each chunk is inserted before the next one is read.
+     *
+     * With more than one chunk in flight, the chunks may be acknowledged out of order. See
+     * [`GridFSBucketOptions::chunk_ordering`].
+     */
+    #[builder(default = 1)]
+    pub max_in_flight_chunks: usize,
+
+    /**
+     * The order in which the chunks of an upload are committed. Integrations tailing the
+     * chunks collection, e.g. through a change stream, need [`ChunkOrdering::Sequential`]
+     * to see each chunk after the previous ones. Defaults to [`ChunkOrdering::Pipelined`].
+     */
+    #[builder(default)]
+    pub chunk_ordering: ChunkOrdering,
+
+    /**
+     * When set, the uploads read back their chunks from the primary once they are written,
+     * the whole file or a sample of chunks, and compare their MD5 checksums to the ones of
+     * the written chunks before the file is committed, to catch a silent corruption of the
+     * writes. Defaults to None: the chunks aren't read back. Requires the `md5` feature.
+     */
+    #[cfg(feature = "md5")]
+    #[builder(default)]
+    pub verify_on_write: Option<WriteVerification>,
+
+    /**
+     * Computes the MD5 checksum of the uploaded chunks on the blocking thread pool of
+     * tokio, while the chunks are inserted, instead of on the async task.
+     * Defaults to false.
+     */
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[builder(default = false)]
+    pub offload_digest: bool,
+
+    /**
+     * The binary subtype of the written chunks. Defaults to generic binary data.
+     */
+    #[builder(default)]
+    pub chunk_binary_subtype: ChunkBinarySubtype,
+
+    /**
+     * Files of at most this many bytes, and of at most one chunk, are stored inline in
+     * their files collection document instead of the chunks collection: the upload and the
+     * download of a small file each save a round trip. Defaults to None: every file is
+     * chunked.
+     *
+     * The downloads read both layouts, so the threshold can be changed at any time. Other
+     * drivers only read the chunks: they see the inlined files as empty.
+     */
+    #[builder(default)]
+    pub inline_threshold: Option<u32>,
+
+    /**
+     * Uploaded files expire this duration after the beginning of their upload, for
+     * ephemeral payloads. Their files and chunks documents get an `expireAt` date and
+     * the bucket creates TTL indexes on it before the first write. Defaults to None:
+     * files never expire.
+     *
+     * The server removes expired documents in the background (every minute), and the
+     * files and chunks collections independently: a file can outlive its expiration or
+     * lose its chunks before its files document. Downloads of an expired file raise
+     * [`GridFSError::FileExpired`](crate::GridFSError::FileExpired) instead of
+     * returning a partial content.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+    pub expire_after: Option<Duration>,
+
+    /**
+     * Treat the filename as the key of the files: a unique index is created on `filename`
+     * and uploading a filename already stored fails instead of adding a revision. Defaults
+     * to false.
+     *
+     * [`GridFSBucket::upload_from_stream_with_id`](crate::GridFSBucket::upload_from_stream_with_id)
+     * raises [`GridFSError::AlreadyExists`](crate::GridFSError::AlreadyExists), the other
+     * uploads a duplicate key error. Enabling it on a bucket which already holds several
+     * revisions of a filename fails the index creation, and so the uploads.
+     */
+    #[builder(default)]
+    pub unique_filenames: bool,
+
+    /**
+     * The name of the `{filename, uploadDate}` index of the files collection, when the bucket
+     * creates it. Defaults to `<bucket_name>.files_index`.
+     */
+    #[builder(default)]
+    pub files_index_name: Option<String>,
+
+    /**
+     * The name of the `{files_id, n}` index of the chunks collection, when the bucket creates
+     * it. Defaults to `<bucket_name>.chunks_index`.
+     */
+    #[builder(default)]
+    pub chunks_index_name: Option<String>,
+
+    /**
+     * A field, e.g. `tenant`, written in every chunk with the
+     * [`GridFSUploadOptions::shard_key_value`] of its file, and leading the index of the
+     * chunks: `{<field>: 1, files_id: 1, n: 1}`. On a sharded cluster, the chunks
+     * collection is sharded on this index, which spreads the files better than
+     * `{files_id: 1, n: 1}`, and the chunk queries target one shard. Set it before the
+     * first upload: the index of an existing bucket isn't changed.
+     */
+    #[builder(default)]
+    pub chunk_shard_key: Option<String>,
+
+    /**
+     * Secondary indexes of the files collection, e.g. on `metadata.tenant`, created with the
+     * indexes of the bucket before the first write. The index names default to the names
+     * generated by the driver.
+     */
+    #[builder(default)]
+    pub files_indexes: Vec<IndexModel>,
+
+    /**
+     * The rules the filenames follow, checked on upload and on rename. A violation raises
+     * [`GridFSError::InvalidFilename`](crate::GridFSError::InvalidFilename). Filenames
+     * aren't checked when None.
+     */
+    #[builder(default)]
+    pub filename_policy: Option<FilenamePolicy>,
+
+    /**
+     * Inspects the content of every upload, and can veto it before the file is committed.
+     * See the [`inspector`](crate::inspector) module.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub content_inspector: Option<Arc<dyn ContentInspector>>,
+
+    /**
+     * Extracts the text of every upload, stored in the `<bucket>.content` collection for
+     * [`GridFSBucket::search_content`](crate::GridFSBucket::search_content).
+     * See the [`content`](crate::content) module.
+     */
+    #[cfg(feature = "content-search")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub text_extractor: Option<Arc<dyn TextExtractor>>,
+
+    /**
+     * The external store of the files tiered by
+     * [`GridFSBucket::tier_files`](crate::GridFSBucket::tier_files), from which their
+     * downloads fetch the content. See the [`tier`](crate::tier) module.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub tier_backend: Option<Arc<dyn TierBackend>>,
+
+    /**
+     * Encrypts the `data` field of the chunks written by the bucket with client-side field
+     * level encryption. See the [`encryption`](crate::encryption) module.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub chunk_encryption: Option<ChunkEncryption>,
+
+    /**
+     * The source of the current time of the bucket, see the [`clock`](crate::clock) module.
+     * Defaults to None: the system clock.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub clock: Option<Arc<dyn Clock>>,
+
+    /**
+     * The uploads longer than this duration are logged with the duration of their phases,
+     * see the [`slow_op`](crate::slow_op) module. Defaults to None: no operation is logged.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+    pub slow_op_threshold: Option<Duration>,
+
+    /**
+     * The sink of the slow operations. Defaults to None: the `tracing` warnings with the
+     * `tracing` feature, nothing otherwise.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub slow_op_logger: Option<Arc<dyn SlowOpLogger>>,
+
+    /**
+     * When true, the download of a tiered file writes its chunks back in the bucket, so the
+     * next downloads don't reach the tier backend. Defaults to false.
+     */
+    #[builder(default = false)]
+    pub rehydrate_tiered: bool,
+
+    /**
+     * When true, a download finding a file corrupted, with a malformed chunk or a checksum
+     * mismatch, [quarantines](crate::GridFSBucket::quarantine) it in the background.
+     * Defaults to false. Requires a tokio runtime.
+     */
+    #[builder(default = false)]
+    pub quarantine_corrupt: bool,
+
+    /**
+     * The bucket the downloads fall back to when a file isn't found in this bucket, e.g. the
+     * bucket of a former deployment being migrated lazily. The other reads only see this
+     * bucket. Defaults to None.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub legacy_bucket: Option<Arc<GridFSBucket>>,
+
+    /**
+     * When true, a file downloaded from the [`GridFSBucketOptions::legacy_bucket`] is
+     * copied to this bucket in the background, verified by its checksum, see
+     * [`GridFSBucket::copy_from_legacy`](crate::GridFSBucket::copy_from_legacy). Defaults to
+     * false. Requires a tokio runtime and the `md5` or the `sha256` feature. A failed copy
+     * is emitted as a `tracing` warning with the `tracing` feature.
+     */
+    #[builder(default = false)]
+    pub legacy_copy_through: bool,
+
+    /**
+     * The limits of the [`Priority::Interactive`] operations. Defaults to unlimited.
+     */
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[builder(default)]
+    pub interactive_limits: PriorityLimits,
+
+    /**
+     * The limits of the [`Priority::Batch`] operations. Defaults to unlimited.
+     */
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    #[builder(default)]
+    pub batch_limits: PriorityLimits,
 }
 
 impl Default for GridFSBucketOptions {
@@ -107,11 +785,117 @@ impl Default for GridFSBucketOptions {
             bucket_name: "fs".into(),
             chunk_size_bytes: 255 * 1024,
             write_concern: None,
+            files_write_concern: None,
+            chunks_write_concern: None,
             read_concern: None,
             read_preference: None,
+            selection_criteria: None,
             disable_md5: false,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            download_retries: 0,
+            primary_fallback: false,
+            hedged_chunk_reads: false,
+            max_in_flight_chunks: 1,
+            chunk_ordering: ChunkOrdering::Pipelined,
+            #[cfg(feature = "md5")]
+            verify_on_write: None,
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            offload_digest: false,
+            chunk_binary_subtype: ChunkBinarySubtype::Generic,
+            inline_threshold: None,
+            expire_after: None,
+            unique_filenames: false,
+            files_index_name: None,
+            chunks_index_name: None,
+            chunk_shard_key: None,
+            files_indexes: vec![],
+            filename_policy: None,
+            content_inspector: None,
+            #[cfg(feature = "content-search")]
+            text_extractor: None,
+            tier_backend: None,
+            chunk_encryption: None,
+            clock: None,
+            slow_op_threshold: None,
+            slow_op_logger: None,
+            rehydrate_tiered: false,
+            quarantine_corrupt: false,
+            legacy_bucket: None,
+            legacy_copy_through: false,
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            interactive_limits: PriorityLimits::default(),
+            #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+            batch_limits: PriorityLimits::default(),
+        }
+    }
+}
+
+impl GridFSBucketOptions {
+    /**
+     * Checks the options: the `chunk_size_bytes` must be more than 0 and at most 15MiB, so
+     * a chunk fits in a BSON document, and the `bucket_name` must make valid collection
+     * names, see [`bucket_name`](crate::bucket_name). The length of the namespaces is
+     * checked against the database by [`GridFSBucket::try_new`](crate::GridFSBucket::try_new).
+     *
+     * # Errors
+     *
+     * Raise [`GridFSError::InvalidChunkSize`] when `chunk_size_bytes` is out of bounds.
+     * Raise [`GridFSError::InvalidBucketName`] when `bucket_name` breaks a rule of the
+     * namespaces.
+     */
+    pub fn validate(&self) -> Result<(), GridFSError> {
+        check_chunk_size(self.chunk_size_bytes)?;
+        bucket_name::check(&self.bucket_name, "")
+    }
+
+    /// The write concern of the writes to the files collection.
+    pub(crate) fn write_concern_of_files(&self) -> Option<WriteConcern> {
+        self.files_write_concern
+            .clone()
+            .or_else(|| self.write_concern.clone())
+    }
+
+    /// The write concern of the writes to the chunks collections.
+    pub(crate) fn write_concern_of_chunks(&self) -> Option<WriteConcern> {
+        self.chunks_write_concern
+            .clone()
+            .or_else(|| self.write_concern.clone())
+    }
+
+    /// The number of chunk inserts in flight during an upload, with the [`ChunkOrdering`].
+    pub(crate) fn in_flight_chunks(&self) -> usize {
+        match self.chunk_ordering {
+            ChunkOrdering::Pipelined => self.max_in_flight_chunks.max(1),
+            ChunkOrdering::Sequential => 1,
         }
     }
+
+    /// The selection criteria of the chunk reads of the downloads: the selection criteria of
+    /// the reads, hedged with `hedged_chunk_reads`.
+    pub(crate) fn chunks_selection_criteria(&self) -> Option<SelectionCriteria> {
+        let mut selection_criteria = self.read_selection_criteria();
+        if self.hedged_chunk_reads {
+            if let Some(SelectionCriteria::ReadPreference(
+                ReadPreference::Secondary { options }
+                | ReadPreference::PrimaryPreferred { options }
+                | ReadPreference::SecondaryPreferred { options }
+                | ReadPreference::Nearest { options },
+            )) = selection_criteria.as_mut()
+            {
+                options.hedge = Some(HedgedReadOptions::with_enabled(true));
+            }
+        }
+        selection_criteria
+    }
+
+    /// The selection criteria of the reads: the explicit criteria, else the read preference.
+    pub(crate) fn read_selection_criteria(&self) -> Option<SelectionCriteria> {
+        self.selection_criteria.clone().or_else(|| {
+            self.read_preference
+                .clone()
+                .map(SelectionCriteria::ReadPreference)
+        })
+    }
 }
 
 /// [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#generic-find-on-files-collection)
@@ -159,32 +943,481 @@ pub struct GridFSFindOptions {
      * The number of documents to skip before returning.
      */
     #[builder(default)]
-    pub skip: u64,
+    pub skip: Option<u64>,
 
     /**
      * The order by which to sort results. Defaults to not sorting.
      */
     #[builder(default)]
     pub sort: Option<Document>,
+
+    /**
+     * Limits the fields of the returned files collection documents.
+     */
+    #[builder(default)]
+    pub projection: Option<Document>,
+
+    /**
+     * The collation to use for the filter and the sort.
+     */
+    #[builder(default)]
+    pub collation: Option<Collation>,
+
+    /**
+     * A comment attached to the query, to help trace it in the server logs and profiler.
+     */
+    #[builder(default)]
+    pub comment: Option<Bson>,
+
+    /**
+     * Finds the files in this state instead of the available ones.
+     */
+    #[builder(default)]
+    pub status: Option<FileStatus>,
+}
+
+/// The options of a download, see
+/// [`GridFSBucket::open_download_stream_with_options`](crate::GridFSBucket::open_download_stream_with_options).
+#[derive(Clone, Debug, Default, TypedBuilder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct GridFSDownloadOptions {
+    /**
+     * The read concern of this download, instead of the one of the bucket.
+     */
+    #[builder(default)]
+    pub read_concern: Option<ReadConcern>,
+
+    /**
+     * The selection criteria of this download, instead of the ones of the bucket.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "selection_criteria"))]
+    pub selection_criteria: Option<SelectionCriteria>,
+
+    /**
+     * When true, the checksum of the downloaded chunks is compared with the one of the file
+     * once the last chunk is read, the bucket's
+     * [`GridFSBucketOptions::checksum_algorithm`] when the file has it, or else the one it
+     * has: a mismatch ends the stream with
+     * [`GridFSError::ChecksumMismatch`]. Files without checksum and ranges aren't verified.
+     * Defaults to false. Requires the `md5` or the `sha256` feature.
+     */
+    #[cfg(any(feature = "md5", feature = "sha256"))]
+    #[builder(default = false)]
+    pub verify_checksum: bool,
+
+    /**
+     * The range of bytes of the file to download, instead of the whole file. Only the
+     * chunks covering the range are read.
+     */
+    #[builder(default)]
+    pub range: Option<Range<u64>>,
+
+    /**
+     * The number of chunks fetched by each round trip to the server. Defaults to enough
+     * chunks for 8MiB, from the chunk size of the file, instead of the batches of the driver.
+     */
+    #[builder(default)]
+    pub cursor_batch_size: Option<u32>,
+
+    /**
+     * The number of chunks fetched by each round trip to the server, when
+     * [`GridFSDownloadOptions::cursor_batch_size`] isn't set.
+     */
+    #[builder(default)]
+    pub prefetch: Option<u32>,
+
+    /**
+     * Keeps the chunks cursor open on the server while the download is idle, instead of
+     * letting the server close it after 10 minutes, e.g. for a consumer slower than that
+     * between two batches.
+     */
+    #[builder(default)]
+    pub no_cursor_timeout: Option<bool>,
+
+    /**
+     * Reopens the chunks cursor from the next chunk once it's open for this long, before the
+     * server times it out, so a long download doesn't end with a `CursorNotFound` error.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+    pub cursor_refresh: Option<Duration>,
+
+    /**
+     * The maximum execution time of the queries of the download on the server.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+    pub timeout: Option<Duration>,
+
+    /**
+     * Reads the file in causally consistent sessions advanced to this token, like
+     * [`GridFSBucket::open_download_stream_after`](crate::GridFSBucket::open_download_stream_after).
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub causal_token: Option<CausalToken>,
+
+    /**
+     * Reads the file in this session of the caller, e.g. a snapshot session or the session
+     * of a transaction, instead of the causal token. The session is locked by each query of
+     * the download, and must belong to the client of the bucket.
+     */
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+/// [Spec](https://github.com/mongodb/specifications/blob/master/source/gridfs/gridfs-spec.rst#file-download-by-filename)
+#[derive(Clone, Debug, TypedBuilder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct GridFSDownloadByNameOptions {
+    /**
+     * Which revision (documents with the same filename and different uploadDate)
+     * of the file to retrieve. Defaults to -1 (the most recent revision).
+     *
+     * Revision numbers are defined as follows:
+     * 0 = the original stored file
+     * 1 = the first revision
+     * 2 = the second revision
+     * etc…
+     * -2 = the second most recent revision
+     * -1 = the most recent revision
+     */
+    #[builder(default = -1)]
+    pub revision: i32,
+
+    /**
+     * The options of the download of the revision.
+     */
+    #[builder(default)]
+    pub download: GridFSDownloadOptions,
+}
+
+impl Default for GridFSDownloadByNameOptions {
+    fn default() -> Self {
+        GridFSDownloadByNameOptions {
+            revision: -1,
+            download: GridFSDownloadOptions::default(),
+        }
+    }
+}
+
+/// Failures injected by a [`ChaosBucket`](crate::chaos::ChaosBucket).
+#[cfg(feature = "test-util")]
+#[derive(Clone, Debug, Default, TypedBuilder)]
+pub struct ChaosOptions {
+    /**
+     * The insert of the chunk number `n` of every upload fails.
+     */
+    #[builder(default)]
+    pub fail_chunk_insert: Option<u32>,
+
+    /**
+     * Downloads fail after having yielded this number of chunks.
+     */
+    #[builder(default)]
+    pub fail_download_after: Option<usize>,
+
+    /**
+     * Delay added before every download and every chunk insert.
+     */
+    #[builder(default)]
+    pub latency: Option<Duration>,
+}
+
+/// Options of [`GridFSBucket::upload_many`](crate::GridFSBucket::upload_many).
+#[derive(Clone, TypedBuilder)]
+pub struct UploadManyOptions {
+    /**
+     * The maximum number of uploads in flight. Defaults to 4.
+     */
+    #[builder(default = 4)]
+    pub concurrency: usize,
+
+    /**
+     * The options used to upload each file.
+     */
+    #[builder(default)]
+    pub upload_options: Option<GridFSUploadOptions>,
+}
+
+impl Default for UploadManyOptions {
+    fn default() -> Self {
+        UploadManyOptions::builder().build()
+    }
+}
+
+/// Options of [`GridFSBucket::ingest_directory`](crate::GridFSBucket::ingest_directory).
+#[cfg(feature = "watch-fs")]
+#[derive(Clone, TypedBuilder)]
+pub struct IngestOptions {
+    /**
+     * Glob patterns, relative to the watched directory, of the files to upload.
+     * Every file is uploaded when empty.
+     */
+    #[builder(default)]
+    pub include: Vec<String>,
+
+    /**
+     * Watch the subdirectories too. Defaults to false.
+     */
+    #[builder(default = false)]
+    pub recursive: bool,
+
+    /**
+     * A file is uploaded once it has not been modified for this duration. Defaults to 500ms.
+     */
+    #[builder(default = Duration::from_millis(500))]
+    pub debounce: Duration,
+
+    /**
+     * The options used to upload each file.
+     */
+    #[builder(default)]
+    pub upload_options: Option<GridFSUploadOptions>,
+}
+
+/// (De)serializes the optional durations of the options as a number of milliseconds, like the
+/// `wtimeout` of the write concerns.
+#[cfg(feature = "serde")]
+mod duration_millis {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        duration
+            .map(|duration| duration.as_millis() as u64)
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_millis))
+    }
+}
+
+/// (De)serializes the optional selection criteria of the options as a read preference. A
+/// predicate isn't serializable: it is serialized as None.
+#[cfg(feature = "serde")]
+mod selection_criteria {
+    use mongodb::options::{ReadPreference, SelectionCriteria};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        selection_criteria: &Option<SelectionCriteria>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match selection_criteria {
+            Some(SelectionCriteria::ReadPreference(read_preference)) => {
+                Some(read_preference).serialize(serializer)
+            }
+            _ => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SelectionCriteria>, D::Error> {
+        Ok(Option::<ReadPreference>::deserialize(deserializer)?
+            .map(SelectionCriteria::ReadPreference))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{GridFSBucketOptions, GridFSFindOptions};
+    use super::{
+        ChunkOrdering, FilenamePolicy, GridFSBucketOptions, GridFSFindOptions, UnicodeNormalization,
+        UploadProgress,
+    };
+    use crate::{BucketNameViolation, FilenameViolation, GridFSError};
+    use mongodb::options::{
+        Acknowledgment, HedgedReadOptions, ReadPreference, ReadPreferenceOptions, SelectionCriteria,
+        WriteConcern,
+    };
+    use std::time::Duration;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn grid_fs_bucket_options_deserialize() {
+        let options: GridFSBucketOptions = bson::from_document(bson::doc! {
+            "bucket_name":"images",
+            "chunk_size_bytes":1024,
+            "write_concern":{"w":"majority", "wtimeout":1000},
+            "read_preference":{"mode":"secondaryPreferred", "maxStalenessSeconds":120},
+            "selection_criteria":{"mode":"nearest"},
+            "expire_after":60000,
+            "chunk_ordering":"sequential",
+            "filename_policy":{"max_length":255, "normalization":"nfc"},
+        })
+        .unwrap();
+        assert_eq!(options.bucket_name, "images");
+        assert_eq!(options.chunk_size_bytes, 1024);
+        let write_concern = options.write_concern.clone().unwrap();
+        assert_eq!(write_concern.w, Some(Acknowledgment::Majority));
+        assert_eq!(write_concern.w_timeout, Some(Duration::from_secs(1)));
+        assert!(matches!(
+            options.read_preference,
+            Some(ReadPreference::SecondaryPreferred { ref options })
+                if options.max_staleness == Some(Duration::from_secs(120))
+        ));
+        assert!(matches!(
+            options.selection_criteria,
+            Some(SelectionCriteria::ReadPreference(
+                ReadPreference::Nearest { .. }
+            ))
+        ));
+        assert_eq!(options.expire_after, Some(Duration::from_secs(60)));
+        assert_eq!(options.chunk_ordering, ChunkOrdering::Sequential);
+        let policy = options.filename_policy.clone().unwrap();
+        assert_eq!(policy.max_length, Some(255));
+        assert_eq!(policy.normalization, Some(UnicodeNormalization::Nfc));
+        assert_eq!(
+            options.max_in_flight_chunks,
+            GridFSBucketOptions::default().max_in_flight_chunks
+        );
+
+        let document = bson::to_document(&options).unwrap();
+        assert_eq!(document.get_str("bucket_name"), Ok("images"));
+        assert_eq!(document.get_i64("expire_after"), Ok(60000));
+        assert_eq!(
+            document.get_document("selection_criteria").unwrap(),
+            &bson::doc! {"mode":"nearest"}
+        );
+        assert!(document.get("clock").is_none());
+        let options: GridFSBucketOptions = bson::from_document(document).unwrap();
+        assert_eq!(options.bucket_name, "images");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn download_options_deserialize() {
+        let options: super::GridFSDownloadOptions = bson::from_document(bson::doc! {
+            "read_concern":{"level":"majority"},
+            "range":{"start":10_i64, "end":20_i64},
+            "cursor_refresh":300000,
+        })
+        .unwrap();
+        assert_eq!(
+            options.read_concern,
+            Some(mongodb::options::ReadConcern::majority())
+        );
+        assert_eq!(options.range, Some(10..20));
+        assert_eq!(options.cursor_refresh, Some(Duration::from_secs(300)));
+        assert_eq!(options.timeout, None);
+
+        let options: super::GridFSDownloadByNameOptions = bson::from_document(bson::doc! {}).unwrap();
+        assert_eq!(options.revision, -1);
+        let options: super::GridFSUploadOptions =
+            bson::from_document(bson::doc! {"chunk_size_bytes":4, "status":"pending"}).unwrap();
+        assert_eq!(options.chunk_size_bytes, Some(4));
+        assert_eq!(options.status, Some(crate::FileStatus::Pending));
+    }
+
+    #[test]
+    fn write_concern_of_files_and_chunks() {
+        let majority = WriteConcern::builder().w(Acknowledgment::Majority).build();
+        let one = WriteConcern::builder().w(Acknowledgment::Nodes(1)).build();
+        let options = GridFSBucketOptions::builder()
+            .write_concern(Some(majority.clone()))
+            .build();
+        assert_eq!(options.write_concern_of_files(), Some(majority.clone()));
+        assert_eq!(options.write_concern_of_chunks(), Some(majority.clone()));
+
+        let options = GridFSBucketOptions::builder()
+            .files_write_concern(Some(majority.clone()))
+            .chunks_write_concern(Some(one.clone()))
+            .build();
+        assert_eq!(options.write_concern_of_files(), Some(majority));
+        assert_eq!(options.write_concern_of_chunks(), Some(one));
+        assert_eq!(
+            GridFSBucketOptions::default().write_concern_of_files(),
+            None
+        );
+    }
+
+    #[test]
+    fn grid_fs_bucket_options_validate() {
+        assert!(GridFSBucketOptions::default().validate().is_ok());
+        for chunk_size in [0, 15 * 1024 * 1024 + 1] {
+            let options = GridFSBucketOptions::builder()
+                .chunk_size_bytes(chunk_size)
+                .build();
+            assert!(matches!(
+                options.validate(),
+                Err(GridFSError::InvalidChunkSize(size)) if size == chunk_size
+            ));
+        }
+        for (bucket_name, violation) in [
+            ("", BucketNameViolation::Empty),
+            ("fs$", BucketNameViolation::ForbiddenCharacter('$')),
+            ("fs.", BucketNameViolation::EmptySegment),
+            ("system.fs", BucketNameViolation::Reserved),
+        ] {
+            let options = GridFSBucketOptions::builder()
+                .bucket_name(bucket_name.into())
+                .build();
+            assert!(matches!(
+                options.validate(),
+                Err(GridFSError::InvalidBucketName(name, v)) if name == bucket_name && v == violation
+            ));
+        }
+    }
+
+    #[test]
+    fn upload_progress() {
+        let progress = UploadProgress::new(8, 2, Some(10), 4);
+        assert_eq!(progress.total_bytes, Some(10));
+        assert_eq!(progress.chunk_count_estimate, Some(3));
+        assert_eq!(progress.percent(), Some(80.0));
+
+        // A hint smaller than the file.
+        let progress = UploadProgress::new(20, 5, Some(10), 4);
+        assert_eq!(progress.chunk_count_estimate, Some(5));
+        assert_eq!(progress.percent(), Some(100.0));
+
+        let progress = UploadProgress::new(8, 2, None, 4);
+        assert_eq!(progress.chunk_count_estimate, None);
+        assert_eq!(progress.percent(), None);
+    }
 
     #[test]
     fn grid_fs_bucket_options_default() {
         let options = GridFSBucketOptions::default();
         assert_eq!(options.bucket_name, "fs");
         assert_eq!(options.chunk_size_bytes, 255 * 1024);
-        assert_eq!(options.disable_md5, false);
+        assert!(!options.disable_md5);
     }
     #[test]
     fn grid_fs_bucket_options_builder_default() {
         let options = GridFSBucketOptions::builder().build();
         assert_eq!(options.bucket_name, "fs");
         assert_eq!(options.chunk_size_bytes, 255 * 1024);
-        assert_eq!(options.disable_md5, false);
+        assert!(!options.disable_md5);
+        assert_eq!(options.max_in_flight_chunks, 1);
+        assert_eq!(options.chunk_ordering, ChunkOrdering::Pipelined);
+    }
+    #[test]
+    fn grid_fs_bucket_options_chunk_ordering() {
+        let options = GridFSBucketOptions::builder()
+            .max_in_flight_chunks(3)
+            .build();
+        assert_eq!(options.in_flight_chunks(), 3);
+        let options = GridFSBucketOptions::builder()
+            .max_in_flight_chunks(3)
+            .chunk_ordering(ChunkOrdering::Sequential)
+            .build();
+        assert_eq!(options.in_flight_chunks(), 1);
+        let options = GridFSBucketOptions::builder()
+            .max_in_flight_chunks(0)
+            .build();
+        assert_eq!(options.in_flight_chunks(), 1);
     }
     #[test]
     fn grid_fs_bucket_options_bucket_name() {
@@ -210,10 +1443,82 @@ mod tests {
         assert_eq!(options.chunk_size_bytes, 1023);
     }
 
+    #[test]
+    fn grid_fs_bucket_options_selection_criteria() {
+        let options = GridFSBucketOptions::builder()
+            .read_preference(Some(ReadPreference::Secondary {
+                options: Default::default(),
+            }))
+            .build();
+        assert!(matches!(
+            options.read_selection_criteria(),
+            Some(SelectionCriteria::ReadPreference(
+                ReadPreference::Secondary { .. }
+            ))
+        ));
+
+        let options = GridFSBucketOptions::builder()
+            .read_preference(Some(ReadPreference::Secondary {
+                options: Default::default(),
+            }))
+            .selection_criteria(Some(SelectionCriteria::ReadPreference(
+                ReadPreference::Nearest {
+                    options: ReadPreferenceOptions::builder()
+                        .max_staleness(Some(Duration::from_secs(90)))
+                        .build(),
+                },
+            )))
+            .build();
+        assert!(matches!(
+            options.read_selection_criteria(),
+            Some(SelectionCriteria::ReadPreference(
+                ReadPreference::Nearest { .. }
+            ))
+        ));
+        assert!(GridFSBucketOptions::default()
+            .read_selection_criteria()
+            .is_none());
+    }
+
+    #[test]
+    fn grid_fs_bucket_options_hedged_chunk_reads() {
+        let options = GridFSBucketOptions::builder()
+            .read_preference(Some(ReadPreference::Secondary {
+                options: Default::default(),
+            }))
+            .hedged_chunk_reads(true)
+            .build();
+        assert!(matches!(
+            options.chunks_selection_criteria(),
+            Some(SelectionCriteria::ReadPreference(ReadPreference::Secondary { options }))
+                if options.hedge == Some(HedgedReadOptions::with_enabled(true))
+        ));
+        assert!(matches!(
+            options.read_selection_criteria(),
+            Some(SelectionCriteria::ReadPreference(ReadPreference::Secondary { options }))
+                if options.hedge.is_none()
+        ));
+
+        let options = GridFSBucketOptions::builder()
+            .read_preference(Some(ReadPreference::Primary))
+            .hedged_chunk_reads(true)
+            .build();
+        assert_eq!(
+            options.chunks_selection_criteria(),
+            Some(SelectionCriteria::ReadPreference(ReadPreference::Primary))
+        );
+    }
+
     #[test]
     fn grid_fs_find_options_builder_chain() {
-        let options = GridFSFindOptions::builder().skip(4).build();
-        assert_eq!(options.skip, 4);
+        let options = GridFSFindOptions::builder()
+            .skip(Some(4))
+            .batch_size(Some(2))
+            .comment(Some("test".into()))
+            .build();
+        assert_eq!(options.skip, Some(4));
+        assert_eq!(options.batch_size, Some(2));
+        assert_eq!(options.comment, Some("test".into()));
     }
     #[test]
     fn grid_fs_find_options_builder_default() {
@@ -223,8 +1528,11 @@ mod tests {
         assert_eq!(options.limit, None);
         assert_eq!(options.max_time, None);
         assert_eq!(options.no_cursor_timeout, None);
-        assert_eq!(options.skip, 0);
+        assert_eq!(options.skip, None);
         assert_eq!(options.sort, None);
+        assert_eq!(options.projection, None);
+        assert!(options.collation.is_none());
+        assert_eq!(options.comment, None);
     }
     #[test]
     fn grid_fs_find_options_default() {
@@ -234,7 +1542,43 @@ mod tests {
         assert_eq!(options.limit, None);
         assert_eq!(options.max_time, None);
         assert_eq!(options.no_cursor_timeout, None);
-        assert_eq!(options.skip, 0);
+        assert_eq!(options.skip, None);
         assert_eq!(options.sort, None);
+        assert_eq!(options.projection, None);
+        assert!(options.collation.is_none());
+        assert_eq!(options.comment, None);
+    }
+
+    #[test]
+    fn filename_policy() {
+        let policy = FilenamePolicy::builder()
+            .max_length(Some(8))
+            .allowed_characters(Some(|c: char| c != '*'))
+            .normalization(Some(UnicodeNormalization::Nfc))
+            .reject_path_traversal(true)
+            .build();
+        assert_eq!(
+            policy.apply("cafe\u{301}.txt"),
+            Ok("caf\u{e9}.txt".to_string())
+        );
+        assert_eq!(policy.apply("a/b.txt"), Ok("a/b.txt".to_string()));
+        assert_eq!(
+            policy.apply("too long.txt"),
+            Err(FilenameViolation::TooLong(8))
+        );
+        assert_eq!(
+            policy.apply("*.txt"),
+            Err(FilenameViolation::ForbiddenCharacter('*'))
+        );
+        assert_eq!(policy.apply("../a"), Err(FilenameViolation::PathTraversal));
+        assert_eq!(
+            policy.apply("a\\..\\b"),
+            Err(FilenameViolation::PathTraversal)
+        );
+        assert_eq!(policy.apply("/etc"), Err(FilenameViolation::PathTraversal));
+        assert_eq!(policy.apply("C:a"), Err(FilenameViolation::PathTraversal));
+
+        let policy = FilenamePolicy::default();
+        assert_eq!(policy.apply("../*"), Ok("../*".to_string()));
     }
 }
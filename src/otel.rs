@@ -0,0 +1,156 @@
+//! Trace context propagation through the files. Requires the `otel` feature.
+//!
+//! Uploads store the current trace context, serialized by the global text map propagator,
+//! in the `traceContext` field of the metadata of the file. Downloads run in a
+//! `gridfs.download` span linked to the stored context, so a distributed trace connects
+//! the service which stored a file with the services which fetch it.
+use bson::{Bson, Document};
+use opentelemetry::{
+    global::{self, BoxedSpan},
+    propagation::TextMapPropagator,
+    trace::{Link, TraceContextExt, Tracer},
+    Context,
+};
+use std::collections::HashMap;
+
+/// The field of the metadata of a file holding its trace context.
+pub const TRACE_CONTEXT_FIELD: &str = "traceContext";
+
+/// Serializes the trace context @cx with @propagator into the @metadata of a file.
+pub(crate) fn inject(propagator: &dyn TextMapPropagator, cx: &Context, metadata: &mut Document) {
+    let mut carrier = HashMap::new();
+    propagator.inject_context(cx, &mut carrier);
+    if carrier.is_empty() {
+        return;
+    }
+    let trace_context: Document = carrier
+        .into_iter()
+        .map(|(key, value)| (key, Bson::String(value)))
+        .collect();
+    metadata.insert(TRACE_CONTEXT_FIELD, trace_context);
+}
+
+/// Serializes the current trace context into the @metadata of a file.
+pub(crate) fn inject_current(metadata: &mut Document) {
+    global::get_text_map_propagator(|propagator| inject(propagator, &Context::current(), metadata));
+}
+
+/// Deserializes with @propagator the trace context stored in the @metadata of a file.
+pub(crate) fn extract(propagator: &dyn TextMapPropagator, metadata: &Document) -> Option<Context> {
+    let trace_context = metadata.get_document(TRACE_CONTEXT_FIELD).ok()?;
+    let carrier: HashMap<String, String> = trace_context
+        .iter()
+        .filter_map(|(key, value)| value.as_str().map(|value| (key.clone(), value.to_string())))
+        .collect();
+    let cx = propagator.extract(&carrier);
+    if cx.span().span_context().is_valid() {
+        Some(cx)
+    } else {
+        None
+    }
+}
+
+/// Starts the span of the download of @file, linked to the trace context of its upload.
+pub(crate) fn download_span(file: &Document) -> BoxedSpan {
+    let tracer = global::tracer("mongodb-gridfs");
+    let mut builder = tracer.span_builder("gridfs.download");
+    let upload = file.get_document("metadata").ok().and_then(|metadata| {
+        global::get_text_map_propagator(|propagator| extract(propagator, metadata))
+    });
+    if let Some(upload) = upload {
+        builder = builder.with_links(vec![Link::with_context(
+            upload.span().span_context().clone(),
+        )]);
+    }
+    builder.start(&tracer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract, inject, TRACE_CONTEXT_FIELD};
+    use bson::doc;
+    use opentelemetry::{
+        propagation::{text_map_propagator::FieldIter, Extractor, Injector, TextMapPropagator},
+        trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+        Context,
+    };
+
+    /// A minimal propagator storing the trace id and the span id.
+    #[derive(Debug)]
+    struct TestPropagator {
+        fields: Vec<String>,
+    }
+
+    impl TextMapPropagator for TestPropagator {
+        fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+            let span = cx.span();
+            let span_context = span.span_context();
+            if span_context.is_valid() {
+                injector.set("trace", span_context.trace_id().to_string());
+                injector.set("span", span_context.span_id().to_string());
+            }
+        }
+
+        fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+            let trace_id = extractor
+                .get("trace")
+                .and_then(|trace| TraceId::from_hex(trace).ok());
+            let span_id = extractor
+                .get("span")
+                .and_then(|span| SpanId::from_hex(span).ok());
+            match (trace_id, span_id) {
+                (Some(trace_id), Some(span_id)) => cx.with_remote_span_context(SpanContext::new(
+                    trace_id,
+                    span_id,
+                    TraceFlags::SAMPLED,
+                    true,
+                    TraceState::default(),
+                )),
+                _ => cx.clone(),
+            }
+        }
+
+        fn fields(&self) -> FieldIter<'_> {
+            FieldIter::new(&self.fields)
+        }
+    }
+
+    #[test]
+    fn trace_context_round_trip() {
+        let propagator = TestPropagator {
+            fields: vec!["trace".into(), "span".into()],
+        };
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let cx = Context::new().with_remote_span_context(span_context.clone());
+
+        let mut metadata = doc! {"owner": "test"};
+        inject(&propagator, &cx, &mut metadata);
+        assert!(metadata.get_document(TRACE_CONTEXT_FIELD).is_ok());
+        assert_eq!(metadata.get_str("owner"), Ok("test"));
+
+        let extracted = extract(&propagator, &metadata).unwrap();
+        assert_eq!(
+            extracted.span().span_context().trace_id(),
+            span_context.trace_id()
+        );
+        assert_eq!(
+            extracted.span().span_context().span_id(),
+            span_context.span_id()
+        );
+    }
+
+    #[test]
+    fn no_trace_context() {
+        let propagator = TestPropagator { fields: vec![] };
+        let mut metadata = doc! {};
+        inject(&propagator, &Context::new(), &mut metadata);
+        assert!(metadata.is_empty());
+        assert!(extract(&propagator, &metadata).is_none());
+    }
+}
@@ -0,0 +1,230 @@
+//! Files spread across several buckets, possibly on several databases.
+//!
+//! [`ShardedBuckets`] routes each file to one of its buckets by consistent hashing of the
+//! id of the file: adding a bucket only moves the files of the ids routed to it.
+use crate::{
+    bucket::GridFSDownloadStream,
+    hash::stable_hash,
+    options::{GridFSFindOptions, GridFSUploadOptions},
+    GridFSBucket, GridFSError,
+};
+use bson::{oid::ObjectId, Document};
+#[cfg(feature = "async-std-runtime")]
+use futures::io::AsyncRead;
+use futures_util::stream::{select_all, SelectAll};
+use mongodb::{error::Result, Cursor};
+#[cfg(any(feature = "default", feature = "tokio-runtime"))]
+use tokio::io::AsyncRead;
+
+/// Number of points of each bucket on the hash ring, spreading the ids evenly.
+const POINTS_PER_BUCKET: u32 = 64;
+
+/// A set of buckets sharing the files: each file is stored in the bucket its id is routed to.
+///
+/// The buckets are identified on the hash ring by their database and bucket names, so the
+/// routing doesn't depend on their order.
+#[derive(Clone, Debug)]
+pub struct ShardedBuckets {
+    buckets: Vec<GridFSBucket>,
+    // (point, index of the bucket), sorted by point.
+    ring: Vec<(u64, usize)>,
+}
+
+impl ShardedBuckets {
+    /**
+     * Create a new ShardedBuckets spreading the files across @buckets.
+     *
+     * # Panics
+     *
+     * Panics when @buckets is empty.
+     */
+    pub fn new(buckets: Vec<GridFSBucket>) -> ShardedBuckets {
+        assert!(
+            !buckets.is_empty(),
+            "ShardedBuckets needs at least one bucket"
+        );
+        let mut ring = vec![];
+        for (index, bucket) in buckets.iter().enumerate() {
+            let name = format!(
+                "{}.{}",
+                bucket.db.name(),
+                bucket.options.clone().unwrap_or_default().bucket_name
+            );
+            for point in 0..POINTS_PER_BUCKET {
+                ring.push((stable_hash(format!("{}#{}", name, point).as_bytes()), index));
+            }
+        }
+        ring.sort_unstable();
+        ShardedBuckets { buckets, ring }
+    }
+
+    /// The buckets sharing the files.
+    pub fn buckets(&self) -> &[GridFSBucket] {
+        &self.buckets
+    }
+
+    fn index_for(&self, id: ObjectId) -> usize {
+        let point = stable_hash(&id.bytes());
+        let position = self
+            .ring
+            .partition_point(|(ring_point, _)| *ring_point < point);
+        self.ring[position % self.ring.len()].1
+    }
+
+    /// The bucket storing the file @id.
+    pub fn bucket_for(&self, id: ObjectId) -> &GridFSBucket {
+        &self.buckets[self.index_for(id)]
+    }
+
+    /**
+     * Uploads a user file to the bucket its new id is routed to.
+     * See [`GridFSBucket::upload_from_stream`].
+     */
+    pub async fn upload_from_stream(
+        &mut self,
+        filename: &str,
+        source: impl AsyncRead + Unpin,
+        options: Option<GridFSUploadOptions>,
+    ) -> std::result::Result<ObjectId, GridFSError> {
+        let id = ObjectId::new();
+        let index = self.index_for(id);
+        self.buckets[index]
+            .upload_from_stream_with_id(id, filename, source, options)
+            .await?;
+        Ok(id)
+    }
+
+    /**
+     * Opens a Stream on the content of the file @id, from the bucket storing it.
+     * See [`GridFSBucket::open_download_stream`].
+     */
+    pub async fn open_download_stream(
+        &self,
+        id: ObjectId,
+    ) -> std::result::Result<GridFSDownloadStream, GridFSError> {
+        self.bucket_for(id).open_download_stream(id).await
+    }
+
+    /**
+     * Deletes the file @id from the bucket storing it. See [`GridFSBucket::delete`].
+     */
+    pub async fn delete(&self, id: ObjectId) -> std::result::Result<(), GridFSError> {
+        self.bucket_for(id).delete(id).await
+    }
+
+    /**
+     * Finds the files collection documents that match @filter in every bucket.
+     * See [`GridFSBucket::find`].
+     *
+     * The documents of the buckets are interleaved as they arrive: the `sort`, `skip`
+     * and `limit` of @options apply to each bucket, not to the whole result.
+     */
+    pub async fn find(
+        &self,
+        filter: Document,
+        options: GridFSFindOptions,
+    ) -> Result<SelectAll<Cursor<Document>>> {
+        let mut cursors = vec![];
+        for bucket in &self.buckets {
+            cursors.push(bucket.find(filter.clone(), options.clone()).await?);
+        }
+        Ok(select_all(cursors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShardedBuckets;
+    use crate::{
+        options::{GridFSBucketOptions, GridFSFindOptions},
+        GridFSBucket, GridFSError,
+    };
+    use bson::{doc, oid::ObjectId};
+    #[cfg(feature = "async-std-runtime")]
+    use futures::stream::StreamExt;
+    use mongodb::{Client, Database};
+    #[cfg(any(feature = "default", feature = "tokio-runtime"))]
+    use tokio_stream::StreamExt;
+    use uuid::Uuid;
+
+    fn db_name_new() -> String {
+        "test_".to_owned()
+            + Uuid::new_v4()
+                .hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+    }
+
+    fn bucket(db: &Database, name: &str) -> GridFSBucket {
+        GridFSBucket::new(
+            db.clone(),
+            Some(
+                GridFSBucketOptions::builder()
+                    .bucket_name(name.into())
+                    .build(),
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn routing() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str("mongodb://localhost:27017/").await?;
+        let db = client.database("test");
+        let sharded = ShardedBuckets::new(vec![bucket(&db, "a"), bucket(&db, "b")]);
+        let reordered = ShardedBuckets::new(vec![bucket(&db, "b"), bucket(&db, "a")]);
+        let grown = ShardedBuckets::new(vec![bucket(&db, "a"), bucket(&db, "b"), bucket(&db, "c")]);
+
+        let mut used = [0; 2];
+        let mut moved = 0;
+        for _ in 0..1000 {
+            let id = ObjectId::new();
+            let index = sharded.index_for(id);
+            used[index] += 1;
+            assert_eq!(reordered.index_for(id), 1 - index);
+            match grown.index_for(id) {
+                2 => moved += 1,
+                grown_index => assert_eq!(grown_index, index),
+            }
+        }
+        assert!(used.iter().all(|count| *count > 250));
+        assert!(moved > 150 && moved < 550);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sharded_buckets() -> Result<(), GridFSError> {
+        let client = Client::with_uri_str(
+            &std::env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017/".to_string()),
+        )
+        .await?;
+        let dbname = db_name_new();
+        let db: Database = client.database(&dbname);
+        let mut sharded = ShardedBuckets::new(vec![bucket(&db, "a"), bucket(&db, "b")]);
+
+        let mut ids = vec![];
+        for _ in 0..8 {
+            ids.push(
+                sharded
+                    .upload_from_stream("test.txt", "test data".as_bytes(), None)
+                    .await?,
+            );
+        }
+        for id in &ids {
+            let mut cursor = sharded.open_download_stream(*id).await?;
+            assert_eq!(cursor.next().await.unwrap()?, b"test data");
+        }
+
+        let cursor = sharded
+            .find(doc! {"filename":"test.txt"}, GridFSFindOptions::default())
+            .await?;
+        assert_eq!(cursor.collect::<Vec<_>>().await.len(), 8);
+
+        sharded.delete(ids[0]).await?;
+        assert!(matches!(
+            sharded.open_download_stream(ids[0]).await,
+            Err(GridFSError::FileNotFound())
+        ));
+
+        db.drop(None).await?;
+        Ok(())
+    }
+}
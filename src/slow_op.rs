@@ -0,0 +1,223 @@
+//! The logging of the slow operations of a bucket.
+//!
+//! The uploads longer than
+//! [`GridFSBucketOptions::slow_op_threshold`](crate::options::GridFSBucketOptions::slow_op_threshold)
+//! are reported to the [`SlowOpLogger`] of
+//! [`GridFSBucketOptions::slow_op_logger`](crate::options::GridFSBucketOptions::slow_op_logger)
+//! as a [`SlowOperation`], with the duration of each of their phases: the wait for a slot
+//! of the bucket points at the client, the index check and the files insert at the network
+//! or the server, and the chunk writes at the throughput of the source or of the server.
+//!
+//! Without logger, the slow operations are emitted as `tracing` warnings with the `tracing`
+//! feature, and are not reported otherwise.
+use crate::{display::HumanSize, options::GridFSBucketOptions};
+use bson::oid::ObjectId;
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    time::{Duration, Instant},
+};
+
+/// An operation longer than the slow operation threshold of its bucket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SlowOperation {
+    /// The kind of operation, e.g. `upload`.
+    pub operation: &'static str,
+    /// The id of the file.
+    pub id: ObjectId,
+    pub filename: String,
+    /// The number of bytes transferred.
+    pub length: u64,
+    /// The duration of the operation.
+    pub elapsed: Duration,
+    /// The duration of each phase of the operation, in order, e.g. `("chunk writes", 2s)`.
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+/// `operation id "filename" (size) took elapsed: phase duration, …`.
+impl Display for SlowOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:?} ({}) took {:?}",
+            self.operation,
+            self.id,
+            self.filename,
+            HumanSize(self.length),
+            self.elapsed
+        )?;
+        for (i, (phase, duration)) in self.phases.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { ", " };
+            write!(f, "{}{} {:?}", separator, phase, duration)?;
+        }
+        Ok(())
+    }
+}
+
+/// A sink of the slow operations of a bucket.
+pub trait SlowOpLogger: Send + Sync {
+    /// Logs the slow @operation.
+    fn log(&self, operation: &SlowOperation);
+}
+
+impl Debug for dyn SlowOpLogger {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "SlowOpLogger")
+    }
+}
+
+/// Emits the slow operations as `tracing` warnings, with a field per phase. The default
+/// logger with the `tracing` feature.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingLogger;
+
+#[cfg(feature = "tracing")]
+impl SlowOpLogger for TracingLogger {
+    fn log(&self, operation: &SlowOperation) {
+        let phases: Vec<String> = operation
+            .phases
+            .iter()
+            .map(|(phase, duration)| format!("{}={:?}", phase, duration))
+            .collect();
+        tracing::warn!(
+            operation = operation.operation,
+            id = %operation.id,
+            filename = %operation.filename,
+            length = operation.length,
+            elapsed_ms = operation.elapsed.as_millis() as u64,
+            phases = %phases.join(" "),
+            "slow GridFS operation"
+        );
+    }
+}
+
+/// Times the phases of an operation.
+pub(crate) struct PhaseTimer {
+    start: Instant,
+    last: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl PhaseTimer {
+    pub(crate) fn start() -> PhaseTimer {
+        let now = Instant::now();
+        PhaseTimer {
+            start: now,
+            last: now,
+            phases: vec![],
+        }
+    }
+
+    /// Ends the @phase, which began at the end of the previous one.
+    pub(crate) fn phase(&mut self, phase: &'static str) {
+        let now = Instant::now();
+        self.phases.push((phase, now - self.last));
+        self.last = now;
+    }
+
+    /// The duration of each phase ended so far, in order.
+    pub(crate) fn phases(&self) -> &[(&'static str, Duration)] {
+        &self.phases
+    }
+
+    /// Reports the @operation on the file @id of @filename and @length to the logger of
+    /// @options when it's longer than their threshold.
+    pub(crate) fn finish(
+        self,
+        options: &GridFSBucketOptions,
+        operation: &'static str,
+        id: ObjectId,
+        filename: &str,
+        length: u64,
+    ) {
+        let elapsed = self.start.elapsed();
+        if options
+            .slow_op_threshold
+            .is_none_or(|threshold| elapsed < threshold)
+        {
+            return;
+        }
+        let slow = SlowOperation {
+            operation,
+            id,
+            filename: filename.to_string(),
+            length,
+            elapsed,
+            phases: self.phases,
+        };
+        match &options.slow_op_logger {
+            Some(logger) => logger.log(&slow),
+            #[cfg(feature = "tracing")]
+            None => TracingLogger.log(&slow),
+            #[cfg(not(feature = "tracing"))]
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PhaseTimer, SlowOpLogger, SlowOperation};
+    use crate::options::GridFSBucketOptions;
+    use bson::oid::ObjectId;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    #[derive(Default)]
+    struct Collected(Mutex<Vec<SlowOperation>>);
+
+    impl SlowOpLogger for Collected {
+        fn log(&self, operation: &SlowOperation) {
+            self.0.lock().unwrap().push(operation.clone());
+        }
+    }
+
+    #[test]
+    fn phase_timer() {
+        let collected = Arc::new(Collected::default());
+        let options = GridFSBucketOptions::builder()
+            .slow_op_threshold(Some(Duration::ZERO))
+            .slow_op_logger(Some(collected.clone()))
+            .build();
+        let id = ObjectId::new();
+        let mut timer = PhaseTimer::start();
+        timer.phase("index check");
+        timer.phase("chunk writes");
+        timer.finish(&options, "upload", id, "test.txt", 9);
+        let logged = collected.0.lock().unwrap().clone();
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].id, id);
+        let phases: Vec<_> = logged[0].phases.iter().map(|(phase, _)| *phase).collect();
+        assert_eq!(phases, ["index check", "chunk writes"]);
+
+        // Under the threshold.
+        let options = GridFSBucketOptions::builder()
+            .slow_op_threshold(Some(Duration::from_secs(60)))
+            .slow_op_logger(Some(collected.clone()))
+            .build();
+        PhaseTimer::start().finish(&options, "upload", id, "test.txt", 9);
+        assert_eq!(collected.0.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn display() {
+        let operation = SlowOperation {
+            operation: "upload",
+            id: ObjectId::parse_str("65a1b2c3d4e5f60718293a4b").unwrap(),
+            filename: "test.txt".into(),
+            length: 2048,
+            elapsed: Duration::from_millis(1500),
+            phases: vec![
+                ("index check", Duration::from_millis(500)),
+                ("chunk writes", Duration::from_secs(1)),
+            ],
+        };
+        assert_eq!(
+            operation.to_string(),
+            "upload 65a1b2c3d4e5f60718293a4b \"test.txt\" (2.0 KiB) took 1.5s: index check 500ms, chunk writes 1s"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Stable storage keys and URL paths for the stored files, for the CDNs and reverse proxies
+//! fronting a bucket.
+//!
+//! The path of a file is `<aa>/<bb>/<id>` or `<aa>/<bb>/<id>.<md5>`, where `<aa>/<bb>` are
+//! the first two bytes, in hexadecimal, of a stable hash of the id. The prefix spreads the
+//! keys evenly across the partitions of an object storage or the directories of a cache,
+//! even though the ids of a bucket share their leading timestamp. With the MD5 checksum of
+//! the content, a new revision of a file gets a new key, and the cached ones never go stale.
+use crate::{hash::stable_hash, FileInfo};
+use bson::oid::ObjectId;
+use std::fmt::{Display, Formatter, Result};
+
+/// The storage key of a stored file: its id, and optionally the MD5 checksum of its content.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StorageKey {
+    /// The id of the file.
+    pub id: ObjectId,
+    /// The MD5 checksum of the content of the file, in lowercase hexadecimal.
+    pub md5: Option<String>,
+}
+
+impl StorageKey {
+    /// The storage key of the file @id with the content of MD5 checksum @md5, in hexadecimal.
+    pub fn new(id: ObjectId, md5: Option<&str>) -> StorageKey {
+        StorageKey {
+            id,
+            md5: md5.map(str::to_ascii_lowercase),
+        }
+    }
+
+    /// The storage key of the file @info, with its MD5 checksum when it is stored.
+    pub fn for_file(info: &FileInfo) -> StorageKey {
+        StorageKey::new(info.id, info.md5.as_deref())
+    }
+
+    /// The path of the key: `<aa>/<bb>/<id>[.<md5>]`.
+    pub fn to_path(&self) -> String {
+        self.to_string()
+    }
+
+    /// Parses the @path of a key, with or without a leading `/`. Returns None when @path
+    /// isn't the path of a key, or its prefix doesn't match its id.
+    pub fn parse(path: &str) -> Option<StorageKey> {
+        let mut parts = path.strip_prefix('/').unwrap_or(path).split('/');
+        let (prefix, name) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(first), Some(second), Some(name), None) => ((first, second), name),
+            _ => return None,
+        };
+        let (id, md5) = match name.split_once('.') {
+            Some((id, md5)) => (id, Some(md5)),
+            None => (name, None),
+        };
+        if id.len() != 24
+            || md5.is_some_and(|md5| md5.len() != 32 || !md5.bytes().all(|b| b.is_ascii_hexdigit()))
+        {
+            return None;
+        }
+        let key = StorageKey::new(ObjectId::parse_str(id).ok()?, md5);
+        let (first, second) = key.prefix();
+        if !prefix.0.eq_ignore_ascii_case(&first) || !prefix.1.eq_ignore_ascii_case(&second) {
+            return None;
+        }
+        Some(key)
+    }
+
+    fn prefix(&self) -> (String, String) {
+        let hash = stable_hash(&self.id.bytes()).to_be_bytes();
+        (format!("{:02x}", hash[0]), format!("{:02x}", hash[1]))
+    }
+}
+
+impl Display for StorageKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let (first, second) = self.prefix();
+        write!(f, "{}/{}/{}", first, second, self.id.to_hex())?;
+        if let Some(md5) = &self.md5 {
+            write!(f, ".{}", md5)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StorageKey;
+    use bson::oid::ObjectId;
+
+    #[test]
+    fn storage_key_path() {
+        let id = ObjectId::parse_str("5f8f8c44b54764421b7156c9").unwrap();
+        let key = StorageKey::new(id, None);
+        let path = key.to_path();
+        assert_eq!(path.len(), 30);
+        assert!(path.ends_with("/5f8f8c44b54764421b7156c9"));
+        assert_eq!(StorageKey::parse(&path), Some(key.clone()));
+        assert_eq!(StorageKey::parse(&("/".to_owned() + &path)), Some(key));
+
+        let key = StorageKey::new(id, Some("EB733A00C0C9D336E65691A37AB54293"));
+        let path = key.to_path();
+        assert!(path.ends_with("/5f8f8c44b54764421b7156c9.eb733a00c0c9d336e65691a37ab54293"));
+        assert_eq!(StorageKey::parse(&path), Some(key));
+    }
+
+    #[test]
+    fn storage_key_parse_invalid() {
+        let id = ObjectId::parse_str("5f8f8c44b54764421b7156c9").unwrap();
+        let path = StorageKey::new(id, None).to_path();
+        let (prefix, name) = path.split_at(6);
+        assert_eq!(StorageKey::parse(&("00/00/".to_owned() + name)), None);
+        assert_eq!(StorageKey::parse(name), None);
+        assert_eq!(StorageKey::parse(&(path.clone() + ".md5")), None);
+        assert_eq!(StorageKey::parse(&(prefix.to_owned() + "not an id")), None);
+        assert_eq!(StorageKey::parse(&(path + "/extra")), None);
+    }
+}
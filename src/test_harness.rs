@@ -0,0 +1,150 @@
+//! Integration test harness for the crates using GridFS. Requires the `test-harness` feature.
+//!
+//! [`TestBucket`] connects to the MongoDB of the `MONGO_URI` environment variable, or starts
+//! a disposable MongoDB container with [testcontainers](https://docs.rs/testcontainers) when
+//! the variable isn't set. Each [`TestBucket`] uses its own database, dropped with the bucket.
+//!
+//! ```rust,no_run
+//! use mongodb_gridfs::test_harness::TestBucket;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! let mut bucket = TestBucket::new().await?;
+//! let id = bucket
+//!     .upload_from_stream("test.txt", "test data".as_bytes(), None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::{options::GridFSBucketOptions, GridFSBucket};
+use bson::oid::ObjectId;
+use mongodb::{Client, Database};
+use std::{
+    error::Error,
+    ops::{Deref, DerefMut},
+};
+use testcontainers::{
+    core::{IntoContainerPort, WaitFor},
+    runners::AsyncRunner,
+    ContainerAsync, GenericImage,
+};
+
+/// A [`GridFSBucket`] on a uniquely named database, dropped when the `TestBucket` is dropped.
+pub struct TestBucket {
+    bucket: GridFSBucket,
+    uri: String,
+    container: Option<ContainerAsync<GenericImage>>,
+}
+
+impl TestBucket {
+    /// Creates a test bucket with the default options.
+    pub async fn new() -> Result<TestBucket, Box<dyn Error + Send + Sync>> {
+        TestBucket::with_options(GridFSBucketOptions::default()).await
+    }
+
+    /// Creates a test bucket with the given @options.
+    pub async fn with_options(
+        options: GridFSBucketOptions,
+    ) -> Result<TestBucket, Box<dyn Error + Send + Sync>> {
+        let (uri, container) = match std::env::var("MONGO_URI") {
+            Ok(uri) => (uri, None),
+            Err(_) => {
+                let container = GenericImage::new("mongo", "latest")
+                    .with_exposed_port(27017.tcp())
+                    .with_wait_for(WaitFor::message_on_stdout("Waiting for connections"))
+                    .start()
+                    .await?;
+                let uri = format!(
+                    "mongodb://{}:{}/",
+                    container.get_host().await?,
+                    container.get_host_port_ipv4(27017).await?
+                );
+                (uri, Some(container))
+            }
+        };
+        let client = Client::with_uri_str(&uri).await?;
+        let db = client.database(&format!("test_{}", ObjectId::new().to_hex()));
+
+        Ok(TestBucket {
+            bucket: GridFSBucket::new(db, Some(options)),
+            uri,
+            container,
+        })
+    }
+
+    /// The database of the bucket.
+    pub fn database(&self) -> &Database {
+        &self.bucket.db
+    }
+}
+
+impl Deref for TestBucket {
+    type Target = GridFSBucket;
+
+    fn deref(&self) -> &GridFSBucket {
+        &self.bucket
+    }
+}
+
+impl DerefMut for TestBucket {
+    fn deref_mut(&mut self) -> &mut GridFSBucket {
+        &mut self.bucket
+    }
+}
+
+impl Drop for TestBucket {
+    fn drop(&mut self) {
+        if self.container.is_some() {
+            // The whole server goes away with the container.
+            return;
+        }
+        // Drop can't await and the runtime of the test may be shutting down: the database is
+        // dropped from a dedicated thread and runtime, with its own client.
+        let uri = self.uri.clone();
+        let dbname = self.bucket.db.name().to_string();
+        let _ = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            runtime.block_on(async {
+                let client = Client::with_uri_str(&uri).await?;
+                client.database(&dbname).drop(None).await
+            })?;
+            Ok::<(), Box<dyn Error + Send + Sync>>(())
+        })
+        .join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TestBucket;
+    use bson::{doc, Document};
+    use mongodb::Client;
+    use std::error::Error;
+
+    #[tokio::test]
+    async fn test_bucket_drops_its_database() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut bucket = TestBucket::new().await?;
+        let id = bucket
+            .upload_from_stream("test.txt", "test data".as_bytes(), None)
+            .await?;
+        let file = bucket
+            .database()
+            .collection::<Document>("fs.files")
+            .find_one(doc! { "_id": id }, None)
+            .await?;
+        assert!(file.is_some());
+
+        let client = Client::with_uri_str(&bucket.uri).await?;
+        let dbname = bucket.database().name().to_string();
+        let container_managed = bucket.container.is_some();
+        drop(bucket);
+
+        if !container_managed {
+            let names = client.list_database_names(None, None).await?;
+            assert!(!names.contains(&dbname));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+//! Cold-storage tiering of the rarely read files.
+//!
+//! With a [`TierBackend`] set in
+//! [`GridFSBucketOptions::tier_backend`](crate::options::GridFSBucketOptions::tier_backend),
+//! [`GridFSBucket::tier_files`](crate::GridFSBucket::tier_files) exports the content of the
+//! files older than an age to the backend and removes their chunks: the files collection
+//! document stays, as a stub holding the key of the content in the backend. The downloads of
+//! a stub fetch its content from the backend, and write its chunks back when
+//! [`GridFSBucketOptions::rehydrate_tiered`](crate::options::GridFSBucketOptions::rehydrate_tiered)
+//! is set.
+use crate::FileInfo;
+use futures_util::future::BoxFuture;
+use std::fmt::{Debug, Formatter, Result};
+
+/// An external store, e.g. an object storage, holding the content of the tiered files.
+pub trait TierBackend: Send + Sync {
+    /// Stores the @content of the @file. Returns the key under which the content is
+    /// stored, or the reason of the failure.
+    fn export<'a>(
+        &'a self,
+        file: &'a FileInfo,
+        content: Vec<u8>,
+    ) -> BoxFuture<'a, std::result::Result<String, String>>;
+
+    /// Reads the content stored under @key, or the reason of the failure.
+    fn fetch<'a>(&'a self, key: &'a str) -> BoxFuture<'a, std::result::Result<Vec<u8>, String>>;
+}
+
+impl Debug for dyn TierBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "TierBackend")
+    }
+}